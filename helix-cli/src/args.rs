@@ -25,6 +25,9 @@ pub enum CommandType {
     /// Lint a Helix project
     Check(LintCommand),
 
+    /// Format the .hx files of a Helix project
+    Fmt(FmtCommand),
+
     /// Install the Helix repo
     Install(InstallCommand),
 
@@ -81,6 +84,9 @@ pub struct CompileCommand {
     #[clap(short, long, help = "Should generate python bindings")]
     pub gen_py: bool,
 
+    #[clap(short, long, help = "Print the planned steps of each query instead of compiling")]
+    pub explain: bool,
+
     // #[clap(short, long, help = "The target platform")]
     // pub target: Option<String>,
 
@@ -93,6 +99,19 @@ pub struct CompileCommand {
 pub struct LintCommand {
     #[clap(short, long, help = "The path to the project")]
     pub path: Option<String>,
+
+    #[clap(long, help = "Print diagnostics as JSON, for editors and CI")]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+#[clap(name = "fmt", about = "Format the .hx files of a Helix project")]
+pub struct FmtCommand {
+    #[clap(short, long, help = "The path to the project")]
+    pub path: Option<String>,
+
+    #[clap(long, help = "Check the files are formatted without changing them")]
+    pub check: bool,
 }
 
 #[derive(Debug, Args)]
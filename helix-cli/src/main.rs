@@ -3,7 +3,10 @@ use clap::Parser;
 use helixdb::{
     helix_engine::graph_core::config::Config,
     helixc::{
-        generator::generator::CodeGenerator,
+        analyzer::analyzer::check_source,
+        diagnostic::render::SourceFiles,
+        formatter::formatter::format_source,
+        generator::{explain::explain_source, generator::CodeGenerator},
         parser::helix_parser::{HelixParser, Source},
     },
     ingestion_engine::sqlite::SqliteIngestor,
@@ -12,7 +15,7 @@ use indicatif::{ProgressBar, ProgressStyle};
 use instance_manager::InstanceManager;
 use std::path::PathBuf;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet, VecDeque},
     fs::{self, DirEntry},
     io::{ErrorKind, Write},
     net::{SocketAddr, TcpListener},
@@ -264,6 +267,11 @@ fn main() {
             // );
             // number of files
             let numb_of_files = files.len();
+            if let Err(e) = compile_hql_to_source(&files) {
+                finish_spinner_with_message(&spinner, false, "Failed to check queries");
+                println!("{}", e);
+                return;
+            }
             let mut successes = HashMap::new();
             let mut errors = HashMap::new();
             let mut code = String::new();
@@ -531,13 +539,17 @@ fn main() {
             let source = match compile_hql_to_source(&files) {
                 Ok(source) => source,
                 Err(e) => {
-                    println!("\n❌ Failed to parse source");
-                    println!("|");
-                    println!("└─ {}", e);
+                    println!("\n❌ Failed to parse source\n");
+                    println!("{}", e);
                     return;
                 }
             };
 
+            if command.explain {
+                println!("{}", explain_source(&source));
+                return;
+            }
+
             generate_rust_from_source(&source, &output, files.len());
             if command.gen_py {
                 generate_python_bindings(&source, &output);
@@ -569,16 +581,95 @@ fn main() {
                 return;
             }
 
-            match compile_hql_to_source(&files) {
-                Ok(_) => {
-                    println!("✅ Successfully parsed source");
+            let source_files = match read_source_files(&files) {
+                Ok(source_files) => source_files,
+                Err(e) => {
+                    println!("{}", e);
+                    return;
+                }
+            };
+
+            let diagnostics = match check_source(source_files.contents()) {
+                Ok(_) => Vec::new(),
+                Err(diagnostics) => diagnostics,
+            };
+            if command.json {
+                println!("{}", source_files.to_json(&diagnostics));
+            } else if diagnostics.is_empty() {
+                println!("✅ Successfully parsed and checked source");
+            } else {
+                println!("\n❌ Failed to check source\n");
+                for diagnostic in &diagnostics {
+                    println!("{}", source_files.render(diagnostic));
                 }
+                println!("{} error(s) found", diagnostics.len());
+            }
+            if !diagnostics.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        args::CommandType::Fmt(command) => {
+            let path = match &command.path {
+                Some(path) => path,
+                None => QUERIES_DIR,
+            };
+
+            let files = match check_and_read_files(path) {
+                Ok(files) => files,
                 Err(e) => {
-                    println!("\n❌ Failed to parse source");
-                    println!("|");
-                    println!("└─ {}", e);
+                    println!("{}", e);
                     return;
                 }
+            };
+
+            let mut unformatted = 0;
+            let mut failed = 0;
+            for file in &files {
+                let name = file.file_name().to_string_lossy().into_owned();
+                let contents = match fs::read_to_string(file.path()) {
+                    Ok(contents) => contents,
+                    Err(e) => {
+                        println!("{}", CliError::Io(e));
+                        failed += 1;
+                        continue;
+                    }
+                };
+                match format_source(&contents) {
+                    Ok(formatted) if formatted == contents => {}
+                    Ok(_) if command.check => {
+                        println!("{} is not formatted", name);
+                        unformatted += 1;
+                    }
+                    Ok(formatted) => match fs::write(file.path(), formatted) {
+                        Ok(_) => println!("Formatted {}", name),
+                        Err(e) => {
+                            println!("{}", CliError::Io(e));
+                            failed += 1;
+                        }
+                    },
+                    Err(diagnostic) => {
+                        let mut source_files = SourceFiles::new();
+                        source_files.add(name, &contents);
+                        println!("{}", source_files.render(&diagnostic));
+                        failed += 1;
+                    }
+                }
+            }
+
+            if failed > 0 {
+                println!("\n❌ {} file(s) could not be formatted", failed);
+            }
+            if unformatted > 0 {
+                println!(
+                    "\n❌ {} file(s) need formatting, run `helix fmt` to fix them",
+                    unformatted
+                );
+            }
+            if failed > 0 || unformatted > 0 {
+                std::process::exit(1);
+            }
+            if command.check {
+                println!("✅ All files are formatted");
             }
         }
         args::CommandType::Install(_command) => {
@@ -940,34 +1031,47 @@ fn check_hql_files(files: &Vec<DirEntry>) -> Result<(), CliError> {
     Ok(())
 }
 
-fn compile_hql_to_source(files: &Vec<DirEntry>) -> Result<Source, CliError> {
-    // let numb_of_files = files.len();
-    // let mut code = String::new();
-    // let mut generator = CodeGenerator::new();
-
-    let contents: String = files
+/// Reads `files` and the files they `IMPORT`, which are relative to the importing file.
+/// Each file is read once, however many times it is imported.
+fn read_source_files(files: &Vec<DirEntry>) -> Result<SourceFiles, CliError> {
+    let mut source_files = SourceFiles::new();
+    let mut seen = HashSet::new();
+    let mut queue = files
         .iter()
-        .map(|file| -> String {
-            match fs::read_to_string(file.path()) {
-                Ok(contents) => contents,
-                Err(e) => {
-                    panic!("{}", e);
-                }
-            }
-        })
-        .fold(String::new(), |acc, contents| acc + &contents);
-
-    let source = match HelixParser::parse_source(&contents) {
-        Ok(source) => {
-            // println!("{:?}", parser);
-            source
+        .map(|file| (file.path(), file.file_name().to_string_lossy().to_string()))
+        .collect::<VecDeque<_>>();
+    while let Some((path, name)) = queue.pop_front() {
+        let canonical = fs::canonicalize(&path)
+            .map_err(|e| CliError::from(format!("Could not read {}: {}\n", name, e)))?;
+        if !seen.insert(canonical) {
+            continue;
         }
-        Err(e) => {
-            return Err(CliError::from(format!("{}\n", e)));
+        let contents = fs::read_to_string(&path)?;
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        for import in HelixParser::parse_imports(&contents) {
+            queue.push_back((dir.join(&import.path), import.path));
         }
-    };
+        source_files.add(name, &contents);
+    }
+    Ok(source_files)
+}
 
-    Ok(source)
+fn compile_hql_to_source(files: &Vec<DirEntry>) -> Result<Source, CliError> {
+    let source_files = read_source_files(files)?;
+    match check_source(source_files.contents()) {
+        Ok(source) => Ok(source),
+        Err(diagnostics) => {
+            let rendered = diagnostics
+                .iter()
+                .map(|diagnostic| source_files.render(diagnostic))
+                .collect::<Vec<_>>();
+            Err(CliError::from(format!(
+                "{}\n{} error(s) found\n",
+                rendered.join("\n"),
+                diagnostics.len()
+            )))
+        }
+    }
 }
 
 fn generate_rust_from_source(source: &Source, output_path: &String, numb_of_files: usize) {
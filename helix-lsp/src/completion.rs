@@ -0,0 +1,258 @@
+use std::collections::BTreeMap;
+
+use helixdb::helixc::{analyzer::analyzer::type_name, parser::helix_parser::Source};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionParams, CompletionResponse, Position, Url,
+};
+
+use crate::project::{is_identifier_char, Project};
+
+/// Steps that can follow `::`, with what they do
+const STEPS: &[(&str, &str)] = &[
+    ("Out", "nodes at the end of outgoing edges"),
+    ("In", "nodes at the start of incoming edges"),
+    ("Both", "nodes connected in either direction"),
+    ("OutE", "outgoing edges"),
+    ("InE", "incoming edges"),
+    ("BothE", "edges in either direction"),
+    ("OutN", "node an edge comes from"),
+    ("InN", "node an edge goes to"),
+    ("BothN", "both nodes of an edge"),
+    ("WHERE", "keep items matching a condition"),
+    ("COUNT", "number of items"),
+    ("ID", "ids of the items"),
+    ("RANGE", "items in a range"),
+    ("ORDER", "sort by properties"),
+    ("DEDUP", "remove duplicates"),
+    ("GROUP_BY", "group by a property"),
+    ("AGG", "aggregate groups"),
+    ("AS", "label the current items"),
+    ("SELECT", "return labelled items"),
+    ("UNION", "merge traversals"),
+    ("OPTIONAL", "follow a traversal if it leads anywhere"),
+    ("COALESCE", "first traversal that leads anywhere"),
+    ("PredictLinks", "predict missing edges"),
+    ("Ancestors", "nodes reachable backwards"),
+    ("Descendants", "nodes reachable forwards"),
+    ("TopoSort", "nodes in topological order"),
+    ("FindCycle", "a cycle if there is one"),
+    ("CriticalPath", "longest weighted path"),
+    ("AddE", "add an edge"),
+    ("UPDATE", "set properties"),
+    ("GT", "greater than"),
+    ("GTE", "greater than or equal"),
+    ("LT", "less than"),
+    ("LTE", "less than or equal"),
+    ("EQ", "equal"),
+    ("NEQ", "not equal"),
+];
+
+const KEYWORDS: &[&str] = &[
+    "QUERY",
+    "RETURN",
+    "N",
+    "E",
+    "V",
+    "AddN",
+    "AddE",
+    "UpsertN",
+    "UpsertE",
+    "AddV",
+    "BatchAddV",
+    "SearchV",
+    "MATCH",
+    "EXISTS",
+    "AND",
+    "OR",
+    "DROP",
+    "IF",
+    "ELSE",
+    "FOR",
+    "IN",
+    "FN",
+    "IMPORT",
+];
+
+/// What kind of name fits where the cursor is
+#[derive(Debug, PartialEq)]
+enum Context {
+    NodeLabel,
+    EdgeLabel,
+    VectorLabel,
+    Step,
+    Property,
+    Other,
+}
+
+pub fn complete(
+    project: &Project,
+    params: CompletionParams,
+) -> Result<Option<CompletionResponse>, String> {
+    let position = params.text_document_position;
+    let Some(source) = &project.source else {
+        return Ok(None);
+    };
+    let items = match context(project, &position.text_document.uri, position.position) {
+        Context::NodeLabel => labels(
+            source.node_schemas.iter().map(|schema| &schema.name),
+            "node",
+        ),
+        Context::EdgeLabel => source
+            .edge_schemas
+            .iter()
+            .map(|schema| CompletionItem {
+                label: schema.name.clone(),
+                kind: Some(CompletionItemKind::CLASS),
+                detail: Some(format!("edge from {} to {}", schema.from, schema.to)),
+                ..Default::default()
+            })
+            .collect(),
+        Context::VectorLabel => labels(
+            source.vector_schemas.iter().map(|schema| &schema.name),
+            "vector",
+        ),
+        Context::Step => STEPS
+            .iter()
+            .map(|(step, detail)| CompletionItem {
+                label: step.to_string(),
+                kind: Some(CompletionItemKind::METHOD),
+                detail: Some(detail.to_string()),
+                ..Default::default()
+            })
+            .collect(),
+        Context::Property => properties(source),
+        Context::Other => KEYWORDS
+            .iter()
+            .map(|keyword| CompletionItem {
+                label: keyword.to_string(),
+                kind: Some(CompletionItemKind::KEYWORD),
+                ..Default::default()
+            })
+            .chain(labels(
+                source.node_schemas.iter().map(|schema| &schema.name),
+                "node",
+            ))
+            .collect(),
+    };
+    Ok(Some(CompletionResponse::Array(items)))
+}
+
+/// Looks at the text before the cursor, minus the name being typed
+fn context(project: &Project, uri: &Url, position: Position) -> Context {
+    let Some((line, column)) = project.point(uri, position) else {
+        return Context::Other;
+    };
+    let prefix = project
+        .line(line)
+        .unwrap_or_default()
+        .chars()
+        .take(column - 1)
+        .collect::<String>();
+    let before = prefix.trim_end_matches(is_identifier_char);
+
+    if let Some(callee) = before.strip_suffix('<') {
+        let start = callee
+            .rfind(|c| !is_identifier_char(c))
+            .map_or(0, |i| i + 1);
+        return match &callee[start..] {
+            "N" | "AddN" | "UpsertN" => Context::NodeLabel,
+            "V" | "AddV" | "SearchV" | "BatchAddV" => Context::VectorLabel,
+            "E" | "AddE" | "UpsertE" | "Out" | "In" | "Both" | "OutE" | "InE" | "BothE"
+            | "PredictLinks" | "Ancestors" | "Descendants" | "TopoSort" | "FindCycle"
+            | "CriticalPath" => Context::EdgeLabel,
+            _ => Context::Other,
+        };
+    }
+    if before.ends_with("::") {
+        return Context::Step;
+    }
+    let open = prefix.matches('{').count() > prefix.matches('}').count();
+    let before = before.trim_end();
+    if open && (before.ends_with('{') || before.ends_with(',') || before.ends_with('(')) {
+        return Context::Property;
+    }
+    Context::Other
+}
+
+fn labels<'a>(names: impl Iterator<Item = &'a String>, kind: &str) -> Vec<CompletionItem> {
+    names
+        .map(|name| CompletionItem {
+            label: name.clone(),
+            kind: Some(CompletionItemKind::CLASS),
+            detail: Some(format!("{} type", kind)),
+            ..Default::default()
+        })
+        .collect()
+}
+
+/// Properties of every node and edge, a name several schemas declare is offered once
+fn properties(source: &Source) -> Vec<CompletionItem> {
+    let mut owners = BTreeMap::<&str, Vec<String>>::new();
+    for name in ["id", "label"] {
+        owners.entry(name).or_default().push("String".to_string());
+    }
+    let fields = source
+        .node_schemas
+        .iter()
+        .map(|schema| (&schema.name, schema.fields.as_slice()))
+        .chain(source.edge_schemas.iter().map(|schema| {
+            (
+                &schema.name,
+                schema.properties.as_deref().unwrap_or_default(),
+            )
+        }));
+    for (owner, fields) in fields {
+        for field in fields {
+            owners.entry(&field.name).or_default().push(format!(
+                "{}.{}: {}",
+                owner,
+                field.name,
+                type_name(&field.field_type)
+            ));
+        }
+    }
+    owners
+        .into_iter()
+        .map(|(name, details)| CompletionItem {
+            label: name.to_string(),
+            kind: Some(CompletionItemKind::FIELD),
+            detail: Some(details.join(", ")),
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::tests::{project, uri, QUERIES};
+
+    fn context_at(line: &str) -> Context {
+        let project = project(&format!("{}{}", QUERIES, line));
+        let position = Position::new(3, line.chars().count() as u32);
+        context(&project, &uri("queries.hx"), position)
+    }
+
+    #[test]
+    fn test_context() {
+        assert_eq!(context_at("    u <- N<Us"), Context::NodeLabel);
+        assert_eq!(context_at("    u <- N<User>::OutE<"), Context::EdgeLabel);
+        assert_eq!(context_at("    u <- SearchV<"), Context::VectorLabel);
+        assert_eq!(context_at("    u <- N<User>::"), Context::Step);
+        assert_eq!(context_at("    u <- N<User>::{na"), Context::Property);
+        assert_eq!(
+            context_at("    u <- AddN<User>({name: n, "),
+            Context::Property
+        );
+        assert_eq!(context_at("    RET"), Context::Other);
+    }
+
+    #[test]
+    fn test_properties_list_every_owner() {
+        let project = project(QUERIES);
+        let items = properties(project.source.as_ref().unwrap());
+        let since = items.iter().find(|item| item.label == "since").unwrap();
+        assert_eq!(since.detail.as_deref(), Some("Follows.since: Integer"));
+        assert!(items.iter().any(|item| item.label == "id"));
+    }
+}
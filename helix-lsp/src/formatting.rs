@@ -0,0 +1,66 @@
+use helixdb::helixc::formatter::formatter::format_source;
+use lsp_types::{DocumentFormattingParams, Position, Range, TextEdit};
+
+use crate::project::Project;
+
+/// Replaces the whole document with its formatted text. Documents with syntax errors are
+/// left alone, their diagnostics already point at the problem.
+pub fn format(
+    project: &Project,
+    params: DocumentFormattingParams,
+) -> Result<Option<Vec<TextEdit>>, String> {
+    let Some(text) = project.text(&params.text_document.uri) else {
+        return Ok(None);
+    };
+    let Ok(formatted) = format_source(text) else {
+        return Ok(None);
+    };
+    if formatted == text {
+        return Ok(Some(Vec::new()));
+    }
+    let range = Range::new(Position::new(0, 0), end_of(text));
+    Ok(Some(vec![TextEdit::new(range, formatted)]))
+}
+
+fn end_of(text: &str) -> Position {
+    let line = text.matches('\n').count();
+    let last = text.rsplit('\n').next().unwrap_or_default();
+    Position::new(line as u32, last.encode_utf16().count() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::tests::{project, uri};
+    use lsp_types::{FormattingOptions, TextDocumentIdentifier, WorkDoneProgressParams};
+
+    fn format_queries(queries: &str) -> Option<Vec<TextEdit>> {
+        let params = DocumentFormattingParams {
+            text_document: TextDocumentIdentifier::new(uri("queries.hx")),
+            options: FormattingOptions::default(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        format(&project(queries), params).unwrap()
+    }
+
+    #[test]
+    fn test_format_replaces_the_document() {
+        let edits = format_queries("QUERY q()=>\n  u<-N<User>\n  RETURN u").unwrap();
+        assert_eq!(edits.len(), 1);
+        assert_eq!(
+            edits[0].range,
+            Range::new(Position::new(0, 0), Position::new(2, 10))
+        );
+        assert_eq!(
+            edits[0].new_text,
+            "QUERY q() =>\n    u <- N<User>\n    RETURN u\n"
+        );
+    }
+
+    #[test]
+    fn test_format_leaves_broken_and_formatted_documents() {
+        assert_eq!(format_queries("QUERY q( =>\n"), None);
+        let formatted = "QUERY q() =>\n    u <- N<User>\n    RETURN u\n";
+        assert_eq!(format_queries(formatted), Some(Vec::new()));
+    }
+}
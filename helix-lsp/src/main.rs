@@ -0,0 +1,187 @@
+use std::error::Error;
+
+use lsp_server::{Connection, ErrorCode, ExtractError, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument, DidSaveTextDocument,
+        Notification as _, PublishDiagnostics,
+    },
+    request::{
+        Completion, DocumentSymbolRequest, Formatting, GotoDefinition, HoverRequest, Rename,
+    },
+    CompletionOptions, DidChangeTextDocumentParams, DidCloseTextDocumentParams,
+    DidOpenTextDocumentParams, HoverProviderCapability, InitializeParams, OneOf,
+    PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability, TextDocumentSyncKind,
+    TextDocumentSyncOptions, TextDocumentSyncSaveOptions,
+};
+
+use crate::{
+    completion::complete,
+    formatting::format,
+    navigation::{definition, hover, rename, symbols},
+    project::Project,
+};
+
+mod completion;
+mod formatting;
+mod navigation;
+mod project;
+
+/// Language server for `.hx` files, talks LSP over stdin and stdout
+fn main() -> Result<(), Box<dyn Error + Send + Sync>> {
+    let (connection, io_threads) = Connection::stdio();
+    let params = connection.initialize(serde_json::to_value(capabilities())?)?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+
+    #[allow(deprecated)]
+    let root = params
+        .workspace_folders
+        .and_then(|folders| folders.into_iter().next())
+        .map(|folder| folder.uri)
+        .or(params.root_uri)
+        .and_then(|uri| uri.to_file_path().ok());
+    let mut server = Server {
+        connection,
+        project: Project::load(root.as_deref()),
+    };
+    server.publish_diagnostics()?;
+    server.run()?;
+    io_threads.join()?;
+    Ok(())
+}
+
+fn capabilities() -> ServerCapabilities {
+    ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Options(
+            TextDocumentSyncOptions {
+                open_close: Some(true),
+                change: Some(TextDocumentSyncKind::FULL),
+                save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                ..Default::default()
+            },
+        )),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec!["<".to_string(), ":".to_string(), "{".to_string()]),
+            ..Default::default()
+        }),
+        definition_provider: Some(OneOf::Left(true)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        document_symbol_provider: Some(OneOf::Left(true)),
+        rename_provider: Some(OneOf::Left(true)),
+        document_formatting_provider: Some(OneOf::Left(true)),
+        ..Default::default()
+    }
+}
+
+struct Server {
+    connection: Connection,
+    project: Project,
+}
+
+type Handler<R> = fn(
+    &Project,
+    <R as lsp_types::request::Request>::Params,
+) -> Result<<R as lsp_types::request::Request>::Result, String>;
+
+impl Server {
+    fn run(&mut self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        while let Ok(message) = self.connection.receiver.recv() {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    self.connection.sender.send(Message::Response(response))?;
+                }
+                Message::Notification(notification) => self.handle_notification(notification)?,
+                Message::Response(_) => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_request(&self, request: Request) -> Response {
+        self.dispatch::<Completion>(request, complete)
+            .or_else(|request| self.dispatch::<GotoDefinition>(request, definition))
+            .or_else(|request| self.dispatch::<HoverRequest>(request, hover))
+            .or_else(|request| self.dispatch::<DocumentSymbolRequest>(request, symbols))
+            .or_else(|request| self.dispatch::<Rename>(request, rename))
+            .or_else(|request| self.dispatch::<Formatting>(request, format))
+            .unwrap_or_else(|request| {
+                Response::new_err(
+                    request.id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("Unsupported request {}", request.method),
+                )
+            })
+    }
+
+    /// Runs `handler` if `request` is an `R`, gives the request back otherwise
+    fn dispatch<R: lsp_types::request::Request>(
+        &self,
+        request: Request,
+        handler: Handler<R>,
+    ) -> Result<Response, Request> {
+        let id = request.id.clone();
+        match request.extract::<R::Params>(R::METHOD) {
+            Ok((_, params)) => Ok(match handler(&self.project, params) {
+                Ok(result) => Response::new_ok(id, result),
+                Err(message) => Response::new_err(id, ErrorCode::RequestFailed as i32, message),
+            }),
+            Err(ExtractError::MethodMismatch(request)) => Err(request),
+            Err(ExtractError::JsonError { error, .. }) => Ok(Response::new_err(
+                id,
+                ErrorCode::InvalidParams as i32,
+                error.to_string(),
+            )),
+        }
+    }
+
+    fn handle_notification(
+        &mut self,
+        notification: Notification,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let document = params.text_document;
+                self.project.set_document(document.uri, document.text);
+                self.publish_diagnostics()?;
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                // full sync, the last change is the whole document
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.project
+                        .set_document(params.text_document.uri, change.text);
+                }
+            }
+            DidSaveTextDocument::METHOD => self.publish_diagnostics()?,
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                self.project.close_document(&params.text_document.uri);
+                self.publish_diagnostics()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Diagnostics cover the whole project, a change to a schema can break queries in other files
+    fn publish_diagnostics(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        for (uri, diagnostics) in self.project.lsp_diagnostics() {
+            let params = PublishDiagnosticsParams::new(uri, diagnostics, None);
+            self.connection
+                .sender
+                .send(Message::Notification(Notification::new(
+                    PublishDiagnostics::METHOD.to_string(),
+                    params,
+                )))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::{BTreeSet, HashMap};
+
+use helixdb::helixc::{
+    analyzer::analyzer::type_name,
+    parser::helix_parser::{Field, Loc, Source},
+};
+use lsp_types::{
+    DocumentSymbol, DocumentSymbolParams, DocumentSymbolResponse, GotoDefinitionParams,
+    GotoDefinitionResponse, Hover, HoverContents, HoverParams, MarkupContent, MarkupKind, Position,
+    RenameParams, SymbolKind, TextEdit, Url, WorkspaceEdit,
+};
+
+use crate::project::{contains, Project};
+
+/// What a name in the source is declared as
+enum Definition<'a> {
+    Node(&'a str, &'a [Field]),
+    Edge(&'a str, &'a str, &'a str, &'a [Field]),
+    Vector(&'a str),
+    Field(&'a str, &'a Field),
+}
+
+/// The name at `position` and the location it is declared at
+fn resolve(project: &Project, uri: &Url, position: Position) -> Option<(String, Loc)> {
+    let (word, point) = project.word_at(uri, position)?;
+    let source = project.source.as_ref()?;
+
+    let definition = project
+        .references
+        .iter()
+        .find(|reference| reference.name == word && contains(&reference.loc, point))
+        .map(|reference| reference.definition)
+        .or_else(|| {
+            // the name of a declaration resolves to itself
+            declarations(source).find_map(|(loc, definition)| {
+                let name = match definition {
+                    Definition::Field(_, field) => field.name.as_str(),
+                    Definition::Node(name, _)
+                    | Definition::Edge(name, ..)
+                    | Definition::Vector(name) => name,
+                };
+                (name == word && contains(&loc, point)).then_some(loc)
+            })
+        })
+        .or_else(|| {
+            // labels in places the analyzer doesn't resolve, like field types
+            declarations(source).find_map(|(loc, definition)| match definition {
+                Definition::Node(name, _)
+                | Definition::Edge(name, ..)
+                | Definition::Vector(name)
+                    if name == word =>
+                {
+                    Some(loc)
+                }
+                _ => None,
+            })
+        })?;
+    Some((word, definition))
+}
+
+/// Every schema and field with its location, fields come after the schema they are in
+fn declarations(source: &Source) -> impl Iterator<Item = (Loc, Definition<'_>)> {
+    let nodes = source.node_schemas.iter().flat_map(|schema| {
+        let fields = schema
+            .fields
+            .iter()
+            .map(move |field| (field.loc, Definition::Field(schema.name.as_str(), field)));
+        std::iter::once((
+            schema.loc,
+            Definition::Node(schema.name.as_str(), schema.fields.as_slice()),
+        ))
+        .chain(fields)
+    });
+    let edges = source.edge_schemas.iter().flat_map(|schema| {
+        let properties = schema.properties.as_deref().unwrap_or_default();
+        let fields = properties
+            .iter()
+            .map(move |field| (field.loc, Definition::Field(schema.name.as_str(), field)));
+        std::iter::once((
+            schema.loc,
+            Definition::Edge(
+                schema.name.as_str(),
+                schema.from.as_str(),
+                schema.to.as_str(),
+                properties,
+            ),
+        ))
+        .chain(fields)
+    });
+    let vectors = source
+        .vector_schemas
+        .iter()
+        .map(|schema| (schema.loc, Definition::Vector(schema.name.as_str())));
+    nodes.chain(edges).chain(vectors)
+}
+
+fn definition_at(source: &Source, loc: Loc) -> Option<Definition<'_>> {
+    declarations(source)
+        .find(|(declared, _)| *declared == loc)
+        .map(|(_, definition)| definition)
+}
+
+pub fn definition(
+    project: &Project,
+    params: GotoDefinitionParams,
+) -> Result<Option<GotoDefinitionResponse>, String> {
+    let position = params.text_document_position_params;
+    Ok(
+        resolve(project, &position.text_document.uri, position.position)
+            .and_then(|(_, loc)| project.location(&loc))
+            .map(GotoDefinitionResponse::Scalar),
+    )
+}
+
+pub fn hover(project: &Project, params: HoverParams) -> Result<Option<Hover>, String> {
+    let position = params.text_document_position_params;
+    let Some((_, loc)) = resolve(project, &position.text_document.uri, position.position) else {
+        return Ok(None);
+    };
+    let Some(definition) = project
+        .source
+        .as_ref()
+        .and_then(|source| definition_at(source, loc))
+    else {
+        return Ok(None);
+    };
+
+    let fields = |fields: &[Field], indent: &str| {
+        fields
+            .iter()
+            .map(|field| format!("{}{}: {}", indent, field.name, type_name(&field.field_type)))
+            .collect::<Vec<_>>()
+            .join(",\n")
+    };
+    let value = match definition {
+        Definition::Node(name, node_fields) => {
+            format!("N::{} {{\n{}\n}}", name, fields(node_fields, "    "))
+        }
+        Definition::Edge(name, from, to, properties) => format!(
+            "E::{} {{\n    From: {},\n    To: {},\n    Properties: {{\n{}\n    }}\n}}",
+            name,
+            from,
+            to,
+            fields(properties, "        ")
+        ),
+        Definition::Vector(name) => format!("V::{}", name),
+        Definition::Field(owner, field) => {
+            format!("{}.{}: {}", owner, field.name, type_name(&field.field_type))
+        }
+    };
+    Ok(Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value: format!("```helixql\n{}\n```", value),
+        }),
+        range: None,
+    }))
+}
+
+pub fn symbols(
+    project: &Project,
+    params: DocumentSymbolParams,
+) -> Result<Option<DocumentSymbolResponse>, String> {
+    let Some(source) = &project.source else {
+        return Ok(None);
+    };
+    let uri = params.text_document.uri;
+    let symbol = |name: &str, detail: String, kind: SymbolKind, loc: &Loc| {
+        let location = project
+            .location(loc)
+            .filter(|location| location.uri == uri)?;
+        #[allow(deprecated)]
+        Some(DocumentSymbol {
+            name: name.to_string(),
+            detail: Some(detail),
+            kind,
+            tags: None,
+            deprecated: None,
+            range: location.range,
+            selection_range: location.range,
+            children: None,
+        })
+    };
+    let with_fields = |parent: Option<DocumentSymbol>, fields: &[Field]| {
+        let mut parent = parent?;
+        let children = fields.iter().filter_map(|field| {
+            let detail = type_name(&field.field_type);
+            symbol(&field.name, detail, SymbolKind::FIELD, &field.loc)
+        });
+        parent.children = Some(children.collect());
+        Some(parent)
+    };
+
+    let nodes = source.node_schemas.iter().filter_map(|schema| {
+        let node = symbol(
+            &schema.name,
+            "node".to_string(),
+            SymbolKind::STRUCT,
+            &schema.loc,
+        );
+        with_fields(node, &schema.fields)
+    });
+    let edges = source.edge_schemas.iter().filter_map(|schema| {
+        let detail = format!("edge from {} to {}", schema.from, schema.to);
+        let edge = symbol(&schema.name, detail, SymbolKind::STRUCT, &schema.loc);
+        with_fields(edge, schema.properties.as_deref().unwrap_or_default())
+    });
+    let vectors = source.vector_schemas.iter().filter_map(|schema| {
+        symbol(
+            &schema.name,
+            "vector".to_string(),
+            SymbolKind::STRUCT,
+            &schema.loc,
+        )
+    });
+    let queries = source.queries.iter().filter_map(|query| {
+        let parameters = query
+            .parameters
+            .iter()
+            .map(|parameter| format!("{}: {}", parameter.name, type_name(&parameter.param_type)))
+            .collect::<Vec<_>>();
+        let detail = format!("({})", parameters.join(", "));
+        symbol(&query.name, detail, SymbolKind::FUNCTION, &query.loc)
+    });
+
+    let mut symbols = nodes
+        .chain(edges)
+        .chain(vectors)
+        .chain(queries)
+        .collect::<Vec<_>>();
+    symbols.sort_by_key(|symbol| symbol.range.start);
+    Ok(Some(DocumentSymbolResponse::Nested(symbols)))
+}
+
+/// Renames a schema field, in its declaration and everywhere the analyzer resolved it
+pub fn rename(project: &Project, params: RenameParams) -> Result<Option<WorkspaceEdit>, String> {
+    let position = params.text_document_position;
+    let new_name = params.new_name;
+    if project.stale {
+        return Err("Fix the syntax errors before renaming".to_string());
+    }
+    let valid = new_name.starts_with(|c: char| c.is_ascii_alphabetic())
+        && new_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(format!("{} is not a valid property name", new_name));
+    }
+
+    let Some((name, definition)) = resolve(project, &position.text_document.uri, position.position)
+    else {
+        return Ok(None);
+    };
+    let source = project.source.as_ref().ok_or("Nothing is parsed yet")?;
+    if !matches!(
+        definition_at(source, definition),
+        Some(Definition::Field(..))
+    ) {
+        return Err("Only schema fields can be renamed".to_string());
+    }
+
+    let uses = project
+        .references
+        .iter()
+        .filter(|reference| reference.definition == definition && reference.name == name)
+        .map(|reference| reference.loc);
+    let tokens = std::iter::once(definition)
+        .chain(uses)
+        .filter_map(|loc| project.find_token(&loc, &name))
+        .collect::<BTreeSet<_>>();
+
+    let mut changes = HashMap::<Url, Vec<TextEdit>>::new();
+    for token in tokens {
+        if let Some(location) = project.location(&token) {
+            changes
+                .entry(location.uri)
+                .or_default()
+                .push(TextEdit::new(location.range, new_name.clone()));
+        }
+    }
+    Ok(Some(WorkspaceEdit {
+        changes: Some(changes),
+        ..Default::default()
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::tests::{project, uri, QUERIES};
+    use lsp_types::{
+        PartialResultParams, Range, TextDocumentIdentifier, TextDocumentPositionParams,
+        WorkDoneProgressParams,
+    };
+
+    fn at(name: &str, line: u32, character: u32) -> TextDocumentPositionParams {
+        TextDocumentPositionParams::new(
+            TextDocumentIdentifier::new(uri(name)),
+            Position::new(line, character),
+        )
+    }
+
+    fn range(line: u32, start: u32, end: u32) -> Range {
+        Range::new(Position::new(line, start), Position::new(line, end))
+    }
+
+    #[test]
+    fn test_definition_of_labels_and_properties() {
+        let project = project(QUERIES);
+        let definition_at = |line, character| {
+            let params = GotoDefinitionParams {
+                text_document_position_params: at("queries.hx", line, character),
+                work_done_progress_params: WorkDoneProgressParams::default(),
+                partial_result_params: PartialResultParams::default(),
+            };
+            match definition(&project, params).unwrap() {
+                Some(GotoDefinitionResponse::Scalar(location)) => {
+                    (location.uri, location.range.start)
+                }
+                response => panic!("Expected a location, got {:?}", response),
+            }
+        };
+        // N<User>, In<Follows> and {name}
+        assert_eq!(
+            definition_at(1, 17),
+            (uri("schema.hx"), Position::new(0, 0))
+        );
+        assert_eq!(
+            definition_at(1, 30),
+            (uri("schema.hx"), Position::new(5, 0))
+        );
+        assert_eq!(
+            definition_at(1, 42),
+            (uri("schema.hx"), Position::new(1, 4))
+        );
+    }
+
+    #[test]
+    fn test_hover_shows_field_types() {
+        let project = project(QUERIES);
+        let params = HoverParams {
+            text_document_position_params: at("queries.hx", 1, 42),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        match hover(&project, params).unwrap().unwrap().contents {
+            HoverContents::Markup(markup) => {
+                assert_eq!(markup.value, "```helixql\nUser.name: String\n```")
+            }
+            contents => panic!("Expected markup, got {:?}", contents),
+        }
+    }
+
+    #[test]
+    fn test_symbols() {
+        let project = project(QUERIES);
+        let params = DocumentSymbolParams {
+            text_document: TextDocumentIdentifier::new(uri("schema.hx")),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+            partial_result_params: PartialResultParams::default(),
+        };
+        let Some(DocumentSymbolResponse::Nested(symbols)) = symbols(&project, params).unwrap()
+        else {
+            panic!("Expected nested symbols");
+        };
+        let names = symbols
+            .iter()
+            .map(|symbol| {
+                let children = symbol.children.iter().flatten();
+                let fields = children
+                    .map(|child| child.name.as_str())
+                    .collect::<Vec<_>>();
+                (symbol.name.as_str(), fields)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![("User", vec!["name", "age"]), ("Follows", vec!["since"])]
+        );
+    }
+
+    #[test]
+    fn test_rename_field_across_files() {
+        let project = project(QUERIES);
+        let params = RenameParams {
+            text_document_position: at("schema.hx", 1, 6),
+            new_name: "fullName".to_string(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        let changes = rename(&project, params).unwrap().unwrap().changes.unwrap();
+        let edits = |name| {
+            changes[&uri(name)]
+                .iter()
+                .map(|edit| (edit.range, edit.new_text.as_str()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(edits("schema.hx"), vec![(range(1, 4, 8), "fullName")]);
+        assert_eq!(edits("queries.hx"), vec![(range(1, 40, 44), "fullName")]);
+    }
+
+    #[test]
+    fn test_rename_only_fields() {
+        let project = project(QUERIES);
+        let params = RenameParams {
+            text_document_position: at("queries.hx", 1, 17),
+            new_name: "Person".to_string(),
+            work_done_progress_params: WorkDoneProgressParams::default(),
+        };
+        assert!(rename(&project, params).is_err());
+    }
+}
@@ -0,0 +1,389 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use helixdb::helixc::{
+    analyzer::analyzer::{analyze, references, Reference},
+    diagnostic::{diagnostic::Diagnostic, render::SourceFiles},
+    parser::helix_parser::{HelixParser, Loc, Source},
+};
+use lsp_types::{
+    DiagnosticRelatedInformation, DiagnosticSeverity, Location, NumberOrString, Position, Range,
+    Url,
+};
+
+/// A line and column in the joined source of a project, both start at 1 like in `Loc`
+pub type Point = (usize, usize);
+
+/// The `.hx` files of a workspace and what was last parsed from them
+pub struct Project {
+    /// Text of every file, open documents shadow the file on disk
+    documents: BTreeMap<Url, String>,
+    files: SourceFiles,
+    /// The last source that parsed, kept while the files have syntax errors so completion
+    /// still knows the schemas
+    pub source: Option<Source>,
+    /// Whether the files changed since `source` was parsed
+    pub stale: bool,
+    pub references: Vec<Reference>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Project {
+    pub fn new() -> Self {
+        Self {
+            documents: BTreeMap::new(),
+            files: SourceFiles::new(),
+            source: None,
+            stale: false,
+            references: Vec::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Reads every `.hx` file under `root`
+    pub fn load(root: Option<&Path>) -> Self {
+        let mut project = Self::new();
+        let mut paths = Vec::new();
+        if let Some(root) = root {
+            find_hx_files(root, &mut paths);
+        }
+        for path in paths {
+            if let (Ok(uri), Ok(text)) = (Url::from_file_path(&path), fs::read_to_string(&path)) {
+                project.documents.insert(uri, text);
+            }
+        }
+        project.rebuild();
+        project
+    }
+
+    pub fn set_document(&mut self, uri: Url, text: String) {
+        self.documents.insert(uri, text);
+        self.rebuild();
+    }
+
+    /// Goes back to the file on disk, or forgets the document if it was never saved
+    pub fn close_document(&mut self, uri: &Url) {
+        let text = uri
+            .to_file_path()
+            .ok()
+            .and_then(|path| fs::read_to_string(path).ok());
+        match text {
+            Some(text) => self.documents.insert(uri.clone(), text),
+            None => self.documents.remove(uri),
+        };
+        self.rebuild();
+    }
+
+    pub fn text(&self, uri: &Url) -> Option<&str> {
+        self.documents.get(uri).map(String::as_str)
+    }
+
+    fn rebuild(&mut self) {
+        self.files = SourceFiles::new();
+        for (uri, text) in &self.documents {
+            self.files.add(uri.as_str(), text);
+        }
+
+        match HelixParser::parse_source_diagnostics(self.files.contents()) {
+            Ok(source) => {
+                self.diagnostics = analyze(&source);
+                self.references = references(&source);
+                self.source = Some(source);
+                self.stale = false;
+            }
+            Err(diagnostics) => {
+                self.diagnostics = diagnostics;
+                self.references.clear();
+                self.stale = true;
+            }
+        }
+    }
+
+    /// A line of the joined source
+    pub fn line(&self, line: usize) -> Option<&str> {
+        self.files.contents().lines().nth(line.checked_sub(1)?)
+    }
+
+    /// The point in the joined source a position in `uri` is at
+    pub fn point(&self, uri: &Url, position: Position) -> Option<Point> {
+        let line = self.files.start_line(uri.as_str())? + position.line as usize;
+        let column = char_column(self.line(line).unwrap_or_default(), position.character);
+        Some((line, column))
+    }
+
+    pub fn location(&self, loc: &Loc) -> Option<Location> {
+        let (name, line) = self.files.locate(loc.line);
+        let uri = Url::parse(name).ok()?;
+        let (_, end_line) = self.files.locate(loc.end_line.max(loc.line));
+        let start = Position::new(
+            line.saturating_sub(1) as u32,
+            utf16_column(self.line(loc.line).unwrap_or_default(), loc.column),
+        );
+        let end = Position::new(
+            end_line.saturating_sub(1) as u32,
+            utf16_column(self.line(loc.end_line).unwrap_or_default(), loc.end_column),
+        );
+        Some(Location::new(uri, Range::new(start, end)))
+    }
+
+    /// The identifier at or just before `position` and the point it starts at
+    pub fn word_at(&self, uri: &Url, position: Position) -> Option<(String, Point)> {
+        let (line, column) = self.point(uri, position)?;
+        let chars = self.line(line)?.chars().collect::<Vec<_>>();
+        let is_word = |i: usize| chars.get(i).is_some_and(|c| is_identifier_char(*c));
+
+        let mut start = column - 1;
+        if !is_word(start) && start > 0 && is_word(start - 1) {
+            start -= 1;
+        }
+        if !is_word(start) {
+            return None;
+        }
+        while start > 0 && is_word(start - 1) {
+            start -= 1;
+        }
+        let word = chars[start..]
+            .iter()
+            .take_while(|c| is_identifier_char(**c))
+            .collect();
+        Some((word, (line, start + 1)))
+    }
+
+    /// The span of identifier `name` inside `loc`, skipping strings and comments
+    pub fn find_token(&self, loc: &Loc, name: &str) -> Option<Loc> {
+        let length = name.chars().count();
+        for line in loc.line..=loc.end_line.max(loc.line) {
+            let chars = self.line(line)?.chars().collect::<Vec<_>>();
+            let mut in_string = false;
+            let mut column = 0;
+            while column < chars.len() {
+                let c = chars[column];
+                let inside = (line, column + 1) >= (loc.line, loc.column)
+                    && (line, column + length + 1) <= (loc.end_line, loc.end_column);
+                if c == '"' {
+                    in_string = !in_string;
+                } else if !in_string && c == '/' && chars.get(column + 1) == Some(&'/') {
+                    break;
+                } else if !in_string && is_identifier_char(c) {
+                    let end = column
+                        + chars[column..]
+                            .iter()
+                            .take_while(|c| is_identifier_char(**c))
+                            .count();
+                    if inside && chars[column..end].iter().copied().eq(name.chars()) {
+                        return Some(Loc {
+                            line,
+                            column: column + 1,
+                            end_line: line,
+                            end_column: end + 1,
+                        });
+                    }
+                    column = end;
+                    continue;
+                }
+                column += 1;
+            }
+        }
+        None
+    }
+
+    /// Diagnostics for every document, documents without errors get an empty list so
+    /// editors clear the ones they showed before
+    pub fn lsp_diagnostics(&self) -> BTreeMap<Url, Vec<lsp_types::Diagnostic>> {
+        let mut by_file = self
+            .documents
+            .keys()
+            .map(|uri| (uri.clone(), Vec::new()))
+            .collect::<BTreeMap<_, _>>();
+        for diagnostic in &self.diagnostics {
+            let location = match self.location(&diagnostic.loc) {
+                Some(location) if diagnostic.loc.line > 0 => location,
+                // errors without a position go at the top of the first file
+                _ => match self.documents.keys().next() {
+                    Some(uri) => Location::new(uri.clone(), Range::default()),
+                    None => continue,
+                },
+            };
+            let message = match &diagnostic.suggestion {
+                Some(suggestion) => {
+                    format!("{}, did you mean `{}`?", diagnostic.message, suggestion)
+                }
+                None => diagnostic.message.clone(),
+            };
+            let related_information = diagnostic
+                .labels
+                .iter()
+                .filter_map(|label| {
+                    Some(DiagnosticRelatedInformation {
+                        location: self.location(&label.loc)?,
+                        message: label.message.clone(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            by_file
+                .entry(location.uri)
+                .or_default()
+                .push(lsp_types::Diagnostic {
+                    range: location.range,
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    code: Some(NumberOrString::String(diagnostic.code.to_string())),
+                    source: Some("helixc".to_string()),
+                    message,
+                    related_information: Some(related_information),
+                    ..Default::default()
+                });
+        }
+        by_file
+    }
+}
+
+impl Default for Project {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether `point` is inside `loc`, the end counts so a cursor right after a name is on it
+pub fn contains(loc: &Loc, point: Point) -> bool {
+    (loc.line, loc.column) <= point && point <= (loc.end_line, loc.end_column)
+}
+
+pub fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn find_hx_files(dir: &Path, paths: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if path.is_dir() {
+            if !name.starts_with('.') && name != "target" {
+                find_hx_files(&path, paths);
+            }
+        } else if name.ends_with(".hx") {
+            paths.push(path);
+        }
+    }
+}
+
+/// LSP positions count UTF-16 code units, pest columns count chars
+fn utf16_column(text: &str, column: usize) -> u32 {
+    text.chars()
+        .take(column.saturating_sub(1))
+        .map(char::len_utf16)
+        .sum::<usize>() as u32
+}
+
+fn char_column(text: &str, character: u32) -> usize {
+    let mut units = 0;
+    let mut column = 1;
+    for c in text.chars() {
+        if units >= character as usize {
+            break;
+        }
+        units += c.len_utf16();
+        column += 1;
+    }
+    column
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub const SCHEMA: &str = r#"N::User {
+    name: String,
+    age: Integer
+}
+
+E::Follows {
+    From: User,
+    To: User,
+    Properties: {
+        since: Integer
+    }
+}
+"#;
+
+    pub const QUERIES: &str = r#"QUERY followers(id: String) =>
+    users <- N<User>(id)::In<Follows>::{name}
+    RETURN users
+"#;
+
+    pub fn uri(name: &str) -> Url {
+        Url::parse(&format!("file:///project/{}", name)).unwrap()
+    }
+
+    pub fn project(queries: &str) -> Project {
+        let mut project = Project::new();
+        project.set_document(uri("schema.hx"), SCHEMA.to_string());
+        project.set_document(uri("queries.hx"), queries.to_string());
+        project
+    }
+
+    #[test]
+    fn test_points_and_locations_across_files() {
+        let project = project(QUERIES);
+        // queries.hx sorts first, schema.hx starts at line 5 after its 4 lines
+        let point = project
+            .point(&uri("schema.hx"), Position::new(1, 4))
+            .unwrap();
+        assert_eq!(point, (6, 5));
+        let location = project
+            .location(&Loc {
+                line: 6,
+                column: 5,
+                end_line: 6,
+                end_column: 9,
+            })
+            .unwrap();
+        assert_eq!(location.uri, uri("schema.hx"));
+        assert_eq!(
+            location.range,
+            Range::new(Position::new(1, 4), Position::new(1, 8))
+        );
+    }
+
+    #[test]
+    fn test_word_at() {
+        let project = project(QUERIES);
+        let queries = uri("queries.hx");
+        assert_eq!(
+            project.word_at(&queries, Position::new(1, 17)),
+            Some(("User".to_string(), (2, 16)))
+        );
+        // right after the word
+        assert_eq!(
+            project.word_at(&queries, Position::new(1, 19)),
+            Some(("User".to_string(), (2, 16)))
+        );
+        assert_eq!(project.word_at(&queries, Position::new(1, 2)), None);
+    }
+
+    #[test]
+    fn test_utf16_columns() {
+        assert_eq!(utf16_column("a😀b", 3), 3);
+        assert_eq!(char_column("a😀b", 3), 3);
+        assert_eq!(char_column("ab", 10), 3);
+    }
+
+    #[test]
+    fn test_diagnostics_are_grouped_by_file() {
+        let project = project(&QUERIES.replace("In<Follows>", "In<Folows>"));
+        let diagnostics = project.lsp_diagnostics();
+        assert!(diagnostics[&uri("schema.hx")].is_empty());
+        let queries = &diagnostics[&uri("queries.hx")];
+        assert_eq!(queries.len(), 1);
+        assert_eq!(
+            queries[0].message,
+            "Unknown edge type Folows, did you mean `Follows`?"
+        );
+        assert_eq!(queries[0].range.start, Position::new(1, 24));
+    }
+}
@@ -0,0 +1,409 @@
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+};
+
+use crate::protocol::{filterable::Filterable, value::Value};
+
+/// Key of a group's `group_by` property and its value in the `group_by` output
+pub const GROUP_KEY: &str = "group";
+/// Key of a group's aggregation results in the `group_by` and `aggregate` output
+pub const AGGREGATES_KEY: &str = "aggregates";
+
+/// Sort direction for `order_by`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Asc,
+    Desc,
+}
+
+/// An aggregation computed per group by `group_by`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Aggregation {
+    Count,
+    Sum(String),
+    Avg(String),
+    Min(String),
+    Max(String),
+    /// Collects the property values of every item in the group
+    Collect(String),
+}
+
+impl Aggregation {
+    /// The key the result is stored under within `aggregates`, e.g. `count` or `avg_price`
+    pub fn name(&self) -> String {
+        match self {
+            Aggregation::Count => "count".to_string(),
+            Aggregation::Sum(property) => format!("sum_{}", property),
+            Aggregation::Avg(property) => format!("avg_{}", property),
+            Aggregation::Min(property) => format!("min_{}", property),
+            Aggregation::Max(property) => format!("max_{}", property),
+            Aggregation::Collect(property) => format!("collect_{}", property),
+        }
+    }
+
+    fn compute<T>(&self, items: &[&T]) -> Value
+    where
+        for<'a> T: Filterable<'a>,
+    {
+        let values = |property: &str| {
+            items
+                .iter()
+                .filter_map(|item| item.check_property(property))
+                .collect::<Vec<_>>()
+        };
+        match self {
+            Aggregation::Count => Value::Integer(items.len() as i32),
+            Aggregation::Sum(property) => sum(&values(property)),
+            Aggregation::Avg(property) => {
+                let numbers: Vec<f64> = values(property).into_iter().filter_map(as_f64).collect();
+                match numbers.is_empty() {
+                    true => Value::Empty,
+                    false => Value::Float(numbers.iter().sum::<f64>() / numbers.len() as f64),
+                }
+            }
+            Aggregation::Min(property) => values(property)
+                .into_iter()
+                .min_by(|a, b| compare_values(a, b))
+                .cloned()
+                .unwrap_or(Value::Empty),
+            Aggregation::Max(property) => values(property)
+                .into_iter()
+                .max_by(|a, b| compare_values(a, b))
+                .cloned()
+                .unwrap_or(Value::Empty),
+            Aggregation::Collect(property) => {
+                Value::Array(values(property).into_iter().cloned().collect())
+            }
+        }
+    }
+}
+
+/// Total order used for sorting and min/max.
+///
+/// Integers and floats compare numerically, values of different kinds are ordered by kind
+/// and `Empty` (a missing property) sorts last.
+pub fn compare_values(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => a.cmp(b),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Boolean(a), Value::Boolean(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b.iter())
+            .map(|(a, b)| compare_values(a, b))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        _ => match (as_f64(a), as_f64(b)) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            _ => kind_rank(a).cmp(&kind_rank(b)),
+        },
+    }
+}
+
+/// A hashable form of a `Value`, equal exactly when the values are, so items can be
+/// deduplicated and grouped by a property in one pass
+#[derive(PartialEq, Eq, Hash)]
+enum ValueKey {
+    String(String),
+    /// The bits of the float, with `-0.0` folded into `0.0`
+    Float(u64),
+    Integer(i32),
+    Boolean(bool),
+    Array(Vec<ValueKey>),
+    /// Entries sorted by key
+    Object(Vec<(String, ValueKey)>),
+    Empty,
+}
+
+impl From<&Value> for ValueKey {
+    fn from(value: &Value) -> Self {
+        match value {
+            Value::String(s) => ValueKey::String(s.clone()),
+            Value::Float(f) => ValueKey::Float(if *f == 0.0 { 0.0f64 } else { *f }.to_bits()),
+            Value::Integer(i) => ValueKey::Integer(*i),
+            Value::Boolean(b) => ValueKey::Boolean(*b),
+            Value::Array(values) => ValueKey::Array(values.iter().map(ValueKey::from).collect()),
+            Value::Object(map) => {
+                let mut entries: Vec<(String, ValueKey)> = map
+                    .iter()
+                    .map(|(k, v)| (k.clone(), ValueKey::from(v)))
+                    .collect();
+                entries.sort_by(|a, b| a.0.cmp(&b.0));
+                ValueKey::Object(entries)
+            }
+            Value::Empty => ValueKey::Empty,
+        }
+    }
+}
+
+fn kind_rank(value: &Value) -> u8 {
+    match value {
+        Value::Boolean(_) => 0,
+        Value::Integer(_) | Value::Float(_) => 1,
+        Value::String(_) => 2,
+        Value::Array(_) => 3,
+        Value::Object(_) => 4,
+        Value::Empty => 5,
+    }
+}
+
+fn as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(i) => Some(*i as f64),
+        Value::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+/// Integer sums stay integers unless they overflow, anything with a float becomes a float
+fn sum(values: &[&Value]) -> Value {
+    let mut integer: Option<i32> = Some(0);
+    let mut float = 0.0;
+    for value in values {
+        match value {
+            Value::Integer(i) => {
+                integer = integer.and_then(|sum| sum.checked_add(*i));
+                float += *i as f64;
+            }
+            Value::Float(f) => {
+                integer = None;
+                float += f;
+            }
+            _ => {}
+        }
+    }
+    match integer {
+        Some(sum) => Value::Integer(sum),
+        None => Value::Float(float),
+    }
+}
+
+/// Stable sort by each key in turn, items missing a property sort last in either direction
+pub(crate) fn sort_items<T>(items: &mut [T], keys: &[(&str, Order)])
+where
+    for<'a> T: Filterable<'a>,
+{
+    items.sort_by(|a, b| {
+        keys.iter()
+            .map(|(property, order)| {
+                match (a.check_property(property), b.check_property(property)) {
+                    (Some(a), Some(b)) => match order {
+                        Order::Asc => compare_values(a, b),
+                        Order::Desc => compare_values(b, a),
+                    },
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                }
+            })
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    });
+}
+
+/// Keeps the first item for each distinct value of `property`, items without it are kept
+pub(crate) fn dedup_items_by<T>(items: Vec<T>, property: &str) -> Vec<T>
+where
+    for<'a> T: Filterable<'a>,
+{
+    let mut seen: HashSet<ValueKey> = HashSet::new();
+    items
+        .into_iter()
+        .filter(|item| match item.check_property(property) {
+            Some(value) => seen.insert(ValueKey::from(value)),
+            None => true,
+        })
+        .collect()
+}
+
+/// Groups items by `property` (or into a single group if `None`) and computes the
+/// aggregations for each group. Groups keep the order in which their key was first seen,
+/// items without the property are grouped under `Empty`.
+///
+/// Each group is `{ "group": { property: value }, "aggregates": { name: result, ... } }`,
+/// so a property can't shadow an aggregation that happens to share its name.
+pub(crate) fn group_items<T>(
+    items: &[T],
+    property: Option<&str>,
+    aggregations: &[Aggregation],
+) -> Vec<HashMap<String, Value>>
+where
+    for<'a> T: Filterable<'a>,
+{
+    let mut positions: HashMap<ValueKey, usize> = HashMap::new();
+    let mut groups: Vec<(Value, Vec<&T>)> = Vec::new();
+    for item in items {
+        let key = property
+            .and_then(|property| item.check_property(property))
+            .cloned()
+            .unwrap_or(Value::Empty);
+        match positions.get(&ValueKey::from(&key)) {
+            Some(&position) => groups[position].1.push(item),
+            None => {
+                positions.insert(ValueKey::from(&key), groups.len());
+                groups.push((key, vec![item]));
+            }
+        }
+    }
+    if property.is_none() && groups.is_empty() {
+        groups.push((Value::Empty, Vec::new()));
+    }
+
+    groups
+        .into_iter()
+        .map(|(key, members)| {
+            let results: HashMap<String, Value> = aggregations
+                .iter()
+                .map(|aggregation| (aggregation.name(), aggregation.compute(&members)))
+                .collect();
+            let mut group = HashMap::from([(AGGREGATES_KEY.to_string(), Value::Object(results))]);
+            if let Some(property) = property {
+                group.insert(
+                    GROUP_KEY.to_string(),
+                    Value::Object(HashMap::from([(property.to_string(), key)])),
+                );
+            }
+            group
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::items::Node;
+
+    fn node(id: &str, properties: Vec<(&str, Value)>) -> Node {
+        Node {
+            id: id.to_string(),
+            label: "Product".to_string(),
+            properties: properties
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        }
+    }
+
+    fn products() -> Vec<Node> {
+        vec![
+            node("1", vec![("category", "books".into()), ("price", 10.into()), ("name", "b".into())]),
+            node("2", vec![("category", "games".into()), ("price", 60.into()), ("name", "a".into())]),
+            node("3", vec![("category", "books".into()), ("price", 20.into()), ("name", "a".into())]),
+            node("4", vec![("category", "games".into()), ("price", 45.5.into()), ("name", "c".into())]),
+            node("5", vec![("name", "d".into())]),
+        ]
+    }
+
+    fn group_value<'a>(group: &'a HashMap<String, Value>, property: &str) -> &'a Value {
+        match &group[GROUP_KEY] {
+            Value::Object(key) => &key[property],
+            _ => panic!("Expected the group key to be an object"),
+        }
+    }
+
+    fn result<'a>(group: &'a HashMap<String, Value>, name: &str) -> &'a Value {
+        match &group[AGGREGATES_KEY] {
+            Value::Object(results) => &results[name],
+            _ => panic!("Expected the aggregates to be an object"),
+        }
+    }
+
+    #[test]
+    fn test_sort_multiple_keys() {
+        let mut items = products();
+        sort_items(&mut items, &[("name", Order::Asc), ("price", Order::Desc)]);
+        let ids: Vec<&str> = items.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, vec!["2", "3", "1", "4", "5"]);
+
+        sort_items(&mut items, &[("price", Order::Desc)]);
+        let ids: Vec<&str> = items.iter().map(|n| n.id.as_str()).collect();
+        // mixed integer and float prices compare numerically, missing prices go last
+        assert_eq!(ids, vec!["2", "4", "3", "1", "5"]);
+    }
+
+    #[test]
+    fn test_dedup_by() {
+        let ids: Vec<String> = dedup_items_by(products(), "category")
+            .into_iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(ids, vec!["1", "2", "5"]);
+    }
+
+    #[test]
+    fn test_group_aggregations() {
+        let aggregations = [
+            Aggregation::Count,
+            Aggregation::Sum("price".to_string()),
+            Aggregation::Avg("price".to_string()),
+            Aggregation::Max("price".to_string()),
+            Aggregation::Collect("name".to_string()),
+        ];
+        let groups = group_items(&products(), Some("category"), &aggregations);
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(group_value(&groups[0], "category"), &Value::from("books"));
+        assert_eq!(result(&groups[0], "count"), &Value::Integer(2));
+        assert_eq!(result(&groups[0], "sum_price"), &Value::Integer(30));
+        assert_eq!(result(&groups[0], "avg_price"), &Value::Float(15.0));
+        assert_eq!(
+            result(&groups[0], "collect_name"),
+            &Value::from(vec![Value::from("b"), Value::from("a")])
+        );
+        assert_eq!(result(&groups[1], "sum_price"), &Value::Float(105.5));
+        assert_eq!(result(&groups[1], "max_price"), &Value::Integer(60));
+        assert_eq!(group_value(&groups[2], "category"), &Value::Empty);
+        assert_eq!(result(&groups[2], "avg_price"), &Value::Empty);
+
+        let total = group_items(&products(), None, &[Aggregation::Count]);
+        assert_eq!(total.len(), 1);
+        assert_eq!(result(&total[0], "count"), &Value::Integer(5));
+        assert!(!total[0].contains_key(GROUP_KEY));
+    }
+
+    #[test]
+    fn test_group_by_property_named_like_an_aggregation() {
+        let items = vec![
+            node("1", vec![("count", 3.into())]),
+            node("2", vec![("count", 3.into())]),
+            node("3", vec![("count", 1.into())]),
+        ];
+        let groups = group_items(&items, Some("count"), &[Aggregation::Count]);
+
+        assert_eq!(groups.len(), 2);
+        assert_eq!(group_value(&groups[0], "count"), &Value::Integer(3));
+        assert_eq!(result(&groups[0], "count"), &Value::Integer(2));
+        assert_eq!(group_value(&groups[1], "count"), &Value::Integer(1));
+        assert_eq!(result(&groups[1], "count"), &Value::Integer(1));
+    }
+
+    #[test]
+    fn test_value_keys_match_value_equality() {
+        let values = [
+            Value::from(1),
+            Value::from(1.0),
+            Value::from("1"),
+            Value::from(vec![Value::from(1)]),
+            Value::Empty,
+        ];
+        for a in &values {
+            for b in &values {
+                assert_eq!(ValueKey::from(a) == ValueKey::from(b), a == b);
+            }
+        }
+        assert!(ValueKey::from(&Value::Float(0.0)) == ValueKey::from(&Value::Float(-0.0)));
+        let object = |entries: [(&str, i32); 2]| {
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), Value::from(v)))
+                    .collect(),
+            )
+        };
+        assert!(
+            ValueKey::from(&object([("a", 1), ("b", 2)]))
+                == ValueKey::from(&object([("b", 2), ("a", 1)]))
+        );
+    }
+}
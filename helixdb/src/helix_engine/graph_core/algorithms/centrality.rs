@@ -0,0 +1,481 @@
+use std::collections::VecDeque;
+
+use rand::{rngs::StdRng, seq::index::sample, SeedableRng};
+use rayon::prelude::*;
+
+use crate::helix_engine::{storage_core::storage_core::HelixGraphStorage, types::GraphError};
+use crate::protocol::value::Value;
+
+use super::projection::{Direction, GraphProjection, DEFAULT_WRITE_BATCH_SIZE};
+
+/// Options shared by the centrality measures
+#[derive(Debug, Clone)]
+pub struct CentralityConfig {
+    /// Node labels to project, all nodes if empty
+    pub node_labels: Vec<String>,
+    /// Edge labels to project, all edges if empty
+    pub edge_labels: Vec<String>,
+    /// Which edges are followed. `Both` treats the graph as undirected
+    pub direction: Direction,
+    /// Scale scores to `[0, 1]` by the number of nodes in the projection
+    pub normalize: bool,
+    /// Node property the score is written to, nothing is written if `None`
+    pub write_property: Option<String>,
+    pub batch_size: usize,
+    /// Number of source nodes to sample for betweenness, exact if `None`
+    pub sample_size: Option<usize>,
+    pub seed: u64,
+}
+
+impl Default for CentralityConfig {
+    fn default() -> Self {
+        Self {
+            node_labels: Vec::new(),
+            edge_labels: Vec::new(),
+            direction: Direction::Out,
+            normalize: true,
+            write_property: None,
+            batch_size: DEFAULT_WRITE_BATCH_SIZE,
+            sample_size: None,
+            seed: 42,
+        }
+    }
+}
+
+/// Node ids with their scores, highest score first
+#[derive(Debug, Clone)]
+pub struct CentralityResult {
+    pub scores: Vec<(String, f64)>,
+}
+
+impl CentralityResult {
+    pub fn top(&self, n: usize) -> &[(String, f64)] {
+        &self.scores[..n.min(self.scores.len())]
+    }
+
+    pub fn get(&self, id: &str) -> Option<f64> {
+        self.scores
+            .iter()
+            .find(|(node_id, _)| node_id == id)
+            .map(|(_, score)| *score)
+    }
+}
+
+pub trait CentralityMethods {
+    /// Number of edges per node in the configured direction
+    fn degree_centrality(&self, config: &CentralityConfig) -> Result<CentralityResult, GraphError>;
+
+    /// Brandes betweenness centrality. Samples `config.sample_size` sources when set
+    /// and extrapolates the result to the whole projection.
+    fn betweenness_centrality(
+        &self,
+        config: &CentralityConfig,
+    ) -> Result<CentralityResult, GraphError>;
+
+    /// Wasserman-Faust closeness centrality, which stays meaningful on disconnected graphs
+    fn closeness_centrality(
+        &self,
+        config: &CentralityConfig,
+    ) -> Result<CentralityResult, GraphError>;
+
+    /// Sum of inverse distances to every other reachable node
+    fn harmonic_centrality(
+        &self,
+        config: &CentralityConfig,
+    ) -> Result<CentralityResult, GraphError>;
+}
+
+impl CentralityMethods for HelixGraphStorage {
+    fn degree_centrality(&self, config: &CentralityConfig) -> Result<CentralityResult, GraphError> {
+        let projection = project(self, config)?;
+        let n = projection.len();
+        let scale = if config.normalize && n > 1 {
+            1.0 / (n - 1) as f64
+        } else {
+            1.0
+        };
+
+        let scores = (0..n)
+            .map(|node| {
+                let degree = match config.direction {
+                    Direction::Out => projection.out_adj[node].len(),
+                    Direction::In => projection.in_adj[node].len(),
+                    Direction::Both => projection.degree(node),
+                };
+                degree as f64 * scale
+            })
+            .collect();
+
+        finish(self, config, &projection, scores)
+    }
+
+    fn betweenness_centrality(
+        &self,
+        config: &CentralityConfig,
+    ) -> Result<CentralityResult, GraphError> {
+        let projection = project(self, config)?;
+        let adj = adjacency(&projection, config.direction);
+        let n = adj.len();
+
+        let sources: Vec<usize> = match config.sample_size {
+            Some(k) if k < n => {
+                let mut rng = StdRng::seed_from_u64(config.seed);
+                sample(&mut rng, n, k).into_vec()
+            }
+            _ => (0..n).collect(),
+        };
+
+        let mut scores = sources
+            .par_iter()
+            .map(|&source| brandes_dependencies(&adj, source))
+            .reduce(
+                || vec![0.0; n],
+                |mut acc, deps| {
+                    acc.iter_mut().zip(deps).for_each(|(a, d)| *a += d);
+                    acc
+                },
+            );
+
+        let mut scale = n as f64 / sources.len().max(1) as f64;
+        if config.direction == Direction::Both {
+            // every shortest path is found from both of its ends
+            scale /= 2.0;
+        }
+        if config.normalize && n > 2 {
+            let pairs = ((n - 1) * (n - 2)) as f64;
+            scale /= match config.direction {
+                Direction::Both => pairs / 2.0,
+                _ => pairs,
+            };
+        }
+        scores.iter_mut().for_each(|s| *s *= scale);
+
+        finish(self, config, &projection, scores)
+    }
+
+    fn closeness_centrality(
+        &self,
+        config: &CentralityConfig,
+    ) -> Result<CentralityResult, GraphError> {
+        let projection = project(self, config)?;
+        let adj = adjacency(&projection, config.direction);
+        let n = adj.len();
+
+        let scores = (0..n)
+            .into_par_iter()
+            .map(|source| {
+                let distances = bfs_distances(&adj, source);
+                let (reached, total) = distances
+                    .iter()
+                    .filter(|d| **d > 0)
+                    .fold((0usize, 0usize), |(r, t), d| (r + 1, t + *d as usize));
+                if total == 0 {
+                    return 0.0;
+                }
+                let closeness = reached as f64 / total as f64;
+                if config.normalize && n > 1 {
+                    closeness * reached as f64 / (n - 1) as f64
+                } else {
+                    closeness
+                }
+            })
+            .collect();
+
+        finish(self, config, &projection, scores)
+    }
+
+    fn harmonic_centrality(
+        &self,
+        config: &CentralityConfig,
+    ) -> Result<CentralityResult, GraphError> {
+        let projection = project(self, config)?;
+        let adj = adjacency(&projection, config.direction);
+        let n = adj.len();
+
+        let scores = (0..n)
+            .into_par_iter()
+            .map(|source| {
+                let harmonic: f64 = bfs_distances(&adj, source)
+                    .iter()
+                    .filter(|d| **d > 0)
+                    .map(|d| 1.0 / *d as f64)
+                    .sum();
+                if config.normalize && n > 1 {
+                    harmonic / (n - 1) as f64
+                } else {
+                    harmonic
+                }
+            })
+            .collect();
+
+        finish(self, config, &projection, scores)
+    }
+}
+
+fn project(
+    storage: &HelixGraphStorage,
+    config: &CentralityConfig,
+) -> Result<GraphProjection, GraphError> {
+    let txn = storage.graph_env.read_txn()?;
+    GraphProjection::new(storage, &txn, &config.node_labels, &config.edge_labels, None)
+}
+
+/// Unweighted, deduplicated adjacency following the given direction
+fn adjacency(projection: &GraphProjection, direction: Direction) -> Vec<Vec<usize>> {
+    match direction {
+        Direction::Both => projection.undirected_neighbours(),
+        Direction::Out | Direction::In => {
+            let edges = match direction {
+                Direction::Out => &projection.out_adj,
+                _ => &projection.in_adj,
+            };
+            edges
+                .iter()
+                .enumerate()
+                .map(|(node, targets)| {
+                    let mut targets: Vec<usize> = targets
+                        .iter()
+                        .map(|&(t, _)| t)
+                        .filter(|&t| t != node)
+                        .collect();
+                    targets.sort_unstable();
+                    targets.dedup();
+                    targets
+                })
+                .collect()
+        }
+    }
+}
+
+/// Hop distance from `source` to every node, `-1` if unreachable
+fn bfs_distances(adj: &[Vec<usize>], source: usize) -> Vec<i64> {
+    let mut distances = vec![-1; adj.len()];
+    let mut queue = VecDeque::new();
+    distances[source] = 0;
+    queue.push_back(source);
+
+    while let Some(node) = queue.pop_front() {
+        for &next in &adj[node] {
+            if distances[next] < 0 {
+                distances[next] = distances[node] + 1;
+                queue.push_back(next);
+            }
+        }
+    }
+
+    distances
+}
+
+/// Single source step of Brandes' algorithm, returns the dependency of `source` on every node
+fn brandes_dependencies(adj: &[Vec<usize>], source: usize) -> Vec<f64> {
+    let n = adj.len();
+    let mut stack = Vec::with_capacity(n);
+    let mut predecessors: Vec<Vec<usize>> = vec![Vec::new(); n];
+    let mut paths = vec![0.0f64; n];
+    let mut distances = vec![-1i64; n];
+    let mut queue = VecDeque::new();
+
+    paths[source] = 1.0;
+    distances[source] = 0;
+    queue.push_back(source);
+
+    while let Some(node) = queue.pop_front() {
+        stack.push(node);
+        for &next in &adj[node] {
+            if distances[next] < 0 {
+                distances[next] = distances[node] + 1;
+                queue.push_back(next);
+            }
+            if distances[next] == distances[node] + 1 {
+                paths[next] += paths[node];
+                predecessors[next].push(node);
+            }
+        }
+    }
+
+    let mut dependencies = vec![0.0f64; n];
+    while let Some(node) = stack.pop() {
+        for &prev in &predecessors[node] {
+            dependencies[prev] += paths[prev] / paths[node] * (1.0 + dependencies[node]);
+        }
+    }
+    dependencies[source] = 0.0;
+
+    dependencies
+}
+
+fn finish(
+    storage: &HelixGraphStorage,
+    config: &CentralityConfig,
+    projection: &GraphProjection,
+    scores: Vec<f64>,
+) -> Result<CentralityResult, GraphError> {
+    if let Some(property) = &config.write_property {
+        let values: Vec<Value> = scores.iter().map(|s| Value::Float(*s)).collect();
+        projection.write_property(storage, property, &values, config.batch_size)?;
+    }
+
+    let mut scores: Vec<(String, f64)> = projection.node_ids.iter().cloned().zip(scores).collect();
+    scores.sort_by(|(id_a, a), (id_b, b)| {
+        b.partial_cmp(a)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| id_a.cmp(id_b))
+    });
+
+    Ok(CentralityResult { scores })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_engine::{
+        graph_core::config::Config, storage_core::storage_methods::StorageMethods,
+    };
+    use crate::props;
+    use tempfile::TempDir;
+
+    fn setup_temp_db() -> (HelixGraphStorage, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+        let storage = HelixGraphStorage::new(db_path, Config::default()).unwrap();
+        (storage, temp_dir)
+    }
+
+    /// (0) - (1) - (2) - (3) - (4), edges in both directions
+    fn create_line(storage: &HelixGraphStorage) -> Vec<String> {
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let ids: Vec<String> = (0..5)
+            .map(|_| {
+                storage
+                    .create_node(&mut txn, "person", props!(), None)
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        for i in 0..4 {
+            storage
+                .create_edge(&mut txn, "knows", &ids[i], &ids[i + 1], props!())
+                .unwrap();
+            storage
+                .create_edge(&mut txn, "knows", &ids[i + 1], &ids[i], props!())
+                .unwrap();
+        }
+        txn.commit().unwrap();
+        ids
+    }
+
+    #[test]
+    fn test_degree_centrality() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_line(&storage);
+
+        let config = CentralityConfig {
+            normalize: false,
+            ..Default::default()
+        };
+        let result = storage.degree_centrality(&config).unwrap();
+
+        assert_eq!(result.get(&ids[0]), Some(1.0));
+        assert_eq!(result.get(&ids[2]), Some(2.0));
+        assert_eq!(result.scores[0].1, 2.0);
+    }
+
+    #[test]
+    fn test_betweenness_centrality() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_line(&storage);
+
+        let config = CentralityConfig {
+            normalize: false,
+            ..Default::default()
+        };
+        let result = storage.betweenness_centrality(&config).unwrap();
+
+        // directed pairs passing through the middle node: {0,1} x {3,4} in both directions
+        assert_eq!(result.scores[0].0, ids[2]);
+        assert!((result.get(&ids[2]).unwrap() - 8.0).abs() < 1e-9);
+        assert!((result.get(&ids[1]).unwrap() - 6.0).abs() < 1e-9);
+        assert_eq!(result.get(&ids[0]), Some(0.0));
+
+        let undirected = storage
+            .betweenness_centrality(&CentralityConfig {
+                direction: Direction::Both,
+                normalize: false,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!((undirected.get(&ids[2]).unwrap() - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_sampled_betweenness_centrality() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_line(&storage);
+
+        // a single source adds 2 to the middle node or nothing, scaled by n / k = 5
+        let single = storage
+            .betweenness_centrality(&CentralityConfig {
+                sample_size: Some(1),
+                normalize: false,
+                ..Default::default()
+            })
+            .unwrap();
+        let middle = single.get(&ids[2]).unwrap();
+        assert!(middle == 0.0 || (middle - 10.0).abs() < 1e-9);
+
+        // averaged over seeds the estimate comes close to the exact score of 8
+        let runs = 50;
+        let mean = (0..runs)
+            .map(|seed| {
+                let config = CentralityConfig {
+                    sample_size: Some(2),
+                    normalize: false,
+                    seed,
+                    ..Default::default()
+                };
+                let result = storage.betweenness_centrality(&config).unwrap();
+                assert_eq!(result.get(&ids[0]), Some(0.0));
+                result.get(&ids[2]).unwrap()
+            })
+            .sum::<f64>()
+            / runs as f64;
+        assert!((mean - 8.0).abs() < 1.5, "mean of sampled estimates {}", mean);
+
+        let config = CentralityConfig {
+            sample_size: Some(3),
+            write_property: Some("betweenness".to_string()),
+            ..Default::default()
+        };
+        let result = storage.betweenness_centrality(&config).unwrap();
+        assert_eq!(result.scores.len(), 5);
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let node = storage.get_node(&txn, &ids[2]).unwrap();
+        assert_eq!(
+            node.properties.get("betweenness"),
+            Some(&Value::Float(result.get(&ids[2]).unwrap()))
+        );
+    }
+
+    #[test]
+    fn test_closeness_and_harmonic_centrality() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_line(&storage);
+
+        let closeness = storage
+            .closeness_centrality(&CentralityConfig::default())
+            .unwrap();
+        // distances from the middle node: 1 + 1 + 2 + 2
+        assert!((closeness.get(&ids[2]).unwrap() - 4.0 / 6.0).abs() < 1e-9);
+        assert_eq!(closeness.scores[0].0, ids[2]);
+
+        let harmonic = storage
+            .harmonic_centrality(&CentralityConfig {
+                normalize: false,
+                ..Default::default()
+            })
+            .unwrap();
+        assert!((harmonic.get(&ids[2]).unwrap() - 3.0).abs() < 1e-9);
+        assert!((harmonic.get(&ids[0]).unwrap() - (1.0 + 0.5 + 1.0 / 3.0 + 0.25)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,438 @@
+use std::collections::HashMap;
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+use crate::helix_engine::{storage_core::storage_core::HelixGraphStorage, types::GraphError};
+use crate::protocol::value::Value;
+
+use super::projection::{GraphProjection, DEFAULT_WRITE_BATCH_SIZE};
+
+/// Options shared by the community detection algorithms
+#[derive(Debug, Clone)]
+pub struct CommunityConfig {
+    /// Node labels to project, all nodes if empty
+    pub node_labels: Vec<String>,
+    /// Edge labels to project, all edges if empty
+    pub edge_labels: Vec<String>,
+    /// Edge property used as weight, edges without it weigh `1.0`
+    pub weight_property: Option<String>,
+    /// Node property the community id is written to, nothing is written if `None`
+    pub write_property: Option<String>,
+    pub batch_size: usize,
+    pub max_iterations: usize,
+    /// Seed for the node visiting order of label propagation
+    pub seed: u64,
+}
+
+impl Default for CommunityConfig {
+    fn default() -> Self {
+        Self {
+            node_labels: Vec::new(),
+            edge_labels: Vec::new(),
+            weight_property: None,
+            write_property: Some("community".to_string()),
+            batch_size: DEFAULT_WRITE_BATCH_SIZE,
+            max_iterations: 20,
+            seed: 42,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CommunityResult {
+    /// Node id => community id. Community ids are dense, starting from 0
+    pub communities: HashMap<String, usize>,
+    pub community_count: usize,
+    pub modularity: f64,
+    /// Louvain levels or label propagation rounds that were run
+    pub iterations: usize,
+}
+
+pub trait CommunityMethods {
+    /// Runs Louvain modularity optimisation over a projection of the graph.
+    /// Edge direction is ignored.
+    fn louvain(&self, config: &CommunityConfig) -> Result<CommunityResult, GraphError>;
+
+    /// Runs weighted label propagation over a projection of the graph.
+    /// Cheaper than Louvain but usually finds a lower modularity partition.
+    fn label_propagation(&self, config: &CommunityConfig) -> Result<CommunityResult, GraphError>;
+}
+
+impl CommunityMethods for HelixGraphStorage {
+    fn louvain(&self, config: &CommunityConfig) -> Result<CommunityResult, GraphError> {
+        let projection = project(self, config)?;
+        let adj = projection.undirected_weighted();
+        let (assignment, iterations) = louvain(&adj, config.max_iterations);
+        finish(self, config, &projection, &adj, assignment, iterations)
+    }
+
+    fn label_propagation(&self, config: &CommunityConfig) -> Result<CommunityResult, GraphError> {
+        let projection = project(self, config)?;
+        let adj = projection.undirected_weighted();
+        let (assignment, iterations) = label_propagation(&adj, config.max_iterations, config.seed);
+        finish(self, config, &projection, &adj, assignment, iterations)
+    }
+}
+
+fn project(
+    storage: &HelixGraphStorage,
+    config: &CommunityConfig,
+) -> Result<GraphProjection, GraphError> {
+    let txn = storage.graph_env.read_txn()?;
+    GraphProjection::new(
+        storage,
+        &txn,
+        &config.node_labels,
+        &config.edge_labels,
+        config.weight_property.as_deref(),
+    )
+}
+
+fn finish(
+    storage: &HelixGraphStorage,
+    config: &CommunityConfig,
+    projection: &GraphProjection,
+    adj: &[HashMap<usize, f64>],
+    assignment: Vec<usize>,
+    iterations: usize,
+) -> Result<CommunityResult, GraphError> {
+    let (assignment, community_count) = renumber(&assignment);
+    let modularity = modularity(adj, &assignment);
+
+    if let Some(property) = &config.write_property {
+        let values: Vec<Value> = assignment
+            .iter()
+            .map(|&c| Value::Integer(c as i32))
+            .collect();
+        projection.write_property(storage, property, &values, config.batch_size)?;
+    }
+
+    let communities = projection
+        .node_ids
+        .iter()
+        .cloned()
+        .zip(assignment)
+        .collect();
+
+    Ok(CommunityResult {
+        communities,
+        community_count,
+        modularity,
+        iterations,
+    })
+}
+
+/// Maps arbitrary community labels to `0..count` in order of first appearance
+fn renumber(assignment: &[usize]) -> (Vec<usize>, usize) {
+    let mut ids = HashMap::new();
+    let renumbered = assignment
+        .iter()
+        .map(|c| {
+            let next = ids.len();
+            *ids.entry(*c).or_insert(next)
+        })
+        .collect();
+    (renumbered, ids.len())
+}
+
+/// Newman-Girvan modularity of a partition of an undirected weighted graph
+pub fn modularity(adj: &[HashMap<usize, f64>], assignment: &[usize]) -> f64 {
+    let total: f64 = adj.iter().flat_map(|row| row.values()).sum();
+    if total == 0.0 {
+        return 0.0;
+    }
+
+    let mut internal: HashMap<usize, f64> = HashMap::new();
+    let mut degree: HashMap<usize, f64> = HashMap::new();
+    for (node, row) in adj.iter().enumerate() {
+        let community = assignment[node];
+        for (&neighbour, &weight) in row {
+            *degree.entry(community).or_insert(0.0) += weight;
+            if assignment[neighbour] == community {
+                *internal.entry(community).or_insert(0.0) += weight;
+            }
+        }
+    }
+
+    degree
+        .iter()
+        .map(|(community, tot)| {
+            internal.get(community).unwrap_or(&0.0) / total - (tot / total).powi(2)
+        })
+        .sum()
+}
+
+/// Returns the community of every node and the number of levels that were run
+fn louvain(adj: &[HashMap<usize, f64>], max_levels: usize) -> (Vec<usize>, usize) {
+    let mut assignment: Vec<usize> = (0..adj.len()).collect();
+    let mut graph = adj.to_vec();
+    let mut levels = 0;
+
+    while levels < max_levels {
+        let (communities, moved) = louvain_local_moving(&graph);
+        if !moved {
+            break;
+        }
+        levels += 1;
+
+        let (communities, count) = renumber(&communities);
+        for community in assignment.iter_mut() {
+            *community = communities[*community];
+        }
+
+        // collapse every community into a single node, intra-community weights become self loops
+        let mut aggregated = vec![HashMap::new(); count];
+        for (node, row) in graph.iter().enumerate() {
+            for (&neighbour, &weight) in row {
+                *aggregated[communities[node]]
+                    .entry(communities[neighbour])
+                    .or_insert(0.0) += weight;
+            }
+        }
+        graph = aggregated;
+    }
+
+    (assignment, levels)
+}
+
+/// One Louvain level: greedily moves nodes to the neighbouring community with the
+/// highest modularity gain until no move improves modularity.
+fn louvain_local_moving(graph: &[HashMap<usize, f64>]) -> (Vec<usize>, bool) {
+    let m2: f64 = graph.iter().flat_map(|row| row.values()).sum();
+    let mut community: Vec<usize> = (0..graph.len()).collect();
+    if m2 == 0.0 {
+        return (community, false);
+    }
+
+    let degree: Vec<f64> = graph.iter().map(|row| row.values().sum()).collect();
+    let mut totals = degree.clone();
+    let mut moved_any = false;
+
+    loop {
+        let mut moved = false;
+        for node in 0..graph.len() {
+            let current = community[node];
+
+            let mut links: HashMap<usize, f64> = HashMap::new();
+            for (&neighbour, &weight) in &graph[node] {
+                if neighbour != node {
+                    *links.entry(community[neighbour]).or_insert(0.0) += weight;
+                }
+            }
+
+            totals[current] -= degree[node];
+            let gain = |c: usize, links_to_c: f64| links_to_c - totals[c] * degree[node] / m2;
+
+            let mut best = current;
+            let mut best_gain = gain(current, *links.get(&current).unwrap_or(&0.0));
+            let mut candidates: Vec<(&usize, &f64)> = links.iter().collect();
+            // deterministic tie breaking regardless of hash order
+            candidates.sort_unstable_by_key(|(c, _)| **c);
+            for (&c, &w) in candidates {
+                let g = gain(c, w);
+                if g > best_gain + 1e-12 {
+                    best = c;
+                    best_gain = g;
+                }
+            }
+
+            totals[best] += degree[node];
+            if best != current {
+                community[node] = best;
+                moved = true;
+                moved_any = true;
+            }
+        }
+        if !moved {
+            break;
+        }
+    }
+
+    (community, moved_any)
+}
+
+/// Returns the label of every node and the number of rounds that were run
+fn label_propagation(
+    adj: &[HashMap<usize, f64>],
+    max_iterations: usize,
+    seed: u64,
+) -> (Vec<usize>, usize) {
+    let mut labels: Vec<usize> = (0..adj.len()).collect();
+    let mut order: Vec<usize> = (0..adj.len()).collect();
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut rounds = 0;
+
+    while rounds < max_iterations {
+        rounds += 1;
+        order.shuffle(&mut rng);
+
+        let mut changed = false;
+        for &node in &order {
+            let mut scores: HashMap<usize, f64> = HashMap::new();
+            for (&neighbour, &weight) in &adj[node] {
+                if neighbour != node {
+                    *scores.entry(labels[neighbour]).or_insert(0.0) += weight;
+                }
+            }
+
+            // highest score wins, ties go to the smallest label so runs are reproducible
+            let best = scores.into_iter().max_by(|(la, sa), (lb, sb)| {
+                sa.partial_cmp(sb)
+                    .unwrap_or(std::cmp::Ordering::Equal)
+                    .then(lb.cmp(la))
+            });
+            if let Some((label, _)) = best {
+                if label != labels[node] {
+                    labels[node] = label;
+                    changed = true;
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    (labels, rounds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_engine::{
+        graph_core::config::Config, storage_core::storage_methods::StorageMethods,
+    };
+    use crate::props;
+    use tempfile::TempDir;
+
+    fn setup_temp_db() -> (HelixGraphStorage, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+        let storage = HelixGraphStorage::new(db_path, Config::default()).unwrap();
+        (storage, temp_dir)
+    }
+
+    /// Two triangles joined by a single edge
+    fn create_two_triangles(storage: &HelixGraphStorage) -> Vec<String> {
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let ids: Vec<String> = (0..6)
+            .map(|_| {
+                storage
+                    .create_node(&mut txn, "customer", props!(), None)
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        for (from, to) in [(0, 1), (1, 2), (2, 0), (3, 4), (4, 5), (5, 3), (2, 3)] {
+            storage
+                .create_edge(&mut txn, "interacts", &ids[from], &ids[to], props!())
+                .unwrap();
+        }
+        txn.commit().unwrap();
+        ids
+    }
+
+    #[test]
+    fn test_louvain_two_triangles() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_two_triangles(&storage);
+
+        let result = storage.louvain(&CommunityConfig::default()).unwrap();
+
+        assert_eq!(result.community_count, 2);
+        assert_eq!(result.communities[&ids[0]], result.communities[&ids[2]]);
+        assert_eq!(result.communities[&ids[3]], result.communities[&ids[5]]);
+        assert_ne!(result.communities[&ids[0]], result.communities[&ids[3]]);
+        assert!((result.modularity - 5.0 / 14.0).abs() < 1e-9);
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let node = storage.get_node(&txn, &ids[1]).unwrap();
+        assert_eq!(
+            node.properties.get("community"),
+            Some(&Value::Integer(result.communities[&ids[1]] as i32))
+        );
+    }
+
+    #[test]
+    fn test_label_propagation_two_triangles() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_two_triangles(&storage);
+
+        let config = CommunityConfig {
+            write_property: None,
+            ..Default::default()
+        };
+        let result = storage.label_propagation(&config).unwrap();
+
+        assert!(result.community_count >= 1);
+        assert_eq!(result.communities.len(), ids.len());
+        assert_eq!(result.communities[&ids[0]], result.communities[&ids[1]]);
+        assert_eq!(result.communities[&ids[4]], result.communities[&ids[5]]);
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let node = storage.get_node(&txn, &ids[0]).unwrap();
+        assert!(node.properties.get("community").is_none());
+    }
+
+    #[test]
+    fn test_louvain_uses_edge_weights() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let ids: Vec<String> = (0..4)
+            .map(|_| {
+                storage
+                    .create_node(&mut txn, "customer", props!(), None)
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        // a square where the weights pull (0,1) and (2,3) together
+        for (from, to, weight) in [(0, 1, 10.0), (1, 2, 1.0), (2, 3, 10.0), (3, 0, 1.0)] {
+            storage
+                .create_edge(
+                    &mut txn,
+                    "interacts",
+                    &ids[from],
+                    &ids[to],
+                    props! { "weight" => weight },
+                )
+                .unwrap();
+        }
+        txn.commit().unwrap();
+
+        let config = CommunityConfig {
+            weight_property: Some("weight".to_string()),
+            write_property: None,
+            ..Default::default()
+        };
+        let result = storage.louvain(&config).unwrap();
+
+        assert_eq!(result.community_count, 2);
+        assert_eq!(result.communities[&ids[0]], result.communities[&ids[1]]);
+        assert_eq!(result.communities[&ids[2]], result.communities[&ids[3]]);
+        assert!(result.modularity > 0.0);
+    }
+
+    #[test]
+    fn test_community_projection_by_label() {
+        let (storage, _temp_dir) = setup_temp_db();
+        create_two_triangles(&storage);
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        storage
+            .create_node(&mut txn, "product", props!(), None)
+            .unwrap();
+        txn.commit().unwrap();
+
+        let config = CommunityConfig {
+            node_labels: vec!["product".to_string()],
+            write_property: None,
+            ..Default::default()
+        };
+        let result = storage.louvain(&config).unwrap();
+
+        assert_eq!(result.communities.len(), 1);
+        assert_eq!(result.community_count, 1);
+        assert_eq!(result.modularity, 0.0);
+    }
+}
@@ -0,0 +1,512 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque},
+};
+
+use heed3::RoTxn;
+
+use crate::decode_str;
+use crate::helix_engine::{
+    storage_core::{
+        storage_core::{HelixGraphStorage, EDGE_LABEL_PREFIX},
+        storage_methods::StorageMethods,
+    },
+    types::GraphError,
+};
+use crate::protocol::items::{Edge, Node};
+
+use super::projection::value_as_f64;
+
+/// A path as its nodes and the edges between them, in traversal order
+pub type NodePath = (Vec<Node>, Vec<Edge>);
+
+pub trait DagMethods {
+    /// Looks for a cycle over edges with the given label, starting the search from
+    /// `start_nodes` or from every node with such an edge if empty.
+    ///
+    /// The cycle is returned as a path whose first and last node are the same.
+    fn find_cycle(
+        &self,
+        txn: &RoTxn,
+        edge_label: &str,
+        start_nodes: &[String],
+    ) -> Result<Option<NodePath>, GraphError>;
+
+    /// Orders the nodes connected by edges with the given label so that every node
+    /// comes before the nodes it points to. Ties are broken by node id.
+    ///
+    /// Fails with a `TraversalError` naming the cycle if the graph is not a DAG.
+    fn topological_sort(&self, txn: &RoTxn, edge_label: &str) -> Result<Vec<Node>, GraphError>;
+
+    /// Heaviest path through the DAG formed by edges with the given label, along with its length.
+    /// Edges are weighted by `weight_property`, or `1.0` if it is not given or missing.
+    fn critical_path(
+        &self,
+        txn: &RoTxn,
+        edge_label: &str,
+        weight_property: Option<&str>,
+    ) -> Result<(Vec<Node>, Vec<Edge>, f64), GraphError>;
+
+    /// Every node that can reach the given node over edges with the given label
+    fn ancestors(&self, txn: &RoTxn, node_id: &str, edge_label: &str)
+        -> Result<Vec<Node>, GraphError>;
+
+    /// Every node reachable from the given node over edges with the given label
+    fn descendants(
+        &self,
+        txn: &RoTxn,
+        node_id: &str,
+        edge_label: &str,
+    ) -> Result<Vec<Node>, GraphError>;
+}
+
+/// The edges with a single label, indexed by node position.
+/// Node ids are sorted so that results do not depend on insertion order.
+struct LabelGraph {
+    node_ids: Vec<String>,
+    index: HashMap<String, usize>,
+    edges: Vec<Edge>,
+    /// Outgoing `(target, edge)` pairs
+    out_adj: Vec<Vec<(usize, usize)>>,
+}
+
+impl LabelGraph {
+    fn new(storage: &HelixGraphStorage, txn: &RoTxn, edge_label: &str) -> Result<Self, GraphError> {
+        let prefix = [EDGE_LABEL_PREFIX, edge_label.as_bytes(), b":"].concat();
+        let mut edges = Vec::new();
+        let iter = storage
+            .edge_labels_db
+            .lazily_decode_data()
+            .prefix_iter(txn, &prefix)?;
+        for result in iter {
+            let (key, _) = result?;
+            let edge_id = std::str::from_utf8(&key[prefix.len()..])?;
+            edges.push(storage.get_edge(txn, edge_id)?);
+        }
+
+        let node_ids: Vec<String> = edges
+            .iter()
+            .flat_map(|edge| [edge.from_node.clone(), edge.to_node.clone()])
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let index: HashMap<String, usize> = node_ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (id.clone(), i))
+            .collect();
+
+        let mut out_adj = vec![Vec::new(); node_ids.len()];
+        for (i, edge) in edges.iter().enumerate() {
+            out_adj[index[&edge.from_node]].push((index[&edge.to_node], i));
+        }
+        for targets in out_adj.iter_mut() {
+            targets.sort_unstable();
+        }
+
+        Ok(Self {
+            node_ids,
+            index,
+            edges,
+            out_adj,
+        })
+    }
+
+    /// Iterative DFS returning the nodes and edges of the first cycle found
+    fn find_cycle(&self, starts: &[usize]) -> Option<(Vec<usize>, Vec<usize>)> {
+        const UNVISITED: u8 = 0;
+        const ON_STACK: u8 = 1;
+        const DONE: u8 = 2;
+
+        let mut state = vec![UNVISITED; self.node_ids.len()];
+        for &start in starts {
+            if state[start] != UNVISITED {
+                continue;
+            }
+            // (node, next out edge to look at) and the edges taken to reach each stack entry
+            let mut stack = vec![(start, 0)];
+            let mut path_edges: Vec<usize> = Vec::new();
+            state[start] = ON_STACK;
+
+            while let Some(&(node, next)) = stack.last() {
+                match self.out_adj[node].get(next) {
+                    Some(&(to, edge)) => {
+                        stack.last_mut().unwrap().1 += 1;
+                        match state[to] {
+                            UNVISITED => {
+                                state[to] = ON_STACK;
+                                stack.push((to, 0));
+                                path_edges.push(edge);
+                            }
+                            ON_STACK => {
+                                let pos = stack.iter().position(|&(n, _)| n == to).unwrap();
+                                let mut nodes: Vec<usize> =
+                                    stack[pos..].iter().map(|&(n, _)| n).collect();
+                                nodes.push(to);
+                                let mut edges = path_edges[pos..].to_vec();
+                                edges.push(edge);
+                                return Some((nodes, edges));
+                            }
+                            _ => {}
+                        }
+                    }
+                    None => {
+                        state[node] = DONE;
+                        stack.pop();
+                        path_edges.pop();
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    /// Kahn's algorithm, `None` if there is a cycle
+    fn topological_order(&self) -> Option<Vec<usize>> {
+        let n = self.node_ids.len();
+        let mut in_degree = vec![0usize; n];
+        for targets in &self.out_adj {
+            for &(to, _) in targets {
+                in_degree[to] += 1;
+            }
+        }
+
+        let mut ready: BinaryHeap<Reverse<usize>> = (0..n)
+            .filter(|&node| in_degree[node] == 0)
+            .map(Reverse)
+            .collect();
+        let mut order = Vec::with_capacity(n);
+        while let Some(Reverse(node)) = ready.pop() {
+            order.push(node);
+            for &(to, _) in &self.out_adj[node] {
+                in_degree[to] -= 1;
+                if in_degree[to] == 0 {
+                    ready.push(Reverse(to));
+                }
+            }
+        }
+
+        (order.len() == n).then_some(order)
+    }
+
+    fn cycle_error(&self) -> GraphError {
+        let all: Vec<usize> = (0..self.node_ids.len()).collect();
+        match self.find_cycle(&all) {
+            Some((nodes, _)) => GraphError::TraversalError(format!(
+                "Cycle detected: {}",
+                nodes
+                    .iter()
+                    .map(|&n| self.node_ids[n].as_str())
+                    .collect::<Vec<_>>()
+                    .join(" -> ")
+            )),
+            None => GraphError::TraversalError("Cycle detected".to_string()),
+        }
+    }
+
+    fn nodes(
+        &self,
+        storage: &HelixGraphStorage,
+        txn: &RoTxn,
+        nodes: &[usize],
+    ) -> Result<Vec<Node>, GraphError> {
+        nodes
+            .iter()
+            .map(|&n| storage.get_node(txn, &self.node_ids[n]))
+            .collect()
+    }
+}
+
+impl DagMethods for HelixGraphStorage {
+    fn find_cycle(
+        &self,
+        txn: &RoTxn,
+        edge_label: &str,
+        start_nodes: &[String],
+    ) -> Result<Option<NodePath>, GraphError> {
+        let graph = LabelGraph::new(self, txn, edge_label)?;
+        let starts: Vec<usize> = match start_nodes.is_empty() {
+            true => (0..graph.node_ids.len()).collect(),
+            false => start_nodes
+                .iter()
+                .filter_map(|id| graph.index.get(id).copied())
+                .collect(),
+        };
+
+        match graph.find_cycle(&starts) {
+            Some((nodes, edges)) => Ok(Some((
+                graph.nodes(self, txn, &nodes)?,
+                edges.into_iter().map(|e| graph.edges[e].clone()).collect(),
+            ))),
+            None => Ok(None),
+        }
+    }
+
+    fn topological_sort(&self, txn: &RoTxn, edge_label: &str) -> Result<Vec<Node>, GraphError> {
+        let graph = LabelGraph::new(self, txn, edge_label)?;
+        match graph.topological_order() {
+            Some(order) => graph.nodes(self, txn, &order),
+            None => Err(graph.cycle_error()),
+        }
+    }
+
+    fn critical_path(
+        &self,
+        txn: &RoTxn,
+        edge_label: &str,
+        weight_property: Option<&str>,
+    ) -> Result<(Vec<Node>, Vec<Edge>, f64), GraphError> {
+        let graph = LabelGraph::new(self, txn, edge_label)?;
+        let order = match graph.topological_order() {
+            Some(order) => order,
+            None => return Err(graph.cycle_error()),
+        };
+        if order.is_empty() {
+            return Ok((vec![], vec![], 0.0));
+        }
+
+        let weight = |edge: &Edge| match weight_property {
+            Some(property) => edge
+                .properties
+                .get(property)
+                .and_then(value_as_f64)
+                .unwrap_or(1.0),
+            None => 1.0,
+        };
+
+        // longest distance ending at each node and the edge it was reached by
+        let mut distance = vec![0.0; graph.node_ids.len()];
+        let mut via: Vec<Option<usize>> = vec![None; graph.node_ids.len()];
+        for &node in &order {
+            for &(to, edge) in &graph.out_adj[node] {
+                let candidate = distance[node] + weight(&graph.edges[edge]);
+                if via[to].is_none() || candidate > distance[to] {
+                    distance[to] = candidate;
+                    via[to] = Some(edge);
+                }
+            }
+        }
+
+        let mut end = order[0];
+        for &node in &order {
+            if distance[node] > distance[end] {
+                end = node;
+            }
+        }
+
+        let mut nodes = vec![end];
+        let mut edges = Vec::new();
+        while let Some(edge) = via[*nodes.last().unwrap()] {
+            edges.push(edge);
+            nodes.push(graph.index[&graph.edges[edge].from_node]);
+        }
+        nodes.reverse();
+        edges.reverse();
+
+        Ok((
+            graph.nodes(self, txn, &nodes)?,
+            edges.into_iter().map(|e| graph.edges[e].clone()).collect(),
+            distance[end],
+        ))
+    }
+
+    fn ancestors(
+        &self,
+        txn: &RoTxn,
+        node_id: &str,
+        edge_label: &str,
+    ) -> Result<Vec<Node>, GraphError> {
+        closure(self, txn, node_id, edge_label, false)
+    }
+
+    fn descendants(
+        &self,
+        txn: &RoTxn,
+        node_id: &str,
+        edge_label: &str,
+    ) -> Result<Vec<Node>, GraphError> {
+        closure(self, txn, node_id, edge_label, true)
+    }
+}
+
+/// Breadth first closure over out edges (or in edges), not including the start node
+fn closure(
+    storage: &HelixGraphStorage,
+    txn: &RoTxn,
+    node_id: &str,
+    edge_label: &str,
+    outgoing: bool,
+) -> Result<Vec<Node>, GraphError> {
+    let mut visited = HashSet::from([node_id.to_string()]);
+    let mut queue = VecDeque::from([node_id.to_string()]);
+    let mut result = Vec::new();
+
+    while let Some(current) = queue.pop_front() {
+        let (db, prefix) = match outgoing {
+            true => (&storage.out_edges_db, HelixGraphStorage::out_edge_key(&current, "")),
+            false => (&storage.in_edges_db, HelixGraphStorage::in_edge_key(&current, "")),
+        };
+        let iter = db.lazily_decode_data().prefix_iter(txn, &prefix)?;
+        for data in iter {
+            let (key, value) = data?;
+            let other = std::str::from_utf8(&key[prefix.len()..])?;
+            if visited.contains(other) {
+                continue;
+            }
+            if !edge_label.is_empty() {
+                let edge_id = decode_str!(value);
+                if storage
+                    .edge_labels_db
+                    .get(txn, &HelixGraphStorage::edge_label_key(edge_label, edge_id))?
+                    .is_none()
+                {
+                    continue;
+                }
+            }
+            visited.insert(other.to_string());
+            result.push(storage.get_node(txn, other)?);
+            queue.push_back(other.to_string());
+        }
+    }
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_engine::graph_core::config::Config;
+    use crate::props;
+    use tempfile::TempDir;
+
+    fn setup_temp_db() -> (HelixGraphStorage, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+        let storage = HelixGraphStorage::new(db_path, Config::default()).unwrap();
+        (storage, temp_dir)
+    }
+
+    /// Creates `count` task nodes and `depends_on` edges with a `cost` property
+    fn create_dag(
+        storage: &HelixGraphStorage,
+        count: usize,
+        edges: &[(usize, usize, f64)],
+    ) -> Vec<String> {
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let ids: Vec<String> = (0..count)
+            .map(|_| {
+                storage
+                    .create_node(&mut txn, "task", props!(), None)
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        for &(from, to, cost) in edges {
+            storage
+                .create_edge(&mut txn, "depends_on", &ids[from], &ids[to], props! { "cost" => cost })
+                .unwrap();
+        }
+        txn.commit().unwrap();
+        ids
+    }
+
+    #[test]
+    fn test_topological_sort() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_dag(&storage, 4, &[(0, 1, 1.0), (0, 2, 1.0), (1, 3, 1.0), (2, 3, 1.0)]);
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let order: Vec<String> = storage
+            .topological_sort(&txn, "depends_on")
+            .unwrap()
+            .into_iter()
+            .map(|node| node.id)
+            .collect();
+
+        let position = |id: &String| order.iter().position(|o| o == id).unwrap();
+        assert_eq!(order.len(), 4);
+        assert!(position(&ids[0]) < position(&ids[1]));
+        assert!(position(&ids[0]) < position(&ids[2]));
+        assert!(position(&ids[1]) < position(&ids[3]));
+        assert!(position(&ids[2]) < position(&ids[3]));
+        assert!(storage.find_cycle(&txn, "depends_on", &[]).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_dag(&storage, 4, &[(0, 1, 1.0), (1, 2, 1.0), (2, 3, 1.0), (3, 1, 1.0)]);
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let (nodes, edges) = storage
+            .find_cycle(&txn, "depends_on", &ids[..1])
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(nodes.len(), 4);
+        assert_eq!(edges.len(), 3);
+        assert_eq!(nodes.first().unwrap().id, nodes.last().unwrap().id);
+        assert!(!nodes.iter().any(|node| node.id == ids[0]));
+        for (i, edge) in edges.iter().enumerate() {
+            assert_eq!(edge.from_node, nodes[i].id);
+            assert_eq!(edge.to_node, nodes[i + 1].id);
+        }
+
+        match storage.topological_sort(&txn, "depends_on") {
+            Err(GraphError::TraversalError(msg)) => assert!(msg.starts_with("Cycle detected")),
+            other => panic!("Expected cycle error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_critical_path() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_dag(
+            &storage,
+            5,
+            &[(0, 1, 2.0), (1, 4, 2.0), (0, 2, 1.0), (2, 3, 1.0), (3, 4, 1.0)],
+        );
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let (nodes, edges, length) = storage
+            .critical_path(&txn, "depends_on", Some("cost"))
+            .unwrap();
+        assert_eq!(length, 4.0);
+        assert_eq!(
+            nodes.iter().map(|n| n.id.clone()).collect::<Vec<_>>(),
+            vec![ids[0].clone(), ids[1].clone(), ids[4].clone()]
+        );
+        assert_eq!(edges.len(), 2);
+
+        let (nodes, _, length) = storage.critical_path(&txn, "depends_on", None).unwrap();
+        assert_eq!(length, 3.0);
+        assert_eq!(nodes.len(), 4);
+    }
+
+    #[test]
+    fn test_ancestors_and_descendants() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_dag(&storage, 5, &[(0, 1, 1.0), (1, 2, 1.0), (3, 2, 1.0)]);
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let ancestors: HashSet<String> = storage
+            .ancestors(&txn, &ids[2], "depends_on")
+            .unwrap()
+            .into_iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(
+            ancestors,
+            HashSet::from([ids[0].clone(), ids[1].clone(), ids[3].clone()])
+        );
+
+        let descendants: Vec<String> = storage
+            .descendants(&txn, &ids[0], "depends_on")
+            .unwrap()
+            .into_iter()
+            .map(|n| n.id)
+            .collect();
+        assert_eq!(descendants, vec![ids[1].clone(), ids[2].clone()]);
+        assert!(storage.descendants(&txn, &ids[4], "depends_on").unwrap().is_empty());
+    }
+}
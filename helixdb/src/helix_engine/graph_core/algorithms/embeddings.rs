@@ -0,0 +1,586 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use heed3::RoTxn;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::helix_engine::{
+    storage_core::{storage_core::HelixGraphStorage, storage_methods::StorageMethods},
+    types::GraphError,
+    vector_core::hnsw::HNSW,
+};
+use crate::protocol::items::Node;
+
+use super::projection::{GraphProjection, DEFAULT_WRITE_BATCH_SIZE};
+
+/// Node property the similarity of a node is stored under
+/// when similar nodes are returned from a traversal
+pub const SIMILARITY_PROPERTY: &str = "similarity";
+
+/// Options for generating (node2vec style) random walks
+#[derive(Debug, Clone)]
+pub struct RandomWalkConfig {
+    /// Node labels to walk over, all nodes if empty
+    pub node_labels: Vec<String>,
+    /// Edge labels to walk over, all edges if empty
+    pub edge_labels: Vec<String>,
+    /// Follow outgoing edges only instead of ignoring direction
+    pub directed: bool,
+    pub walk_length: usize,
+    pub walks_per_node: usize,
+    /// Return parameter, higher values make stepping back to the previous node less likely
+    pub p: f64,
+    /// In-out parameter, higher values keep walks local (BFS like), lower values explore (DFS like)
+    pub q: f64,
+    pub seed: u64,
+}
+
+impl Default for RandomWalkConfig {
+    fn default() -> Self {
+        Self {
+            node_labels: Vec::new(),
+            edge_labels: Vec::new(),
+            directed: false,
+            walk_length: 20,
+            walks_per_node: 10,
+            p: 1.0,
+            q: 1.0,
+            seed: 42,
+        }
+    }
+}
+
+/// Options for the skip-gram with negative sampling trainer
+#[derive(Debug, Clone)]
+pub struct SkipGramConfig {
+    pub dimensions: usize,
+    pub window: usize,
+    pub negative_samples: usize,
+    pub epochs: usize,
+    pub learning_rate: f64,
+    pub seed: u64,
+    pub batch_size: usize,
+}
+
+impl Default for SkipGramConfig {
+    fn default() -> Self {
+        Self {
+            dimensions: 64,
+            window: 5,
+            negative_samples: 5,
+            epochs: 5,
+            learning_rate: 0.025,
+            seed: 42,
+            batch_size: DEFAULT_WRITE_BATCH_SIZE,
+        }
+    }
+}
+
+/// Random walks over a projection, nodes are referred to by their index in `node_ids`
+#[derive(Debug, Clone)]
+pub struct RandomWalks {
+    pub node_ids: Vec<String>,
+    pub walks: Vec<Vec<usize>>,
+}
+
+impl RandomWalks {
+    /// Writes one walk per line as space separated node ids,
+    /// the corpus format expected by DeepWalk/word2vec tooling
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<(), GraphError> {
+        for walk in &self.walks {
+            let line = walk
+                .iter()
+                .map(|&node| self.node_ids[node].as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            writeln!(writer, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    pub fn export<P: AsRef<Path>>(&self, path: P) -> Result<(), GraphError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+pub trait EmbeddingMethods {
+    /// Generates `walks_per_node` biased random walks starting from every projected node
+    fn random_walks(&self, config: &RandomWalkConfig) -> Result<RandomWalks, GraphError>;
+
+    /// Trains structural embeddings on random walks and returns them per node id
+    fn node_embeddings(
+        &self,
+        walk_config: &RandomWalkConfig,
+        config: &SkipGramConfig,
+    ) -> Result<HashMap<String, Vec<f64>>, GraphError>;
+
+    /// Trains structural embeddings and stores them in the `node_embeddings` index,
+    /// replacing the ones from a previous run. Each vector gets the id of the node it was
+    /// trained for, so search results can be mapped straight back to nodes.
+    ///
+    /// Returns the number of vectors inserted
+    fn embed_nodes(
+        &self,
+        walk_config: &RandomWalkConfig,
+        config: &SkipGramConfig,
+    ) -> Result<usize, GraphError>;
+
+    /// Returns the `limit` nodes whose stored embedding is closest to the one of `node_id`
+    /// by cosine similarity, most similar first. Needs `embed_nodes` to have run.
+    fn similar_nodes(
+        &self,
+        txn: &RoTxn,
+        node_id: &str,
+        limit: usize,
+    ) -> Result<Vec<(Node, f64)>, GraphError>;
+}
+
+impl EmbeddingMethods for HelixGraphStorage {
+    fn random_walks(&self, config: &RandomWalkConfig) -> Result<RandomWalks, GraphError> {
+        let projection = {
+            let txn = self.graph_env.read_txn()?;
+            GraphProjection::new(self, &txn, &config.node_labels, &config.edge_labels, None)?
+        };
+
+        let neighbours = if config.directed {
+            projection
+                .out_adj
+                .iter()
+                .map(|targets| {
+                    let mut targets: Vec<usize> = targets.iter().map(|&(t, _)| t).collect();
+                    targets.sort_unstable();
+                    targets.dedup();
+                    targets
+                })
+                .collect()
+        } else {
+            projection.undirected_neighbours()
+        };
+
+        let walks = (0..projection.len() * config.walks_per_node)
+            .into_par_iter()
+            .map(|i| {
+                // one rng per walk keeps the result independent of thread scheduling
+                let mut rng = StdRng::seed_from_u64(config.seed.wrapping_add(i as u64));
+                walk(&neighbours, i % projection.len().max(1), config, &mut rng)
+            })
+            .collect();
+
+        Ok(RandomWalks {
+            node_ids: projection.node_ids,
+            walks,
+        })
+    }
+
+    fn node_embeddings(
+        &self,
+        walk_config: &RandomWalkConfig,
+        config: &SkipGramConfig,
+    ) -> Result<HashMap<String, Vec<f64>>, GraphError> {
+        let walks = self.random_walks(walk_config)?;
+        let embeddings = train_skip_gram(&walks.walks, walks.node_ids.len(), config);
+        Ok(walks.node_ids.into_iter().zip(embeddings).collect())
+    }
+
+    fn embed_nodes(
+        &self,
+        walk_config: &RandomWalkConfig,
+        config: &SkipGramConfig,
+    ) -> Result<usize, GraphError> {
+        let embeddings: Vec<(String, Vec<f64>)> =
+            self.node_embeddings(walk_config, config)?.into_iter().collect();
+
+        let mut txn = self.graph_env.write_txn()?;
+        self.node_embeddings.clear(&mut txn)?;
+        txn.commit()?;
+
+        for batch in embeddings.chunks(config.batch_size.max(1)) {
+            let mut txn = self.graph_env.write_txn()?;
+            for (node_id, embedding) in batch {
+                self.node_embeddings
+                    .insert(&mut txn, embedding, Some(node_id.clone()))?;
+            }
+            txn.commit()?;
+        }
+
+        Ok(embeddings.len())
+    }
+
+    fn similar_nodes(
+        &self,
+        txn: &RoTxn,
+        node_id: &str,
+        limit: usize,
+    ) -> Result<Vec<(Node, f64)>, GraphError> {
+        let query = self.node_embeddings.get_vector(txn, node_id, 0, true)?;
+        // the index only narrows down the candidates, they are ranked by similarity here
+        // so the order does not depend on the distance the index was built with
+        let candidates = self
+            .node_embeddings
+            .search(txn, query.get_data(), self.node_embeddings.config.ef.max(limit + 1))?;
+
+        let mut seen = HashSet::new();
+        let mut scored = Vec::with_capacity(candidates.len());
+        for candidate in candidates {
+            let id = candidate.get_id();
+            if id == node_id || !seen.insert(id.to_string()) {
+                continue;
+            }
+            let node = match self.get_node(txn, id) {
+                Ok(node) => node,
+                // deleted since the embeddings were trained
+                Err(GraphError::NodeNotFound) => continue,
+                Err(err) => return Err(err),
+            };
+            scored.push((node, cosine_similarity(query.get_data(), candidate.get_data())));
+        }
+
+        scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+        scored.truncate(limit);
+        Ok(scored)
+    }
+}
+
+fn cosine_similarity(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm =
+        a.iter().map(|x| x * x).sum::<f64>().sqrt() * b.iter().map(|x| x * x).sum::<f64>().sqrt();
+    if norm == 0.0 {
+        0.0
+    } else {
+        dot / norm
+    }
+}
+
+/// Second order random walk as described by node2vec
+fn walk(
+    neighbours: &[Vec<usize>],
+    start: usize,
+    config: &RandomWalkConfig,
+    rng: &mut StdRng,
+) -> Vec<usize> {
+    let mut walk = Vec::with_capacity(config.walk_length);
+    if neighbours.is_empty() {
+        return walk;
+    }
+    walk.push(start);
+
+    while walk.len() < config.walk_length {
+        let current = walk[walk.len() - 1];
+        let candidates = &neighbours[current];
+        if candidates.is_empty() {
+            break;
+        }
+
+        let next = match walk.len() {
+            1 => candidates[rng.random_range(0..candidates.len())],
+            _ => {
+                let previous = walk[walk.len() - 2];
+                let weights: Vec<f64> = candidates
+                    .iter()
+                    .map(|&candidate| {
+                        if candidate == previous {
+                            1.0 / config.p
+                        } else if neighbours[previous].binary_search(&candidate).is_ok() {
+                            1.0
+                        } else {
+                            1.0 / config.q
+                        }
+                    })
+                    .collect();
+                candidates[sample_weighted(&weights, rng)]
+            }
+        };
+        walk.push(next);
+    }
+
+    walk
+}
+
+#[inline(always)]
+fn sample_weighted(weights: &[f64], rng: &mut StdRng) -> usize {
+    let total: f64 = weights.iter().sum();
+    let mut target = rng.random::<f64>() * total;
+    for (i, weight) in weights.iter().enumerate() {
+        if target < *weight {
+            return i;
+        }
+        target -= weight;
+    }
+    weights.len() - 1
+}
+
+#[inline(always)]
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x.clamp(-6.0, 6.0)).exp())
+}
+
+/// Skip-gram with negative sampling (word2vec) over node walks.
+/// Returns one embedding of `config.dimensions` per node index.
+pub fn train_skip_gram(walks: &[Vec<usize>], node_count: usize, config: &SkipGramConfig) -> Vec<Vec<f64>> {
+    let dims = config.dimensions;
+    let mut rng = StdRng::seed_from_u64(config.seed);
+    let mut input: Vec<Vec<f64>> = (0..node_count)
+        .map(|_| {
+            (0..dims)
+                .map(|_| (rng.random::<f64>() - 0.5) / dims as f64)
+                .collect()
+        })
+        .collect();
+    let mut output = vec![vec![0.0; dims]; node_count];
+
+    // negatives are drawn from the unigram distribution raised to 3/4
+    let mut frequency = vec![0.0f64; node_count];
+    walks.iter().flatten().for_each(|&node| frequency[node] += 1.0);
+    let mut cumulative = Vec::with_capacity(node_count);
+    let mut total = 0.0;
+    for f in &frequency {
+        total += f.powf(0.75);
+        cumulative.push(total);
+    }
+    if total == 0.0 {
+        return input;
+    }
+
+    let pairs: usize = walks.iter().map(|w| w.len()).sum::<usize>() * config.epochs.max(1);
+    let mut seen = 0usize;
+    let mut gradient = vec![0.0; dims];
+
+    for _ in 0..config.epochs.max(1) {
+        for walk in walks {
+            for (i, &center) in walk.iter().enumerate() {
+                seen += 1;
+                // linearly decaying learning rate, as in word2vec
+                let lr = (config.learning_rate * (1.0 - seen as f64 / (pairs + 1) as f64))
+                    .max(config.learning_rate * 1e-4);
+
+                let start = i.saturating_sub(config.window);
+                let end = (i + config.window + 1).min(walk.len());
+                for (j, &context) in walk.iter().enumerate().take(end).skip(start) {
+                    if j == i {
+                        continue;
+                    }
+                    gradient.iter_mut().for_each(|g| *g = 0.0);
+
+                    for k in 0..=config.negative_samples {
+                        let (target, label) = if k == 0 {
+                            (context, 1.0)
+                        } else {
+                            let r = rng.random::<f64>() * total;
+                            let sample = cumulative.partition_point(|c| *c <= r).min(node_count - 1);
+                            if sample == context {
+                                continue;
+                            }
+                            (sample, 0.0)
+                        };
+
+                        let dot: f64 = input[center]
+                            .iter()
+                            .zip(output[target].iter())
+                            .map(|(a, b)| a * b)
+                            .sum();
+                        let g = (label - sigmoid(dot)) * lr;
+                        for d in 0..dims {
+                            gradient[d] += g * output[target][d];
+                            output[target][d] += g * input[center][d];
+                        }
+                    }
+
+                    input[center]
+                        .iter_mut()
+                        .zip(gradient.iter())
+                        .for_each(|(v, g)| *v += g);
+                }
+            }
+        }
+    }
+
+    input
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_engine::graph_core::{
+        config::Config,
+        traversal::TraversalBuilder,
+        traversal_steps::TraversalSearchMethods,
+    };
+    use crate::props;
+    use crate::protocol::{traversal_value::TraversalValue, value::Value};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn setup_temp_db() -> (HelixGraphStorage, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+        let storage = HelixGraphStorage::new(db_path, Config::default()).unwrap();
+        (storage, temp_dir)
+    }
+
+    /// Two squares joined by a single edge, plus an isolated node
+    fn create_graph(storage: &HelixGraphStorage) -> Vec<String> {
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let ids: Vec<String> = (0..9)
+            .map(|_| {
+                storage
+                    .create_node(&mut txn, "user", props!(), None)
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        for (from, to) in [(0, 1), (1, 2), (2, 3), (3, 0), (4, 5), (5, 6), (6, 7), (7, 4), (3, 4)] {
+            storage
+                .create_edge(&mut txn, "follows", &ids[from], &ids[to], props!())
+                .unwrap();
+        }
+        txn.commit().unwrap();
+        ids
+    }
+
+    #[test]
+    fn test_random_walks_are_reproducible() {
+        let (storage, _temp_dir) = setup_temp_db();
+        create_graph(&storage);
+
+        let config = RandomWalkConfig {
+            walk_length: 8,
+            walks_per_node: 3,
+            q: 0.5,
+            ..Default::default()
+        };
+        let first = storage.random_walks(&config).unwrap();
+        let second = storage.random_walks(&config).unwrap();
+
+        assert_eq!(first.walks.len(), 27);
+        assert_eq!(first.walks, second.walks);
+
+        for walk in &first.walks {
+            // the isolated node cannot go anywhere
+            assert!(walk.len() == 8 || walk.len() == 1);
+        }
+    }
+
+    #[test]
+    fn test_walks_follow_edges() {
+        let (storage, _temp_dir) = setup_temp_db();
+        create_graph(&storage);
+
+        let walks = storage.random_walks(&RandomWalkConfig::default()).unwrap();
+        let txn = storage.graph_env.read_txn().unwrap();
+        let projection = GraphProjection::new(&storage, &txn, &[], &[], None).unwrap();
+        let neighbours = projection.undirected_neighbours();
+
+        for walk in &walks.walks {
+            for pair in walk.windows(2) {
+                assert!(neighbours[pair[0]].contains(&pair[1]));
+            }
+        }
+    }
+
+    #[test]
+    fn test_export_walks() {
+        let (storage, temp_dir) = setup_temp_db();
+        create_graph(&storage);
+
+        let config = RandomWalkConfig {
+            walks_per_node: 1,
+            walk_length: 4,
+            ..Default::default()
+        };
+        let walks = storage.random_walks(&config).unwrap();
+        let path = temp_dir.path().join("walks.txt");
+        walks.export(&path).unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents.lines().count(), 9);
+        let first = contents.lines().next().unwrap();
+        assert_eq!(
+            first.split(' ').next().unwrap(),
+            walks.node_ids[walks.walks[0][0]]
+        );
+    }
+
+    #[test]
+    fn test_embed_nodes_into_their_own_index() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_graph(&storage);
+
+        let config = SkipGramConfig {
+            dimensions: 16,
+            epochs: 2,
+            ..Default::default()
+        };
+        for _ in 0..2 {
+            let inserted = storage
+                .embed_nodes(&RandomWalkConfig::default(), &config)
+                .unwrap();
+            assert_eq!(inserted, ids.len());
+        }
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        for id in &ids {
+            let vector = storage.node_embeddings.get_vector(&txn, id, 0, true).unwrap();
+            assert_eq!(vector.get_data().len(), 16);
+            assert!(storage.vectors.get_vector(&txn, id, 0, true).is_err());
+        }
+        // the second run replaced the first one instead of adding to it
+        let query = storage.node_embeddings.get_vector(&txn, &ids[0], 0, true).unwrap();
+        let mut found = storage
+            .node_embeddings
+            .search(&txn, query.get_data(), ids.len() * 2)
+            .unwrap()
+            .into_iter()
+            .map(|vector| vector.get_id().to_string())
+            .collect::<Vec<_>>();
+        found.sort();
+        found.dedup();
+        assert_eq!(found.len(), ids.len());
+    }
+
+    #[test]
+    fn test_similar_nodes_share_structure() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_graph(&storage);
+        let config = SkipGramConfig {
+            dimensions: 16,
+            ..Default::default()
+        };
+        storage
+            .embed_nodes(&RandomWalkConfig::default(), &config)
+            .unwrap();
+
+        let storage = Arc::new(storage);
+        let txn = storage.graph_env.read_txn().unwrap();
+        let start = storage.get_node(&txn, &ids[0]).unwrap();
+        let mut tr = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::from(start));
+        tr.similar_nodes(&txn, 3);
+
+        let similar = match &tr.current_step {
+            TraversalValue::NodeArray(nodes) => nodes.clone(),
+            other => panic!("expected nodes, got {:?}", other),
+        };
+        assert_eq!(similar.len(), 3);
+        // the nodes of the same square are closer than the other square and the isolated node
+        for node in &similar {
+            assert!(ids[1..4].contains(&node.id), "{} is not in the same square", node.id);
+        }
+        let scores: Vec<f64> = similar
+            .iter()
+            .filter_map(|node| match node.properties.get(SIMILARITY_PROPERTY) {
+                Some(Value::Float(score)) => Some(*score),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(scores.len(), 3);
+        assert!(scores.windows(2).all(|pair| pair[0] >= pair[1]));
+    }
+}
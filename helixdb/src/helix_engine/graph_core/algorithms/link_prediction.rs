@@ -0,0 +1,456 @@
+use std::collections::{HashMap, HashSet};
+
+use heed3::RoTxn;
+use rayon::prelude::*;
+
+use crate::decode_str;
+use crate::helix_engine::{
+    storage_core::{storage_core::HelixGraphStorage, storage_methods::StorageMethods},
+    types::GraphError,
+};
+use crate::protocol::items::Node;
+
+use super::projection::GraphProjection;
+
+/// Node property the score of a predicted link is stored under
+/// when predictions are returned from a traversal
+pub const LINK_SCORE_PROPERTY: &str = "link_score";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkPredictionMetric {
+    CommonNeighbours,
+    Jaccard,
+    AdamicAdar,
+    PreferentialAttachment,
+}
+
+impl TryFrom<&str> for LinkPredictionMetric {
+    type Error = GraphError;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "CommonNeighbours" | "CommonNeighbors" => Ok(Self::CommonNeighbours),
+            "Jaccard" => Ok(Self::Jaccard),
+            "AdamicAdar" => Ok(Self::AdamicAdar),
+            "PreferentialAttachment" => Ok(Self::PreferentialAttachment),
+            _ => Err(GraphError::New(format!(
+                "Unknown link prediction metric: {}",
+                value
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TriangleResult {
+    /// Node id => number of triangles the node is part of
+    pub triangles: HashMap<String, usize>,
+    /// Node id => local clustering coefficient
+    pub clustering: HashMap<String, f64>,
+    /// Number of distinct triangles in the projection
+    pub total_triangles: usize,
+}
+
+pub trait LinkPredictionMethods {
+    /// Ids of the nodes connected to a node in either direction by an edge with the given label,
+    /// or any label if `edge_label` is empty
+    fn neighbour_ids(
+        &self,
+        txn: &RoTxn,
+        node_id: &str,
+        edge_label: &str,
+    ) -> Result<HashSet<String>, GraphError>;
+
+    /// Number of triangles a node is part of, ignoring edge direction
+    fn triangle_count(&self, txn: &RoTxn, node_id: &str, edge_label: &str)
+        -> Result<usize, GraphError>;
+
+    /// Fraction of a node's neighbour pairs that are connected themselves
+    fn clustering_coefficient(
+        &self,
+        txn: &RoTxn,
+        node_id: &str,
+        edge_label: &str,
+    ) -> Result<f64, GraphError>;
+
+    /// Triangle counts and clustering coefficients for every node with the given labels
+    fn triangles(
+        &self,
+        txn: &RoTxn,
+        node_labels: &[String],
+        edge_labels: &[String],
+    ) -> Result<TriangleResult, GraphError>;
+
+    /// Scores how likely a link between two nodes is
+    fn link_score(
+        &self,
+        txn: &RoTxn,
+        from_id: &str,
+        to_id: &str,
+        edge_label: &str,
+        metric: LinkPredictionMetric,
+    ) -> Result<f64, GraphError>;
+
+    /// Scores every node two hops away from `node_id` that is not already a neighbour
+    /// and returns the `limit` highest scoring ones, highest first
+    fn predict_links(
+        &self,
+        txn: &RoTxn,
+        node_id: &str,
+        edge_label: &str,
+        metric: LinkPredictionMetric,
+        limit: usize,
+    ) -> Result<Vec<(Node, f64)>, GraphError>;
+}
+
+impl LinkPredictionMethods for HelixGraphStorage {
+    fn neighbour_ids(
+        &self,
+        txn: &RoTxn,
+        node_id: &str,
+        edge_label: &str,
+    ) -> Result<HashSet<String>, GraphError> {
+        let mut neighbours = HashSet::new();
+
+        for (db, prefix) in [
+            (&self.out_edges_db, Self::out_edge_key(node_id, "")),
+            (&self.in_edges_db, Self::in_edge_key(node_id, "")),
+        ] {
+            let iter = db.lazily_decode_data().prefix_iter(txn, &prefix)?;
+            for result in iter {
+                let (key, value) = result?;
+                let other = std::str::from_utf8(&key[prefix.len()..])?;
+                if other == node_id {
+                    continue;
+                }
+                if !edge_label.is_empty() {
+                    // the label index avoids deserializing every edge
+                    let edge_id = decode_str!(value);
+                    if self
+                        .edge_labels_db
+                        .get(txn, &Self::edge_label_key(edge_label, edge_id))?
+                        .is_none()
+                    {
+                        continue;
+                    }
+                }
+                neighbours.insert(other.to_string());
+            }
+        }
+
+        Ok(neighbours)
+    }
+
+    fn triangle_count(
+        &self,
+        txn: &RoTxn,
+        node_id: &str,
+        edge_label: &str,
+    ) -> Result<usize, GraphError> {
+        let neighbours = self.neighbour_ids(txn, node_id, edge_label)?;
+        let mut links = 0;
+        for neighbour in &neighbours {
+            links += self
+                .neighbour_ids(txn, neighbour, edge_label)?
+                .intersection(&neighbours)
+                .count();
+        }
+        // every link between two neighbours is seen from both ends
+        Ok(links / 2)
+    }
+
+    fn clustering_coefficient(
+        &self,
+        txn: &RoTxn,
+        node_id: &str,
+        edge_label: &str,
+    ) -> Result<f64, GraphError> {
+        let degree = self.neighbour_ids(txn, node_id, edge_label)?.len();
+        if degree < 2 {
+            return Ok(0.0);
+        }
+        let triangles = self.triangle_count(txn, node_id, edge_label)?;
+        Ok(2.0 * triangles as f64 / (degree * (degree - 1)) as f64)
+    }
+
+    fn triangles(
+        &self,
+        txn: &RoTxn,
+        node_labels: &[String],
+        edge_labels: &[String],
+    ) -> Result<TriangleResult, GraphError> {
+        let projection = GraphProjection::new(self, txn, node_labels, edge_labels, None)?;
+        let neighbours = projection.undirected_neighbours();
+
+        // count each triangle once from its lowest node, then credit all three corners
+        let found: Vec<(usize, usize, usize)> = (0..neighbours.len())
+            .into_par_iter()
+            .flat_map_iter(|u| {
+                let higher: Vec<usize> = neighbours[u].iter().copied().filter(|&v| v > u).collect();
+                let mut local = Vec::new();
+                for (i, &v) in higher.iter().enumerate() {
+                    for &w in &higher[i + 1..] {
+                        if neighbours[v].binary_search(&w).is_ok() {
+                            local.push((u, v, w));
+                        }
+                    }
+                }
+                local
+            })
+            .collect();
+
+        let mut counts = vec![0usize; neighbours.len()];
+        for &(u, v, w) in &found {
+            counts[u] += 1;
+            counts[v] += 1;
+            counts[w] += 1;
+        }
+
+        let mut result = TriangleResult {
+            total_triangles: found.len(),
+            ..Default::default()
+        };
+        for (node, id) in projection.node_ids.iter().enumerate() {
+            let degree = neighbours[node].len();
+            let clustering = if degree < 2 {
+                0.0
+            } else {
+                2.0 * counts[node] as f64 / (degree * (degree - 1)) as f64
+            };
+            result.triangles.insert(id.clone(), counts[node]);
+            result.clustering.insert(id.clone(), clustering);
+        }
+
+        Ok(result)
+    }
+
+    fn link_score(
+        &self,
+        txn: &RoTxn,
+        from_id: &str,
+        to_id: &str,
+        edge_label: &str,
+        metric: LinkPredictionMetric,
+    ) -> Result<f64, GraphError> {
+        let from = self.neighbour_ids(txn, from_id, edge_label)?;
+        let to = self.neighbour_ids(txn, to_id, edge_label)?;
+        score(self, txn, &from, &to, edge_label, metric, &mut HashMap::new())
+    }
+
+    fn predict_links(
+        &self,
+        txn: &RoTxn,
+        node_id: &str,
+        edge_label: &str,
+        metric: LinkPredictionMetric,
+        limit: usize,
+    ) -> Result<Vec<(Node, f64)>, GraphError> {
+        let neighbours = self.neighbour_ids(txn, node_id, edge_label)?;
+        let mut degrees = HashMap::new();
+
+        let mut candidates = HashMap::new();
+        for neighbour in &neighbours {
+            for candidate in self.neighbour_ids(txn, neighbour, edge_label)? {
+                if candidate != node_id
+                    && !neighbours.contains(&candidate)
+                    && !candidates.contains_key(&candidate)
+                {
+                    let candidate_neighbours = self.neighbour_ids(txn, &candidate, edge_label)?;
+                    candidates.insert(candidate, candidate_neighbours);
+                }
+            }
+        }
+
+        let mut scored = Vec::with_capacity(candidates.len());
+        for (candidate, candidate_neighbours) in &candidates {
+            let s = score(
+                self,
+                txn,
+                &neighbours,
+                candidate_neighbours,
+                edge_label,
+                metric,
+                &mut degrees,
+            )?;
+            scored.push((candidate, s));
+        }
+        scored.sort_by(|(id_a, a), (id_b, b)| {
+            b.partial_cmp(a)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| id_a.cmp(id_b))
+        });
+
+        scored
+            .into_iter()
+            .take(limit)
+            .map(|(id, s)| Ok((self.get_node(txn, id)?, s)))
+            .collect()
+    }
+}
+
+fn score(
+    storage: &HelixGraphStorage,
+    txn: &RoTxn,
+    from: &HashSet<String>,
+    to: &HashSet<String>,
+    edge_label: &str,
+    metric: LinkPredictionMetric,
+    degrees: &mut HashMap<String, usize>,
+) -> Result<f64, GraphError> {
+    let common = || from.intersection(to);
+
+    Ok(match metric {
+        LinkPredictionMetric::CommonNeighbours => common().count() as f64,
+        LinkPredictionMetric::Jaccard => {
+            let union = from.union(to).count();
+            if union == 0 {
+                0.0
+            } else {
+                common().count() as f64 / union as f64
+            }
+        }
+        LinkPredictionMetric::AdamicAdar => {
+            let mut total = 0.0;
+            for z in common() {
+                let degree = match degrees.get(z) {
+                    Some(degree) => *degree,
+                    None => {
+                        let degree = storage.neighbour_ids(txn, z, edge_label)?.len();
+                        degrees.insert(z.clone(), degree);
+                        degree
+                    }
+                };
+                if degree > 1 {
+                    total += 1.0 / (degree as f64).ln();
+                }
+            }
+            total
+        }
+        LinkPredictionMetric::PreferentialAttachment => (from.len() * to.len()) as f64,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_engine::graph_core::config::Config;
+    use crate::props;
+    use tempfile::TempDir;
+
+    fn setup_temp_db() -> (HelixGraphStorage, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+        let storage = HelixGraphStorage::new(db_path, Config::default()).unwrap();
+        (storage, temp_dir)
+    }
+
+    // Graph Structure:
+    // (0)-[knows]->(1)-[knows]->(2)-[knows]->(0)   triangle
+    // (2)-[knows]->(3)
+    // (1)-[knows]->(4), (3)-[knows]->(4)
+    // (0)-[likes]->(3)
+    fn create_graph(storage: &HelixGraphStorage) -> Vec<String> {
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let ids: Vec<String> = (0..5)
+            .map(|_| {
+                storage
+                    .create_node(&mut txn, "person", props!(), None)
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        for (from, to) in [(0, 1), (1, 2), (2, 0), (2, 3), (1, 4), (3, 4)] {
+            storage
+                .create_edge(&mut txn, "knows", &ids[from], &ids[to], props!())
+                .unwrap();
+        }
+        storage
+            .create_edge(&mut txn, "likes", &ids[0], &ids[3], props!())
+            .unwrap();
+        txn.commit().unwrap();
+        ids
+    }
+
+    #[test]
+    fn test_triangle_count_and_clustering() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_graph(&storage);
+        let txn = storage.graph_env.read_txn().unwrap();
+
+        assert_eq!(storage.triangle_count(&txn, &ids[0], "knows").unwrap(), 1);
+        assert_eq!(storage.triangle_count(&txn, &ids[3], "knows").unwrap(), 0);
+        // the likes edge closes (0, 2, 3) when every label is considered
+        assert_eq!(storage.triangle_count(&txn, &ids[0], "").unwrap(), 2);
+
+        let coefficient = storage.clustering_coefficient(&txn, &ids[2], "knows").unwrap();
+        assert!((coefficient - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(storage.clustering_coefficient(&txn, &ids[4], "knows").unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_triangles_over_projection() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_graph(&storage);
+        let txn = storage.graph_env.read_txn().unwrap();
+
+        let result = storage
+            .triangles(&txn, &[], &["knows".to_string()])
+            .unwrap();
+        assert_eq!(result.total_triangles, 1);
+        assert_eq!(result.triangles[&ids[1]], 1);
+        assert_eq!(result.triangles[&ids[4]], 0);
+        assert!((result.clustering[&ids[0]] - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_link_scores() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_graph(&storage);
+        let txn = storage.graph_env.read_txn().unwrap();
+
+        // knows neighbours: 1 -> {0, 2, 4}, 3 -> {2, 4}
+        let score = |metric| {
+            storage
+                .link_score(&txn, &ids[1], &ids[3], "knows", metric)
+                .unwrap()
+        };
+        assert_eq!(score(LinkPredictionMetric::CommonNeighbours), 2.0);
+        assert!((score(LinkPredictionMetric::Jaccard) - 2.0 / 3.0).abs() < 1e-9);
+        assert_eq!(score(LinkPredictionMetric::PreferentialAttachment), 6.0);
+        // node 2 has degree 3, node 4 has degree 2
+        let expected = 1.0 / 3f64.ln() + 1.0 / 2f64.ln();
+        assert!((score(LinkPredictionMetric::AdamicAdar) - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_predict_links() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_graph(&storage);
+        let txn = storage.graph_env.read_txn().unwrap();
+
+        let predictions = storage
+            .predict_links(&txn, &ids[0], "knows", LinkPredictionMetric::CommonNeighbours, 10)
+            .unwrap();
+        let predicted: Vec<&str> = predictions.iter().map(|(n, _)| n.id.as_str()).collect();
+
+        // 3 via 2, 4 via 1, existing neighbours are never suggested
+        assert_eq!(predictions.len(), 2);
+        assert!(predicted.contains(&ids[3].as_str()));
+        assert!(predicted.contains(&ids[4].as_str()));
+        assert!(!predicted.contains(&ids[1].as_str()));
+
+        let limited = storage
+            .predict_links(&txn, &ids[0], "knows", LinkPredictionMetric::Jaccard, 1)
+            .unwrap();
+        assert_eq!(limited.len(), 1);
+    }
+
+    #[test]
+    fn test_metric_from_str() {
+        assert_eq!(
+            LinkPredictionMetric::try_from("AdamicAdar").unwrap(),
+            LinkPredictionMetric::AdamicAdar
+        );
+        assert!(LinkPredictionMetric::try_from("Unknown").is_err());
+    }
+}
@@ -0,0 +1,7 @@
+pub mod projection;
+pub mod community;
+pub mod centrality;
+pub mod link_prediction;
+pub mod embeddings;
+pub mod sampling;
+pub mod dag;
@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+
+use heed3::RoTxn;
+
+use crate::decode_str;
+use crate::helix_engine::{
+    storage_core::{
+        storage_core::{HelixGraphStorage, NODE_LABEL_PREFIX, NODE_PREFIX},
+        storage_methods::StorageMethods,
+    },
+    types::GraphError,
+};
+use crate::protocol::value::Value;
+
+/// Number of node updates committed per write transaction when writing
+/// algorithm results back onto nodes.
+pub const DEFAULT_WRITE_BATCH_SIZE: usize = 10_000;
+
+/// Which edges are followed from a node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    Out,
+    In,
+    Both,
+}
+
+/// An in-memory, index based copy of a subset of the graph.
+///
+/// Graph algorithms that need to visit every node many times (community detection,
+/// centrality, ...) run over a projection instead of going back to LMDB on every hop.
+/// Nodes are addressed by their position in `node_ids`.
+#[derive(Debug, Default)]
+pub struct GraphProjection {
+    pub node_ids: Vec<String>,
+    pub index: HashMap<String, usize>,
+    /// Outgoing adjacency as `(target, weight)` pairs
+    pub out_adj: Vec<Vec<(usize, f64)>>,
+    /// Incoming adjacency as `(source, weight)` pairs
+    pub in_adj: Vec<Vec<(usize, f64)>>,
+    pub edge_count: usize,
+}
+
+impl GraphProjection {
+    /// Builds a projection of the graph
+    ///
+    /// ## Arguments
+    ///
+    /// * `node_labels` - Labels of the nodes to include, all nodes if empty
+    /// * `edge_labels` - Labels of the edges to include, all edges if empty
+    /// * `weight_property` - Edge property to use as weight. Edges without it weigh `1.0`
+    ///
+    /// Only edges whose endpoints are both part of the projection are kept.
+    pub fn new(
+        storage: &HelixGraphStorage,
+        txn: &RoTxn,
+        node_labels: &[String],
+        edge_labels: &[String],
+        weight_property: Option<&str>,
+    ) -> Result<Self, GraphError> {
+        let node_ids = Self::collect_node_ids(storage, txn, node_labels)?;
+        let index: HashMap<String, usize> = node_ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| (id.clone(), i))
+            .collect();
+
+        let mut out_adj = vec![Vec::new(); node_ids.len()];
+        let mut in_adj = vec![Vec::new(); node_ids.len()];
+        let mut edge_count = 0;
+
+        for (from, node_id) in node_ids.iter().enumerate() {
+            let prefix = HelixGraphStorage::out_edge_key(node_id, "");
+            let iter = storage
+                .out_edges_db
+                .lazily_decode_data()
+                .prefix_iter(txn, &prefix)?;
+
+            for result in iter {
+                let (key, value) = result?;
+                let to_node = std::str::from_utf8(&key[prefix.len()..])?;
+                let to = match index.get(to_node) {
+                    Some(to) => *to,
+                    None => continue,
+                };
+
+                let edge_id = decode_str!(value);
+                let edge = storage.get_edge(txn, edge_id)?;
+                if !edge_labels.is_empty() && !edge_labels.contains(&edge.label) {
+                    continue;
+                }
+
+                let weight = match weight_property {
+                    Some(property) => edge
+                        .properties
+                        .get(property)
+                        .and_then(value_as_f64)
+                        .unwrap_or(1.0),
+                    None => 1.0,
+                };
+
+                out_adj[from].push((to, weight));
+                in_adj[to].push((from, weight));
+                edge_count += 1;
+            }
+        }
+
+        Ok(Self {
+            node_ids,
+            index,
+            out_adj,
+            in_adj,
+            edge_count,
+        })
+    }
+
+    fn collect_node_ids(
+        storage: &HelixGraphStorage,
+        txn: &RoTxn,
+        node_labels: &[String],
+    ) -> Result<Vec<String>, GraphError> {
+        let mut ids = Vec::new();
+
+        if node_labels.is_empty() {
+            let iter = storage
+                .nodes_db
+                .lazily_decode_data()
+                .prefix_iter(txn, NODE_PREFIX)?;
+            for result in iter {
+                let (key, _) = result?;
+                ids.push(std::str::from_utf8(&key[NODE_PREFIX.len()..])?.to_string());
+            }
+            return Ok(ids);
+        }
+
+        for label in node_labels {
+            let prefix = [NODE_LABEL_PREFIX, label.as_bytes(), b":"].concat();
+            let iter = storage
+                .node_labels_db
+                .lazily_decode_data()
+                .prefix_iter(txn, &prefix)?;
+            for result in iter {
+                let (key, _) = result?;
+                ids.push(std::str::from_utf8(&key[prefix.len()..])?.to_string());
+            }
+        }
+
+        Ok(ids)
+    }
+
+    #[inline(always)]
+    pub fn len(&self) -> usize {
+        self.node_ids.len()
+    }
+
+    #[inline(always)]
+    pub fn is_empty(&self) -> bool {
+        self.node_ids.is_empty()
+    }
+
+    /// Out degree plus in degree of a node
+    #[inline(always)]
+    pub fn degree(&self, node: usize) -> usize {
+        self.out_adj[node].len() + self.in_adj[node].len()
+    }
+
+    /// Collapses edge direction, summing the weights of parallel and reciprocal edges.
+    ///
+    /// Self loops are stored with twice their weight so that the weighted degree
+    /// of a node is the sum of its row.
+    pub fn undirected_weighted(&self) -> Vec<HashMap<usize, f64>> {
+        let mut adj = vec![HashMap::new(); self.len()];
+        for (from, targets) in self.out_adj.iter().enumerate() {
+            for &(to, weight) in targets {
+                *adj[from].entry(to).or_insert(0.0) += weight;
+                *adj[to].entry(from).or_insert(0.0) += weight;
+            }
+        }
+        adj
+    }
+
+    /// Sorted, deduplicated neighbours of every node ignoring direction and self loops
+    pub fn undirected_neighbours(&self) -> Vec<Vec<usize>> {
+        (0..self.len())
+            .map(|node| {
+                let mut neighbours: Vec<usize> = self.out_adj[node]
+                    .iter()
+                    .chain(self.in_adj[node].iter())
+                    .map(|&(n, _)| n)
+                    .filter(|&n| n != node)
+                    .collect();
+                neighbours.sort_unstable();
+                neighbours.dedup();
+                neighbours
+            })
+            .collect()
+    }
+
+    /// Writes one property per node, committing every `batch_size` updates
+    /// so that large projections do not hold a single huge write transaction.
+    pub fn write_property(
+        &self,
+        storage: &HelixGraphStorage,
+        property: &str,
+        values: &[Value],
+        batch_size: usize,
+    ) -> Result<(), GraphError> {
+        if values.len() != self.len() {
+            return Err(GraphError::New(format!(
+                "Expected {} values to write, got {}",
+                self.len(),
+                values.len()
+            )));
+        }
+
+        for (ids, values) in self
+            .node_ids
+            .chunks(batch_size.max(1))
+            .zip(values.chunks(batch_size.max(1)))
+        {
+            let mut txn = storage.graph_env.write_txn()?;
+            for (id, value) in ids.iter().zip(values.iter()) {
+                storage.update_node(&mut txn, id, vec![(property.to_string(), value.clone())])?;
+            }
+            txn.commit()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads a numeric property value as `f64`
+#[inline(always)]
+pub fn value_as_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float(f) => Some(*f),
+        Value::Integer(i) => Some(*i as f64),
+        _ => None,
+    }
+}
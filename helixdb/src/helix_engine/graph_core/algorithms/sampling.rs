@@ -0,0 +1,585 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use heed3::RoTxn;
+use rand::{rngs::StdRng, seq::index::sample, SeedableRng};
+
+use crate::decode_str;
+use crate::helix_engine::{
+    storage_core::{storage_core::HelixGraphStorage, storage_methods::StorageMethods},
+    types::GraphError,
+};
+
+use super::projection::{value_as_f64, Direction};
+
+/// Options for sampling the k-hop neighbourhood of a set of seed nodes
+#[derive(Debug, Clone)]
+pub struct SamplingConfig {
+    /// Edge labels to follow and keep, all edges if empty
+    pub edge_labels: Vec<String>,
+    /// Maximum number of neighbours sampled per node, one entry per hop.
+    /// A node with fewer neighbours keeps all of them.
+    pub fan_outs: Vec<usize>,
+    pub direction: Direction,
+    /// Numeric node properties turned into feature columns. Missing values become `0.0`
+    pub feature_properties: Vec<String>,
+    /// Also fetch the vector linked to each node (the vector sharing the node's id)
+    pub include_vectors: bool,
+    pub seed: u64,
+}
+
+impl Default for SamplingConfig {
+    fn default() -> Self {
+        Self {
+            edge_labels: Vec::new(),
+            fan_outs: vec![10, 5],
+            direction: Direction::Out,
+            feature_properties: Vec::new(),
+            include_vectors: false,
+            seed: 42,
+        }
+    }
+}
+
+/// The subgraph induced by a sampled neighbourhood, laid out for GNN training.
+///
+/// Nodes are addressed by their position in `node_ids`, seeds come first.
+/// `edge_index` holds the source row and target row of every edge in COO format.
+#[derive(Debug, Clone, Default)]
+pub struct SampledSubgraph {
+    pub node_ids: Vec<String>,
+    pub node_labels: Vec<String>,
+    /// Hop at which each node was first reached, `0` for seeds
+    pub hops: Vec<usize>,
+    pub edge_ids: Vec<String>,
+    pub edge_labels: Vec<String>,
+    pub edge_index: [Vec<i64>; 2],
+    /// Row major `node_ids.len() x feature_properties.len()` matrix
+    pub features: Vec<Vec<f64>>,
+    /// Linked vector of each node, `None` if the node has none or vectors were not requested
+    pub vectors: Vec<Option<Vec<f64>>>,
+}
+
+impl SampledSubgraph {
+    #[inline(always)]
+    pub fn num_nodes(&self) -> usize {
+        self.node_ids.len()
+    }
+
+    #[inline(always)]
+    pub fn num_edges(&self) -> usize {
+        self.edge_ids.len()
+    }
+
+    /// Vectors as a dense matrix, nodes without a vector get a row of zeros.
+    /// Returns `None` if no node has a vector.
+    pub fn vector_matrix(&self) -> Option<Vec<Vec<f64>>> {
+        let dimensions = self.vectors.iter().flatten().map(|v| v.len()).max()?;
+        Some(
+            self.vectors
+                .iter()
+                .map(|v| match v {
+                    Some(v) if v.len() == dimensions => v.clone(),
+                    _ => vec![0.0; dimensions],
+                })
+                .collect(),
+        )
+    }
+
+    /// Writes the subgraph as NumPy arrays into `dir`:
+    ///
+    /// * `edge_index.npy` - `2 x E` int64
+    /// * `features.npy` - `N x F` float64
+    /// * `vectors.npy` - `N x D` float64, only if any node has a vector
+    /// * `node_ids.txt` - one node id per line, in row order
+    pub fn export_npy<P: AsRef<Path>>(&self, dir: P) -> Result<(), GraphError> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let mut writer = BufWriter::new(File::create(dir.join("edge_index.npy"))?);
+        write_npy_i64(&mut writer, &self.edge_index)?;
+        writer.flush()?;
+
+        let columns = self.features.first().map_or(0, |row| row.len());
+        let mut writer = BufWriter::new(File::create(dir.join("features.npy"))?);
+        write_npy_f64(&mut writer, &self.features, columns)?;
+        writer.flush()?;
+
+        if let Some(vectors) = self.vector_matrix() {
+            let columns = vectors.first().map_or(0, |row| row.len());
+            let mut writer = BufWriter::new(File::create(dir.join("vectors.npy"))?);
+            write_npy_f64(&mut writer, &vectors, columns)?;
+            writer.flush()?;
+        }
+
+        let mut writer = BufWriter::new(File::create(dir.join("node_ids.txt"))?);
+        for id in &self.node_ids {
+            writeln!(writer, "{}", id)?;
+        }
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Writes the subgraph as `nodes.parquet` (id, label, hop, one column per feature,
+    /// and `vector` if present) and `edges.parquet` (id, label, source, target) into `dir`.
+    ///
+    /// Needs the `polars` feature. Tests always build it, polars is a dev-dependency.
+    #[cfg(any(feature = "polars", test))]
+    pub fn export_parquet<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        feature_names: &[String],
+    ) -> Result<(), GraphError> {
+        use polars::prelude::*;
+
+        let to_error = |e: PolarsError| GraphError::New(format!("Parquet export failed: {}", e));
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        let mut columns = vec![
+            Column::new("id".into(), &self.node_ids),
+            Column::new("label".into(), &self.node_labels),
+            Column::new(
+                "hop".into(),
+                self.hops.iter().map(|&h| h as u32).collect::<Vec<_>>(),
+            ),
+        ];
+        for (i, name) in feature_names.iter().enumerate() {
+            let values: Vec<f64> = self.features.iter().map(|row| row[i]).collect();
+            columns.push(Column::new(name.as_str().into(), values));
+        }
+        if let Some(vectors) = self.vector_matrix() {
+            let rows: Vec<Series> = vectors
+                .into_iter()
+                .map(|v| Series::new("".into(), v))
+                .collect();
+            columns.push(Column::new("vector".into(), rows));
+        }
+        let mut nodes = DataFrame::new(columns).map_err(to_error)?;
+        ParquetWriter::new(File::create(dir.join("nodes.parquet"))?)
+            .finish(&mut nodes)
+            .map_err(to_error)?;
+
+        let mut edges = DataFrame::new(vec![
+            Column::new("id".into(), &self.edge_ids),
+            Column::new("label".into(), &self.edge_labels),
+            Column::new("source".into(), &self.edge_index[0]),
+            Column::new("target".into(), &self.edge_index[1]),
+        ])
+        .map_err(to_error)?;
+        ParquetWriter::new(File::create(dir.join("edges.parquet"))?)
+            .finish(&mut edges)
+            .map_err(to_error)?;
+
+        Ok(())
+    }
+}
+
+pub trait SamplingMethods {
+    /// Samples up to `config.fan_outs[hop]` neighbours of every node reached at each hop,
+    /// starting from `seeds`, and returns the subgraph induced by the sampled nodes.
+    ///
+    /// The same seeds, config and graph always produce the same subgraph.
+    fn sample_subgraph(
+        &self,
+        txn: &RoTxn,
+        seeds: &[String],
+        config: &SamplingConfig,
+    ) -> Result<SampledSubgraph, GraphError>;
+}
+
+impl SamplingMethods for HelixGraphStorage {
+    fn sample_subgraph(
+        &self,
+        txn: &RoTxn,
+        seeds: &[String],
+        config: &SamplingConfig,
+    ) -> Result<SampledSubgraph, GraphError> {
+        let mut rng = StdRng::seed_from_u64(config.seed);
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut node_ids = Vec::new();
+        let mut hops = Vec::new();
+
+        for seed in seeds {
+            if !index.contains_key(seed) {
+                // fail early on unknown seeds rather than returning an empty neighbourhood
+                self.get_node(txn, seed)?;
+                index.insert(seed.clone(), node_ids.len());
+                node_ids.push(seed.clone());
+                hops.push(0);
+            }
+        }
+
+        let mut frontier: Vec<usize> = (0..node_ids.len()).collect();
+        for (hop, &fan_out) in config.fan_outs.iter().enumerate() {
+            let mut next = Vec::new();
+            for node in frontier {
+                let neighbours = neighbours(self, txn, &node_ids[node], config)?;
+                let picked: Vec<&String> = if neighbours.len() <= fan_out {
+                    neighbours.iter().collect()
+                } else {
+                    let mut picked = sample(&mut rng, neighbours.len(), fan_out).into_vec();
+                    // keep the scan order so results only depend on the rng draws
+                    picked.sort_unstable();
+                    picked.into_iter().map(|i| &neighbours[i]).collect()
+                };
+
+                for id in picked {
+                    if !index.contains_key(id) {
+                        index.insert(id.clone(), node_ids.len());
+                        next.push(node_ids.len());
+                        node_ids.push(id.clone());
+                        hops.push(hop + 1);
+                    }
+                }
+            }
+            frontier = next;
+        }
+
+        let mut subgraph = SampledSubgraph {
+            hops,
+            ..Default::default()
+        };
+
+        for (from, node_id) in node_ids.iter().enumerate() {
+            let node = self.get_node(txn, node_id)?;
+            subgraph.features.push(
+                config
+                    .feature_properties
+                    .iter()
+                    .map(|p| node.properties.get(p).and_then(value_as_f64).unwrap_or(0.0))
+                    .collect(),
+            );
+            subgraph.vectors.push(match config.include_vectors {
+                true => self
+                    .vectors
+                    .get_vector(txn, node_id, 0, true)
+                    .ok()
+                    .map(|v| v.get_data().to_vec()),
+                false => None,
+            });
+            subgraph.node_labels.push(node.label);
+
+            let prefix = HelixGraphStorage::out_edge_key(node_id, "");
+            let iter = self
+                .out_edges_db
+                .lazily_decode_data()
+                .prefix_iter(txn, &prefix)?;
+            for result in iter {
+                let (key, value) = result?;
+                let to_node = std::str::from_utf8(&key[prefix.len()..])?;
+                let to = match index.get(to_node) {
+                    Some(to) => *to,
+                    None => continue,
+                };
+                let edge = self.get_edge(txn, decode_str!(value))?;
+                if !config.edge_labels.is_empty() && !config.edge_labels.contains(&edge.label) {
+                    continue;
+                }
+                subgraph.edge_index[0].push(from as i64);
+                subgraph.edge_index[1].push(to as i64);
+                subgraph.edge_ids.push(edge.id);
+                subgraph.edge_labels.push(edge.label);
+            }
+        }
+
+        subgraph.node_ids = node_ids;
+        Ok(subgraph)
+    }
+}
+
+/// Neighbours of a node in key order, following the configured direction and labels.
+/// A neighbour connected by several edges appears once.
+fn neighbours(
+    storage: &HelixGraphStorage,
+    txn: &RoTxn,
+    node_id: &str,
+    config: &SamplingConfig,
+) -> Result<Vec<String>, GraphError> {
+    let mut prefixes = Vec::with_capacity(2);
+    if config.direction != Direction::In {
+        prefixes.push((&storage.out_edges_db, HelixGraphStorage::out_edge_key(node_id, "")));
+    }
+    if config.direction != Direction::Out {
+        prefixes.push((&storage.in_edges_db, HelixGraphStorage::in_edge_key(node_id, "")));
+    }
+
+    // the vec keeps the database order, so sampling stays reproducible
+    let mut neighbours = Vec::new();
+    let mut seen = HashSet::new();
+    for (db, prefix) in prefixes {
+        let iter = db.lazily_decode_data().prefix_iter(txn, &prefix)?;
+        for result in iter {
+            let (key, value) = result?;
+            let other = std::str::from_utf8(&key[prefix.len()..])?;
+            if other == node_id {
+                continue;
+            }
+            if !config.edge_labels.is_empty() {
+                let edge_id = decode_str!(value);
+                let mut matches = false;
+                for label in &config.edge_labels {
+                    if storage
+                        .edge_labels_db
+                        .get(txn, &HelixGraphStorage::edge_label_key(label, edge_id))?
+                        .is_some()
+                    {
+                        matches = true;
+                        break;
+                    }
+                }
+                if !matches {
+                    continue;
+                }
+            }
+            if seen.insert(other) {
+                neighbours.push(other.to_string());
+            }
+        }
+    }
+
+    Ok(neighbours)
+}
+
+/// Writes a `.npy` (format version 1.0) header for a little endian array
+fn write_npy_header<W: Write>(writer: &mut W, descr: &str, shape: &[usize]) -> Result<(), GraphError> {
+    let shape = match shape {
+        [n] => format!("({},)", n),
+        _ => format!(
+            "({})",
+            shape.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        descr, shape
+    );
+    // magic (6) + version (2) + header length (2) + header must be a multiple of 64
+    let padding = (64 - (10 + header.len() + 1) % 64) % 64;
+    header.push_str(&" ".repeat(padding));
+    header.push('\n');
+
+    writer.write_all(b"\x93NUMPY\x01\x00")?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    Ok(())
+}
+
+fn write_npy_i64<W: Write>(writer: &mut W, rows: &[Vec<i64>]) -> Result<(), GraphError> {
+    let columns = rows.first().map_or(0, |row| row.len());
+    write_npy_header(writer, "<i8", &[rows.len(), columns])?;
+    for value in rows.iter().flatten() {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+fn write_npy_f64<W: Write>(
+    writer: &mut W,
+    rows: &[Vec<f64>],
+    columns: usize,
+) -> Result<(), GraphError> {
+    write_npy_header(writer, "<f8", &[rows.len(), columns])?;
+    for value in rows.iter().flatten() {
+        writer.write_all(&value.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_engine::{graph_core::config::Config, vector_core::hnsw::HNSW};
+    use crate::props;
+    use crate::protocol::value::Value;
+    use tempfile::TempDir;
+
+    fn setup_temp_db() -> (HelixGraphStorage, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+        let storage = HelixGraphStorage::new(db_path, Config::default()).unwrap();
+        (storage, temp_dir)
+    }
+
+    /// A root with 6 children, each child having 3 children of its own
+    fn create_tree(storage: &HelixGraphStorage) -> Vec<String> {
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let ids: Vec<String> = (0..25i32)
+            .map(|i| {
+                storage
+                    .create_node(&mut txn, "user", props! { "age" => i }, None)
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        for child in 1..7 {
+            storage
+                .create_edge(&mut txn, "follows", &ids[0], &ids[child], props!())
+                .unwrap();
+            for grandchild in 0..3 {
+                let to = 7 + (child - 1) * 3 + grandchild;
+                storage
+                    .create_edge(&mut txn, "follows", &ids[child], &ids[to], props!())
+                    .unwrap();
+            }
+        }
+        txn.commit().unwrap();
+        ids
+    }
+
+    #[test]
+    fn test_sample_respects_fan_out() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_tree(&storage);
+
+        let config = SamplingConfig {
+            fan_outs: vec![2, 2],
+            ..Default::default()
+        };
+        let txn = storage.graph_env.read_txn().unwrap();
+        let subgraph = storage
+            .sample_subgraph(&txn, &ids[..1], &config)
+            .unwrap();
+
+        assert_eq!(subgraph.node_ids[0], ids[0]);
+        assert_eq!(subgraph.hops.iter().filter(|&&h| h == 1).count(), 2);
+        assert_eq!(subgraph.hops.iter().filter(|&&h| h == 2).count(), 4);
+        assert_eq!(subgraph.num_nodes(), 7);
+        // a tree sampled top down keeps exactly one edge per non seed node
+        assert_eq!(subgraph.num_edges(), 6);
+    }
+
+    #[test]
+    fn test_sample_is_reproducible() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_tree(&storage);
+
+        let config = SamplingConfig {
+            fan_outs: vec![3, 1],
+            seed: 7,
+            ..Default::default()
+        };
+        let txn = storage.graph_env.read_txn().unwrap();
+        let first = storage.sample_subgraph(&txn, &ids[..1], &config).unwrap();
+        let second = storage.sample_subgraph(&txn, &ids[..1], &config).unwrap();
+
+        assert_eq!(first.node_ids, second.node_ids);
+        assert_eq!(first.edge_index, second.edge_index);
+    }
+
+    #[test]
+    fn test_sample_features_and_vectors() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_tree(&storage);
+
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        storage
+            .vectors
+            .insert(&mut txn, &[1.0, 2.0, 3.0], Some(ids[0].clone()))
+            .unwrap();
+        storage
+            .update_node(&mut txn, &ids[1], vec![("age".to_string(), Value::Float(1.5))])
+            .unwrap();
+        txn.commit().unwrap();
+
+        let config = SamplingConfig {
+            fan_outs: vec![6],
+            feature_properties: vec!["age".to_string(), "missing".to_string()],
+            include_vectors: true,
+            ..Default::default()
+        };
+        let txn = storage.graph_env.read_txn().unwrap();
+        let subgraph = storage.sample_subgraph(&txn, &ids[..1], &config).unwrap();
+
+        assert_eq!(subgraph.features[0], vec![0.0, 0.0]);
+        let row = subgraph.node_ids.iter().position(|id| id == &ids[1]).unwrap();
+        assert_eq!(subgraph.features[row], vec![1.5, 0.0]);
+
+        let vectors = subgraph.vector_matrix().unwrap();
+        assert_eq!(vectors[0], vec![1.0, 2.0, 3.0]);
+        assert_eq!(vectors[row], vec![0.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_export_npy() {
+        let (storage, temp_dir) = setup_temp_db();
+        let ids = create_tree(&storage);
+
+        let config = SamplingConfig {
+            fan_outs: vec![6],
+            feature_properties: vec!["age".to_string()],
+            ..Default::default()
+        };
+        let txn = storage.graph_env.read_txn().unwrap();
+        let subgraph = storage.sample_subgraph(&txn, &ids[..1], &config).unwrap();
+
+        let dir = temp_dir.path().join("export");
+        subgraph.export_npy(&dir).unwrap();
+
+        let bytes = fs::read(dir.join("edge_index.npy")).unwrap();
+        assert_eq!(&bytes[..6], b"\x93NUMPY");
+        let header_len = u16::from_le_bytes([bytes[8], bytes[9]]) as usize;
+        assert_eq!((10 + header_len) % 64, 0);
+        let header = std::str::from_utf8(&bytes[10..10 + header_len]).unwrap();
+        assert!(header.contains("'shape': (2, 6)"));
+        assert_eq!(bytes.len(), 10 + header_len + 2 * 6 * 8);
+        assert!(!dir.join("vectors.npy").exists());
+    }
+
+    #[test]
+    fn test_export_parquet() {
+        use polars::prelude::*;
+
+        let (storage, temp_dir) = setup_temp_db();
+        let ids = create_tree(&storage);
+
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        storage
+            .vectors
+            .insert(&mut txn, &[1.0, 2.0, 3.0], Some(ids[0].clone()))
+            .unwrap();
+        txn.commit().unwrap();
+
+        let config = SamplingConfig {
+            fan_outs: vec![6],
+            feature_properties: vec!["age".to_string()],
+            include_vectors: true,
+            ..Default::default()
+        };
+        let txn = storage.graph_env.read_txn().unwrap();
+        let subgraph = storage.sample_subgraph(&txn, &ids[..1], &config).unwrap();
+
+        let dir = temp_dir.path().join("export");
+        subgraph.export_parquet(&dir, &["age".to_string()]).unwrap();
+
+        let nodes = ParquetReader::new(File::open(dir.join("nodes.parquet")).unwrap())
+            .finish()
+            .unwrap();
+        assert_eq!(nodes.height(), 7);
+        assert_eq!(
+            nodes.get_column_names_str(),
+            vec!["id", "label", "hop", "age", "vector"]
+        );
+        assert_eq!(
+            nodes.column("id").unwrap().str().unwrap().get(0),
+            Some(ids[0].as_str())
+        );
+        assert_eq!(
+            nodes.column("age").unwrap().f64().unwrap().get(0),
+            Some(0.0)
+        );
+
+        let edges = ParquetReader::new(File::open(dir.join("edges.parquet")).unwrap())
+            .finish()
+            .unwrap();
+        assert_eq!(edges.height(), 6);
+        // every sampled edge starts at the seed, which is row 0
+        let sources = edges.column("source").unwrap().i64().unwrap();
+        assert!(sources.into_no_null_iter().all(|source| source == 0));
+    }
+}
@@ -24,10 +24,30 @@ pub struct GraphConfig {
     pub secondary_indices: Option<Vec<String>>,
 }
 
+#[derive(Serialize, Deserialize, Debug, Default)]
+pub struct ExecutionConfig {
+    // Threads queries asking for parallelism can use at most, defaults to the number of cores
+    pub threads: Option<usize>,
+
+    // Number of items a step must have before it is split across threads
+    pub parallel_threshold: Option<usize>,
+
+    // Milliseconds a query may run before it is aborted
+    pub timeout_ms: Option<u64>,
+
+    // Most items a single traversal step may produce
+    pub max_items: Option<usize>,
+
+    // Estimated bytes a single traversal step may hold
+    pub max_memory_bytes: Option<usize>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct Config {
     pub vector_config: VectorConfig,
     pub graph_config: GraphConfig,
+    #[serde(default)]
+    pub execution_config: ExecutionConfig,
     // // Path to the database
     // pub db_path: String,
 
@@ -47,6 +67,7 @@ impl Config {
             graph_config: GraphConfig {
                 secondary_indices: None,
             },
+            execution_config: ExecutionConfig::default(),
         }
     }
 
@@ -71,6 +92,10 @@ impl Config {
     },
     "graph_config": {
         "secondary_indices": []
+    },
+    "execution_config": {
+        "parallel_threshold": 1024,
+        "timeout_ms": 30000
     }
 }"#
         .to_string()
@@ -89,6 +114,7 @@ impl Default for Config {
             graph_config: GraphConfig {
                 secondary_indices: None,
             },
+            execution_config: ExecutionConfig::default(),
         }
     }
 }
@@ -0,0 +1,256 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::helix_engine::{graph_core::config::ExecutionConfig, types::GraphError};
+use crate::protocol::{
+    items::{Edge, Node},
+    traversal_value::TraversalValue,
+    value::Value,
+};
+
+/// Set by the gateway when the client that sent a request disconnects
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// How long a query may run and how much a single traversal step may produce.
+///
+/// The global limits come from `execution_config`, queries override them with
+/// `TIMEOUT(ms)`, `MAX_ITEMS(n)` and `MAX_MEMORY(bytes)`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryLimits {
+    pub timeout: Option<Duration>,
+    pub max_items: Option<usize>,
+    pub max_memory: Option<usize>,
+}
+
+impl QueryLimits {
+    pub fn from_config(config: &ExecutionConfig) -> Self {
+        Self {
+            timeout: config.timeout_ms.map(Duration::from_millis),
+            max_items: config.max_items,
+            max_memory: config.max_memory_bytes,
+        }
+    }
+
+    pub fn timeout_ms(mut self, timeout_ms: u64) -> Self {
+        self.timeout = Some(Duration::from_millis(timeout_ms));
+        self
+    }
+
+    pub fn max_items(mut self, max_items: usize) -> Self {
+        self.max_items = Some(max_items);
+        self
+    }
+
+    pub fn max_memory(mut self, max_memory: usize) -> Self {
+        self.max_memory = Some(max_memory);
+        self
+    }
+}
+
+/// The limits of one running query.
+///
+/// Checks return `GraphError::Timeout`, `GraphError::Cancelled` or
+/// `GraphError::LimitExceeded`. Handlers return the error with `?`, which drops the
+/// transaction so a write query that runs out of budget is aborted rather than committed.
+#[derive(Debug, Clone)]
+pub struct QueryBudget {
+    limits: QueryLimits,
+    deadline: Option<Instant>,
+    cancel: CancelToken,
+}
+
+impl QueryBudget {
+    pub fn new(limits: &QueryLimits, cancel: &CancelToken) -> Self {
+        Self {
+            limits: limits.clone(),
+            deadline: limits.timeout.map(|timeout| Instant::now() + timeout),
+            cancel: cancel.clone(),
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(&QueryLimits::default(), &CancelToken::default())
+    }
+
+    /// Fails once the deadline has passed or the client has gone away
+    #[inline]
+    pub fn check_time(&self) -> Result<(), GraphError> {
+        if self.cancel.is_cancelled() {
+            return Err(GraphError::Cancelled);
+        }
+        match (self.deadline, self.limits.timeout) {
+            (Some(deadline), Some(timeout)) if Instant::now() >= deadline => {
+                Err(GraphError::Timeout(timeout))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Fails if a step holding `items` items of roughly `bytes` bytes is over budget
+    #[inline]
+    pub fn check_size(&self, items: usize, bytes: usize) -> Result<(), GraphError> {
+        if let Some(max_items) = self.limits.max_items {
+            if items > max_items {
+                return Err(GraphError::LimitExceeded(format!(
+                    "traversal produced more than {} items",
+                    max_items
+                )));
+            }
+        }
+        if let Some(max_memory) = self.limits.max_memory {
+            if bytes > max_memory {
+                return Err(GraphError::LimitExceeded(format!(
+                    "traversal held more than {} bytes",
+                    max_memory
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks the time budget and the size of `value`
+    pub fn check(&self, value: &TraversalValue) -> Result<(), GraphError> {
+        self.check_time()?;
+        match (self.limits.max_items, self.limits.max_memory) {
+            (None, None) => Ok(()),
+            (_, None) => self.check_size(item_count(value), 0),
+            _ => self.check_size(item_count(value), value_size(value)),
+        }
+    }
+
+    pub(crate) fn tracks_memory(&self) -> bool {
+        self.limits.max_memory.is_some()
+    }
+}
+
+fn item_count(value: &TraversalValue) -> usize {
+    match value {
+        TraversalValue::Empty | TraversalValue::Count(_) => 0,
+        TraversalValue::NodeArray(nodes) => nodes.len(),
+        TraversalValue::EdgeArray(edges) => edges.len(),
+        TraversalValue::ValueArray(values) => values.len(),
+        TraversalValue::Paths(paths) => paths.len(),
+        TraversalValue::VectorArray(vectors) => vectors.len(),
+        TraversalValue::Bindings(rows) => rows.len(),
+        TraversalValue::Groups(groups) => groups.len(),
+    }
+}
+
+/// Rough heap size of a traversal value, enough to catch a step that is about to exhaust
+/// memory rather than an exact accounting
+pub fn value_size(value: &TraversalValue) -> usize {
+    match value {
+        TraversalValue::NodeArray(nodes) => nodes.iter().map(node_size).sum(),
+        TraversalValue::EdgeArray(edges) => edges.iter().map(edge_size).sum(),
+        TraversalValue::Paths(paths) => paths
+            .iter()
+            .map(|(nodes, edges)| {
+                nodes.iter().map(node_size).sum::<usize>()
+                    + edges.iter().map(edge_size).sum::<usize>()
+            })
+            .sum(),
+        TraversalValue::Groups(groups) => groups
+            .iter()
+            .flat_map(|group| group.iter())
+            .map(|(key, value)| key.len() + property_size(value))
+            .sum(),
+        value => item_count(value) * std::mem::size_of::<Node>(),
+    }
+}
+
+pub fn node_size(node: &Node) -> usize {
+    std::mem::size_of::<Node>()
+        + node.id.len()
+        + node.label.len()
+        + node
+            .properties
+            .iter()
+            .map(|(key, value)| key.len() + property_size(value))
+            .sum::<usize>()
+}
+
+pub fn edge_size(edge: &Edge) -> usize {
+    std::mem::size_of::<Edge>()
+        + edge.id.len()
+        + edge.label.len()
+        + edge.from_node.len()
+        + edge.to_node.len()
+        + edge
+            .properties
+            .iter()
+            .map(|(key, value)| key.len() + property_size(value))
+            .sum::<usize>()
+}
+
+fn property_size(value: &Value) -> usize {
+    std::mem::size_of::<Value>()
+        + match value {
+            Value::String(string) => string.len(),
+            Value::Array(values) => values.iter().map(property_size).sum(),
+            Value::Object(object) => object
+                .iter()
+                .map(|(key, value)| key.len() + property_size(value))
+                .sum(),
+            _ => 0,
+        }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn nodes(count: usize) -> TraversalValue {
+        TraversalValue::NodeArray(
+            (0..count)
+                .map(|i| Node {
+                    id: i.to_string(),
+                    label: "User".to_string(),
+                    properties: HashMap::from([("name".to_string(), Value::String("a".repeat(100)))]),
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_item_and_memory_limits() {
+        let cancel = CancelToken::default();
+        let budget = QueryBudget::new(&QueryLimits::default().max_items(10), &cancel);
+        assert!(budget.check(&nodes(10)).is_ok());
+        assert!(matches!(budget.check(&nodes(11)), Err(GraphError::LimitExceeded(_))));
+
+        let budget = QueryBudget::new(&QueryLimits::default().max_memory(1000), &cancel);
+        assert!(budget.check(&nodes(1)).is_ok());
+        assert!(matches!(budget.check(&nodes(10)), Err(GraphError::LimitExceeded(_))));
+    }
+
+    #[test]
+    fn test_timeout_and_cancel() {
+        let cancel = CancelToken::default();
+        let budget = QueryBudget::new(&QueryLimits::default().timeout_ms(0), &cancel);
+        assert!(matches!(budget.check_time(), Err(GraphError::Timeout(_))));
+
+        let budget = QueryBudget::new(&QueryLimits::default(), &cancel);
+        assert!(budget.check_time().is_ok());
+        cancel.cancel();
+        assert!(matches!(budget.check_time(), Err(GraphError::Cancelled)));
+        assert_eq!(GraphError::Cancelled.status_code(), 408);
+        assert_eq!(GraphError::LimitExceeded(String::new()).status_code(), 413);
+    }
+}
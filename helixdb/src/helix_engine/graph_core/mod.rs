@@ -1,7 +1,14 @@
 pub mod graph_core;
 pub mod traversal;
 pub mod traversal_steps;
+pub mod traversal_iter;
+pub mod aggregate;
+pub mod pattern;
 pub mod config;
+pub mod algorithms;
+pub mod parallel;
+pub mod limits;
+pub mod profile;
 
 #[cfg(test)]
 mod traversal_tests;
\ No newline at end of file
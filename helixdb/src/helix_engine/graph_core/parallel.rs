@@ -0,0 +1,283 @@
+use std::sync::OnceLock;
+
+use heed3::RoTxn;
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuilder};
+
+use crate::helix_engine::{
+    graph_core::{
+        config::ExecutionConfig,
+        profile::{self, ReadStats},
+    },
+    storage_core::storage_core::HelixGraphStorage,
+    types::GraphError,
+};
+
+pub const DEFAULT_PARALLEL_THRESHOLD: usize = 1024;
+
+/// Rayon pool that traversal steps fan out on once the frontier is large enough
+/// and the query asked for more than one thread.
+///
+/// The pool is built the first time a step needs it, so storages that never see a large
+/// parallel frontier (and every test) don't spawn any threads.
+pub struct TraversalPool {
+    threads: usize,
+    threshold: usize,
+    pool: OnceLock<Option<ThreadPool>>,
+}
+
+impl TraversalPool {
+    pub fn new(config: &ExecutionConfig) -> Self {
+        let threads = config.threads.unwrap_or_else(|| {
+            std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1)
+        });
+        Self {
+            threads: threads.max(1),
+            threshold: config
+                .parallel_threshold
+                .unwrap_or(DEFAULT_PARALLEL_THRESHOLD),
+            pool: OnceLock::new(),
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    /// `None` if the pool could not be built, in which case steps run sequentially
+    fn pool(&self) -> Option<&ThreadPool> {
+        self.pool
+            .get_or_init(|| {
+                ThreadPoolBuilder::new()
+                    .num_threads(self.threads)
+                    .thread_name(|i| format!("helix-traversal-{}", i))
+                    .build()
+                    .ok()
+            })
+            .as_ref()
+    }
+}
+
+pub trait ParallelMethods {
+    /// Applies `step` to every item and returns the results in the order of `items`.
+    ///
+    /// Below the pool's threshold, or unless `parallelism` asks for more than one thread,
+    /// this runs on the calling thread with `txn`, so it sees the caller's uncommitted writes.
+    /// Otherwise the items are split into one chunk per thread (capped by `parallelism` and
+    /// the pool size) and each chunk is read through its own read transaction, so parallel
+    /// steps only see committed data. Results are merged back in chunk order,
+    /// which keeps the output identical to a sequential run.
+    fn fan_out<T, R, F>(
+        &self,
+        txn: &RoTxn,
+        items: &[T],
+        parallelism: Option<usize>,
+        step: F,
+    ) -> Vec<Result<R, GraphError>>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&RoTxn, &T) -> Result<R, GraphError> + Sync;
+}
+
+impl ParallelMethods for HelixGraphStorage {
+    fn fan_out<T, R, F>(
+        &self,
+        txn: &RoTxn,
+        items: &[T],
+        parallelism: Option<usize>,
+        step: F,
+    ) -> Vec<Result<R, GraphError>>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&RoTxn, &T) -> Result<R, GraphError> + Sync,
+    {
+        let pool = &self.traversal_pool;
+        let threads = parallelism.map_or(1, |hint| hint.min(pool.threads()));
+        let pool = match pool.pool() {
+            Some(rayon_pool) if threads > 1 && items.len() >= pool.threshold() => rayon_pool,
+            _ => return items.iter().map(|item| step(txn, item)).collect(),
+        };
+
+        let chunk_size = items.len().div_ceil(threads);
+        let chunks: Vec<(Vec<Result<R, GraphError>>, ReadStats)> = pool.install(|| {
+            items
+                .par_chunks(chunk_size)
+                .map(|chunk| {
+                    profile::measure_reads(|| match self.graph_env.read_txn() {
+                        Ok(txn) => chunk.iter().map(|item| step(&txn, item)).collect(),
+                        Err(err) => vec![Err(GraphError::from(err))],
+                    })
+                })
+                .collect()
+        });
+        chunks
+            .into_iter()
+            .flat_map(|(results, reads)| {
+                // keeps the reads made on worker threads visible to the query's profile
+                profile::add_reads(reads);
+                results
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_engine::{
+        graph_core::{
+            config::Config,
+            traversal::TraversalBuilder,
+            traversal_steps::{SourceTraversalSteps, TraversalMethods, TraversalSteps},
+        },
+        storage_core::storage_methods::StorageMethods,
+    };
+    use crate::props;
+    use crate::protocol::{filterable::Filterable, traversal_value::TraversalValue, value::Value};
+    use std::sync::Arc;
+    use tempfile::TempDir;
+
+    fn setup_temp_db(threads: usize, threshold: usize) -> (Arc<HelixGraphStorage>, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+        let mut config = Config::default();
+        config.execution_config = ExecutionConfig {
+            threads: Some(threads),
+            parallel_threshold: Some(threshold),
+            ..Default::default()
+        };
+        let storage = HelixGraphStorage::new(db_path, config).unwrap();
+        (Arc::new(storage), temp_dir)
+    }
+
+    #[test]
+    fn test_fan_out_keeps_order() {
+        let (storage, _temp_dir) = setup_temp_db(4, 2);
+        let txn = storage.graph_env.read_txn().unwrap();
+        let items: Vec<usize> = (0..100).collect();
+
+        let results = storage.fan_out(&txn, &items, Some(4), |_, i| Ok(i * 2));
+        let results: Vec<usize> = results.into_iter().map(Result::unwrap).collect();
+        assert_eq!(results, (0..100).map(|i| i * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_parallel_out_matches_sequential() {
+        let (storage, _temp_dir) = setup_temp_db(4, 8);
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let users: Vec<String> = (0..50)
+            .map(|i| {
+                storage
+                    .create_node(&mut txn, "user", props! { "index" => i }, None)
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        for (i, id) in users.iter().enumerate() {
+            for j in 1..4 {
+                storage
+                    .create_edge(&mut txn, "follows", id, &users[(i * 7 + j) % 50], props!())
+                    .unwrap();
+            }
+        }
+        txn.commit().unwrap();
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let run = |parallelism: usize| {
+            let mut tr = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
+            tr.parallelism(parallelism)
+                .v_from_types(&txn, &["user"])
+                .out(&txn, "follows")
+                .in_(&txn, "follows");
+            match tr.current_step {
+                TraversalValue::NodeArray(nodes) => {
+                    nodes.into_iter().map(|node| node.id).collect::<Vec<_>>()
+                }
+                _ => panic!("Expected NodeArray value"),
+            }
+        };
+
+        let sequential = run(1);
+        assert_eq!(sequential.len(), 450);
+        assert_eq!(run(4), sequential);
+    }
+
+    #[test]
+    fn test_fan_out_is_sequential_by_default() {
+        let (storage, _temp_dir) = setup_temp_db(4, 2);
+        let txn = storage.graph_env.read_txn().unwrap();
+        let items: Vec<usize> = (0..100).collect();
+
+        let caller = std::thread::current().id();
+        let results = storage.fan_out(&txn, &items, None, |_, _| Ok(std::thread::current().id()));
+        assert!(results.into_iter().all(|id| id.unwrap() == caller));
+    }
+
+    #[test]
+    fn test_uncommitted_writes_are_visible_by_default() {
+        let (storage, _temp_dir) = setup_temp_db(4, 2);
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let users: Vec<String> = (0..20)
+            .map(|i| {
+                storage
+                    .create_node(&mut txn, "user", props! { "index" => i }, None)
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        for (i, id) in users.iter().enumerate() {
+            storage
+                .create_edge(&mut txn, "follows", id, &users[(i + 1) % 20], props!())
+                .unwrap();
+        }
+
+        // nothing is committed, so fanning out to fresh read transactions would find nothing
+        let mut tr = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
+        tr.v_from_types(&txn, &["user"])
+            .filter_nodes(&txn, |_, node| Ok(node.check_property("index").is_some()))
+            .out(&txn, "follows");
+        match &tr.current_step {
+            TraversalValue::NodeArray(nodes) => assert_eq!(nodes.len(), 20),
+            other => panic!("Expected NodeArray value, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parallel_filter_matches_sequential() {
+        let (storage, _temp_dir) = setup_temp_db(4, 8);
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        for i in 0..100 {
+            storage
+                .create_node(&mut txn, "user", props! { "index" => i }, None)
+                .unwrap();
+        }
+        txn.commit().unwrap();
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let run = |parallelism: usize| {
+            let mut tr = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
+            tr.parallelism(parallelism)
+                .v_from_types(&txn, &["user"])
+                .filter_nodes(&txn, |_, node| {
+                    Ok(matches!(node.check_property("index"), Some(Value::Integer(i)) if i % 3 == 0))
+                });
+            match tr.current_step {
+                TraversalValue::NodeArray(nodes) => {
+                    nodes.into_iter().map(|node| node.id).collect::<Vec<_>>()
+                }
+                _ => panic!("Expected NodeArray value"),
+            }
+        };
+
+        let sequential = run(1);
+        assert_eq!(sequential.len(), 34);
+        assert_eq!(run(4), sequential);
+    }
+}
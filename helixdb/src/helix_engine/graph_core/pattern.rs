@@ -0,0 +1,683 @@
+use std::collections::HashMap;
+
+use heed3::RoTxn;
+
+use crate::decode_str;
+use crate::helix_engine::{
+    storage_core::{
+        storage_core::{HelixGraphStorage, NODE_LABEL_PREFIX},
+        storage_methods::StorageMethods,
+    },
+    types::GraphError,
+};
+use crate::protocol::{
+    items::{Edge, Node},
+    traversal_value::{Binding, BindingRow},
+    value::Value,
+};
+
+/// A node variable and the constraints a node must meet to be bound to it
+#[derive(Debug, Clone)]
+pub struct NodePattern {
+    pub variable: String,
+    pub label: Option<String>,
+    pub properties: Vec<(String, Value)>,
+}
+
+/// An edge between two node variables. The edge itself is only returned
+/// in the binding rows if it has a variable.
+#[derive(Debug, Clone)]
+pub struct EdgePattern {
+    pub variable: Option<String>,
+    pub label: Option<String>,
+    pub from: String,
+    pub to: String,
+    pub properties: Vec<(String, Value)>,
+}
+
+/// A graph pattern such as `(a:User)-[:Follows]->(b:User)-[:Follows]->(a)`.
+///
+/// Different node variables always bind different nodes and every edge pattern
+/// binds a different edge, so the example above never matches a node with itself.
+#[derive(Debug, Clone, Default)]
+pub struct Pattern {
+    pub nodes: Vec<NodePattern>,
+    pub edges: Vec<EdgePattern>,
+    pub limit: Option<usize>,
+}
+
+impl Pattern {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declares a node variable. Declaring an existing variable again adds to its constraints.
+    pub fn node(
+        mut self,
+        variable: &str,
+        label: Option<&str>,
+        properties: Vec<(String, Value)>,
+    ) -> Self {
+        match self.nodes.iter_mut().find(|node| node.variable == variable) {
+            Some(node) => {
+                if label.is_some() {
+                    node.label = label.map(str::to_string);
+                }
+                node.properties.extend(properties);
+            }
+            None => self.nodes.push(NodePattern {
+                variable: variable.to_string(),
+                label: label.map(str::to_string),
+                properties,
+            }),
+        }
+        self
+    }
+
+    /// Adds an edge going from one node variable to another, declaring them if needed
+    pub fn edge(
+        mut self,
+        variable: Option<&str>,
+        label: Option<&str>,
+        from: &str,
+        to: &str,
+        properties: Vec<(String, Value)>,
+    ) -> Self {
+        for node in [from, to] {
+            if !self.nodes.iter().any(|n| n.variable == node) {
+                self = self.node(node, None, vec![]);
+            }
+        }
+        self.edges.push(EdgePattern {
+            variable: variable.map(str::to_string),
+            label: label.map(str::to_string),
+            from: from.to_string(),
+            to: to.to_string(),
+            properties,
+        });
+        self
+    }
+
+    /// Stops matching once this many rows have been found
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn node_index(&self, variable: &str) -> usize {
+        self.nodes
+            .iter()
+            .position(|node| node.variable == variable)
+            .unwrap()
+    }
+
+    fn validate(&self) -> Result<(), GraphError> {
+        if self.nodes.is_empty() {
+            return Err(GraphError::TraversalError("Pattern has no nodes".to_string()));
+        }
+        let mut variables: Vec<&str> = self.nodes.iter().map(|n| n.variable.as_str()).collect();
+        if let Some(edge) = self
+            .edges
+            .iter()
+            .find(|e| !variables.contains(&e.from.as_str()) || !variables.contains(&e.to.as_str()))
+        {
+            return Err(GraphError::TraversalError(format!(
+                "Pattern edge {} -> {} connects an unknown node",
+                edge.from, edge.to
+            )));
+        }
+        for variable in self.edges.iter().filter_map(|e| e.variable.as_deref()) {
+            if variables.contains(&variable) {
+                return Err(GraphError::TraversalError(format!(
+                    "Pattern variable {} is bound more than once",
+                    variable
+                )));
+            }
+            variables.push(variable);
+        }
+        Ok(())
+    }
+
+    /// Orders the pattern so that the rarest nodes are bound first and every other node
+    /// is reached by following an edge from an already bound node where possible.
+    /// Edges between two bound nodes are checked as soon as both ends are bound.
+    fn plan(&self, estimates: &[u64]) -> Vec<PlanStep> {
+        let edges: Vec<(usize, usize)> = self
+            .edges
+            .iter()
+            .map(|e| (self.node_index(&e.from), self.node_index(&e.to)))
+            .collect();
+        let mut bound = vec![false; self.nodes.len()];
+        let mut used = vec![false; self.edges.len()];
+        let mut plan = Vec::with_capacity(self.nodes.len() + self.edges.len());
+
+        while bound.iter().any(|b| !b) {
+            let mut best: Option<(u64, PlanStep)> = None;
+            for (i, &(from, to)) in edges.iter().enumerate() {
+                let step = match (used[i], bound[from], bound[to]) {
+                    (false, true, false) => PlanStep::Expand {
+                        edge: i,
+                        node: to,
+                        outgoing: true,
+                    },
+                    (false, false, true) => PlanStep::Expand {
+                        edge: i,
+                        node: from,
+                        outgoing: false,
+                    },
+                    _ => continue,
+                };
+                let node = match step {
+                    PlanStep::Expand { node, .. } => node,
+                    _ => unreachable!(),
+                };
+                if best.as_ref().is_none_or(|(cost, _)| estimates[node] < *cost) {
+                    best = Some((estimates[node], step));
+                }
+            }
+
+            let step = match best {
+                Some((_, step)) => step,
+                // nothing reachable from what is bound, start a new component
+                None => PlanStep::Scan(
+                    (0..self.nodes.len())
+                        .filter(|&n| !bound[n])
+                        .min_by_key(|&n| estimates[n])
+                        .unwrap(),
+                ),
+            };
+            match step {
+                PlanStep::Scan(node) => bound[node] = true,
+                PlanStep::Expand { edge, node, .. } => {
+                    bound[node] = true;
+                    used[edge] = true;
+                }
+                PlanStep::Check(_) => unreachable!(),
+            }
+            plan.push(step);
+
+            for (i, &(from, to)) in edges.iter().enumerate() {
+                if !used[i] && bound[from] && bound[to] {
+                    used[i] = true;
+                    plan.push(PlanStep::Check(i));
+                }
+            }
+        }
+
+        plan
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PlanStep {
+    /// Binds a node by scanning every candidate for it
+    Scan(usize),
+    /// Binds `node` by following `edge` from its other, already bound, end
+    Expand {
+        edge: usize,
+        node: usize,
+        outgoing: bool,
+    },
+    /// Checks that an edge exists between two bound nodes
+    Check(usize),
+}
+
+pub trait PatternMethods {
+    /// Finds every way the pattern can be bound in the graph, one row per match
+    fn match_pattern(&self, txn: &RoTxn, pattern: &Pattern) -> Result<Vec<BindingRow>, GraphError>;
+}
+
+impl PatternMethods for HelixGraphStorage {
+    fn match_pattern(&self, txn: &RoTxn, pattern: &Pattern) -> Result<Vec<BindingRow>, GraphError> {
+        pattern.validate()?;
+        let estimates = pattern
+            .nodes
+            .iter()
+            .map(|node| estimate(self, txn, node))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut matcher = Matcher {
+            storage: self,
+            txn,
+            pattern,
+            plan: pattern.plan(&estimates),
+            nodes: vec![None; pattern.nodes.len()],
+            edges: vec![None; pattern.edges.len()],
+            rows: Vec::new(),
+        };
+        matcher.step(0)?;
+        Ok(matcher.rows)
+    }
+}
+
+/// Rough number of nodes that can be bound to a node pattern
+fn estimate(
+    storage: &HelixGraphStorage,
+    txn: &RoTxn,
+    node: &NodePattern,
+) -> Result<u64, GraphError> {
+    if node
+        .properties
+        .iter()
+        .any(|(key, _)| storage.secondary_indices.contains_key(key))
+    {
+        return Ok(1);
+    }
+
+    let count = match &node.label {
+        Some(label) => {
+            let prefix = [NODE_LABEL_PREFIX, label.as_bytes(), b":"].concat();
+            let mut count = 0;
+            for result in storage
+                .node_labels_db
+                .lazily_decode_data()
+                .prefix_iter(txn, &prefix)?
+            {
+                result?;
+                count += 1;
+            }
+            count
+        }
+        None => storage.nodes_db.len(txn)?,
+    };
+    if count == 0 {
+        return Ok(0);
+    }
+    // assume every property constraint keeps about a tenth of the nodes
+    Ok((count / 10u64.pow(node.properties.len().min(6) as u32)).max(1))
+}
+
+struct Matcher<'a, 't> {
+    storage: &'a HelixGraphStorage,
+    txn: &'a RoTxn<'t>,
+    pattern: &'a Pattern,
+    plan: Vec<PlanStep>,
+    nodes: Vec<Option<Node>>,
+    /// Bound edge ids, with the edge itself once it has been read
+    edges: Vec<Option<(String, Option<Edge>)>>,
+    rows: Vec<BindingRow>,
+}
+
+impl Matcher<'_, '_> {
+    fn done(&self) -> bool {
+        self.pattern
+            .limit
+            .is_some_and(|limit| self.rows.len() >= limit)
+    }
+
+    fn step(&mut self, position: usize) -> Result<(), GraphError> {
+        if self.done() {
+            return Ok(());
+        }
+        let pattern = self.pattern;
+        let step = match self.plan.get(position) {
+            Some(step) => *step,
+            None => return self.emit(),
+        };
+
+        match step {
+            PlanStep::Scan(node) => {
+                for candidate in self.candidates(node)? {
+                    if self.done() {
+                        break;
+                    }
+                    if self.node_matches(node, &candidate) {
+                        self.nodes[node] = Some(candidate);
+                        self.step(position + 1)?;
+                        self.nodes[node] = None;
+                    }
+                }
+            }
+            PlanStep::Expand {
+                edge,
+                node,
+                outgoing,
+            } => {
+                let source = match outgoing {
+                    true => &pattern.edges[edge].from,
+                    false => &pattern.edges[edge].to,
+                };
+                let source_id = self.nodes[pattern.node_index(source)]
+                    .as_ref()
+                    .unwrap()
+                    .id
+                    .clone();
+                let (db, prefix) = match outgoing {
+                    true => (
+                        &self.storage.out_edges_db,
+                        HelixGraphStorage::out_edge_key(&source_id, ""),
+                    ),
+                    false => (
+                        &self.storage.in_edges_db,
+                        HelixGraphStorage::in_edge_key(&source_id, ""),
+                    ),
+                };
+
+                let mut neighbours = Vec::new();
+                for result in db.lazily_decode_data().prefix_iter(self.txn, &prefix)? {
+                    let (key, value) = result?;
+                    let other = std::str::from_utf8(&key[prefix.len()..])?;
+                    neighbours.push((other.to_string(), decode_str!(value).to_string()));
+                }
+
+                for (other, edge_id) in neighbours {
+                    if self.done() {
+                        break;
+                    }
+                    let Some(bound_edge) = self.edge_matches(edge, &edge_id)? else {
+                        continue;
+                    };
+                    let candidate = self.storage.get_node(self.txn, &other)?;
+                    if !self.node_matches(node, &candidate) {
+                        continue;
+                    }
+                    self.nodes[node] = Some(candidate);
+                    self.edges[edge] = Some(bound_edge);
+                    self.step(position + 1)?;
+                    self.nodes[node] = None;
+                    self.edges[edge] = None;
+                }
+            }
+            PlanStep::Check(edge) => {
+                let from = &self.nodes[pattern.node_index(&pattern.edges[edge].from)]
+                    .as_ref()
+                    .unwrap()
+                    .id;
+                let to = &self.nodes[pattern.node_index(&pattern.edges[edge].to)]
+                    .as_ref()
+                    .unwrap()
+                    .id;
+                let edge_id = match self
+                    .storage
+                    .out_edges_db
+                    .get(self.txn, &HelixGraphStorage::out_edge_key(from, to))?
+                {
+                    Some(value) => std::str::from_utf8(value)?.to_string(),
+                    None => return Ok(()),
+                };
+                if let Some(bound_edge) = self.edge_matches(edge, &edge_id)? {
+                    self.edges[edge] = Some(bound_edge);
+                    self.step(position + 1)?;
+                    self.edges[edge] = None;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn candidates(&self, node: usize) -> Result<Vec<Node>, GraphError> {
+        let pattern = &self.pattern.nodes[node];
+        if let Some((key, value)) = pattern
+            .properties
+            .iter()
+            .find(|(key, _)| self.storage.secondary_indices.contains_key(key))
+        {
+            return match self
+                .storage
+                .get_node_by_secondary_index(self.txn, key, value)
+            {
+                Ok(node) => Ok(vec![node]),
+                Err(GraphError::NodeNotFound) => Ok(vec![]),
+                Err(err) => Err(err),
+            };
+        }
+        match &pattern.label {
+            Some(label) => self.storage.get_nodes_by_types(self.txn, &[label.as_str()]),
+            None => self.storage.get_all_nodes(self.txn),
+        }
+    }
+
+    /// Checks the label, properties and that no other variable is bound to the node
+    fn node_matches(&self, node: usize, candidate: &Node) -> bool {
+        let pattern = &self.pattern.nodes[node];
+        pattern.label.as_ref().is_none_or(|l| *l == candidate.label)
+            && pattern
+                .properties
+                .iter()
+                .all(|(key, value)| candidate.properties.get(key) == Some(value))
+            && !self
+                .nodes
+                .iter()
+                .flatten()
+                .any(|bound| bound.id == candidate.id)
+    }
+
+    /// Returns what to bind for the edge pattern if the edge meets its constraints
+    fn edge_matches(
+        &self,
+        edge: usize,
+        edge_id: &str,
+    ) -> Result<Option<(String, Option<Edge>)>, GraphError> {
+        if self
+            .edges
+            .iter()
+            .flatten()
+            .any(|(bound, _)| bound == edge_id)
+        {
+            return Ok(None);
+        }
+
+        let pattern = &self.pattern.edges[edge];
+        if pattern.variable.is_none() && pattern.properties.is_empty() {
+            // the label index avoids deserializing edges that are never returned
+            if let Some(label) = &pattern.label {
+                if self
+                    .storage
+                    .edge_labels_db
+                    .get(self.txn, &HelixGraphStorage::edge_label_key(label, edge_id))?
+                    .is_none()
+                {
+                    return Ok(None);
+                }
+            }
+            return Ok(Some((edge_id.to_string(), None)));
+        }
+
+        let candidate = self.storage.get_edge(self.txn, edge_id)?;
+        let matches = pattern.label.as_ref().is_none_or(|l| *l == candidate.label)
+            && pattern
+                .properties
+                .iter()
+                .all(|(key, value)| candidate.properties.get(key) == Some(value));
+        Ok(matches.then(|| (edge_id.to_string(), Some(candidate))))
+    }
+
+    fn emit(&mut self) -> Result<(), GraphError> {
+        let mut row = HashMap::with_capacity(self.nodes.len());
+        for (pattern, node) in self.pattern.nodes.iter().zip(self.nodes.iter()) {
+            row.insert(
+                pattern.variable.clone(),
+                Binding::Node(node.clone().unwrap()),
+            );
+        }
+        for (pattern, edge) in self.pattern.edges.iter().zip(self.edges.iter()) {
+            if let (Some(variable), Some((_, edge))) = (&pattern.variable, edge) {
+                row.insert(variable.clone(), Binding::Edge(edge.clone().unwrap()));
+            }
+        }
+        self.rows.push(row);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_engine::graph_core::config::Config;
+    use crate::props;
+    use tempfile::TempDir;
+
+    fn setup_temp_db() -> (HelixGraphStorage, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+        let storage = HelixGraphStorage::new(db_path, Config::default()).unwrap();
+        (storage, temp_dir)
+    }
+
+    fn node_id(row: &BindingRow, variable: &str) -> String {
+        match &row[variable] {
+            Binding::Node(node) => node.id.clone(),
+            Binding::Edge(edge) => panic!("Expected node, got edge {:?}", edge),
+        }
+    }
+
+    #[test]
+    fn test_mutual_follows() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let users: Vec<Node> = (0..3)
+            .map(|_| storage.create_node(&mut txn, "User", props!(), None).unwrap())
+            .collect();
+        for (from, to) in [(0, 1), (1, 0), (1, 2)] {
+            storage
+                .create_edge(&mut txn, "Follows", &users[from].id, &users[to].id, props!())
+                .unwrap();
+        }
+        txn.commit().unwrap();
+
+        let pattern = Pattern::new()
+            .node("a", Some("User"), props!())
+            .node("b", Some("User"), props!())
+            .edge(None, Some("Follows"), "a", "b", props!())
+            .edge(None, Some("Follows"), "b", "a", props!());
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let rows = storage.match_pattern(&txn, &pattern).unwrap();
+        assert_eq!(rows.len(), 2);
+        for row in &rows {
+            let pair = [node_id(row, "a"), node_id(row, "b")];
+            assert!(pair.contains(&users[0].id) && pair.contains(&users[1].id));
+        }
+
+        let rows = storage.match_pattern(&txn, &pattern.limit(1)).unwrap();
+        assert_eq!(rows.len(), 1);
+    }
+
+    #[test]
+    fn test_two_products_in_same_category() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let alice = storage
+            .create_node(&mut txn, "User", props! { "name" => "alice" }, None)
+            .unwrap();
+        let bob = storage
+            .create_node(&mut txn, "User", props! { "name" => "bob" }, None)
+            .unwrap();
+        let books = storage.create_node(&mut txn, "Category", props!(), None).unwrap();
+        let games = storage.create_node(&mut txn, "Category", props!(), None).unwrap();
+        let products: Vec<Node> = [&books, &books, &games]
+            .iter()
+            .map(|category| {
+                let product = storage.create_node(&mut txn, "Product", props!(), None).unwrap();
+                storage
+                    .create_edge(&mut txn, "InCategory", &product.id, &category.id, props!())
+                    .unwrap();
+                product
+            })
+            .collect();
+        for (user, product) in [(&alice, 0), (&alice, 1), (&bob, 0), (&bob, 2)] {
+            storage
+                .create_edge(
+                    &mut txn,
+                    "Bought",
+                    &user.id,
+                    &products[product].id,
+                    props! { "quantity" => 1 },
+                )
+                .unwrap();
+        }
+        txn.commit().unwrap();
+
+        let pattern = Pattern::new()
+            .node("u", Some("User"), props!())
+            .edge(Some("first"), Some("Bought"), "u", "p1", props!())
+            .edge(None, Some("InCategory"), "p1", "c", props!())
+            .edge(None, Some("Bought"), "u", "p2", props! { "quantity" => 1 })
+            .edge(None, Some("InCategory"), "p2", "c", props!());
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let rows = storage.match_pattern(&txn, &pattern).unwrap();
+
+        // (p1, p2) and (p2, p1) for alice only
+        assert_eq!(rows.len(), 2);
+        for row in &rows {
+            assert_eq!(node_id(row, "u"), alice.id);
+            assert_eq!(node_id(row, "c"), books.id);
+            match &row["first"] {
+                Binding::Edge(edge) => assert_eq!(edge.label, "Bought"),
+                binding => panic!("Expected edge, got {:?}", binding),
+            }
+        }
+
+        let pattern = pattern.node("u", None, props! { "name" => "bob" });
+        assert!(storage.match_pattern(&txn, &pattern).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_plan_starts_from_rarest_label() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        for _ in 0..20 {
+            storage.create_node(&mut txn, "User", props!(), None).unwrap();
+        }
+        storage.create_node(&mut txn, "Admin", props!(), None).unwrap();
+        txn.commit().unwrap();
+
+        let pattern = Pattern::new()
+            .node("u", Some("User"), props!())
+            .node("a", Some("Admin"), props!())
+            .edge(None, Some("Manages"), "a", "u", props!())
+            .edge(None, Some("Follows"), "u", "a", props!());
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let estimates: Vec<u64> = pattern
+            .nodes
+            .iter()
+            .map(|node| estimate(&storage, &txn, node).unwrap())
+            .collect();
+        assert_eq!(estimates, vec![20, 1]);
+        assert_eq!(
+            pattern.plan(&estimates),
+            vec![
+                PlanStep::Scan(1),
+                PlanStep::Expand {
+                    edge: 0,
+                    node: 0,
+                    outgoing: true
+                },
+                PlanStep::Check(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_duplicate_variable_is_rejected() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let pattern = Pattern::new().edge(Some("a"), None, "a", "b", props!());
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        assert!(matches!(
+            storage.match_pattern(&txn, &pattern),
+            Err(GraphError::TraversalError(_))
+        ));
+    }
+
+    #[test]
+    fn test_edge_to_unknown_node_is_rejected() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let mut pattern = Pattern::new().node("a", None, props!());
+        pattern.edges.push(EdgePattern {
+            variable: None,
+            label: None,
+            from: "a".to_string(),
+            to: "b".to_string(),
+            properties: vec![],
+        });
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        assert!(matches!(
+            storage.match_pattern(&txn, &pattern),
+            Err(GraphError::TraversalError(_))
+        ));
+    }
+}
@@ -0,0 +1,217 @@
+use std::{cell::Cell, time::Instant};
+
+use serde::Serialize;
+
+use crate::protocol::{request::Request, traversal_value::TraversalValue};
+
+/// Request header that turns on profiling, any value other than `0` or `false` enables it
+pub const PROFILE_HEADER: &str = "x-helix-profile";
+/// Key the profile is returned under, next to the query's return values
+pub const PROFILE_KEY: &str = "__profile";
+
+/// LMDB's default page size, used to estimate the pages a read touched
+const PAGE_SIZE: usize = 4096;
+
+thread_local! {
+    static READS: Cell<ReadStats> = const { Cell::new(ReadStats { pages: 0, bytes: 0 }) };
+}
+
+/// Records and values read from LMDB and deserialized on the current thread.
+///
+/// `pages` is an estimate of the leaf and overflow pages holding the records, branch pages
+/// walked on the way down the B-tree are not counted.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ReadStats {
+    pub pages: usize,
+    pub bytes: usize,
+}
+
+impl ReadStats {
+    fn since(self, earlier: ReadStats) -> ReadStats {
+        ReadStats {
+            pages: self.pages.wrapping_sub(earlier.pages),
+            bytes: self.bytes.wrapping_sub(earlier.bytes),
+        }
+    }
+}
+
+/// Called by the storage layer for every record it deserializes
+#[inline(always)]
+pub fn record_read(bytes: usize) {
+    READS.with(|reads| {
+        let stats = reads.get();
+        reads.set(ReadStats {
+            pages: stats.pages.wrapping_add(bytes / PAGE_SIZE + 1),
+            bytes: stats.bytes.wrapping_add(bytes),
+        });
+    });
+}
+
+/// Running totals for the current thread, subtract two snapshots to get the reads in between
+pub fn read_stats() -> ReadStats {
+    READS.with(|reads| reads.get())
+}
+
+/// Runs `f` and returns the reads it made on this thread, used to carry the reads made on
+/// worker threads back to the thread running the query
+pub fn measure_reads<R>(f: impl FnOnce() -> R) -> (R, ReadStats) {
+    let before = read_stats();
+    let result = f();
+    (result, read_stats().since(before))
+}
+
+/// Adds reads made on another thread to the current thread's totals
+pub fn add_reads(stats: ReadStats) {
+    READS.with(|reads| {
+        let current = reads.get();
+        reads.set(ReadStats {
+            pages: current.pages.wrapping_add(stats.pages),
+            bytes: current.bytes.wrapping_add(stats.bytes),
+        });
+    });
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StepProfile {
+    pub step: String,
+    pub elapsed_us: u64,
+    pub input: usize,
+    pub output: usize,
+    pub pages: usize,
+    pub bytes: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryProfile {
+    pub elapsed_us: u64,
+    pub steps: Vec<StepProfile>,
+}
+
+/// Records each step of a generated handler when the request asked for a profile.
+///
+/// Generated code calls `begin` before and `end` after every top level step, both are a
+/// single branch when profiling is off.
+pub struct QueryProfiler {
+    enabled: bool,
+    started: Instant,
+    steps: Vec<StepProfile>,
+    current: Option<(String, usize, Instant, ReadStats)>,
+}
+
+impl QueryProfiler {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            started: Instant::now(),
+            steps: Vec::new(),
+            current: None,
+        }
+    }
+
+    pub fn from_request(request: &Request) -> Self {
+        let enabled = request
+            .headers
+            .get(PROFILE_HEADER)
+            .is_some_and(|value| !matches!(value.as_str(), "0" | "false"));
+        Self::new(enabled)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    #[inline(always)]
+    pub fn begin(&mut self, step: &str, input: &TraversalValue) {
+        if !self.enabled {
+            return;
+        }
+        self.current = Some((step.to_string(), cardinality(input), Instant::now(), read_stats()));
+    }
+
+    #[inline(always)]
+    pub fn end(&mut self, output: &TraversalValue) {
+        if let Some((step, input, started, reads)) = self.current.take() {
+            let reads = read_stats().since(reads);
+            self.steps.push(StepProfile {
+                step,
+                elapsed_us: started.elapsed().as_micros() as u64,
+                input,
+                output: cardinality(output),
+                pages: reads.pages,
+                bytes: reads.bytes,
+            });
+        }
+    }
+
+    /// The recorded profile, `None` if profiling was off
+    pub fn finish(self) -> Option<QueryProfile> {
+        match self.enabled {
+            true => Some(QueryProfile {
+                elapsed_us: self.started.elapsed().as_micros() as u64,
+                steps: self.steps,
+            }),
+            false => None,
+        }
+    }
+}
+
+fn cardinality(value: &TraversalValue) -> usize {
+    match value {
+        TraversalValue::Empty => 0,
+        TraversalValue::Count(_) => 1,
+        TraversalValue::NodeArray(nodes) => nodes.len(),
+        TraversalValue::EdgeArray(edges) => edges.len(),
+        TraversalValue::ValueArray(values) => values.len(),
+        TraversalValue::Paths(paths) => paths.len(),
+        TraversalValue::VectorArray(vectors) => vectors.len(),
+        TraversalValue::Bindings(rows) => rows.len(),
+        TraversalValue::Groups(groups) => groups.len(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::items::Node;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_profiler_records_steps() {
+        let mut profiler = QueryProfiler::new(true);
+        let nodes = TraversalValue::NodeArray(vec![
+            Node { id: "1".to_string(), label: "User".to_string(), properties: HashMap::new() },
+            Node { id: "2".to_string(), label: "User".to_string(), properties: HashMap::new() },
+        ]);
+
+        profiler.begin("N<User>", &TraversalValue::Empty);
+        record_read(100);
+        record_read(5000);
+        profiler.end(&nodes);
+
+        let profile = profiler.finish().unwrap();
+        assert_eq!(profile.steps.len(), 1);
+        assert_eq!(profile.steps[0].step, "N<User>");
+        assert_eq!(profile.steps[0].input, 0);
+        assert_eq!(profile.steps[0].output, 2);
+        assert_eq!(profile.steps[0].bytes, 5100);
+        assert_eq!(profile.steps[0].pages, 3);
+    }
+
+    #[test]
+    fn test_disabled_profiler() {
+        let mut profiler = QueryProfiler::new(false);
+        profiler.begin("N<User>", &TraversalValue::Empty);
+        profiler.end(&TraversalValue::Empty);
+        assert!(profiler.finish().is_none());
+    }
+
+    #[test]
+    fn test_measure_reads_on_other_thread() {
+        let (_, stats) = std::thread::spawn(|| measure_reads(|| record_read(10)))
+            .join()
+            .unwrap();
+        let before = read_stats();
+        add_reads(stats);
+        assert_eq!(read_stats().since(before), ReadStats { pages: 1, bytes: 10 });
+    }
+}
@@ -1,4 +1,15 @@
 use crate::helix_engine::{
+    graph_core::aggregate::{self, Aggregation, Order},
+    graph_core::algorithms::dag::DagMethods,
+    graph_core::algorithms::embeddings::{EmbeddingMethods, SIMILARITY_PROPERTY},
+    graph_core::algorithms::projection::Direction,
+    graph_core::limits::QueryBudget,
+    graph_core::parallel::ParallelMethods,
+    graph_core::pattern::{Pattern, PatternMethods},
+    graph_core::algorithms::link_prediction::{
+        LinkPredictionMethods, LinkPredictionMetric, LINK_SCORE_PROPERTY,
+    },
+    graph_core::traversal_iter::LazyTraversal,
     graph_core::traversal_steps::{
         TraversalBuilderMethods, TraversalMethods, TraversalSearchMethods,
     },
@@ -13,14 +24,17 @@ use crate::protocol::{
     count::Count,
     filterable::Filterable,
     items::{Edge, Node},
-    traversal_value::TraversalValue,
+    traversal_value::{Binding, BindingRow, TraversalValue},
     value::Value,
 };
-use core::panic;
 use heed3::{Error, RoTxn, RwTxn, WithTls};
 use std::{
     collections::{HashMap, HashSet},
-    sync::Arc,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
 };
 
 use super::traversal_steps::{SourceTraversalSteps, TraversalSteps, VectorTraversalSteps};
@@ -30,6 +44,15 @@ pub struct TraversalBuilder {
     pub current_step: TraversalValue,
     pub storage: Arc<HelixGraphStorage>,
     pub error: Option<GraphError>,
+    /// Items labelled with `as_`, one row per item in `current_step`
+    pub labels: Option<Vec<BindingRow>>,
+    /// The route each item in `current_step` took, recorded after `track_paths`
+    pub paths: Option<Vec<(Vec<Node>, Vec<Edge>)>>,
+    /// Caps how many threads `out`, `in_`, `out_e`, `in_e`, `filter_nodes` and `filter_edges`
+    /// fan out on, `None` keeps every step on the calling thread
+    pub parallelism: Option<usize>,
+    /// Checked while steps read from storage, see `within`
+    pub budget: Option<QueryBudget>,
 }
 
 impl TraversalBuilder {
@@ -39,6 +62,46 @@ impl TraversalBuilder {
             current_step: start_nodes,
             storage,
             error: None,
+            labels: None,
+            paths: None,
+            parallelism: None,
+            budget: None,
+        }
+    }
+
+    /// Lets steps fan out on up to `threads` threads of the traversal pool,
+    /// `1` keeps every step on the calling thread (the default).
+    ///
+    /// Parallel steps read through their own read transactions and so only see committed
+    /// data, queries that write should not set this.
+    pub fn parallelism(&mut self, threads: usize) -> &mut Self {
+        self.parallelism = Some(threads.max(1));
+        self
+    }
+
+    /// Called by generated handlers between steps, fails with `Timeout`, `Cancelled` or
+    /// `LimitExceeded` once the query is over its budget
+    pub fn check_budget(&self, budget: &QueryBudget) -> Result<(), GraphError> {
+        budget.check(&self.current_step)
+    }
+
+    /// Also checks the time and item limits of `budget` while steps read from storage, so
+    /// scanning every node or fanning out from a large frontier stops once the query is over
+    /// budget instead of only failing `check_budget` after the step.
+    pub fn within(&mut self, budget: &QueryBudget) -> &mut Self {
+        self.budget = Some(budget.clone());
+        self
+    }
+
+    /// Checks the budget set with `within` from inside a step, `produced` counting the items
+    /// the step has read so far on all of its threads
+    fn check_within(&self, produced: &AtomicUsize, items: usize) -> Result<(), GraphError> {
+        match &self.budget {
+            Some(budget) => {
+                budget.check_time()?;
+                budget.check_size(produced.fetch_add(items, Ordering::Relaxed) + items, 0)
+            }
+            None => Ok(()),
         }
     }
 
@@ -64,16 +127,186 @@ impl TraversalBuilder {
         }
     }
 
+    /// Whether a step has failed. Every step returns straight away once one has, so the
+    /// first error is the one `result`, `finish` and `execute` return.
     #[inline(always)]
-    fn store_error(&mut self, err: GraphError) {
+    pub fn has_failed(&self) -> bool {
+        self.error.is_some()
+    }
+
+    /// Keeps the first error, wrapped in `GraphError::StepFailed` with the step that raised
+    /// it and the arguments it was called with
+    #[inline(always)]
+    fn store_error(&mut self, step: &'static str, args: &[&dyn Debug], err: GraphError) {
         if let GraphError::Empty = err {
             return;
         }
         if self.error.is_none() {
-            self.error = Some(err);
+            self.error = Some(match err {
+                GraphError::StepFailed { .. } => err,
+                err => GraphError::StepFailed {
+                    step,
+                    args: describe_args(args),
+                    source: Box::new(err),
+                },
+            });
         }
     }
 
+    /// Shared implementation of `group_by` and `aggregate`
+    fn group_step(&mut self, property: Option<&str>, aggregations: &[Aggregation]) -> &mut Self {
+        self.clear_tracking();
+        let groups = match &self.current_step {
+            TraversalValue::NodeArray(nodes) => aggregate::group_items(nodes, property, aggregations),
+            TraversalValue::EdgeArray(edges) => aggregate::group_items(edges, property, aggregations),
+            TraversalValue::Empty => aggregate::group_items::<Node>(&[], property, aggregations),
+            _ => {
+                self.current_step = TraversalValue::Empty;
+                let step = match property {
+                    Some(_) => "group_by",
+                    None => "aggregate",
+                };
+                self.store_error(
+                    step,
+                    &[&property, &aggregations],
+                    GraphError::TraversalError("group_by expects nodes or edges".to_string()),
+                );
+                return self;
+            }
+        };
+        self.current_step = match groups.is_empty() {
+            true => TraversalValue::Empty,
+            false => TraversalValue::Groups(groups),
+        };
+        self
+    }
+
+    fn is_tracking(&self) -> bool {
+        self.labels.is_some() || self.paths.is_some()
+    }
+
+    /// Drops labels and paths, for steps that reorder or aggregate the current items
+    fn clear_tracking(&mut self) {
+        self.labels = None;
+        self.paths = None;
+    }
+
+    /// Runs `step` on each item separately, so labels, paths and branch results stay
+    /// attached to the item they came from
+    fn per_item<F>(&mut self, mut step: F) -> &mut Self
+    where
+        F: FnMut(&mut TraversalBuilder),
+    {
+        let items = split_items(std::mem::replace(&mut self.current_step, TraversalValue::Empty));
+        let rows = self.labels.take();
+        let paths = self.paths.take();
+        let mut results = Vec::with_capacity(items.len());
+        let mut new_rows = Vec::new();
+        let mut new_paths = Vec::new();
+        for (i, item) in items.into_iter().enumerate() {
+            if self.has_failed() {
+                break;
+            }
+            let item_id = match &item {
+                TraversalValue::NodeArray(nodes) => nodes.first().map(|node| node.id.clone()),
+                TraversalValue::EdgeArray(edges) => edges.first().map(|edge| edge.id.clone()),
+                _ => None,
+            };
+            let mut tr = TraversalBuilder::new(Arc::clone(&self.storage), item);
+            tr.parallelism = self.parallelism;
+            tr.budget = self.budget.clone();
+            step(&mut tr);
+            if let Some(err) = tr.error.take() {
+                // already carries the step that failed inside the sub-traversal
+                self.error = Some(err);
+            }
+            if let Some(rows) = &rows {
+                let row = rows.get(i).cloned().unwrap_or_default();
+                new_rows.extend(std::iter::repeat(row).take(item_count(&tr.current_step)));
+            }
+            if let Some(paths) = &paths {
+                let path = paths.get(i).cloned().unwrap_or_default();
+                extend_paths(&mut new_paths, &path, &tr.current_step, item_id.as_deref());
+            }
+            results.push(tr.current_step);
+        }
+        match merge_items(results) {
+            Ok(merged) => {
+                self.current_step = merged;
+                self.labels = rows.map(|_| new_rows);
+                self.paths = paths.map(|_| new_paths);
+            }
+            Err(err) => self.store_error("per_item", &[], err),
+        }
+        self
+    }
+
+    /// `out`, `in_` and `both` while paths are tracked. These go through the edges so the
+    /// edge that was crossed ends up in the path as well.
+    fn tracked_adjacent(
+        &mut self,
+        txn: &RoTxn,
+        edge_label: &str,
+        direction: Direction,
+    ) -> &mut Self {
+        match direction {
+            Direction::Out => self.out_e(txn, edge_label).in_v(txn),
+            Direction::In => self.in_e(txn, edge_label).out_v(txn),
+            Direction::Both => {
+                let current = self.current_step.clone();
+                let (labels, paths) = (self.labels.clone(), self.paths.clone());
+                self.tracked_adjacent(txn, edge_label, Direction::In);
+
+                let incoming = std::mem::replace(&mut self.current_step, current);
+                let incoming_labels = std::mem::replace(&mut self.labels, labels);
+                let incoming_paths = std::mem::replace(&mut self.paths, paths);
+                self.tracked_adjacent(txn, edge_label, Direction::Out);
+
+                let outgoing = std::mem::replace(&mut self.current_step, TraversalValue::Empty);
+                self.current_step = [incoming, outgoing].into_iter().collect();
+                self.labels = concat_tracked(incoming_labels, self.labels.take());
+                self.paths = concat_tracked(incoming_paths, self.paths.take());
+                self
+            }
+        }
+    }
+
+    /// Shared implementation of `ancestors` and `descendants`
+    fn closure_step(&mut self, txn: &RoTxn, edge_label: &str, outgoing: bool) -> &mut Self {
+        let mut e = GraphError::Empty;
+        if let TraversalValue::NodeArray(nodes) = &self.current_step {
+            let mut seen = HashSet::new();
+            let mut new_current = Vec::new();
+            for node in nodes {
+                let reached = match outgoing {
+                    true => self.storage.descendants(txn, &node.id, edge_label),
+                    false => self.storage.ancestors(txn, &node.id, edge_label),
+                };
+                match reached {
+                    Ok(reached) => new_current.extend(
+                        reached
+                            .into_iter()
+                            .filter(|node| seen.insert(node.id.clone())),
+                    ),
+                    Err(err) => e = err,
+                }
+            }
+            if new_current.is_empty() {
+                self.current_step = TraversalValue::Empty;
+            } else {
+                self.current_step = TraversalValue::NodeArray(new_current);
+            }
+        } else {
+            self.current_step = TraversalValue::Empty;
+        }
+        let step = match outgoing {
+            true => "descendants",
+            false => "ancestors",
+        };
+        self.store_error(step, &[&edge_label], e);
+        self
+    }
+
     pub fn add_v_temp(
         &mut self,
         txn: &mut RwTxn,
@@ -91,6 +324,9 @@ impl TraversalBuilder {
     }
 
     pub fn drop(&mut self, txn: &mut RwTxn) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         let mut e = GraphError::Empty;
         match &self.current_step {
             TraversalValue::NodeArray(nodes) => {
@@ -111,11 +347,14 @@ impl TraversalBuilder {
             }
             _ => {}
         }
-        self.store_error(e);
+        self.store_error("drop", &[], e);
         self
     }
 
     pub fn id(&mut self) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         match &self.current_step {
             TraversalValue::NodeArray(nodes) => match nodes.first() {
                 Some(node) => {
@@ -149,6 +388,17 @@ impl TraversalBuilder {
 
 impl SourceTraversalSteps for TraversalBuilder {
     fn v(&mut self, txn: &RoTxn) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        if let Some(budget) = self.budget.clone() {
+            let storage = Arc::clone(&self.storage);
+            match LazyTraversal::v(&storage, txn).within(&budget).collect() {
+                Ok(value) => self.current_step = value,
+                Err(err) => self.store_error("v", &[], err),
+            }
+            return self;
+        }
         match self.storage.get_all_nodes(txn) {
             Ok(nodes) => {
                 self.current_step = TraversalValue::NodeArray(nodes);
@@ -158,7 +408,7 @@ impl SourceTraversalSteps for TraversalBuilder {
                     self.current_step = TraversalValue::Empty;
                 }
                 _ => {
-                    self.store_error(err);
+                    self.store_error("v", &[], err);
                 }
             },
         }
@@ -166,6 +416,17 @@ impl SourceTraversalSteps for TraversalBuilder {
     }
 
     fn e(&mut self, txn: &RoTxn) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        if let Some(budget) = self.budget.clone() {
+            let storage = Arc::clone(&self.storage);
+            match LazyTraversal::e(&storage, txn).within(&budget).collect() {
+                Ok(value) => self.current_step = value,
+                Err(err) => self.store_error("e", &[], err),
+            }
+            return self;
+        }
         match self.storage.get_all_edges(txn) {
             Ok(edges) => {
                 self.current_step = TraversalValue::EdgeArray(edges);
@@ -175,7 +436,7 @@ impl SourceTraversalSteps for TraversalBuilder {
                     self.current_step = TraversalValue::Empty;
                 }
                 _ => {
-                    self.store_error(err);
+                    self.store_error("e", &[], err);
                 }
             },
         }
@@ -183,6 +444,9 @@ impl SourceTraversalSteps for TraversalBuilder {
     }
 
     fn v_from_id(&mut self, txn: &RoTxn, node_id: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         match self.storage.get_node(txn, node_id) {
             Ok(node) => {
                 self.current_step = TraversalValue::from(node);
@@ -192,7 +456,7 @@ impl SourceTraversalSteps for TraversalBuilder {
                     self.current_step = TraversalValue::Empty;
                 }
                 _ => {
-                    self.store_error(err);
+                    self.store_error("v_from_id", &[&node_id], err);
                 }
             },
         }
@@ -200,12 +464,15 @@ impl SourceTraversalSteps for TraversalBuilder {
     }
 
     fn v_from_ids(&mut self, txn: &RoTxn, node_ids: &[&str]) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         let mut new_current = Vec::with_capacity(node_ids.len());
         for node_id in node_ids {
             match self.storage.get_node(txn, node_id) {
                 Ok(node) => new_current.push(node),
                 Err(err) => {
-                    self.store_error(err);
+                    self.store_error("v_from_ids", &[&node_ids], err);
                 }
             }
         }
@@ -218,6 +485,9 @@ impl SourceTraversalSteps for TraversalBuilder {
     }
 
     fn e_from_id(&mut self, txn: &RoTxn, edge_id: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         match self.storage.get_edge(txn, edge_id) {
             Ok(edge) => {
                 self.current_step = TraversalValue::from(edge);
@@ -227,7 +497,7 @@ impl SourceTraversalSteps for TraversalBuilder {
                     self.current_step = TraversalValue::Empty;
                 }
                 _ => {
-                    self.store_error(err);
+                    self.store_error("e_from_id", &[&edge_id], err);
                 }
             },
         }
@@ -235,6 +505,20 @@ impl SourceTraversalSteps for TraversalBuilder {
     }
 
     fn v_from_types(&mut self, txn: &RoTxn, node_labels: &[&str]) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        if let Some(budget) = self.budget.clone() {
+            let storage = Arc::clone(&self.storage);
+            match LazyTraversal::v_from_types(&storage, txn, node_labels)
+                .within(&budget)
+                .collect()
+            {
+                Ok(value) => self.current_step = value,
+                Err(err) => self.store_error("v_from_types", &[&node_labels], err),
+            }
+            return self;
+        }
         match self.storage.get_nodes_by_types(txn, node_labels) {
             Ok(nodes) => {
                 self.current_step = TraversalValue::NodeArray(nodes);
@@ -244,7 +528,7 @@ impl SourceTraversalSteps for TraversalBuilder {
                     self.current_step = TraversalValue::Empty;
                 }
                 _ => {
-                    self.store_error(err);
+                    self.store_error("v_from_types", &[&node_labels], err);
                 }
             },
         }
@@ -252,6 +536,9 @@ impl SourceTraversalSteps for TraversalBuilder {
     }
 
     fn v_from_secondary_index(&mut self, txn: &RoTxn, index: &str, value: &Value) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         match self.storage.get_node_by_secondary_index(txn, index, value) {
             Ok(node) => {
                 self.current_step = TraversalValue::from(node);
@@ -261,13 +548,28 @@ impl SourceTraversalSteps for TraversalBuilder {
                     self.current_step = TraversalValue::Empty;
                 }
                 _ => {
-                    self.store_error(err);
+                    self.store_error("v_from_secondary_index", &[&index, value], err);
                 }
             },
         }
         self
     }
 
+    fn match_pattern(&mut self, txn: &RoTxn, pattern: &Pattern) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        match self.storage.match_pattern(txn, pattern) {
+            Ok(rows) if !rows.is_empty() => self.current_step = TraversalValue::Bindings(rows),
+            Ok(_) => self.current_step = TraversalValue::Empty,
+            Err(err) => {
+                self.current_step = TraversalValue::Empty;
+                self.store_error("match_pattern", &[pattern], err);
+            }
+        }
+        self
+    }
+
     fn add_v(
         &mut self,
         txn: &mut RwTxn,
@@ -275,6 +577,9 @@ impl SourceTraversalSteps for TraversalBuilder {
         props: Vec<(String, Value)>,
         secondary_indices: Option<&[String]>,
     ) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         match self
             .storage
             .create_node(txn, node_label, props, secondary_indices)
@@ -283,7 +588,7 @@ impl SourceTraversalSteps for TraversalBuilder {
                 self.current_step = TraversalValue::from(node);
             }
             Err(err) => {
-                self.store_error(err);
+                self.store_error("add_v", &[&node_label, &secondary_indices], err);
             }
         }
         self
@@ -297,6 +602,9 @@ impl SourceTraversalSteps for TraversalBuilder {
         to_id: &str,
         props: Vec<(String, Value)>,
     ) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         match self
             .storage
             .create_edge(txn, edge_label, from_id, to_id, props)
@@ -305,20 +613,185 @@ impl SourceTraversalSteps for TraversalBuilder {
                 self.current_step = TraversalValue::from(edge);
             }
             Err(err) => {
-                self.store_error(err);
+                self.store_error("add_e", &[&edge_label, &from_id, &to_id], err);
             }
         }
         self
     }
+
+    fn upsert_v(
+        &mut self,
+        txn: &mut RwTxn,
+        node_label: &str,
+        match_props: Vec<(String, Value)>,
+        on_create: Vec<(String, Value)>,
+        on_match: Vec<(String, Value)>,
+    ) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        match self.storage.upsert_node(
+            txn,
+            node_label,
+            match_props.iter().cloned(),
+            on_create,
+            on_match,
+        ) {
+            Ok(node) => {
+                self.current_step = TraversalValue::from(node);
+            }
+            Err(err) => {
+                self.store_error("upsert_v", &[&node_label, &match_props], err);
+            }
+        }
+        self
+    }
+
+    fn upsert_e(
+        &mut self,
+        txn: &mut RwTxn,
+        edge_label: &str,
+        from_id: &str,
+        to_id: &str,
+        on_create: Vec<(String, Value)>,
+        on_match: Vec<(String, Value)>,
+    ) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        match self
+            .storage
+            .upsert_edge(txn, edge_label, from_id, to_id, on_create, on_match)
+        {
+            Ok(edge) => {
+                self.current_step = TraversalValue::from(edge);
+            }
+            Err(err) => {
+                self.store_error("upsert_e", &[&edge_label, &from_id, &to_id], err);
+            }
+        }
+        self
+    }
+}
+
+/// Formats step arguments for `GraphError::StepFailed`, cutting long ones such as vectors
+/// or property lists short
+fn describe_args(args: &[&dyn Debug]) -> String {
+    const MAX_ARG_LEN: usize = 64;
+    args.iter()
+        .map(|arg| {
+            let arg = format!("{:?}", arg);
+            match arg.char_indices().nth(MAX_ARG_LEN) {
+                Some((end, _)) => format!("{}..", &arg[..end]),
+                None => arg,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Splits nodes and edges into one traversal value per item
+fn split_items(value: TraversalValue) -> Vec<TraversalValue> {
+    match value {
+        TraversalValue::NodeArray(nodes) => nodes.into_iter().map(TraversalValue::from).collect(),
+        TraversalValue::EdgeArray(edges) => edges.into_iter().map(TraversalValue::from).collect(),
+        TraversalValue::Empty => Vec::new(),
+        value => vec![value],
+    }
+}
+
+/// Merges the results of several branches or items. Nodes and edges can't share a step,
+/// so a mix of both is an error rather than collecting into just one of them.
+fn merge_items(results: Vec<TraversalValue>) -> Result<TraversalValue, GraphError> {
+    let has_nodes = results
+        .iter()
+        .any(|value| matches!(value, TraversalValue::NodeArray(nodes) if !nodes.is_empty()));
+    let has_edges = results
+        .iter()
+        .any(|value| matches!(value, TraversalValue::EdgeArray(edges) if !edges.is_empty()));
+    if has_nodes && has_edges {
+        return Err(GraphError::TraversalError(
+            "cannot merge nodes and edges into one step".to_string(),
+        ));
+    }
+    Ok(results.into_iter().collect())
+}
+
+/// Adds each item produced from one traverser to a copy of its path. Items that are the
+/// traverser itself (e.g. after a filter) are not added again.
+fn extend_paths(
+    paths: &mut Vec<(Vec<Node>, Vec<Edge>)>,
+    path: &(Vec<Node>, Vec<Edge>),
+    step: &TraversalValue,
+    from_id: Option<&str>,
+) {
+    match step {
+        TraversalValue::NodeArray(nodes) => {
+            for node in nodes {
+                let mut path = path.clone();
+                if from_id != Some(node.id.as_str()) {
+                    path.0.push(node.clone());
+                }
+                paths.push(path);
+            }
+        }
+        TraversalValue::EdgeArray(edges) => {
+            for edge in edges {
+                let mut path = path.clone();
+                if from_id != Some(edge.id.as_str()) {
+                    path.1.push(edge.clone());
+                }
+                paths.push(path);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn concat_tracked<T>(first: Option<Vec<T>>, second: Option<Vec<T>>) -> Option<Vec<T>> {
+    match (first, second) {
+        (Some(mut first), Some(second)) => {
+            first.extend(second);
+            Some(first)
+        }
+        (first, None) => first,
+        (None, second) => second,
+    }
+}
+
+fn item_count(value: &TraversalValue) -> usize {
+    match value {
+        TraversalValue::NodeArray(nodes) => nodes.len(),
+        TraversalValue::EdgeArray(edges) => edges.len(),
+        _ => 0,
+    }
 }
 
 impl TraversalSteps for TraversalBuilder {
     fn out(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        if self.paths.is_some() {
+            return self.tracked_adjacent(txn, edge_label, Direction::Out);
+        }
+        if self.is_tracking() {
+            return self.per_item(|tr| {
+                tr.out(txn, edge_label);
+            });
+        }
         let mut e = GraphError::Empty;
         if let TraversalValue::NodeArray(nodes) = &self.current_step {
             let mut new_current = Vec::with_capacity(nodes.len());
-            for node in nodes {
-                match self.storage.get_out_nodes(txn, &node.id, edge_label) {
+            let produced = AtomicUsize::new(0);
+            let results = self.storage.fan_out(txn, nodes, self.parallelism, |txn, node| {
+                self.check_within(&produced, 0)?;
+                let items = self.storage.get_out_nodes(txn, &node.id, edge_label)?;
+                self.check_within(&produced, items.len())?;
+                Ok(items)
+            });
+            for result in results {
+                match result {
                     Ok(nodes) => match nodes.is_empty() {
                         false => new_current.extend(nodes),
                         true => continue,
@@ -334,16 +807,31 @@ impl TraversalSteps for TraversalBuilder {
         } else {
             self.current_step = TraversalValue::Empty;
         }
-        self.store_error(e);
+        self.store_error("out", &[&edge_label], e);
         self
     }
 
     fn out_e(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        if self.is_tracking() {
+            return self.per_item(|tr| {
+                tr.out_e(txn, edge_label);
+            });
+        }
         let mut e = GraphError::Empty;
         if let TraversalValue::NodeArray(nodes) = &self.current_step {
             let mut new_current = Vec::with_capacity(nodes.len());
-            for node in nodes {
-                match self.storage.get_out_edges(txn, &node.id, edge_label) {
+            let produced = AtomicUsize::new(0);
+            let results = self.storage.fan_out(txn, nodes, self.parallelism, |txn, node| {
+                self.check_within(&produced, 0)?;
+                let items = self.storage.get_out_edges(txn, &node.id, edge_label)?;
+                self.check_within(&produced, items.len())?;
+                Ok(items)
+            });
+            for result in results {
+                match result {
                     Ok(edges) => match edges.is_empty() {
                         false => new_current.extend(edges),
                         true => continue,
@@ -359,16 +847,34 @@ impl TraversalSteps for TraversalBuilder {
         } else {
             self.current_step = TraversalValue::Empty;
         }
-        self.store_error(e);
+        self.store_error("out_e", &[&edge_label], e);
         self
     }
 
     fn in_(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        if self.paths.is_some() {
+            return self.tracked_adjacent(txn, edge_label, Direction::In);
+        }
+        if self.is_tracking() {
+            return self.per_item(|tr| {
+                tr.in_(txn, edge_label);
+            });
+        }
         let mut e = GraphError::Empty;
         if let TraversalValue::NodeArray(nodes) = &self.current_step {
             let mut new_current = Vec::with_capacity(nodes.len());
-            for node in nodes {
-                match self.storage.get_in_nodes(txn, &node.id, edge_label) {
+            let produced = AtomicUsize::new(0);
+            let results = self.storage.fan_out(txn, nodes, self.parallelism, |txn, node| {
+                self.check_within(&produced, 0)?;
+                let items = self.storage.get_in_nodes(txn, &node.id, edge_label)?;
+                self.check_within(&produced, items.len())?;
+                Ok(items)
+            });
+            for result in results {
+                match result {
                     Ok(nodes) => match nodes.is_empty() {
                         false => new_current.extend(nodes),
                         true => continue,
@@ -384,16 +890,31 @@ impl TraversalSteps for TraversalBuilder {
         } else {
             self.current_step = TraversalValue::Empty;
         }
-        self.store_error(e);
+        self.store_error("in_", &[&edge_label], e);
         self
     }
 
     fn in_e(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        if self.is_tracking() {
+            return self.per_item(|tr| {
+                tr.in_e(txn, edge_label);
+            });
+        }
         let mut e = GraphError::Empty;
         if let TraversalValue::NodeArray(nodes) = &self.current_step {
             let mut new_current = Vec::with_capacity(nodes.len());
-            for node in nodes {
-                match self.storage.get_in_edges(txn, &node.id, edge_label) {
+            let produced = AtomicUsize::new(0);
+            let results = self.storage.fan_out(txn, nodes, self.parallelism, |txn, node| {
+                self.check_within(&produced, 0)?;
+                let items = self.storage.get_in_edges(txn, &node.id, edge_label)?;
+                self.check_within(&produced, items.len())?;
+                Ok(items)
+            });
+            for result in results {
+                match result {
                     Ok(edges) => match edges.is_empty() {
                         false => new_current.extend(edges),
                         true => continue,
@@ -409,11 +930,19 @@ impl TraversalSteps for TraversalBuilder {
         } else {
             self.current_step = TraversalValue::Empty;
         }
-        self.store_error(e);
+        self.store_error("in_e", &[&edge_label], e);
         self
     }
 
     fn both_e(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        if self.is_tracking() {
+            return self.per_item(|tr| {
+                tr.both_e(txn, edge_label);
+            });
+        }
         let mut e = GraphError::Empty;
         if let TraversalValue::NodeArray(nodes) = &self.current_step {
             let mut new_current = Vec::with_capacity(nodes.len());
@@ -443,11 +972,22 @@ impl TraversalSteps for TraversalBuilder {
         } else {
             self.current_step = TraversalValue::Empty;
         }
-        self.store_error(e);
+        self.store_error("both_e", &[&edge_label], e);
         self
     }
 
     fn both(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        if self.paths.is_some() {
+            return self.tracked_adjacent(txn, edge_label, Direction::Both);
+        }
+        if self.is_tracking() {
+            return self.per_item(|tr| {
+                tr.both(txn, edge_label);
+            });
+        }
         let mut e = GraphError::Empty;
         if let TraversalValue::NodeArray(nodes) = &self.current_step {
             let mut new_current = Vec::with_capacity(nodes.len());
@@ -477,11 +1017,19 @@ impl TraversalSteps for TraversalBuilder {
         } else {
             self.current_step = TraversalValue::Empty;
         }
-        self.store_error(e);
+        self.store_error("both", &[&edge_label], e);
         self
     }
 
     fn out_v(&mut self, txn: &RoTxn) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        if self.is_tracking() {
+            return self.per_item(|tr| {
+                tr.out_v(txn);
+            });
+        }
         let mut e = GraphError::Empty;
         if let TraversalValue::EdgeArray(edges) = &self.current_step {
             let mut new_current = Vec::with_capacity(edges.len());
@@ -499,11 +1047,19 @@ impl TraversalSteps for TraversalBuilder {
         } else {
             self.current_step = TraversalValue::Empty;
         }
-        self.store_error(e);
+        self.store_error("out_v", &[], e);
         self
     }
 
     fn in_v(&mut self, txn: &RoTxn) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        if self.is_tracking() {
+            return self.per_item(|tr| {
+                tr.in_v(txn);
+            });
+        }
         let mut e = GraphError::Empty;
         if let TraversalValue::EdgeArray(edges) = &self.current_step {
             let mut new_current = Vec::with_capacity(edges.len());
@@ -521,11 +1077,19 @@ impl TraversalSteps for TraversalBuilder {
         } else {
             self.current_step = TraversalValue::Empty;
         }
-        self.store_error(e);
+        self.store_error("in_v", &[], e);
         self
     }
 
     fn both_v(&mut self, txn: &RoTxn) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        if self.is_tracking() {
+            return self.per_item(|tr| {
+                tr.both_v(txn);
+            });
+        }
         let mut e = GraphError::Empty;
         if let TraversalValue::EdgeArray(edges) = &self.current_step {
             let mut new_current = Vec::with_capacity(edges.len() * 2);
@@ -547,11 +1111,14 @@ impl TraversalSteps for TraversalBuilder {
         } else {
             self.current_step = TraversalValue::Empty;
         }
-        self.store_error(e);
+        self.store_error("both_v", &[], e);
         self
     }
 
     fn mutual(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         let mut e: GraphError = GraphError::Empty;
 
         if let TraversalValue::NodeArray(nodes) = &self.current_step {
@@ -584,7 +1151,7 @@ impl TraversalSteps for TraversalBuilder {
         } else {
             self.current_step = TraversalValue::Empty;
         }
-        self.store_error(e);
+        self.store_error("mutual", &[&edge_label], e);
         self
     }
 
@@ -595,6 +1162,9 @@ impl TraversalSteps for TraversalBuilder {
         from_id: &str,
         props: Vec<(String, Value)>,
     ) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         let mut e = GraphError::Empty;
         match &self.current_step {
             TraversalValue::NodeArray(nodes) => {
@@ -614,9 +1184,9 @@ impl TraversalSteps for TraversalBuilder {
                 self.current_step = TraversalValue::EdgeArray(new_current);
             }
             TraversalValue::Empty => {}
-            _ => panic!("Invalid traversal step for add_e_from"),
+            _ => e = GraphError::TraversalError("add_e_from expects nodes".to_string()),
         }
-        self.store_error(e);
+        self.store_error("add_e_from", &[&edge_label, &from_id, &props], e);
         self
     }
 
@@ -627,6 +1197,9 @@ impl TraversalSteps for TraversalBuilder {
         to_id: &str,
         props: Vec<(String, Value)>,
     ) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         let mut e = GraphError::Empty;
         match &self.current_step {
             TraversalValue::NodeArray(nodes) => {
@@ -643,19 +1216,25 @@ impl TraversalSteps for TraversalBuilder {
                 self.current_step = TraversalValue::EdgeArray(new_current);
             }
             TraversalValue::Empty => {}
-            _ => panic!("Invalid traversal step for add_e_to"),
+            _ => e = GraphError::TraversalError("add_e_to expects nodes".to_string()),
         }
-        self.store_error(e);
+        self.store_error("add_e_to", &[&edge_label, &to_id, &props], e);
         self
     }
 
     fn update_props(&mut self, txn: &mut RwTxn, props: Vec<(String, Value)>) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         let mut e = GraphError::Empty;
         match &self.current_step {
+            TraversalValue::NodeArray(nodes) if nodes.len() > 1 => {
+                e = GraphError::TraversalError("update_props expects a single node".to_string());
+            }
+            TraversalValue::EdgeArray(edges) if edges.len() > 1 => {
+                e = GraphError::TraversalError("update_props expects a single edge".to_string());
+            }
             TraversalValue::NodeArray(nodes) => {
-                if nodes.len() > 1 {
-                    panic!("Invalid traversal step for update_props");
-                }
                 let mut new_current = Vec::with_capacity(nodes.len());
                 for node in nodes {
                     match self.storage.update_node(txn, &node.id, props.clone()) {
@@ -666,9 +1245,6 @@ impl TraversalSteps for TraversalBuilder {
                 self.current_step = TraversalValue::NodeArray(new_current);
             }
             TraversalValue::EdgeArray(edges) => {
-                if edges.len() > 1 {
-                    panic!("Invalid traversal step for update_props");
-                }
                 let mut new_current = Vec::with_capacity(edges.len());
                 for edge in edges {
                     match self.storage.update_edge(txn, &edge.id, props.clone()) {
@@ -678,49 +1254,86 @@ impl TraversalSteps for TraversalBuilder {
                 }
                 self.current_step = TraversalValue::EdgeArray(new_current);
             }
-            _ => panic!("Invalid traversal step for update_props"),
+            TraversalValue::Empty => {}
+            _ => {
+                e = GraphError::TraversalError("update_props expects nodes or edges".to_string());
+            }
         }
-        self.store_error(e);
+        self.store_error("update_props", &[&props], e);
         self
     }
 }
 
 impl TraversalMethods for TraversalBuilder {
     fn count(&mut self) -> &mut Self {
-        self.current_step = TraversalValue::Count(Count::new(match &self.current_step {
+        if self.has_failed() {
+            return self;
+        }
+        self.clear_tracking();
+        let count = match &self.current_step {
             TraversalValue::NodeArray(nodes) => nodes.len(),
             TraversalValue::EdgeArray(edges) => edges.len(),
             TraversalValue::Empty => 0,
-            _ => panic!("Invalid traversal step for count {:?}", &self.current_step),
-        }));
+            _ => {
+                self.store_error(
+                    "count",
+                    &[],
+                    GraphError::TraversalError("count expects nodes or edges".to_string()),
+                );
+                return self;
+            }
+        };
+        self.current_step = TraversalValue::Count(Count::new(count));
         self
     }
     fn range(&mut self, start: i32, end: i32) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        if start < 0 || end < start {
+            self.current_step = TraversalValue::Empty;
+            self.store_error(
+                "range",
+                &[&start, &end],
+                GraphError::TraversalError("range expects 0 <= start <= end".to_string()),
+            );
+            return self;
+        }
         let start = start as usize;
         let end = end as usize;
+        // bounds past the end of the step are clamped rather than sliced out of range
+        let bounds = |len: usize| start.min(len)..end.min(len);
         match &self.current_step {
             TraversalValue::NodeArray(nodes) => {
-                if nodes.len() == 0 {
-                    self.current_step = TraversalValue::Empty;
-                } else if nodes.len() < end {
-                    self.current_step =
-                        TraversalValue::NodeArray(nodes[start..nodes.len() - 1].to_vec());
-                } else {
-                    self.current_step = TraversalValue::NodeArray(nodes[start..end].to_vec());
-                }
+                let nodes = nodes[bounds(nodes.len())].to_vec();
+                self.current_step = match nodes.is_empty() {
+                    true => TraversalValue::Empty,
+                    false => TraversalValue::NodeArray(nodes),
+                };
             }
             TraversalValue::EdgeArray(edges) => {
-                if edges.len() == 0 {
-                    self.current_step = TraversalValue::Empty;
-                } else if edges.len() < end {
-                    self.current_step =
-                        TraversalValue::EdgeArray(edges[start..edges.len() - 1].to_vec());
-                } else {
-                    self.current_step = TraversalValue::EdgeArray(edges[start..end].to_vec());
-                }
+                let edges = edges[bounds(edges.len())].to_vec();
+                self.current_step = match edges.is_empty() {
+                    true => TraversalValue::Empty,
+                    false => TraversalValue::EdgeArray(edges),
+                };
             }
             TraversalValue::Empty => {}
-            _ => panic!("Invalid traversal step for range {:?}", &self.current_step),
+            _ => {
+                self.store_error(
+                    "range",
+                    &[&start, &end],
+                    GraphError::TraversalError("range expects nodes or edges".to_string()),
+                );
+                return self;
+            }
+        }
+        let len = item_count(&self.current_step);
+        if let Some(rows) = self.labels.take() {
+            self.labels = Some(rows.into_iter().skip(start).take(len).collect());
+        }
+        if let Some(paths) = self.paths.take() {
+            self.paths = Some(paths.into_iter().skip(start).take(len).collect());
         }
         self
     }
@@ -728,25 +1341,76 @@ impl TraversalMethods for TraversalBuilder {
     // Then modify the filter function
     fn filter_nodes<F>(&mut self, txn: &RoTxn, predicate: F) -> &mut Self
     where
-        F: Fn(&Node) -> Result<bool, GraphError>,
+        F: Fn(&RoTxn, &Node) -> Result<bool, GraphError> + Sync,
     {
+        if self.has_failed() {
+            return self;
+        }
+        if self.is_tracking() {
+            // a trait object, so the recursive call doesn't instantiate a new `F` each time
+            let predicate: &(dyn Fn(&RoTxn, &Node) -> Result<bool, GraphError> + Sync) =
+                &predicate;
+            return self.per_item(|tr| {
+                tr.filter_nodes(txn, predicate);
+            });
+        }
+        let mut e = GraphError::Empty;
         if let TraversalValue::NodeArray(nodes) = &mut self.current_step {
-            nodes.retain(|node| predicate(node).unwrap());
+            let mut keep = self
+                .storage
+                .fan_out(txn, nodes, self.parallelism, &predicate)
+                .into_iter();
+            nodes.retain(|_| match keep.next() {
+                Some(Ok(keep)) => keep,
+                Some(Err(err)) => {
+                    e = err;
+                    false
+                }
+                None => false,
+            });
         }
+        self.store_error("filter_nodes", &[], e);
         self
     }
 
     fn filter_edges<F>(&mut self, txn: &RoTxn, predicate: F) -> &mut Self
     where
-        F: Fn(&Edge) -> Result<bool, GraphError>,
+        F: Fn(&RoTxn, &Edge) -> Result<bool, GraphError> + Sync,
     {
+        if self.has_failed() {
+            return self;
+        }
+        if self.is_tracking() {
+            // a trait object, so the recursive call doesn't instantiate a new `F` each time
+            let predicate: &(dyn Fn(&RoTxn, &Edge) -> Result<bool, GraphError> + Sync) =
+                &predicate;
+            return self.per_item(|tr| {
+                tr.filter_edges(txn, predicate);
+            });
+        }
+        let mut e = GraphError::Empty;
         if let TraversalValue::EdgeArray(edges) = &mut self.current_step {
-            edges.retain(|edge| predicate(edge).unwrap());
+            let mut keep = self
+                .storage
+                .fan_out(txn, edges, self.parallelism, &predicate)
+                .into_iter();
+            edges.retain(|_| match keep.next() {
+                Some(Ok(keep)) => keep,
+                Some(Err(err)) => {
+                    e = err;
+                    false
+                }
+                None => false,
+            });
         }
+        self.store_error("filter_edges", &[], e);
         self
     }
 
     fn get_properties(&mut self, txn: &RoTxn, keys: &Vec<String>) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         match &mut self.current_step {
             TraversalValue::NodeArray(nodes) => {
                 let mut new_props = Vec::with_capacity(nodes.len() * keys.len());
@@ -782,7 +1446,12 @@ impl TraversalMethods for TraversalBuilder {
                 }
                 self.current_step = TraversalValue::ValueArray(new_props);
             }
-            _ => panic!("Invalid traversal step for get_properties"),
+            TraversalValue::Empty => {}
+            _ => self.store_error(
+                "get_properties",
+                &[keys],
+                GraphError::TraversalError("get_properties expects nodes or edges".to_string()),
+            ),
         }
         self
     }
@@ -791,9 +1460,14 @@ impl TraversalMethods for TraversalBuilder {
     where
         F: Fn(&Node) -> Result<Node, GraphError>,
     {
-        if let TraversalValue::NodeArray(nodes) = &mut self.current_step {
-            let new_nodes = nodes.iter().map(|node| map_fn(node).unwrap()).collect();
-            self.current_step = TraversalValue::NodeArray(new_nodes);
+        if self.has_failed() {
+            return self;
+        }
+        if let TraversalValue::NodeArray(nodes) = &self.current_step {
+            match nodes.iter().map(|node| map_fn(node)).collect::<Result<Vec<_>, _>>() {
+                Ok(new_nodes) => self.current_step = TraversalValue::NodeArray(new_nodes),
+                Err(err) => self.store_error("map_nodes", &[], err),
+            }
         }
         self
     }
@@ -802,9 +1476,14 @@ impl TraversalMethods for TraversalBuilder {
     where
         F: Fn(&Edge) -> Result<Edge, GraphError>,
     {
-        if let TraversalValue::EdgeArray(edges) = &mut self.current_step {
-            let new_edges = edges.iter().map(|edge| map_fn(edge).unwrap()).collect();
-            self.current_step = TraversalValue::EdgeArray(new_edges);
+        if self.has_failed() {
+            return self;
+        }
+        if let TraversalValue::EdgeArray(edges) = &self.current_step {
+            match edges.iter().map(|edge| map_fn(edge)).collect::<Result<Vec<_>, _>>() {
+                Ok(new_edges) => self.current_step = TraversalValue::EdgeArray(new_edges),
+                Err(err) => self.store_error("map_edges", &[], err),
+            }
         }
         self
     }
@@ -813,14 +1492,16 @@ impl TraversalMethods for TraversalBuilder {
     where
         F: FnMut(&Node, &RoTxn) -> Result<(), GraphError>,
     {
+        if self.has_failed() {
+            return self;
+        }
+        let mut e = GraphError::Empty;
         if let TraversalValue::NodeArray(nodes) = &self.current_step {
-            for node in nodes {
-                match map_fn(node, txn) {
-                    Ok(_) => (),
-                    Err(err) => panic!("Error in for_each_node: {:?}", err),
-                }
+            if let Some(err) = nodes.iter().find_map(|node| map_fn(node, txn).err()) {
+                e = err;
             }
         }
+        self.store_error("for_each_node", &[], e);
         self
     }
 
@@ -828,11 +1509,16 @@ impl TraversalMethods for TraversalBuilder {
     where
         F: FnMut(&Node, &mut RwTxn) -> Result<(), GraphError>,
     {
+        if self.has_failed() {
+            return self;
+        }
+        let mut e = GraphError::Empty;
         if let TraversalValue::NodeArray(nodes) = &self.current_step {
-            for node in nodes {
-                map_fn(node, txn);
+            if let Some(err) = nodes.iter().find_map(|node| map_fn(node, txn).err()) {
+                e = err;
             }
         }
+        self.store_error("for_each_node_mut", &[], e);
         self
     }
 
@@ -840,17 +1526,249 @@ impl TraversalMethods for TraversalBuilder {
     where
         F: Fn(&Edge) -> Result<(), GraphError>,
     {
+        if self.has_failed() {
+            return self;
+        }
+        let mut e = GraphError::Empty;
         if let TraversalValue::EdgeArray(edges) = &self.current_step {
-            for edge in edges {
-                map_fn(edge).unwrap();
+            if let Some(err) = edges.iter().find_map(|edge| map_fn(edge).err()) {
+                e = err;
+            }
+        }
+        self.store_error("for_each_edge", &[], e);
+        self
+    }
+
+    fn track_paths(&mut self) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        self.paths = Some(match &self.current_step {
+            TraversalValue::NodeArray(nodes) => nodes
+                .iter()
+                .map(|node| (vec![node.clone()], Vec::new()))
+                .collect(),
+            TraversalValue::EdgeArray(edges) => edges
+                .iter()
+                .map(|edge| (Vec::new(), vec![edge.clone()]))
+                .collect(),
+            _ => Vec::new(),
+        });
+        self
+    }
+
+    fn path(&mut self) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        self.labels = None;
+        match self.paths.take() {
+            Some(paths) if !paths.is_empty() => self.current_step = TraversalValue::Paths(paths),
+            Some(_) => self.current_step = TraversalValue::Empty,
+            None => {
+                self.current_step = TraversalValue::Empty;
+                self.store_error("path", &[], GraphError::TraversalError(
+                    "path needs track_paths at the start of the traversal".to_string(),
+                ));
+            }
+        }
+        self
+    }
+
+    fn as_(&mut self, name: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        let bindings: Vec<Binding> = match &self.current_step {
+            TraversalValue::NodeArray(nodes) => nodes.iter().cloned().map(Binding::Node).collect(),
+            TraversalValue::EdgeArray(edges) => edges.iter().cloned().map(Binding::Edge).collect(),
+            TraversalValue::Empty => Vec::new(),
+            _ => {
+                self.store_error("as_", &[&name], GraphError::TraversalError(
+                    "as_ expects nodes or edges".to_string(),
+                ));
+                return self;
+            }
+        };
+        let mut rows = self
+            .labels
+            .take()
+            .unwrap_or_else(|| vec![BindingRow::new(); bindings.len()]);
+        for (row, binding) in rows.iter_mut().zip(bindings) {
+            row.insert(name.to_string(), binding);
+        }
+        self.labels = Some(rows);
+        self
+    }
+
+    fn select(&mut self, names: &[&str]) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        let rows = match self.labels.take() {
+            Some(rows) => rows,
+            None => {
+                self.current_step = TraversalValue::Empty;
+                self.store_error("select", &[&names], GraphError::TraversalError(
+                    "select needs labels set with as_".to_string(),
+                ));
+                return self;
+            }
+        };
+        if let Some(name) = rows
+            .first()
+            .and_then(|row| names.iter().find(|name| !row.contains_key(**name)))
+        {
+            self.current_step = TraversalValue::Empty;
+            self.store_error(
+                "select",
+                &[&names],
+                GraphError::TraversalError(format!("Unknown label: {}", name)),
+            );
+            return self;
+        }
+        let rows: Vec<BindingRow> = rows
+            .into_iter()
+            .map(|row| {
+                row.into_iter()
+                    .filter(|(name, _)| names.contains(&name.as_str()))
+                    .collect()
+            })
+            .collect();
+        self.current_step = match rows.is_empty() {
+            true => TraversalValue::Empty,
+            false => TraversalValue::Bindings(rows),
+        };
+        self
+    }
+
+    fn union(&mut self, branches: &[&dyn Fn(&mut Self)]) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        self.per_item(|tr| {
+            let start = tr.current_step.clone();
+            let mut results = Vec::with_capacity(branches.len());
+            for branch in branches {
+                tr.current_step = start.clone();
+                branch(tr);
+                results.push(std::mem::replace(&mut tr.current_step, TraversalValue::Empty));
+            }
+            match merge_items(results) {
+                Ok(merged) => tr.current_step = merged,
+                Err(err) => tr.store_error("union", &[], err),
+            }
+        })
+    }
+
+    fn optional<F>(&mut self, branch: F) -> &mut Self
+    where
+        F: Fn(&mut Self),
+    {
+        if self.has_failed() {
+            return self;
+        }
+        self.per_item(|tr| {
+            let start = tr.current_step.clone();
+            branch(tr);
+            if item_count(&tr.current_step) == 0 {
+                tr.current_step = start;
+            }
+        })
+    }
+
+    fn coalesce(&mut self, branches: &[&dyn Fn(&mut Self)]) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        self.per_item(|tr| {
+            let start = tr.current_step.clone();
+            for branch in branches {
+                tr.current_step = start.clone();
+                branch(tr);
+                if item_count(&tr.current_step) > 0 {
+                    return;
+                }
+            }
+            tr.current_step = TraversalValue::Empty;
+        })
+    }
+
+    fn order_by(&mut self, keys: &[(&str, Order)]) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        self.clear_tracking();
+        match &mut self.current_step {
+            TraversalValue::NodeArray(nodes) => aggregate::sort_items(nodes, keys),
+            TraversalValue::EdgeArray(edges) => aggregate::sort_items(edges, keys),
+            TraversalValue::Empty => {}
+            _ => self.store_error("order_by", &[&keys], GraphError::TraversalError(
+                "order_by expects nodes or edges".to_string(),
+            )),
+        }
+        self
+    }
+
+    fn dedup(&mut self) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        self.clear_tracking();
+        let mut seen = HashSet::new();
+        match &mut self.current_step {
+            TraversalValue::NodeArray(nodes) => nodes.retain(|node| seen.insert(node.id.clone())),
+            TraversalValue::EdgeArray(edges) => edges.retain(|edge| seen.insert(edge.id.clone())),
+            TraversalValue::Empty => {}
+            _ => self.store_error("dedup", &[], GraphError::TraversalError(
+                "dedup expects nodes or edges".to_string(),
+            )),
+        }
+        self
+    }
+
+    fn dedup_by(&mut self, property: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        self.clear_tracking();
+        match std::mem::replace(&mut self.current_step, TraversalValue::Empty) {
+            TraversalValue::NodeArray(nodes) => {
+                self.current_step =
+                    TraversalValue::NodeArray(aggregate::dedup_items_by(nodes, property));
             }
+            TraversalValue::EdgeArray(edges) => {
+                self.current_step =
+                    TraversalValue::EdgeArray(aggregate::dedup_items_by(edges, property));
+            }
+            TraversalValue::Empty => {}
+            _ => self.store_error("dedup_by", &[&property], GraphError::TraversalError(
+                "dedup_by expects nodes or edges".to_string(),
+            )),
         }
         self
     }
+
+    fn group_by(&mut self, property: &str, aggregations: &[Aggregation]) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        self.group_step(Some(property), aggregations)
+    }
+
+    fn aggregate(&mut self, aggregations: &[Aggregation]) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        self.group_step(None, aggregations)
+    }
 }
 
 impl TraversalSearchMethods for TraversalBuilder {
     fn shortest_path_between(&mut self, txn: &RoTxn, from_id: &str, to_id: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         let s = Arc::clone(&self.storage);
         let paths = {
             match s.shortest_path(txn, from_id, to_id) {
@@ -871,21 +1789,26 @@ impl TraversalSearchMethods for TraversalBuilder {
     }
 
     fn shortest_path_to(&mut self, txn: &RoTxn, to_id: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         let mut paths = Vec::with_capacity(24);
         let nodes = match &self.current_step {
             TraversalValue::NodeArray(nodes) => nodes.clone(),
+            TraversalValue::Empty => return self,
             _ => {
-                println!(
-                    "Invalid traversal step for shortest_path_to {:?}",
-                    &self.current_step
+                self.store_error(
+                    "shortest_path_to",
+                    &[&to_id],
+                    GraphError::TraversalError("shortest_path_to expects nodes".to_string()),
                 );
-                unreachable!();
+                return self;
             }
         };
         for node in nodes {
             match self.storage.shortest_path(txn, &node.id, to_id) {
                 Ok(path) => paths.push(path),
-                Err(e) => self.store_error(e),
+                Err(e) => self.store_error("shortest_path_to", &[&to_id], e),
             }
         }
         self.current_step = TraversalValue::Paths(paths);
@@ -893,15 +1816,26 @@ impl TraversalSearchMethods for TraversalBuilder {
     }
 
     fn shortest_path_from(&mut self, txn: &RoTxn, from_id: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         let mut paths = Vec::with_capacity(24);
         let nodes = match &self.current_step {
             TraversalValue::NodeArray(nodes) => nodes.clone(),
-            _ => unreachable!(),
+            TraversalValue::Empty => return self,
+            _ => {
+                self.store_error(
+                    "shortest_path_from",
+                    &[&from_id],
+                    GraphError::TraversalError("shortest_path_from expects nodes".to_string()),
+                );
+                return self;
+            }
         };
         for node in nodes {
             match self.storage.shortest_path(txn, from_id, &node.id) {
                 Ok(path) => paths.push(path),
-                Err(e) => self.store_error(e),
+                Err(e) => self.store_error("shortest_path_from", &[&from_id], e),
             }
         }
         self.current_step = TraversalValue::Paths(paths);
@@ -909,16 +1843,21 @@ impl TraversalSearchMethods for TraversalBuilder {
     }
 
     fn shortest_mutual_path_from(&mut self, txn: &RoTxn, from_id: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         let s = Arc::clone(&self.storage);
         let mut e = GraphError::Empty;
         let nodes = match &self.current_step {
             TraversalValue::NodeArray(nodes) => nodes.clone(),
+            TraversalValue::Empty => return self,
             _ => {
-                e = GraphError::TraversalError(format!(
-                    "Invalid traversal step for shortest_mutual_path_between {:?}",
-                    &self.current_step
-                ));
-                unreachable!();
+                self.store_error(
+                    "shortest_mutual_path_from",
+                    &[&from_id],
+                    GraphError::TraversalError("shortest_mutual_path_from expects nodes".to_string()),
+                );
+                return self;
             }
         };
 
@@ -934,23 +1873,28 @@ impl TraversalSearchMethods for TraversalBuilder {
                 }
             }
         }
-        self.store_error(e);
+        self.store_error("shortest_mutual_path_from", &[&from_id], e);
         self.current_step = TraversalValue::Paths(paths);
 
         self
     }
 
     fn shortest_mutual_path_to(&mut self, txn: &RoTxn, to_id: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         let s = Arc::clone(&self.storage);
         let mut e = GraphError::Empty;
         let nodes = match &self.current_step {
-            TraversalValue::NodeArray(nodes) => nodes,
+            TraversalValue::NodeArray(nodes) => nodes.clone(),
+            TraversalValue::Empty => return self,
             _ => {
-                e = GraphError::TraversalError(format!(
-                    "Invalid traversal step for shortest_mutual_path_between {:?}",
-                    &self.current_step
-                ));
-                unreachable!();
+                self.store_error(
+                    "shortest_mutual_path_to",
+                    &[&to_id],
+                    GraphError::TraversalError("shortest_mutual_path_to expects nodes".to_string()),
+                );
+                return self;
             }
         };
 
@@ -966,11 +1910,168 @@ impl TraversalSearchMethods for TraversalBuilder {
                 }
             }
         }
-        self.store_error(e);
+        self.store_error("shortest_mutual_path_to", &[&to_id], e);
         self.current_step = TraversalValue::Paths(paths);
 
         self
     }
+
+    fn predict_links(
+        &mut self,
+        txn: &RoTxn,
+        edge_label: &str,
+        metric: LinkPredictionMetric,
+        limit: usize,
+    ) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        let mut e = GraphError::Empty;
+        if let TraversalValue::NodeArray(nodes) = &self.current_step {
+            let mut new_current = Vec::with_capacity(nodes.len() * limit);
+            for node in nodes {
+                match self
+                    .storage
+                    .predict_links(txn, &node.id, edge_label, metric, limit)
+                {
+                    Ok(predictions) => {
+                        new_current.extend(predictions.into_iter().map(|(mut node, score)| {
+                            node.properties
+                                .insert(LINK_SCORE_PROPERTY.to_string(), Value::Float(score));
+                            node
+                        }))
+                    }
+                    Err(err) => e = err,
+                }
+            }
+            if new_current.is_empty() {
+                self.current_step = TraversalValue::Empty;
+            } else {
+                self.current_step = TraversalValue::NodeArray(new_current);
+            }
+        } else {
+            self.current_step = TraversalValue::Empty;
+        }
+        self.store_error("predict_links", &[&edge_label, &metric, &limit], e);
+        self
+    }
+
+    fn similar_nodes(&mut self, txn: &RoTxn, limit: usize) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        let mut e = GraphError::Empty;
+        if let TraversalValue::NodeArray(nodes) = &self.current_step {
+            let mut new_current = Vec::with_capacity(nodes.len() * limit);
+            for node in nodes {
+                match self.storage.similar_nodes(txn, &node.id, limit) {
+                    Ok(similar) => {
+                        new_current.extend(similar.into_iter().map(|(mut node, similarity)| {
+                            node.properties
+                                .insert(SIMILARITY_PROPERTY.to_string(), Value::Float(similarity));
+                            node
+                        }))
+                    }
+                    Err(err) => e = err,
+                }
+            }
+            if new_current.is_empty() {
+                self.current_step = TraversalValue::Empty;
+            } else {
+                self.current_step = TraversalValue::NodeArray(new_current);
+            }
+        } else {
+            self.current_step = TraversalValue::Empty;
+        }
+        self.store_error("similar_nodes", &[&limit], e);
+        self
+    }
+
+    fn ancestors(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        self.closure_step(txn, edge_label, false)
+    }
+
+    fn descendants(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        self.closure_step(txn, edge_label, true)
+    }
+
+    fn topological_sort(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        let mut e = GraphError::Empty;
+        if let TraversalValue::NodeArray(nodes) = &mut self.current_step {
+            match self.storage.topological_sort(txn, edge_label) {
+                Ok(order) => {
+                    let position: HashMap<&str, usize> = order
+                        .iter()
+                        .enumerate()
+                        .map(|(i, node)| (node.id.as_str(), i + 1))
+                        .collect();
+                    // stable, so unconnected nodes keep their relative order at the front
+                    nodes.sort_by_key(|node| position.get(node.id.as_str()).copied().unwrap_or(0));
+                }
+                Err(err) => {
+                    e = err;
+                    self.current_step = TraversalValue::Empty;
+                }
+            }
+        } else {
+            self.current_step = TraversalValue::Empty;
+        }
+        self.store_error("topological_sort", &[&edge_label], e);
+        self
+    }
+
+    fn find_cycle(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        let mut e = GraphError::Empty;
+        if let TraversalValue::NodeArray(nodes) = &self.current_step {
+            let start_nodes: Vec<String> = nodes.iter().map(|node| node.id.clone()).collect();
+            self.current_step = match self.storage.find_cycle(txn, edge_label, &start_nodes) {
+                Ok(Some(cycle)) => TraversalValue::Paths(vec![cycle]),
+                Ok(None) => TraversalValue::Empty,
+                Err(err) => {
+                    e = err;
+                    TraversalValue::Empty
+                }
+            };
+        } else {
+            self.current_step = TraversalValue::Empty;
+        }
+        self.store_error("find_cycle", &[&edge_label], e);
+        self
+    }
+
+    fn critical_path(
+        &mut self,
+        txn: &RoTxn,
+        edge_label: &str,
+        weight_property: Option<&str>,
+    ) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
+        let mut e = GraphError::Empty;
+        self.current_step = match self.storage.critical_path(txn, edge_label, weight_property) {
+            Ok((nodes, edges, _)) if !nodes.is_empty() => TraversalValue::Paths(vec![(nodes, edges)]),
+            Ok(_) => TraversalValue::Empty,
+            Err(err) => {
+                e = err;
+                TraversalValue::Empty
+            }
+        };
+        self.store_error("critical_path", &[&edge_label, &weight_property], e);
+        self
+    }
 }
 
 pub trait TransactionCommit {
@@ -1026,10 +2127,13 @@ impl TraversalBuilderMethods for TraversalBuilder {
 
 impl VectorTraversalSteps for TraversalBuilder {
     fn vector_search(&mut self, txn: &RoTxn, query_vector: &[f64], k: usize) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         let result = match self.storage.vectors.search(txn, query_vector, k) {
             Ok(result) => result,
             Err(err) => {
-                self.store_error(GraphError::from(err));
+                self.store_error("vector_search", &[&query_vector, &k], GraphError::from(err));
                 return self;
             }
         };
@@ -1038,15 +2142,26 @@ impl VectorTraversalSteps for TraversalBuilder {
     }
 
     fn insert_vector(&mut self, txn: &mut RwTxn, vector: &[f64]) -> &mut Self {
-        self.storage.vectors.insert(txn, vector, None).unwrap();
+        if self.has_failed() {
+            return self;
+        }
+        if let Err(err) = self.storage.vectors.insert(txn, vector, None) {
+            self.store_error("insert_vector", &[&vector], GraphError::from(err));
+        }
         self
     }
 
     fn delete_vector(&mut self, txn: &mut RwTxn, vector_id: &str) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         self
     }
 
     fn update_vector(&mut self, txn: &mut RwTxn, vector_id: &str, vector: &[f64]) -> &mut Self {
+        if self.has_failed() {
+            return self;
+        }
         self
     }
 }
@@ -0,0 +1,582 @@
+use std::iter;
+
+use heed3::RoTxn;
+
+use crate::helix_engine::{
+    graph_core::{
+        limits::{edge_size, node_size, QueryBudget},
+        profile::record_read,
+    },
+    storage_core::{
+        storage_core::{HelixGraphStorage, NODE_LABEL_PREFIX},
+        storage_methods::StorageMethods,
+    },
+    types::GraphError,
+};
+use crate::protocol::{
+    count::Count,
+    items::{Edge, Node},
+    traversal_value::TraversalValue,
+};
+
+/// A node or edge flowing through a lazy traversal
+#[derive(Debug, Clone)]
+pub enum TraversalItem {
+    Node(Node),
+    Edge(Edge),
+}
+
+type ItemIter<'a> = Box<dyn Iterator<Item = Result<TraversalItem, GraphError>> + 'a>;
+
+/// A pull based alternative to `TraversalBuilder`.
+///
+/// Every step wraps the previous one in an iterator adaptor over LMDB cursors, so nothing
+/// is read until the traversal is consumed and only as much is read as the consumer asks for.
+/// `N<User>::Out<Follows>::RANGE(0, 10)` stops after the tenth follow instead of loading every
+/// user, and at most one node's adjacency cursor is open per step at any time.
+///
+/// Errors are passed along as items and returned by whichever method consumes the traversal.
+pub struct LazyTraversal<'a, 't> {
+    storage: &'a HelixGraphStorage,
+    txn: &'a RoTxn<'t>,
+    iter: ItemIter<'a>,
+}
+
+impl<'a, 't> LazyTraversal<'a, 't> {
+    /// Streams every node in the graph
+    pub fn v(storage: &'a HelixGraphStorage, txn: &'a RoTxn<'t>) -> Self {
+        let iter: ItemIter<'a> = match storage.nodes_db.iter(txn) {
+            Ok(nodes) => Box::new(nodes.filter_map(|result| match result {
+                Ok((_, [])) => None,
+                Ok((_, value)) => Some({
+                    record_read(value.len());
+                    bincode::deserialize::<Node>(value)
+                        .map(TraversalItem::Node)
+                        .map_err(GraphError::from)
+                }),
+                Err(err) => Some(Err(GraphError::from(err))),
+            })),
+            Err(err) => Box::new(iter::once(Err(GraphError::from(err)))),
+        };
+        Self { storage, txn, iter }
+    }
+
+    /// Streams every edge in the graph
+    pub fn e(storage: &'a HelixGraphStorage, txn: &'a RoTxn<'t>) -> Self {
+        let iter: ItemIter<'a> = match storage.edges_db.iter(txn) {
+            Ok(edges) => Box::new(edges.filter_map(|result| match result {
+                Ok((_, [])) => None,
+                Ok((_, value)) => Some({
+                    record_read(value.len());
+                    bincode::deserialize::<Edge>(value)
+                        .map(TraversalItem::Edge)
+                        .map_err(GraphError::from)
+                }),
+                Err(err) => Some(Err(GraphError::from(err))),
+            })),
+            Err(err) => Box::new(iter::once(Err(GraphError::from(err)))),
+        };
+        Self { storage, txn, iter }
+    }
+
+    /// Streams the nodes with any of the given labels using the label index
+    pub fn v_from_types(
+        storage: &'a HelixGraphStorage,
+        txn: &'a RoTxn<'t>,
+        node_labels: &[&str],
+    ) -> Self {
+        let prefixes: Vec<Vec<u8>> = node_labels
+            .iter()
+            .map(|label| [NODE_LABEL_PREFIX, label.as_bytes(), b":"].concat())
+            .collect();
+        let iter = prefixes.into_iter().flat_map(move |prefix| -> ItemIter<'a> {
+            let keys = match storage.node_labels_db.prefix_iter(txn, &prefix) {
+                Ok(keys) => keys,
+                Err(err) => return Box::new(iter::once(Err(GraphError::from(err)))),
+            };
+            Box::new(keys.map(move |result| {
+                let (key, _) = result?;
+                let node_id = std::str::from_utf8(&key[prefix.len()..])?;
+                storage.get_node(txn, node_id).map(TraversalItem::Node)
+            }))
+        });
+        Self {
+            storage,
+            txn,
+            iter: Box::new(iter),
+        }
+    }
+
+    pub fn v_from_id(storage: &'a HelixGraphStorage, txn: &'a RoTxn<'t>, node_id: &str) -> Self {
+        let item = storage.get_node(txn, node_id).map(TraversalItem::Node);
+        Self {
+            storage,
+            txn,
+            iter: Box::new(iter::once(item)),
+        }
+    }
+
+    pub fn e_from_id(storage: &'a HelixGraphStorage, txn: &'a RoTxn<'t>, edge_id: &str) -> Self {
+        let item = storage.get_edge(txn, edge_id).map(TraversalItem::Edge);
+        Self {
+            storage,
+            txn,
+            iter: Box::new(iter::once(item)),
+        }
+    }
+
+    /// Continues from an already materialized traversal value, e.g. a query variable
+    pub fn from_value(
+        storage: &'a HelixGraphStorage,
+        txn: &'a RoTxn<'t>,
+        value: TraversalValue,
+    ) -> Self {
+        let iter: ItemIter<'a> = match value {
+            TraversalValue::NodeArray(nodes) => {
+                Box::new(nodes.into_iter().map(|node| Ok(TraversalItem::Node(node))))
+            }
+            TraversalValue::EdgeArray(edges) => {
+                Box::new(edges.into_iter().map(|edge| Ok(TraversalItem::Edge(edge))))
+            }
+            TraversalValue::Empty => Box::new(iter::empty()),
+            value => Box::new(iter::once(Err(GraphError::TraversalError(format!(
+                "Cannot stream {:?}, only nodes and edges can be traversed lazily",
+                value
+            ))))),
+        };
+        Self { storage, txn, iter }
+    }
+
+    /// Nodes at the end of the outgoing edges with the given label, or any label if empty
+    pub fn out(self, edge_label: &str) -> Self {
+        self.adjacent(edge_label, true, false)
+    }
+
+    /// Nodes at the start of the incoming edges with the given label, or any label if empty
+    pub fn in_(self, edge_label: &str) -> Self {
+        self.adjacent(edge_label, false, false)
+    }
+
+    pub fn out_e(self, edge_label: &str) -> Self {
+        self.adjacent(edge_label, true, true)
+    }
+
+    pub fn in_e(self, edge_label: &str) -> Self {
+        self.adjacent(edge_label, false, true)
+    }
+
+    /// The node each edge points to
+    pub fn in_v(self) -> Self {
+        self.edge_endpoint(false)
+    }
+
+    /// The node each edge starts from
+    pub fn out_v(self) -> Self {
+        self.edge_endpoint(true)
+    }
+
+    pub fn filter_nodes<F>(self, predicate: F) -> Self
+    where
+        F: Fn(&Node) -> Result<bool, GraphError> + 'a,
+    {
+        let Self { storage, txn, iter } = self;
+        let iter = iter.filter_map(move |item| match item {
+            Ok(TraversalItem::Node(node)) => match predicate(&node) {
+                Ok(true) => Some(Ok(TraversalItem::Node(node))),
+                Ok(false) => None,
+                Err(err) => Some(Err(err)),
+            },
+            Ok(TraversalItem::Edge(_)) => Some(Err(GraphError::TraversalError(
+                "filter_nodes expects nodes, got an edge".to_string(),
+            ))),
+            Err(err) => Some(Err(err)),
+        });
+        Self {
+            storage,
+            txn,
+            iter: Box::new(iter),
+        }
+    }
+
+    pub fn filter_edges<F>(self, predicate: F) -> Self
+    where
+        F: Fn(&Edge) -> Result<bool, GraphError> + 'a,
+    {
+        let Self { storage, txn, iter } = self;
+        let iter = iter.filter_map(move |item| match item {
+            Ok(TraversalItem::Edge(edge)) => match predicate(&edge) {
+                Ok(true) => Some(Ok(TraversalItem::Edge(edge))),
+                Ok(false) => None,
+                Err(err) => Some(Err(err)),
+            },
+            Ok(TraversalItem::Node(_)) => Some(Err(GraphError::TraversalError(
+                "filter_edges expects edges, got a node".to_string(),
+            ))),
+            Err(err) => Some(Err(err)),
+        });
+        Self {
+            storage,
+            txn,
+            iter: Box::new(iter),
+        }
+    }
+
+    /// Keeps the items from `start` (inclusive) to `end` (exclusive).
+    /// Nothing past `end` is ever read.
+    pub fn range(self, start: i32, end: i32) -> Self {
+        let start = start.max(0) as usize;
+        let end = (end.max(0) as usize).max(start);
+        let Self { storage, txn, iter } = self;
+        Self {
+            storage,
+            txn,
+            iter: Box::new(iter.skip(start).take(end - start)),
+        }
+    }
+
+    pub fn take(self, limit: usize) -> Self {
+        self.range(0, limit.min(i32::MAX as usize) as i32)
+    }
+
+    /// Stops with the budget's error once the query runs out of time or is cancelled, or the
+    /// traversal produces more items or estimated bytes than the budget allows
+    pub fn within(self, budget: &'a QueryBudget) -> Self {
+        let Self { storage, txn, iter } = self;
+        let tracks_memory = budget.tracks_memory();
+        let mut items = 0;
+        let mut bytes = 0;
+        let mut exhausted = false;
+        let iter = iter.map_while(move |item| {
+            if exhausted {
+                return None;
+            }
+            let checked = item.and_then(|item| {
+                items += 1;
+                if tracks_memory {
+                    bytes += match &item {
+                        TraversalItem::Node(node) => node_size(node),
+                        TraversalItem::Edge(edge) => edge_size(edge),
+                    };
+                }
+                budget.check_time()?;
+                budget.check_size(items, bytes)?;
+                Ok(item)
+            });
+            exhausted = checked.is_err();
+            Some(checked)
+        });
+        Self {
+            storage,
+            txn,
+            iter: Box::new(iter),
+        }
+    }
+
+    /// Consumes the traversal, counting the items
+    pub fn count(self) -> Result<usize, GraphError> {
+        let mut count = 0;
+        for item in self.iter {
+            item?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Returns as soon as the first item is produced
+    pub fn exists(mut self) -> Result<bool, GraphError> {
+        match self.iter.next() {
+            Some(Ok(_)) => Ok(true),
+            Some(Err(err)) => Err(err),
+            None => Ok(false),
+        }
+    }
+
+    /// Consumes the traversal into a `TraversalValue` for use with `TraversalBuilder`
+    /// and the return value remapping
+    pub fn collect(self) -> Result<TraversalValue, GraphError> {
+        let mut nodes = Vec::new();
+        let mut edges = Vec::new();
+        for item in self.iter {
+            match item? {
+                TraversalItem::Node(node) => nodes.push(node),
+                TraversalItem::Edge(edge) => edges.push(edge),
+            }
+        }
+        match (nodes.is_empty(), edges.is_empty()) {
+            (true, true) => Ok(TraversalValue::Empty),
+            (false, true) => Ok(TraversalValue::NodeArray(nodes)),
+            (true, false) => Ok(TraversalValue::EdgeArray(edges)),
+            (false, false) => Err(GraphError::TraversalError(
+                "Traversal produced both nodes and edges".to_string(),
+            )),
+        }
+    }
+
+    /// Like `count`, wrapped as a traversal value
+    pub fn collect_count(self) -> Result<TraversalValue, GraphError> {
+        Ok(TraversalValue::Count(Count::new(self.count()?)))
+    }
+
+    fn adjacent(self, edge_label: &str, outgoing: bool, edges: bool) -> Self {
+        let Self { storage, txn, iter } = self;
+        let edge_label = edge_label.to_string();
+        let iter = iter.flat_map(move |item| -> ItemIter<'a> {
+            let node = match item {
+                Ok(TraversalItem::Node(node)) => node,
+                Ok(TraversalItem::Edge(_)) => {
+                    return Box::new(iter::once(Err(GraphError::TraversalError(
+                        "Expected nodes to traverse from, got an edge".to_string(),
+                    ))))
+                }
+                Err(err) => return Box::new(iter::once(Err(err))),
+            };
+            let (db, prefix) = match outgoing {
+                true => (&storage.out_edges_db, HelixGraphStorage::out_edge_key(&node.id, "")),
+                false => (&storage.in_edges_db, HelixGraphStorage::in_edge_key(&node.id, "")),
+            };
+            let entries = match db.prefix_iter(txn, &prefix) {
+                Ok(entries) => entries,
+                Err(err) => return Box::new(iter::once(Err(GraphError::from(err)))),
+            };
+            let edge_label = edge_label.clone();
+            Box::new(entries.filter_map(move |result| {
+                result
+                    .map_err(GraphError::from)
+                    .and_then(|(key, value)| {
+                        neighbour(storage, txn, &key[prefix.len()..], value, &edge_label, edges)
+                    })
+                    .transpose()
+            }))
+        });
+        Self {
+            storage,
+            txn,
+            iter: Box::new(iter),
+        }
+    }
+
+    fn edge_endpoint(self, from: bool) -> Self {
+        let Self { storage, txn, iter } = self;
+        let iter = iter.map(move |item| match item? {
+            TraversalItem::Edge(edge) => {
+                let node_id = if from { &edge.from_node } else { &edge.to_node };
+                storage.get_node(txn, node_id).map(TraversalItem::Node)
+            }
+            TraversalItem::Node(_) => Err(GraphError::TraversalError(
+                "Expected edges, got a node".to_string(),
+            )),
+        });
+        Self {
+            storage,
+            txn,
+            iter: Box::new(iter),
+        }
+    }
+}
+
+impl Iterator for LazyTraversal<'_, '_> {
+    type Item = Result<TraversalItem, GraphError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+}
+
+/// Resolves one adjacency entry, `None` if the edge does not have the wanted label
+fn neighbour(
+    storage: &HelixGraphStorage,
+    txn: &RoTxn,
+    other_id: &[u8],
+    edge_id: &[u8],
+    edge_label: &str,
+    edges: bool,
+) -> Result<Option<TraversalItem>, GraphError> {
+    let edge_id = std::str::from_utf8(edge_id)?;
+    if !edge_label.is_empty()
+        && storage
+            .edge_labels_db
+            .get(txn, &HelixGraphStorage::edge_label_key(edge_label, edge_id))?
+            .is_none()
+    {
+        return Ok(None);
+    }
+    if edges {
+        return Ok(Some(TraversalItem::Edge(storage.get_edge(txn, edge_id)?)));
+    }
+    let other_id = std::str::from_utf8(other_id)?;
+    Ok(Some(TraversalItem::Node(storage.get_node(txn, other_id)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_engine::graph_core::{config::Config, profile::measure_reads};
+    use crate::props;
+    use crate::protocol::value::Value;
+    use tempfile::TempDir;
+
+    fn setup_temp_db() -> (HelixGraphStorage, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+        let storage = HelixGraphStorage::new(db_path, Config::default()).unwrap();
+        (storage, temp_dir)
+    }
+
+    /// `users` users each following the next `follows` users, plus one post per user
+    fn create_graph(storage: &HelixGraphStorage, users: usize, follows: usize) -> Vec<String> {
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let ids: Vec<String> = (0..users)
+            .map(|i| {
+                storage
+                    .create_node(&mut txn, "User", props! { "index" => i as i32 }, None)
+                    .unwrap()
+                    .id
+            })
+            .collect();
+        for (i, id) in ids.iter().enumerate() {
+            let post = storage.create_node(&mut txn, "Post", props!(), None).unwrap();
+            storage
+                .create_edge(&mut txn, "Posted", id, &post.id, props!())
+                .unwrap();
+            for j in 1..=follows {
+                storage
+                    .create_edge(&mut txn, "Follows", id, &ids[(i + j) % users], props!())
+                    .unwrap();
+            }
+        }
+        txn.commit().unwrap();
+        ids
+    }
+
+    #[test]
+    fn test_out_with_range() {
+        let (storage, _temp_dir) = setup_temp_db();
+        create_graph(&storage, 20, 3);
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let result = LazyTraversal::v_from_types(&storage, &txn, &["User"])
+            .out("Follows")
+            .range(0, 10)
+            .collect()
+            .unwrap();
+        match result {
+            TraversalValue::NodeArray(nodes) => {
+                assert_eq!(nodes.len(), 10);
+                assert!(nodes.iter().all(|node| node.label == "User"));
+            }
+            value => panic!("Expected nodes, got {:?}", value),
+        }
+
+        let posts = LazyTraversal::v_from_types(&storage, &txn, &["User"])
+            .out("Posted")
+            .count()
+            .unwrap();
+        assert_eq!(posts, 20);
+        assert_eq!(LazyTraversal::v(&storage, &txn).count().unwrap(), 40);
+    }
+
+    #[test]
+    fn test_range_only_reads_what_it_needs() {
+        let (storage, _temp_dir) = setup_temp_db();
+        create_graph(&storage, 20, 3);
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let visited = std::cell::Cell::new(0);
+        let nodes = LazyTraversal::v_from_types(&storage, &txn, &["User"])
+            .filter_nodes(|_| {
+                visited.set(visited.get() + 1);
+                Ok(true)
+            })
+            .out("Follows")
+            .range(0, 4)
+            .count()
+            .unwrap();
+
+        assert_eq!(nodes, 4);
+        // every user follows three others, so two users are enough for four results
+        assert_eq!(visited.get(), 2);
+    }
+
+    #[test]
+    fn test_edges_and_endpoints() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let ids = create_graph(&storage, 5, 2);
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let followers = LazyTraversal::v_from_id(&storage, &txn, &ids[0])
+            .in_e("Follows")
+            .out_v()
+            .filter_nodes(|node| {
+                Ok(matches!(node.properties.get("index"), Some(Value::Integer(i)) if *i >= 3))
+            })
+            .count()
+            .unwrap();
+        assert_eq!(followers, 2);
+
+        assert!(LazyTraversal::v_from_id(&storage, &txn, &ids[0])
+            .out("Posted")
+            .exists()
+            .unwrap());
+        assert!(!LazyTraversal::v_from_id(&storage, &txn, &ids[0])
+            .out("Likes")
+            .exists()
+            .unwrap());
+    }
+
+    #[test]
+    fn test_scans_record_reads() {
+        let (storage, _temp_dir) = setup_temp_db();
+        create_graph(&storage, 5, 2);
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let (_, lazy) = measure_reads(|| LazyTraversal::v(&storage, &txn).count().unwrap());
+        let (_, eager) = measure_reads(|| storage.get_all_nodes(&txn).unwrap());
+        assert_eq!(lazy, eager);
+        let (_, lazy) = measure_reads(|| LazyTraversal::e(&storage, &txn).count().unwrap());
+        let (_, eager) = measure_reads(|| storage.get_all_edges(&txn).unwrap());
+        assert_eq!(lazy, eager);
+    }
+
+    #[test]
+    fn test_errors_are_returned() {
+        let (storage, _temp_dir) = setup_temp_db();
+        create_graph(&storage, 3, 1);
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        assert!(LazyTraversal::v_from_id(&storage, &txn, "missing")
+            .out("")
+            .collect()
+            .is_err());
+        assert!(LazyTraversal::e(&storage, &txn).out("").count().is_err());
+    }
+
+    #[test]
+    fn test_within_budget() {
+        use crate::helix_engine::graph_core::limits::{CancelToken, QueryLimits};
+
+        let (storage, _temp_dir) = setup_temp_db();
+        create_graph(&storage, 20, 3);
+
+        let txn = storage.graph_env.read_txn().unwrap();
+        let cancel = CancelToken::default();
+        let budget = QueryBudget::new(&QueryLimits::default().max_items(30), &cancel);
+        let result = LazyTraversal::v_from_types(&storage, &txn, &["User"])
+            .out("Follows")
+            .out("Follows")
+            .within(&budget)
+            .collect();
+        assert!(matches!(result, Err(GraphError::LimitExceeded(_))));
+
+        let within_limit = LazyTraversal::v_from_types(&storage, &txn, &["User"])
+            .out("Follows")
+            .range(0, 30)
+            .within(&budget)
+            .count()
+            .unwrap();
+        assert_eq!(within_limit, 30);
+
+        cancel.cancel();
+        let result = LazyTraversal::v(&storage, &txn).within(&budget).count();
+        assert!(matches!(result, Err(GraphError::Cancelled)));
+    }
+}
@@ -5,7 +5,10 @@ use crate::{helix_engine::vector_core::vector::HVector, protocol::{
 
 use crate::helix_engine::types::GraphError;
 
-use super::traversal::TransactionCommit;
+use super::{
+    aggregate::{Aggregation, Order},
+    algorithms::link_prediction::LinkPredictionMetric, pattern::Pattern, traversal::TransactionCommit,
+};
 
 pub trait SourceTraversalSteps {
     /// Adds all nodes in the graph to current traversal step
@@ -35,6 +38,10 @@ pub trait SourceTraversalSteps {
         value: &Value,
     ) -> &mut Self;
 
+    /// Binds the pattern against the graph, replacing the current traversal step
+    /// with one row of bindings per match
+    fn match_pattern(&mut self, txn: &RoTxn, pattern: &Pattern) -> &mut Self;
+
     /// Creates a new node in the graph and adds it to current traversal step
     fn add_v(
         &mut self,
@@ -52,6 +59,27 @@ pub trait SourceTraversalSteps {
         to_id: &str,
         props: Vec<(String, Value)>,
     ) -> &mut Self;
+    /// Finds the node with a given label that matches all of `match_props`, or creates it,
+    /// and adds it to current traversal step
+    fn upsert_v(
+        &mut self,
+        txn: &mut RwTxn,
+        node_label: &str,
+        match_props: Vec<(String, Value)>,
+        on_create: Vec<(String, Value)>,
+        on_match: Vec<(String, Value)>,
+    ) -> &mut Self;
+    /// Finds the edge with a given label between two nodes, or creates it,
+    /// and adds it to current traversal step
+    fn upsert_e(
+        &mut self,
+        txn: &mut RwTxn,
+        edge_label: &str,
+        from_id: &str,
+        to_id: &str,
+        on_create: Vec<(String, Value)>,
+        on_match: Vec<(String, Value)>,
+    ) -> &mut Self;
 }
 
 pub trait TraversalSteps {
@@ -118,6 +146,9 @@ pub trait TraversalMethods {
 
     /// Filters the current traversal step
     ///
+    /// The predicate gets the transaction to read through, which is the worker's own read
+    /// transaction when the step fans out (see `TraversalBuilder::parallelism`).
+    ///
     /// ### Returns:
     /// - The traversal builder with the current step overwritten with the remaining values
     ///
@@ -173,7 +204,7 @@ pub trait TraversalMethods {
     ///
     /// // Example With Closure
     /// let mut traversal = TraversalBuilder::new(Arc::clone(&engine.storage), TraversalValue::Empty);
-    /// let test_with_closure = traversal.v(&txn).filter_nodes(&txn, |val| {
+    /// let test_with_closure = traversal.v(&txn).filter_nodes(&txn, |_, val| {
     ///     if let Some(value) = val.check_property("age") {
     ///         match value {
     ///             Value::Float(age) => Ok(*age > 25.0),
@@ -192,7 +223,7 @@ pub trait TraversalMethods {
     ///    
     /// // Example passing function that takes input
     /// let mut traversal = TraversalBuilder::new(Arc::clone(&engine.storage), TraversalValue::Empty);
-    /// let test_calling_function_with_inputs = traversal.v(&txn).filter_nodes(&txn, |node| age_greater_than(node, 30)).count();
+    /// let test_calling_function_with_inputs = traversal.v(&txn).filter_nodes(&txn, |_, node| age_greater_than(node, 30)).count();
     /// if let TraversalValue::Count(count) = &test_calling_function_with_inputs.current_step {
     ///     assert_eq!(count.value(), 1, "W input");
     /// } else {
@@ -201,7 +232,7 @@ pub trait TraversalMethods {
     ///  
     /// // Example passing function that takes NO input
     /// let mut traversal = TraversalBuilder::new(Arc::clone(&engine.storage), TraversalValue::Empty);
-    /// let test_calling_function_without_inputs = traversal.v(&txn).filter_nodes(&txn, has_name).count();
+    /// let test_calling_function_without_inputs = traversal.v(&txn).filter_nodes(&txn, |_, node| has_name(node)).count();
     /// if let TraversalValue::Count(count) = &test_calling_function_without_inputs.current_step {
     ///     assert_eq!(count.value(), 2, "WO input");
     /// } else {
@@ -212,8 +243,8 @@ pub trait TraversalMethods {
     /// // Example of chained traversal
     /// let mut traversal = TraversalBuilder::new(Arc::clone(&engine.storage), TraversalValue::Empty);
     /// let test_chained_traversal = traversal.v(&txn)
-    ///     .filter_nodes(&txn, has_name)
-    ///     .filter_nodes(&txn, |val| age_greater_than(val, 27)).count();
+    ///     .filter_nodes(&txn, |_, node| has_name(node))
+    ///     .filter_nodes(&txn, |_, val| age_greater_than(val, 27)).count();
     /// if let TraversalValue::Count(count) = &test_chained_traversal.current_step {
     ///     assert_eq!(count.value(), 1, "Chained");
     /// } else {
@@ -225,11 +256,11 @@ pub trait TraversalMethods {
     /// ```
     fn filter_nodes<F>(&mut self, txn: &RoTxn, predicate: F) -> &mut Self
     where
-        F: Fn(&Node) -> Result<bool, GraphError>;
+        F: Fn(&RoTxn, &Node) -> Result<bool, GraphError> + Sync;
 
     fn filter_edges<F>(&mut self, txn: &RoTxn, predicate: F) -> &mut Self
     where
-        F: Fn(&Edge) -> Result<bool, GraphError>;
+        F: Fn(&RoTxn, &Edge) -> Result<bool, GraphError> + Sync;
 
     /// Maps the current traversal step to a new traversal step
     fn get_properties(&mut self, txn: &RoTxn, keys: &Vec<String>) -> &mut Self;
@@ -255,6 +286,54 @@ pub trait TraversalMethods {
     fn for_each_edge<F>(&mut self, txn: &RoTxn, map_fn: F) -> &mut Self
     where
         F: Fn(&Edge) -> Result<(), GraphError>;
+
+    /// Starts recording the nodes and edges each current item goes through.
+    /// Only the graph steps and filters after this call are recorded.
+    fn track_paths(&mut self) -> &mut Self;
+
+    /// Replaces the current step with the recorded route of every item as
+    /// `TraversalValue::Paths`, in the same shape `shortest_path` returns
+    fn path(&mut self) -> &mut Self;
+
+    /// Labels each current node or edge with `name` so it can be returned by `select`
+    /// further down the traversal. Labels follow the items through the graph steps and
+    /// filters; `count`, `order_by`, `dedup` and `group_by` drop them, as they do paths.
+    fn as_(&mut self, name: &str) -> &mut Self;
+
+    /// Replaces the current step with one row per item holding the items labelled
+    /// with the given names along the way
+    fn select(&mut self, names: &[&str]) -> &mut Self;
+
+    /// Runs every branch from each current item and merges the results, which must all be
+    /// nodes or all be edges
+    fn union(&mut self, branches: &[&dyn Fn(&mut Self)]) -> &mut Self;
+
+    /// Runs the branch from each current item, keeping the item itself where the
+    /// branch produces nothing
+    fn optional<F>(&mut self, branch: F) -> &mut Self
+    where
+        F: Fn(&mut Self);
+
+    /// For each current item, takes the results of the first branch that produces anything
+    fn coalesce(&mut self, branches: &[&dyn Fn(&mut Self)]) -> &mut Self;
+
+    /// Sorts the current nodes or edges by each `(property, order)` key in turn.
+    /// Items missing a property are placed last.
+    fn order_by(&mut self, keys: &[(&str, Order)]) -> &mut Self;
+
+    /// Removes repeated nodes or edges, keeping the first occurrence of each id
+    fn dedup(&mut self) -> &mut Self;
+
+    /// Keeps the first node or edge for each distinct value of the property
+    fn dedup_by(&mut self, property: &str) -> &mut Self;
+
+    /// Groups the current nodes or edges by a property and computes the aggregations for
+    /// each group, e.g. `group_by("category", &[Aggregation::Count])` gives
+    /// `[{ "group": { "category": "books" }, "aggregates": { "count": 12 } }, ...]`
+    fn group_by(&mut self, property: &str, aggregations: &[Aggregation]) -> &mut Self;
+
+    /// Computes the aggregations over the whole current step as a single group
+    fn aggregate(&mut self, aggregations: &[Aggregation]) -> &mut Self;
 }
 
 pub trait TraversalBuilderMethods {
@@ -287,6 +366,47 @@ pub trait TraversalSearchMethods {
     fn shortest_mutual_path_from(&mut self, txn: &RoTxn, from_id: &str) -> &mut Self;
 
     fn shortest_mutual_path_to(&mut self, txn: &RoTxn, to_id: &str) -> &mut Self;
+
+    /// Replaces the current nodes with their top `limit` predicted new neighbours
+    /// over edges with the given label, scored by `metric`.
+    /// The score of each prediction is stored in its `link_score` property.
+    fn predict_links(
+        &mut self,
+        txn: &RoTxn,
+        edge_label: &str,
+        metric: LinkPredictionMetric,
+        limit: usize,
+    ) -> &mut Self;
+
+    /// Replaces the current nodes with the `limit` nodes whose structural embedding is most
+    /// similar to theirs, see `EmbeddingMethods::embed_nodes`.
+    /// The similarity of each node is stored in its `similarity` property.
+    fn similar_nodes(&mut self, txn: &RoTxn, limit: usize) -> &mut Self;
+
+    /// Replaces the current nodes with every node they depend on (transitively)
+    /// over edges with the given label
+    fn ancestors(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self;
+
+    /// Replaces the current nodes with every node depending on them (transitively)
+    /// over edges with the given label
+    fn descendants(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self;
+
+    /// Orders the current nodes so that every node comes before the nodes it points to
+    /// over edges with the given label. Nodes without such edges come first.
+    fn topological_sort(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self;
+
+    /// Replaces the current nodes with a cycle reachable from them over edges with the given label
+    /// as a single path, or with nothing if there is none
+    fn find_cycle(&mut self, txn: &RoTxn, edge_label: &str) -> &mut Self;
+
+    /// Replaces the current traversal step with the heaviest path through the DAG formed by
+    /// edges with the given label, weighting edges by `weight_property` if given
+    fn critical_path(
+        &mut self,
+        txn: &RoTxn,
+        edge_label: &str,
+        weight_property: Option<&str>,
+    ) -> &mut Self;
 }
 
 pub trait VectorTraversalSteps {
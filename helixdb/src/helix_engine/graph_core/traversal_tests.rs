@@ -1,4 +1,6 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
+
+use heed3::RwTxn;
 
 use crate::helix_engine::{
     graph_core::traversal_steps::{
@@ -11,12 +13,18 @@ use crate::props;
 use crate::protocol::{
     filterable::Filterable,
     items::{Edge, Node},
-    traversal_value::TraversalValue,
+    traversal_value::{Binding, TraversalValue},
     value::Value,
 };
 use tempfile::TempDir;
 
-use super::{traversal::TraversalBuilder, traversal_steps::{TraversalMethods, TraversalSteps}};
+use super::{
+    aggregate::{Aggregation, Order, AGGREGATES_KEY, GROUP_KEY},
+    limits::{CancelToken, QueryBudget, QueryLimits},
+    profile::measure_reads,
+    traversal::TraversalBuilder,
+    traversal_steps::{TraversalMethods, TraversalSteps},
+};
 
 fn setup_test_db() -> (Arc<HelixGraphStorage>, TempDir) {
     let temp_dir = TempDir::new().unwrap();
@@ -878,7 +886,7 @@ fn test_filter_nodes() {
     traversal.v(&txn);
 
     // Filter nodes with age > 30
-    traversal.filter_nodes(&txn, |val| {
+    traversal.filter_nodes(&txn, |_, val| {
         if let Some(value) = val.check_property("age") {
             match value {
                 Value::Float(age) => Ok(*age > 30.0),
@@ -924,7 +932,7 @@ fn test_filter_macro_single_argument() {
     txn.commit().unwrap();
     let txn = storage.graph_env.read_txn().unwrap();
     let mut traversal = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
-    traversal.v(&txn).filter_nodes(&txn, has_name);
+    traversal.v(&txn).filter_nodes(&txn, |_, node| has_name(node));
 
     if let TraversalValue::Count(count) = &traversal.count().current_step {
         assert_eq!(count.value(), 2);
@@ -961,7 +969,7 @@ fn test_filter_macro_multiple_arguments() {
     let txn = storage.graph_env.read_txn().unwrap();
     let mut traversal = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
     traversal.v(&txn);
-    traversal.filter_nodes(&txn, |node| age_greater_than(node, 27));
+    traversal.filter_nodes(&txn, |_, node| age_greater_than(node, 27));
 
     match &traversal.current_step {
         TraversalValue::NodeArray(nodes) => {
@@ -1025,7 +1033,7 @@ fn test_filter_edges() {
         Err(GraphError::TraversalError("Invalid edge".to_string()))
     }
 
-    traversal.filter_edges(&txn, |edge| recent_edge(edge, 2021));
+    traversal.filter_edges(&txn, |_, edge| recent_edge(edge, 2021));
 
     match &traversal.current_step {
         // TraversalValue::SingleEdge(edge) => {
@@ -1060,7 +1068,7 @@ fn test_filter_empty_result() {
     traversal.v(&txn);
 
     // Filter with a condition that no nodes satisfy
-    traversal.filter_nodes(&txn, |val| {
+    traversal.filter_nodes(&txn, |_, val| {
         if let Some(value) = val.check_property("age") {
             match value {
                 Value::Integer(age) => return Ok(*age > 100),
@@ -1130,8 +1138,8 @@ fn test_filter_chain() {
     }
 
     traversal
-        .filter_nodes(&txn, has_name)
-        .filter_nodes(&txn, |val| age_greater_than(val, 27));
+        .filter_nodes(&txn, |_, node| has_name(node))
+        .filter_nodes(&txn, |_, val| age_greater_than(val, 27));
 
     match &traversal.current_step {
         TraversalValue::NodeArray(nodes) => {
@@ -1389,3 +1397,525 @@ fn test_shortest_mutual_path() {
     assert_eq!(nodes[2].id, users[1].id); // Bob
     assert_eq!(nodes[3].id, users[0].id); // Alice
 }
+
+#[test]
+fn test_order_dedup_and_group_by() {
+    let (storage, _temp_dir) = setup_test_db();
+    let mut txn = storage.graph_env.write_txn().unwrap();
+
+    let players = [("alice", "red", 30), ("bob", "blue", 50), ("carol", "red", 40), ("dave", "blue", 10)];
+    for (name, team, score) in players {
+        storage
+            .create_node(
+                &mut txn,
+                "player",
+                props! { "name" => name, "team" => team, "score" => score },
+                None,
+            )
+            .unwrap();
+    }
+    txn.commit().unwrap();
+
+    let txn = storage.graph_env.read_txn().unwrap();
+    let mut traversal = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
+    traversal
+        .v(&txn)
+        .order_by(&[("score", Order::Desc)])
+        .dedup_by("team");
+    match &traversal.current_step {
+        TraversalValue::NodeArray(nodes) => {
+            let names: Vec<&Value> =
+                nodes.iter().map(|n| n.check_property("name").unwrap()).collect();
+            assert_eq!(names, vec![&Value::from("bob"), &Value::from("carol")]);
+        }
+        _ => panic!("Expected NodeArray value"),
+    }
+
+    let mut traversal = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
+    traversal
+        .v(&txn)
+        .order_by(&[("team", Order::Asc)])
+        .group_by("team", &[Aggregation::Count, Aggregation::Sum("score".to_string())]);
+    match &traversal.current_step {
+        TraversalValue::Groups(groups) => {
+            assert_eq!(groups.len(), 2);
+            let field = |group: &HashMap<String, Value>, key: &str, name: &str| match &group[key] {
+                Value::Object(values) => values[name].clone(),
+                _ => panic!("Expected an object under {}", key),
+            };
+            assert_eq!(field(&groups[0], GROUP_KEY, "team"), Value::from("blue"));
+            assert_eq!(field(&groups[0], AGGREGATES_KEY, "count"), Value::Integer(2));
+            assert_eq!(field(&groups[0], AGGREGATES_KEY, "sum_score"), Value::Integer(60));
+            assert_eq!(field(&groups[1], AGGREGATES_KEY, "sum_score"), Value::Integer(70));
+        }
+        _ => panic!("Expected Groups value"),
+    }
+
+    let nodes = storage.get_all_nodes(&txn).unwrap();
+    let repeated = nodes.iter().chain(nodes.iter()).cloned().collect();
+    let mut traversal =
+        TraversalBuilder::new(Arc::clone(&storage), TraversalValue::NodeArray(repeated));
+    traversal.dedup().count();
+    assert_eq!(traversal.current_step.as_count().unwrap().value(), 4);
+}
+
+#[test]
+fn test_labels_and_branches() {
+    let (storage, _temp_dir) = setup_test_db();
+    let mut txn = storage.graph_env.write_txn().unwrap();
+
+    let alice = storage
+        .create_node(&mut txn, "user", props! { "name" => "alice" }, None)
+        .unwrap();
+    let bob = storage
+        .create_node(&mut txn, "user", props! { "name" => "bob" }, None)
+        .unwrap();
+    let lead = storage
+        .create_node(&mut txn, "user", props! { "name" => "lead" }, None)
+        .unwrap();
+    let order = storage
+        .create_node(&mut txn, "order", props!(), None)
+        .unwrap();
+    let book = storage
+        .create_node(&mut txn, "item", props! { "title" => "book" }, None)
+        .unwrap();
+    let pen = storage
+        .create_node(&mut txn, "item", props! { "title" => "pen" }, None)
+        .unwrap();
+    storage
+        .create_edge(&mut txn, "ordered", &alice.id, &order.id, props!())
+        .unwrap();
+    storage
+        .create_edge(&mut txn, "contains", &order.id, &book.id, props!())
+        .unwrap();
+    storage
+        .create_edge(&mut txn, "contains", &order.id, &pen.id, props!())
+        .unwrap();
+    storage
+        .create_edge(&mut txn, "team_lead", &bob.id, &lead.id, props!())
+        .unwrap();
+    txn.commit().unwrap();
+
+    let txn = storage.graph_env.read_txn().unwrap();
+    let mut traversal = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
+    traversal
+        .v_from_types(&txn, &["user"])
+        .as_("u")
+        .out(&txn, "ordered")
+        .out(&txn, "contains")
+        .as_("item")
+        .select(&["u", "item"]);
+    match &traversal.current_step {
+        TraversalValue::Bindings(rows) => {
+            assert_eq!(rows.len(), 2);
+            assert!(rows.iter().all(|row| row["u"] == Binding::Node(alice.clone())));
+            let items: Vec<&Binding> = rows.iter().map(|row| &row["item"]).collect();
+            assert!(items.contains(&&Binding::Node(book.clone())));
+            assert!(items.contains(&&Binding::Node(pen.clone())));
+        }
+        _ => panic!("Expected Bindings value"),
+    }
+
+    // manager if present, otherwise the team lead
+    let mut traversal =
+        TraversalBuilder::new(Arc::clone(&storage), TraversalValue::from(bob.clone()));
+    traversal.coalesce(&[
+        &|tr: &mut TraversalBuilder| {
+            tr.out(&txn, "manager");
+        },
+        &|tr: &mut TraversalBuilder| {
+            tr.out(&txn, "team_lead");
+        },
+    ]);
+    match &traversal.current_step {
+        TraversalValue::NodeArray(nodes) => assert_eq!(nodes, &vec![lead.clone()]),
+        _ => panic!("Expected NodeArray value"),
+    }
+
+    let mut traversal = TraversalBuilder::new(
+        Arc::clone(&storage),
+        TraversalValue::NodeArray(vec![alice.clone(), bob.clone()]),
+    );
+    traversal.optional(|tr| {
+        tr.out(&txn, "team_lead");
+    });
+    match &traversal.current_step {
+        TraversalValue::NodeArray(nodes) => assert_eq!(nodes, &vec![alice.clone(), lead.clone()]),
+        _ => panic!("Expected NodeArray value"),
+    }
+
+    let mut traversal =
+        TraversalBuilder::new(Arc::clone(&storage), TraversalValue::from(order.clone()));
+    traversal
+        .union(&[
+            &|tr: &mut TraversalBuilder| {
+                tr.out(&txn, "contains");
+            },
+            &|tr: &mut TraversalBuilder| {
+                tr.in_(&txn, "ordered");
+            },
+        ])
+        .count();
+    assert_eq!(traversal.current_step.as_count().unwrap().value(), 3);
+
+    let mut traversal =
+        TraversalBuilder::new(Arc::clone(&storage), TraversalValue::from(order.clone()));
+    traversal.union(&[
+        &|tr: &mut TraversalBuilder| {
+            tr.out(&txn, "contains");
+        },
+        &|tr: &mut TraversalBuilder| {
+            tr.out_e(&txn, "contains");
+        },
+    ]);
+    assert_eq!(failed_step(&traversal), "union");
+    assert!(matches!(traversal.current_step, TraversalValue::Empty));
+}
+
+#[test]
+fn test_filter_labelled_items() {
+    let (storage, _temp_dir) = setup_test_db();
+    let mut txn = storage.graph_env.write_txn().unwrap();
+
+    let alice = storage
+        .create_node(&mut txn, "user", props! { "name" => "alice" }, None)
+        .unwrap();
+    let bob = storage
+        .create_node(&mut txn, "user", props! { "name" => "bob" }, None)
+        .unwrap();
+    let lead = storage
+        .create_node(&mut txn, "user", props! { "name" => "lead" }, None)
+        .unwrap();
+    storage
+        .create_edge(&mut txn, "team_lead", &alice.id, &lead.id, props! { "since" => 2020 })
+        .unwrap();
+    let edge = storage
+        .create_edge(&mut txn, "team_lead", &bob.id, &lead.id, props! { "since" => 2022 })
+        .unwrap();
+    txn.commit().unwrap();
+
+    let txn = storage.graph_env.read_txn().unwrap();
+    let mut traversal = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
+    traversal
+        .v_from_types(&txn, &["user"])
+        .as_("u")
+        .filter_nodes(&txn, |_, node| {
+            Ok(node.check_property("name") != Some(&Value::from("lead")))
+        })
+        .out_e(&txn, "team_lead")
+        .filter_edges(&txn, |_, edge| {
+            Ok(edge.check_property("since") == Some(&Value::from(2022)))
+        })
+        .as_("e")
+        .select(&["u", "e"]);
+    match &traversal.current_step {
+        TraversalValue::Bindings(rows) => {
+            assert_eq!(rows.len(), 1);
+            assert_eq!(rows[0]["u"], Binding::Node(bob.clone()));
+            assert_eq!(rows[0]["e"], Binding::Edge(edge.clone()));
+        }
+        _ => panic!("Expected Bindings value"),
+    }
+}
+
+#[test]
+fn test_path_tracking() {
+    let (storage, _temp_dir) = setup_test_db();
+    let mut txn = storage.graph_env.write_txn().unwrap();
+
+    let alice = storage
+        .create_node(&mut txn, "user", props! { "name" => "alice" }, None)
+        .unwrap();
+    let bob = storage
+        .create_node(&mut txn, "user", props! { "name" => "bob" }, None)
+        .unwrap();
+    let book = storage
+        .create_node(&mut txn, "product", props! { "name" => "book" }, None)
+        .unwrap();
+    let pen = storage
+        .create_node(&mut txn, "product", props! { "name" => "pen" }, None)
+        .unwrap();
+    let alice_book = storage
+        .create_edge(&mut txn, "bought", &alice.id, &book.id, props! { "quantity" => 1 })
+        .unwrap();
+    let bob_book = storage
+        .create_edge(&mut txn, "bought", &bob.id, &book.id, props! { "quantity" => 2 })
+        .unwrap();
+    let bob_pen = storage
+        .create_edge(&mut txn, "bought", &bob.id, &pen.id, props! { "quantity" => 3 })
+        .unwrap();
+    txn.commit().unwrap();
+
+    let txn = storage.graph_env.read_txn().unwrap();
+    let mut traversal =
+        TraversalBuilder::new(Arc::clone(&storage), TraversalValue::from(alice.clone()));
+    traversal
+        .track_paths()
+        .out(&txn, "bought")
+        .in_(&txn, "bought")
+        .filter_nodes(&txn, |_, node| Ok(node.id != alice.id))
+        .out(&txn, "bought")
+        .filter_nodes(&txn, |_, node| Ok(node.id != book.id))
+        .path();
+
+    match &traversal.current_step {
+        TraversalValue::Paths(paths) => {
+            assert_eq!(paths.len(), 1);
+            let (nodes, edges) = &paths[0];
+            assert_eq!(nodes, &vec![alice.clone(), book.clone(), bob.clone(), pen.clone()]);
+            assert_eq!(edges, &vec![alice_book, bob_book, bob_pen.clone()]);
+            assert_eq!(edges[2].properties.get("quantity"), Some(&Value::Integer(3)));
+        }
+        _ => panic!("Expected Paths value"),
+    }
+
+    let mut traversal =
+        TraversalBuilder::new(Arc::clone(&storage), TraversalValue::from(bob.clone()));
+    traversal
+        .track_paths()
+        .out_e(&txn, "bought")
+        .filter_edges(&txn, |_, edge| {
+            Ok(edge.check_property("quantity") == Some(&Value::Integer(3)))
+        })
+        .in_v(&txn)
+        .path();
+
+    match &traversal.current_step {
+        TraversalValue::Paths(paths) => {
+            assert_eq!(paths.len(), 1);
+            let (nodes, edges) = &paths[0];
+            assert_eq!(nodes, &vec![bob.clone(), pen.clone()]);
+            assert_eq!(edges, &vec![bob_pen.clone()]);
+        }
+        _ => panic!("Expected Paths value"),
+    }
+
+    let mut traversal = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
+    traversal.v(&txn).path();
+    assert!(traversal.error.is_some());
+}
+
+/// Every `TraversalSteps` method
+const TRAVERSAL_STEPS: [&str; 13] = [
+    "out", "out_e", "in_", "in_e", "in_v", "out_v", "both", "both_e", "mutual", "both_v",
+    "add_e_to", "add_e_from", "update_props",
+];
+
+fn run_traversal_step(traversal: &mut TraversalBuilder, txn: &mut RwTxn, step: &str) {
+    match step {
+        "out" => traversal.out(txn, "knows"),
+        "out_e" => traversal.out_e(txn, "knows"),
+        "in_" => traversal.in_(txn, "knows"),
+        "in_e" => traversal.in_e(txn, "knows"),
+        "in_v" => traversal.in_v(txn),
+        "out_v" => traversal.out_v(txn),
+        "both" => traversal.both(txn, "knows"),
+        "both_e" => traversal.both_e(txn, "knows"),
+        "mutual" => traversal.mutual(txn, "knows"),
+        "both_v" => traversal.both_v(txn),
+        "add_e_to" => traversal.add_e_to(txn, "knows", "missing", props!()),
+        "add_e_from" => traversal.add_e_from(txn, "knows", "missing", props!()),
+        "update_props" => traversal.update_props(txn, props! { "age" => 1 }),
+        _ => panic!("Unknown step {}", step),
+    };
+}
+
+/// `in_v`, `out_v` and `both_v` start from edges, every other step from nodes
+fn step_start(step: &str, nodes: &TraversalValue, edges: &TraversalValue) -> TraversalValue {
+    match step {
+        "in_v" | "out_v" | "both_v" => edges.clone(),
+        _ => nodes.clone(),
+    }
+}
+
+fn failed_step(traversal: &TraversalBuilder) -> &'static str {
+    match &traversal.error {
+        Some(GraphError::StepFailed { step, .. }) => *step,
+        other => panic!("Expected StepFailed error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_steps_short_circuit_after_error() {
+    let (storage, _temp_dir) = setup_test_db();
+    let mut txn = storage.graph_env.write_txn().unwrap();
+    let alice = storage
+        .create_node(&mut txn, "person", props!(), None)
+        .unwrap();
+    let bob = storage
+        .create_node(&mut txn, "person", props!(), None)
+        .unwrap();
+    let knows = storage
+        .create_edge(&mut txn, "knows", &alice.id, &bob.id, props!())
+        .unwrap();
+
+    let nodes = TraversalValue::NodeArray(vec![alice.clone()]);
+    let edges = TraversalValue::EdgeArray(vec![knows]);
+    for step in TRAVERSAL_STEPS {
+        let start = step_start(step, &nodes, &edges);
+        let mut traversal = TraversalBuilder::new(Arc::clone(&storage), start.clone());
+        traversal.range(-1, 1);
+        assert_eq!(failed_step(&traversal), "range");
+
+        traversal.current_step = start.clone();
+        run_traversal_step(&mut traversal, &mut txn, step);
+        assert_eq!(failed_step(&traversal), "range", "{} ran after an error", step);
+        assert_eq!(traversal.current_step, start, "{} changed the current step", step);
+    }
+
+    // none of the skipped writes reached the graph
+    assert_eq!(storage.get_all_edges(&txn).unwrap().len(), 1);
+    assert!(storage.get_node(&txn, &alice.id).unwrap().properties.is_empty());
+}
+
+#[test]
+fn test_step_errors_carry_step_and_arguments() {
+    let (storage, _temp_dir) = setup_test_db();
+    let mut txn = storage.graph_env.write_txn().unwrap();
+    let alice = storage
+        .create_node(&mut txn, "person", props!(), None)
+        .unwrap();
+    let bob = storage
+        .create_node(&mut txn, "person", props!(), None)
+        .unwrap();
+    for (from, to) in [(&alice, &bob), (&bob, &alice)] {
+        let edge = storage
+            .create_edge(&mut txn, "knows", &from.id, &to.id, props!())
+            .unwrap();
+        // leaves the adjacency entries pointing at an edge that no longer exists
+        storage
+            .edges_db
+            .delete(&mut txn, &HelixGraphStorage::edge_key(&edge.id))
+            .unwrap();
+    }
+
+    let missing = Node {
+        id: "missing".to_string(),
+        label: "person".to_string(),
+        properties: Default::default(),
+    };
+    let dangling = Edge {
+        id: "dangling".to_string(),
+        label: "knows".to_string(),
+        from_node: "missing".to_string(),
+        to_node: "missing".to_string(),
+        properties: Default::default(),
+    };
+    let nodes = TraversalValue::NodeArray(vec![alice.clone()]);
+    let edges = TraversalValue::EdgeArray(vec![dangling]);
+    for step in TRAVERSAL_STEPS {
+        let start = match step {
+            "update_props" => TraversalValue::NodeArray(vec![missing.clone()]),
+            _ => step_start(step, &nodes, &edges),
+        };
+        let mut traversal = TraversalBuilder::new(Arc::clone(&storage), start);
+        run_traversal_step(&mut traversal, &mut txn, step);
+        assert_eq!(failed_step(&traversal), step);
+
+        // later steps are skipped and the error is returned as is
+        traversal.count();
+        assert!(!matches!(traversal.current_step, TraversalValue::Count(_)));
+        let err = traversal.finish().unwrap_err();
+        assert!(
+            matches!(err.root_cause(), GraphError::NodeNotFound | GraphError::EdgeNotFound),
+            "{}: {:?}",
+            step,
+            err
+        );
+    }
+
+    let mut traversal = TraversalBuilder::new(Arc::clone(&storage), nodes);
+    traversal.add_e_to(&mut txn, "knows", "missing", props! { "since" => 2020 });
+    let message = traversal.finish().unwrap_err().to_string();
+    assert!(message.starts_with("Step add_e_to(\"knows\", \"missing\""), "{}", message);
+    assert!(message.contains("since"), "{}", message);
+}
+
+#[test]
+fn test_invalid_steps_do_not_panic() {
+    let (storage, _temp_dir) = setup_test_db();
+    let mut txn = storage.graph_env.write_txn().unwrap();
+    let alice = storage
+        .create_node(&mut txn, "person", props! { "age" => 30 }, None)
+        .unwrap();
+    let values = TraversalValue::ValueArray(vec![("age".to_string(), Value::Integer(30))]);
+
+    let mut traversal = TraversalBuilder::new(Arc::clone(&storage), values.clone());
+    traversal.count();
+    assert_eq!(failed_step(&traversal), "count");
+
+    let mut traversal = TraversalBuilder::new(Arc::clone(&storage), values);
+    traversal.add_e_to(&mut txn, "knows", &alice.id, props!());
+    assert_eq!(failed_step(&traversal), "add_e_to");
+
+    let mut traversal = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
+    traversal
+        .v(&txn)
+        .filter_nodes(&txn, |_, _| Err(GraphError::TraversalError("bad predicate".to_string())));
+    assert_eq!(failed_step(&traversal), "filter_nodes");
+
+    let mut traversal = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
+    traversal.v(&txn).range(0, 10);
+    assert_eq!(traversal.current_step, TraversalValue::NodeArray(vec![alice]));
+    assert!(traversal.error.is_none());
+}
+
+#[test]
+fn test_budget_is_checked_inside_steps() {
+    let (storage, _temp_dir) = setup_test_db();
+    let mut txn = storage.graph_env.write_txn().unwrap();
+    let users: Vec<Node> = (0..50)
+        .map(|i| {
+            storage
+                .create_node(&mut txn, "user", props! { "index" => i }, None)
+                .unwrap()
+        })
+        .collect();
+    for (i, user) in users.iter().enumerate() {
+        for j in 1..=10 {
+            storage
+                .create_edge(&mut txn, "follows", &user.id, &users[(i + j) % 50].id, props!())
+                .unwrap();
+        }
+    }
+    txn.commit().unwrap();
+
+    let txn = storage.graph_env.read_txn().unwrap();
+    let cancel = CancelToken::default();
+    let budget = QueryBudget::new(&QueryLimits::default().max_items(20), &cancel);
+    let out = |budget: Option<&QueryBudget>| {
+        let mut traversal =
+            TraversalBuilder::new(Arc::clone(&storage), TraversalValue::NodeArray(users.clone()));
+        if let Some(budget) = budget {
+            traversal.within(budget);
+        }
+        let (_, reads) = measure_reads(|| {
+            traversal.out(&txn, "follows");
+        });
+        (traversal, reads)
+    };
+
+    // the step goes over the budget after a few nodes, not after reading all 500 edges
+    let (unbounded, all_reads) = out(None);
+    assert!(unbounded.error.is_none());
+    let (traversal, reads) = out(Some(&budget));
+    assert_eq!(failed_step(&traversal), "out");
+    assert!(matches!(
+        traversal.error.as_ref().map(GraphError::root_cause),
+        Some(GraphError::LimitExceeded(_))
+    ));
+    assert!(reads.bytes * 5 < all_reads.bytes);
+
+    // scanning every node stops at the limit as well
+    let mut traversal = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
+    traversal.within(&budget).v(&txn);
+    assert_eq!(failed_step(&traversal), "v");
+
+    cancel.cancel();
+    let mut traversal = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
+    traversal.within(&budget).v_from_types(&txn, &["user"]);
+    assert!(matches!(
+        traversal.error.as_ref().map(GraphError::root_cause),
+        Some(GraphError::Cancelled)
+    ));
+}
@@ -1,5 +1,8 @@
 
 use crate::helix_engine::graph_core::config::Config;
+use crate::helix_engine::graph_core::limits::QueryLimits;
+use crate::helix_engine::graph_core::parallel::TraversalPool;
+use crate::helix_engine::graph_core::profile::record_read;
 use crate::helix_engine::vector_core::vector_core::{HNSWConfig, VectorCore};
 use crate::protocol::filterable::Filterable;
 
@@ -31,8 +34,8 @@ const DB_IN_EDGES: &str = "in_edges"; // For incoming edge indices (i:)
 // Key prefixes for different types of data
 pub const NODE_PREFIX: &[u8] = b"n:";
 pub const EDGE_PREFIX: &[u8] = b"e:";
-const NODE_LABEL_PREFIX: &[u8] = b"nl:";
-const EDGE_LABEL_PREFIX: &[u8] = b"el:";
+pub const NODE_LABEL_PREFIX: &[u8] = b"nl:";
+pub const EDGE_LABEL_PREFIX: &[u8] = b"el:";
 pub const OUT_EDGES_PREFIX: &[u8] = b"o:";
 pub const IN_EDGES_PREFIX: &[u8] = b"i:";
 
@@ -46,11 +49,19 @@ pub struct HelixGraphStorage {
     pub in_edges_db: Database<Bytes, Bytes>,
     pub secondary_indices: HashMap<String, Database<Bytes, Bytes>>,
     pub vectors: VectorCore,
+    /// Structural embeddings written by `embed_nodes`, apart from `vectors` since they
+    /// have a dimension of their own
+    pub node_embeddings: VectorCore,
+    pub traversal_pool: TraversalPool,
+    /// Limits every query runs under unless it sets its own
+    pub query_limits: QueryLimits,
 }
 
 impl HelixGraphStorage {
     pub fn new(path: &str, config: Config) -> Result<HelixGraphStorage, GraphError> {
         fs::create_dir_all(path)?;
+        let traversal_pool = TraversalPool::new(&config.execution_config);
+        let query_limits = QueryLimits::from_config(&config.execution_config);
 
         // Configure and open LMDB environment
         let graph_env = unsafe {
@@ -82,15 +93,14 @@ impl HelixGraphStorage {
         }
         println!("Secondary Indices: {:?}", secondary_indices);
 
-        let vectors = VectorCore::new(
-            &graph_env,
-            &mut wtxn,
-            HNSWConfig::new(
-                config.vector_config.m,
-                config.vector_config.ef_construction,
-                config.vector_config.ef_search,
-            ),
-        )?;
+        let vector_config = HNSWConfig::new(
+            config.vector_config.m,
+            config.vector_config.ef_construction,
+            config.vector_config.ef_search,
+        );
+        let vectors = VectorCore::new(&graph_env, &mut wtxn, vector_config.clone())?;
+        let node_embeddings =
+            VectorCore::named(&graph_env, &mut wtxn, vector_config, "node_embeddings")?;
 
         wtxn.commit()?;
         Ok(Self {
@@ -103,6 +113,9 @@ impl HelixGraphStorage {
             in_edges_db,
             secondary_indices,
             vectors,
+            node_embeddings,
+            traversal_pool,
+            query_limits,
         })
     }
 
@@ -133,7 +146,10 @@ impl HelixGraphStorage {
 
     pub fn get_random_node(&self, txn: &RoTxn) -> Result<Node, GraphError> {
         match self.nodes_db.first(&txn)? {
-            Some((_, data)) => Ok(bincode::deserialize(data)?),
+            Some((_, data)) => {
+                record_read(data.len());
+                Ok(bincode::deserialize(data)?)
+            }
             None => Err(GraphError::NodeNotFound),
         }
     }
@@ -180,6 +196,41 @@ impl HelixGraphStorage {
         .concat()
     }
 
+    /// The node with `label` whose properties include all of `props`, looked up through the
+    /// secondary index of one of them when there is one. Fails if that index already points
+    /// at a node that doesn't match, since a new node would take over its index entry
+    fn find_node(
+        &self,
+        txn: &RoTxn,
+        label: &str,
+        props: &[(String, Value)],
+    ) -> Result<Option<Node>, GraphError> {
+        let matches = |node: &Node| {
+            node.label == label
+                && props
+                    .iter()
+                    .all(|(key, value)| node.properties.get(key) == Some(value))
+        };
+        let indexed = props
+            .iter()
+            .find(|(key, _)| self.secondary_indices.contains_key(key));
+        match indexed {
+            Some((index, value)) => match self.get_node_by_secondary_index(txn, index, value) {
+                Ok(node) if matches(&node) => Ok(Some(node)),
+                Ok(node) => Err(GraphError::StorageError(format!(
+                    "{} {:?} is already indexed for {} node {}",
+                    index, value, node.label, node.id
+                ))),
+                Err(GraphError::NodeNotFound) => Ok(None),
+                Err(err) => Err(err),
+            },
+            None => Ok(self
+                .get_nodes_by_types(txn, &[label])?
+                .into_iter()
+                .find(matches)),
+        }
+    }
+
     pub fn create_node_(
         &self,
         txn: &mut RwTxn,
@@ -284,12 +335,14 @@ impl StorageMethods for HelixGraphStorage {
     #[inline(always)]
     fn get_node(&self, txn: &RoTxn, id: &str) -> Result<Node, GraphError> {
         let node = self.get_temp_node(txn, id)?;
+        record_read(node.len());
         Ok(bincode::deserialize(node)?)
     }
 
     #[inline(always)]
     fn get_edge(&self, txn: &RoTxn, id: &str) -> Result<Edge, GraphError> {
         let edge = self.get_temp_edge(txn, id)?;
+        record_read(edge.len());
         Ok(bincode::deserialize(edge)?)
     }
 
@@ -431,6 +484,7 @@ impl StorageMethods for HelixGraphStorage {
         for result in iter {
             let (_, value) = result?;
             if !value.is_empty() {
+                record_read(value.len());
                 let node: Node = bincode::deserialize(value)?;
                 nodes.push(node);
             }
@@ -455,7 +509,10 @@ impl StorageMethods for HelixGraphStorage {
 
                 let n: Result<Node, GraphError> =
                     match self.nodes_db.get(&txn, &Self::node_key(node_id))? {
-                        Some(data) => Ok(bincode::deserialize(data)?),
+                        Some(data) => {
+                            record_read(data.len());
+                            Ok(bincode::deserialize(data)?)
+                        }
                         None => Err(GraphError::NodeNotFound),
                     };
                 if let Ok(node) = n {
@@ -475,6 +532,7 @@ impl StorageMethods for HelixGraphStorage {
         for result in iter {
             let (_, value) = result?;
             if !value.is_empty() {
+                record_read(value.len());
                 let edge: Edge = bincode::deserialize(value)?;
                 edges.push(edge);
             }
@@ -710,6 +768,50 @@ impl StorageMethods for HelixGraphStorage {
             .put(txn, &Self::edge_key(id), &bincode::serialize(&edge)?)?;
         Ok(edge)
     }
+
+    fn upsert_node(
+        &self,
+        txn: &mut RwTxn,
+        label: &str,
+        match_props: impl IntoIterator<Item = (String, Value)>,
+        set_on_create: impl IntoIterator<Item = (String, Value)>,
+        set_on_match: impl IntoIterator<Item = (String, Value)>,
+    ) -> Result<Node, GraphError> {
+        let match_props = match_props.into_iter().collect::<Vec<_>>();
+        if let Some(node) = self.find_node(txn, label, &match_props)? {
+            return self.update_node(txn, &node.id, set_on_match);
+        }
+
+        let properties = match_props
+            .into_iter()
+            .chain(set_on_create)
+            .collect::<HashMap<_, _>>();
+        let indices = properties
+            .keys()
+            .filter(|key| self.secondary_indices.contains_key(*key))
+            .cloned()
+            .collect::<Vec<_>>();
+        self.create_node(txn, label, properties, Some(&indices))
+    }
+
+    fn upsert_edge(
+        &self,
+        txn: &mut RwTxn,
+        label: &str,
+        from_node: &str,
+        to_node: &str,
+        set_on_create: impl IntoIterator<Item = (String, Value)>,
+        set_on_match: impl IntoIterator<Item = (String, Value)>,
+    ) -> Result<Edge, GraphError> {
+        let existing = self
+            .get_out_edges(txn, from_node, label)?
+            .into_iter()
+            .find(|edge| edge.to_node == to_node);
+        match existing {
+            Some(edge) => self.update_edge(txn, &edge.id, set_on_match),
+            None => self.create_edge(txn, label, from_node, to_node, set_on_create),
+        }
+    }
 }
 
 impl SearchMethods for HelixGraphStorage {
@@ -1420,6 +1522,138 @@ mod tests {
         assert_eq!(retrieved_node.id, node.id);
     }
 
+    #[test]
+    fn test_upsert() {
+        let mut storage = setup_temp_db();
+        storage.create_secondary_index("email").unwrap();
+        let mut txn = storage.graph_env.write_txn().unwrap();
+
+        let created = storage
+            .upsert_node(
+                &mut txn,
+                "person",
+                props! { "email" => "george@helix.db" },
+                props! { "name" => "George", "visits" => 1 },
+                props! { "visits" => 2 },
+            )
+            .unwrap();
+        let matched = storage
+            .upsert_node(
+                &mut txn,
+                "person",
+                props! { "email" => "george@helix.db" },
+                props! { "name" => "Other", "visits" => 1 },
+                props! { "visits" => 2 },
+            )
+            .unwrap();
+        assert_eq!(matched.id, created.id);
+        assert_eq!(
+            matched.properties.get("name"),
+            Some(&Value::String("George".to_string()))
+        );
+        assert_eq!(matched.properties.get("visits"), Some(&Value::Integer(2)));
+
+        let other = storage
+            .upsert_node(
+                &mut txn,
+                "person",
+                props! { "email" => "john@helix.db" },
+                props! {},
+                props! {},
+            )
+            .unwrap();
+        assert_ne!(other.id, created.id);
+        assert_eq!(
+            storage
+                .get_node_by_secondary_index(
+                    &txn,
+                    "email",
+                    &Value::String("john@helix.db".to_string())
+                )
+                .unwrap()
+                .id,
+            other.id
+        );
+
+        let edge = storage
+            .upsert_edge(
+                &mut txn,
+                "knows",
+                &created.id,
+                &other.id,
+                props! { "since" => 2020 },
+                props! {},
+            )
+            .unwrap();
+        let same = storage
+            .upsert_edge(
+                &mut txn,
+                "knows",
+                &created.id,
+                &other.id,
+                props! {},
+                props! { "since" => 2021 },
+            )
+            .unwrap();
+        txn.commit().unwrap();
+
+        assert_eq!(same.id, edge.id);
+        assert_eq!(same.properties.get("since"), Some(&Value::Integer(2021)));
+        let txn = storage.graph_env.read_txn().unwrap();
+        assert_eq!(
+            storage
+                .get_out_edges(&txn, &created.id, "knows")
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            storage.get_nodes_by_types(&txn, &["person"]).unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_upsert_with_index_taken_by_another_label() {
+        let mut storage = setup_temp_db();
+        storage.create_secondary_index("email").unwrap();
+        let mut txn = storage.graph_env.write_txn().unwrap();
+
+        let company = storage
+            .upsert_node(
+                &mut txn,
+                "company",
+                props! { "email" => "info@helix.db" },
+                props! {},
+                props! {},
+            )
+            .unwrap();
+        let result = storage.upsert_node(
+            &mut txn,
+            "person",
+            props! { "email" => "info@helix.db" },
+            props! {},
+            props! {},
+        );
+        assert!(matches!(result, Err(GraphError::StorageError(_))));
+
+        assert!(storage
+            .get_nodes_by_types(&txn, &["person"])
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            storage
+                .get_node_by_secondary_index(
+                    &txn,
+                    "email",
+                    &Value::String("info@helix.db".to_string())
+                )
+                .unwrap()
+                .id,
+            company.id
+        );
+    }
+
     fn create_test_users(
         storage: &HelixGraphStorage,
         txn: &mut RwTxn,
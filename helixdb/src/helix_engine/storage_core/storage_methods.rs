@@ -124,6 +124,33 @@ pub trait StorageMethods {
         id: &str,
         properties: impl IntoIterator<Item = (String, Value)>,
     ) -> Result<Edge, GraphError>;
+
+    /// Finds the node with a given label whose properties include all of `match_props`
+    /// and sets `set_on_match` on it, or creates it with `match_props` and `set_on_create`
+    /// when there is none.
+    /// Matching reads through the secondary index of a matched property when there is one
+    /// and scans the label otherwise. Secondary indices of the created node's properties
+    /// are filled in.
+    fn upsert_node(
+        &self,
+        txn: &mut RwTxn,
+        label: &str,
+        match_props: impl IntoIterator<Item = (String, Value)>,
+        set_on_create: impl IntoIterator<Item = (String, Value)>,
+        set_on_match: impl IntoIterator<Item = (String, Value)>,
+    ) -> Result<Node, GraphError>;
+
+    /// Finds the edge with a given label between two nodes and sets `set_on_match` on it,
+    /// or creates it with `set_on_create` when there is none
+    fn upsert_edge(
+        &self,
+        txn: &mut RwTxn,
+        label: &str,
+        from_node: &str,
+        to_node: &str,
+        set_on_create: impl IntoIterator<Item = (String, Value)>,
+        set_on_match: impl IntoIterator<Item = (String, Value)>,
+    ) -> Result<Edge, GraphError>;
 }
 
 pub trait SearchMethods {
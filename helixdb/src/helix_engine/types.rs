@@ -24,6 +24,38 @@ pub enum GraphError {
     MultipleEdgesWithSameId,
     InvalidNode,
     ConfigFileNotFound,
+    /// The query ran past its deadline
+    Timeout(std::time::Duration),
+    /// The client disconnected while the query was running
+    Cancelled,
+    /// A traversal step produced more items or memory than the query's budget
+    LimitExceeded(String),
+    /// A traversal step failed, `args` are the arguments it was called with
+    StepFailed {
+        step: &'static str,
+        args: String,
+        source: Box<GraphError>,
+    },
+}
+
+impl GraphError {
+    /// HTTP status the gateway responds with when a handler fails with this error
+    pub fn status_code(&self) -> u16 {
+        match self {
+            GraphError::Timeout(_) | GraphError::Cancelled => 408,
+            GraphError::LimitExceeded(_) => 413,
+            GraphError::StepFailed { source, .. } => source.status_code(),
+            _ => 500,
+        }
+    }
+
+    /// The error without the step it was raised in
+    pub fn root_cause(&self) -> &GraphError {
+        match self {
+            GraphError::StepFailed { source, .. } => source.root_cause(),
+            err => err,
+        }
+    }
 }
 
 impl fmt::Display for GraphError {
@@ -49,6 +81,14 @@ impl fmt::Display for GraphError {
             GraphError::InvalidNode => write!(f, "Invalid node"),
             GraphError::ConfigFileNotFound => write!(f, "Config file not found"),
             GraphError::VectorError(msg) => write!(f, "Vector error: {}", msg),
+            GraphError::Timeout(timeout) => {
+                write!(f, "Query timed out after {}ms", timeout.as_millis())
+            }
+            GraphError::Cancelled => write!(f, "Query cancelled"),
+            GraphError::LimitExceeded(msg) => write!(f, "Query limit exceeded: {}", msg),
+            GraphError::StepFailed { step, args, source } => {
+                write!(f, "Step {}({}) failed: {}", step, args, source)
+            }
         }
     }
 }
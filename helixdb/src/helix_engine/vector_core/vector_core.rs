@@ -155,6 +155,32 @@ impl VectorCore {
         })
     }
 
+    /// Opens an index of its own, with its databases prefixed by `name`, for vectors that
+    /// shouldn't be searched together with the main index
+    pub fn named(
+        env: &Env,
+        txn: &mut RwTxn,
+        config: HNSWConfig,
+        name: &str,
+    ) -> Result<Self, VectorError> {
+        let vectors_db = env.create_database(txn, Some(&format!("{}_{}", name, DB_VECTORS)))?;
+        let out_edges_db =
+            env.create_database(txn, Some(&format!("{}_{}", name, DB_HNSW_OUT_EDGES)))?;
+        Ok(Self {
+            vectors_db,
+            out_edges_db,
+            config,
+            num_of_vecs: 0,
+        })
+    }
+
+    /// Removes every vector and link from the index
+    pub fn clear(&self, txn: &mut RwTxn) -> Result<(), VectorError> {
+        self.vectors_db.clear(txn)?;
+        self.out_edges_db.clear(txn)?;
+        Ok(())
+    }
+
     #[inline(always)]
     fn vector_key(id: &str, level: usize) -> Vec<u8> {
         [VECTOR_PREFIX, id.as_bytes(), b":", &level.to_le_bytes()].concat()
@@ -213,7 +239,7 @@ impl VectorCore {
     }
 
     #[inline(always)]
-    fn get_vector(
+    pub fn get_vector(
         &self,
         txn: &RoTxn,
         id: &str,
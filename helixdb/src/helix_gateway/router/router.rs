@@ -8,7 +8,10 @@
 // returns response
 
 use core::fmt;
-use crate::helix_engine::{graph_core::graph_core::HelixGraphEngine, types::GraphError};
+use crate::helix_engine::{
+    graph_core::{graph_core::HelixGraphEngine, limits::CancelToken},
+    types::GraphError,
+};
 use std::{collections::HashMap, sync::Arc};
 
 use crate::protocol::{request::Request, response::Response};
@@ -16,6 +19,8 @@ use crate::protocol::{request::Request, response::Response};
 pub struct HandlerInput {
     pub request: Request,
     pub graph: Arc<HelixGraphEngine>,
+    /// Cancelled when the client disconnects before the handler returns
+    pub cancel: CancelToken,
 }
 
 // basic type for function pointer
@@ -80,6 +85,17 @@ impl HelixRouter {
         graph_access: Arc<HelixGraphEngine>,
         request: Request,
         response: &mut Response,
+    ) -> Result<(), GraphError> {
+        self.handle_with_cancel(graph_access, request, response, CancelToken::default())
+    }
+
+    /// Like `handle`, with a token the caller cancels if the client goes away
+    pub fn handle_with_cancel(
+        &self,
+        graph_access: Arc<HelixGraphEngine>,
+        request: Request,
+        response: &mut Response,
+        cancel: CancelToken,
     ) -> Result<(), GraphError> {
         let route_key = (request.method.clone(), request.path.clone());
         let handler = match self.routes.get(&route_key) {
@@ -94,6 +110,7 @@ impl HelixRouter {
         let input = HandlerInput {
             request,
             graph: Arc::clone(&graph_access),
+            cancel,
         };
         handler(&input, response)
     }
@@ -1,4 +1,6 @@
 use crate::helix_engine::graph_core::graph_core::HelixGraphEngine;
+use crate::helix_engine::graph_core::limits::CancelToken;
+use crate::helix_engine::types::GraphError;
 use chrono::format;
 use flume::{Receiver, Sender};
 use std::sync::{Arc, Mutex};
@@ -46,11 +48,43 @@ impl Worker {
                         continue;
                     }
                 };
-                let mut response = Response::new();
+                // Handlers block, so they run off the async worker while it watches the
+                // connection and cancels the query if the client goes away
+                let cancel = CancelToken::default();
+                let handler = {
+                    let router = Arc::clone(&router);
+                    let graph_access = Arc::clone(&graph_access);
+                    let cancel = cancel.clone();
+                    tokio::task::spawn_blocking(move || {
+                        let mut response = Response::new();
+                        let result =
+                            router.handle_with_cancel(graph_access, request, &mut response, cancel);
+                        (response, result)
+                    })
+                };
+                tokio::pin!(handler);
+                let mut watching = true;
+                let (mut response, result) = loop {
+                    tokio::select! {
+                        joined = &mut handler => break match joined {
+                            Ok(handled) => handled,
+                            Err(e) => (
+                                Response::new(),
+                                Err(GraphError::New(format!("Handler panicked: {}", e))),
+                            ),
+                        },
+                        disconnected = client_disconnected(&conn), if watching => {
+                            watching = false;
+                            if disconnected {
+                                cancel.cancel();
+                            }
+                        }
+                    }
+                };
 
-                if let Err(e) = router.handle(Arc::clone(&graph_access), request, &mut response) {
+                if let Err(e) = result {
                     eprintln!("Error handling request: {:?}", e);
-                    response.status = 500;
+                    response.status = e.status_code();
                     response.body = format!("\n{:?}", e).into_bytes();
                 }
 
@@ -75,6 +109,13 @@ impl Worker {
     }
 }
 
+/// Resolves to `true` once the client has closed the connection. Resolves to `false` if the
+/// client sends more data instead, in which case the connection is no longer watched.
+async fn client_disconnected(conn: &TcpStream) -> bool {
+    let mut buf = [0u8; 1];
+    matches!(conn.peek(&mut buf).await, Ok(0) | Err(_))
+}
+
 pub struct ThreadPool {
     pub sender: Sender<TcpStream>,
     pub num_unused_workers: Mutex<usize>,
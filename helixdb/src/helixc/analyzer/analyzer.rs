@@ -0,0 +1,1848 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::helixc::diagnostic::diagnostic::{
+    did_you_mean, Diagnostic, DUPLICATE_DEFINITION, FUNCTION_WRITES, NOT_A_CONDITION, NOT_ITERABLE,
+    RECURSIVE_FUNCTION, TYPE_MISMATCH, UNKNOWN_EDGE_TYPE, UNKNOWN_FUNCTION, UNKNOWN_LABEL,
+    UNKNOWN_NODE_TYPE, UNKNOWN_PROPERTY, UNKNOWN_TYPE, UNKNOWN_VARIABLE, UNKNOWN_VECTOR_TYPE,
+    WRONG_ARGUMENT_COUNT, WRONG_EDGE_END, WRONG_STEP_INPUT,
+};
+use crate::helixc::generator::generator::expression_writes;
+use crate::helixc::parser::helix_parser::{
+    AddEdge, AddNode, AddVector, Aggregation, BatchAddVector, BooleanOp, Call, EdgeSchema,
+    EvaluatesToNumber, Expression, Field, FieldType, FieldValue, Function, GraphStep, HelixParser,
+    IdType, Loc, MatchPattern, NodeSchema, Object, Query, SearchVector, Source, StartNode,
+    Statement, Step, Traversal, UpsertNode, ValueType, VectorData,
+};
+use crate::protocol::value::Value;
+
+/// Parses and analyzes a source, returning every syntax or semantic error in it
+pub fn check_source(input: &str) -> Result<Source, Vec<Diagnostic>> {
+    let source = HelixParser::parse_source_diagnostics(input)?;
+    match analyze(&source) {
+        diagnostics if diagnostics.is_empty() => Ok(source),
+        diagnostics => Err(diagnostics),
+    }
+}
+
+/// Resolves every label, property, parameter, variable and function the queries use against
+/// the node, edge and vector schemas and the functions of the source, and type checks
+/// comparisons, function arguments and the values written by `AddN`, `AddE` and `UPDATE`.
+///
+/// Runs between `HelixParser` and `CodeGenerator`. All errors are returned, ordered by
+/// location, so an empty result means the source can be generated.
+pub fn analyze(source: &Source) -> Vec<Diagnostic> {
+    let mut diagnostics = run(source).diagnostics;
+    diagnostics.sort_by_key(|diagnostic| diagnostic.loc);
+    diagnostics
+}
+
+/// A use of a schema or schema field and the location it is declared at
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reference {
+    pub name: String,
+    /// The span the name is used in, e.g. the whole `::Out<Follows>` step for `Follows`
+    pub loc: Loc,
+    pub definition: Loc,
+}
+
+/// Every label and property use the analyzer could resolve, for editor navigation
+pub fn references(source: &Source) -> Vec<Reference> {
+    run(source).references
+}
+
+fn run(source: &Source) -> Analyzer<'_> {
+    let mut analyzer = Analyzer::new(source);
+    analyzer.check_schemas(source);
+
+    let mut functions = source.functions.iter().collect::<Vec<_>>();
+    functions.sort_by_key(|function| function.loc);
+    let mut names = HashMap::new();
+    for function in functions {
+        let first = *names.entry(function.name.as_str()).or_insert(function.loc);
+        if first != function.loc {
+            analyzer.duplicate(&format!("function {}", function.name), function.loc, first);
+        }
+        analyzer.function_type(function, function.loc);
+    }
+
+    // queries come out of the parser in no particular order, the first one is the earliest
+    let mut queries = source.queries.iter().collect::<Vec<_>>();
+    queries.sort_by_key(|query| query.loc);
+    let mut names = HashMap::new();
+    for query in queries {
+        let first = *names.entry(query.name.as_str()).or_insert(query.loc);
+        if first != query.loc {
+            analyzer.duplicate(&format!("query {}", query.name), query.loc, first);
+        }
+        analyzer.check_query(query);
+    }
+    analyzer
+}
+
+/// What an expression evaluates to, labels are `None` when they can't be known statically
+#[derive(Debug, Clone, PartialEq)]
+enum Type {
+    Nodes(Option<String>),
+    Edges(Option<String>),
+    Vectors(Option<String>),
+    /// Items split by `GROUP_BY`, waiting for `AGG`
+    Groups(Box<Type>),
+    Scalar(FieldType),
+    Unknown,
+}
+
+impl Type {
+    fn describe(&self) -> String {
+        match self {
+            Type::Nodes(Some(label)) => format!("{} nodes", label),
+            Type::Nodes(None) => "nodes".to_string(),
+            Type::Edges(Some(label)) => format!("{} edges", label),
+            Type::Edges(None) => "edges".to_string(),
+            Type::Vectors(_) => "vectors".to_string(),
+            Type::Groups(_) => "groups".to_string(),
+            Type::Scalar(field_type) => type_name(field_type),
+            Type::Unknown => "a value".to_string(),
+        }
+    }
+
+    fn is_numeric(&self) -> bool {
+        match self {
+            Type::Unknown | Type::Scalar(FieldType::Integer) | Type::Scalar(FieldType::Float) => {
+                true
+            }
+            Type::Scalar(FieldType::Optional(inner)) => Type::Scalar(*inner.clone()).is_numeric(),
+            _ => false,
+        }
+    }
+}
+
+/// Name, fields and location of the schema a value's properties are declared in
+type Fields<'a> = (&'a str, &'a [Field], Loc);
+
+fn node_fields(schema: &NodeSchema) -> Fields<'_> {
+    (schema.name.as_str(), schema.fields.as_slice(), schema.loc)
+}
+
+fn edge_fields(schema: &EdgeSchema) -> Fields<'_> {
+    (
+        schema.name.as_str(),
+        schema.properties.as_deref().unwrap_or_default(),
+        schema.loc,
+    )
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Out,
+    In,
+    Both,
+}
+
+struct Analyzer<'a> {
+    nodes: HashMap<&'a str, &'a NodeSchema>,
+    edges: HashMap<&'a str, &'a EdgeSchema>,
+    vectors: HashMap<&'a str, Loc>,
+    functions: HashMap<&'a str, &'a Function>,
+    /// What the body of each function checked so far evaluates to
+    function_types: HashMap<&'a str, Type>,
+    /// Functions whose bodies are being checked, the innermost last
+    calling: Vec<&'a str>,
+    /// Parameters and variables of the query or function being checked
+    scope: HashMap<String, Type>,
+    diagnostics: Vec<Diagnostic>,
+    references: Vec<Reference>,
+}
+
+impl<'a> Analyzer<'a> {
+    fn new(source: &'a Source) -> Self {
+        let mut nodes = HashMap::new();
+        for schema in &source.node_schemas {
+            nodes.entry(schema.name.as_str()).or_insert(schema);
+        }
+        let mut edges = HashMap::new();
+        for schema in &source.edge_schemas {
+            edges.entry(schema.name.as_str()).or_insert(schema);
+        }
+        let mut vectors = HashMap::new();
+        for schema in &source.vector_schemas {
+            vectors.entry(schema.name.as_str()).or_insert(schema.loc);
+        }
+        let mut functions = HashMap::<&str, &Function>::new();
+        for function in &source.functions {
+            let first = functions.entry(function.name.as_str()).or_insert(function);
+            if function.loc < first.loc {
+                *first = function;
+            }
+        }
+        Self {
+            nodes,
+            edges,
+            vectors,
+            functions,
+            function_types: HashMap::new(),
+            calling: Vec::new(),
+            scope: HashMap::new(),
+            diagnostics: Vec::new(),
+            references: Vec::new(),
+        }
+    }
+
+    fn error(&mut self, code: &'static str, loc: Loc, message: String) {
+        self.report(Diagnostic::new(code, loc, message));
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) {
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn reference(&mut self, name: &str, loc: Loc, definition: Loc) {
+        self.references.push(Reference {
+            name: name.to_string(),
+            loc,
+            definition,
+        });
+    }
+
+    fn duplicate(&mut self, what: &str, loc: Loc, first: Loc) {
+        self.report(
+            Diagnostic::new(DUPLICATE_DEFINITION, loc, format!("Duplicate {}", what))
+                .with_label(first, "first defined here"),
+        );
+    }
+
+    fn check_schemas(&mut self, source: &'a Source) {
+        let mut names = HashMap::new();
+        let schemas = source
+            .node_schemas
+            .iter()
+            .map(|schema| (schema.name.as_str(), schema.loc))
+            .chain(
+                source
+                    .edge_schemas
+                    .iter()
+                    .map(|schema| (schema.name.as_str(), schema.loc)),
+            )
+            .chain(
+                source
+                    .vector_schemas
+                    .iter()
+                    .map(|schema| (schema.name.as_str(), schema.loc)),
+            );
+        for (name, loc) in schemas {
+            let first = *names.entry(name).or_insert(loc);
+            if first != loc {
+                self.duplicate(&format!("schema {}", name), loc, first);
+            }
+        }
+
+        for schema in &source.node_schemas {
+            self.check_fields(&schema.fields);
+        }
+        for schema in &source.edge_schemas {
+            for (end, label) in [("From", &schema.from), ("To", &schema.to)] {
+                if let Some(node) = self.nodes.get(label.as_str()).copied() {
+                    self.reference(label, schema.loc, node.loc);
+                } else {
+                    let suggestion = did_you_mean(label, self.nodes.keys().copied());
+                    self.report(
+                        Diagnostic::new(
+                            UNKNOWN_NODE_TYPE,
+                            schema.loc,
+                            format!(
+                                "{} of edge {} is an unknown node type {}",
+                                end, schema.name, label
+                            ),
+                        )
+                        .with_suggestion(suggestion),
+                    );
+                }
+            }
+            if let Some(properties) = &schema.properties {
+                self.check_fields(properties);
+            }
+        }
+    }
+
+    fn check_fields(&mut self, fields: &[Field]) {
+        let mut names = HashMap::new();
+        for field in fields {
+            let first = *names.entry(field.name.as_str()).or_insert(field.loc);
+            if first != field.loc {
+                self.duplicate(&format!("field {}", field.name), field.loc, first);
+            }
+            self.check_field_type(&field.field_type, field.loc);
+        }
+    }
+
+    fn check_field_type(&mut self, field_type: &FieldType, loc: Loc) {
+        match field_type {
+            FieldType::Array(inner) | FieldType::Optional(inner) => {
+                self.check_field_type(inner, loc)
+            }
+            FieldType::Object(fields) => {
+                for (_, field_type) in fields {
+                    self.check_field_type(field_type, loc);
+                }
+            }
+            FieldType::Identifier(name)
+                if !self.nodes.contains_key(name.as_str())
+                    && !self.edges.contains_key(name.as_str()) =>
+            {
+                let labels = self.nodes.keys().chain(self.edges.keys()).copied();
+                let suggestion = did_you_mean(name, labels);
+                self.report(
+                    Diagnostic::new(UNKNOWN_TYPE, loc, format!("Unknown type {}", name))
+                        .with_suggestion(suggestion),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    fn check_query(&mut self, query: &Query) {
+        self.scope.clear();
+        for parameter in &query.parameters {
+            self.check_field_type(&parameter.param_type, parameter.loc);
+            self.scope.insert(
+                parameter.name.clone(),
+                Type::Scalar(parameter.param_type.clone()),
+            );
+        }
+        for statement in &query.statements {
+            self.check_statement(statement, query.loc);
+        }
+        for value in &query.return_values {
+            self.infer(value, &Type::Unknown, query.return_loc);
+        }
+    }
+
+    /// Checks the body of a function once, with only its parameters in scope, and returns
+    /// what it evaluates to
+    fn function_type(&mut self, function: &'a Function, loc: Loc) -> Type {
+        let name = function.name.as_str();
+        if let Some(body) = self.function_types.get(name) {
+            return body.clone();
+        }
+        if self.calling.contains(&name) {
+            self.error(
+                RECURSIVE_FUNCTION,
+                loc,
+                format!("Function {} calls itself", name),
+            );
+            return Type::Unknown;
+        }
+
+        self.calling.push(name);
+        let scope = std::mem::take(&mut self.scope);
+        for parameter in &function.parameters {
+            self.check_field_type(&parameter.param_type, parameter.loc);
+            let value = self.parameter_type(&parameter.param_type);
+            self.scope.insert(parameter.name.clone(), value);
+        }
+        let body = self.infer(&function.body, &Type::Unknown, function.loc);
+        if expression_writes(&function.body) {
+            self.error(
+                FUNCTION_WRITES,
+                function.loc,
+                format!(
+                    "Function {} writes to the graph, functions can only read",
+                    name
+                ),
+            );
+        }
+        self.scope = scope;
+        self.calling.pop();
+
+        self.function_types.insert(name, body.clone());
+        body
+    }
+
+    /// Function parameters typed with a node or edge label hold items of that label, query
+    /// parameters hold their ids
+    fn parameter_type(&self, field_type: &FieldType) -> Type {
+        match field_type {
+            FieldType::Identifier(label) if self.nodes.contains_key(label.as_str()) => {
+                Type::Nodes(Some(label.clone()))
+            }
+            FieldType::Identifier(label) if self.edges.contains_key(label.as_str()) => {
+                Type::Edges(Some(label.clone()))
+            }
+            field_type => Type::Scalar(field_type.clone()),
+        }
+    }
+
+    fn check_call(&mut self, call: &Call) -> Type {
+        let args = call
+            .args
+            .iter()
+            .map(|arg| self.infer(arg, &Type::Unknown, call.loc))
+            .collect::<Vec<_>>();
+        let Some(function) = self.functions.get(call.name.as_str()).copied() else {
+            let suggestion = did_you_mean(&call.name, self.functions.keys().copied());
+            self.report(
+                Diagnostic::new(
+                    UNKNOWN_FUNCTION,
+                    call.loc,
+                    format!("Unknown function {}", call.name),
+                )
+                .with_suggestion(suggestion),
+            );
+            return Type::Unknown;
+        };
+        self.reference(&call.name, call.loc, function.loc);
+
+        if args.len() != function.parameters.len() {
+            self.report(
+                Diagnostic::new(
+                    WRONG_ARGUMENT_COUNT,
+                    call.loc,
+                    format!(
+                        "{} takes {} arguments, got {}",
+                        call.name,
+                        function.parameters.len(),
+                        args.len()
+                    ),
+                )
+                .with_label(function.loc, format!("{} is defined here", call.name)),
+            );
+        }
+        for (parameter, arg) in function.parameters.iter().zip(&args) {
+            let accepted = match (&parameter.param_type, arg) {
+                // items are passed to label typed parameters, not ids
+                (FieldType::Identifier(_), Type::Scalar(_)) => false,
+                (expected, arg) => assignable(expected, arg),
+            };
+            if !accepted {
+                self.report(
+                    Diagnostic::new(
+                        TYPE_MISMATCH,
+                        call.loc,
+                        format!(
+                            "Argument {} of {} is {}, got {}",
+                            parameter.name,
+                            call.name,
+                            type_name(&parameter.param_type),
+                            arg.describe()
+                        ),
+                    )
+                    .with_label(
+                        parameter.loc,
+                        format!("{} is declared here", parameter.name),
+                    ),
+                );
+            }
+        }
+        self.function_type(function, call.loc)
+    }
+
+    fn check_statement(&mut self, statement: &Statement, loc: Loc) {
+        match statement {
+            Statement::Assignment(assignment) => {
+                let value = self.infer(&assignment.value, &Type::Unknown, assignment.loc);
+                self.scope.insert(assignment.variable.clone(), value);
+            }
+            Statement::AddNode(add) => {
+                self.check_add_node(add);
+            }
+            Statement::AddEdge(add) | Statement::UpsertEdge(add) => {
+                self.check_add_edge(add, &Type::Unknown);
+            }
+            Statement::UpsertNode(upsert) => {
+                self.check_upsert_node(upsert);
+            }
+            Statement::AddVector(add) => {
+                self.check_add_vector(add);
+            }
+            Statement::SearchVector(search) => {
+                self.check_search_vector(search);
+            }
+            Statement::BatchAddVector(batch) => {
+                self.check_batch_add_vector(batch);
+            }
+            Statement::Drop(expression) => {
+                self.infer(expression, &Type::Unknown, loc);
+            }
+            Statement::If(if_statement) => {
+                let condition =
+                    self.infer(&if_statement.condition, &Type::Unknown, if_statement.loc);
+                self.expect_condition(&condition, if_statement.loc);
+                self.expect_condition_form(&if_statement.condition, if_statement.loc);
+                self.check_block(&if_statement.then, None, loc);
+                self.check_block(&if_statement.otherwise, None, loc);
+            }
+            Statement::For(for_loop) => {
+                let iterable = self.infer(&for_loop.iterable, &Type::Unknown, for_loop.loc);
+                let item = match iterable {
+                    _ if !matches!(
+                        for_loop.iterable,
+                        Expression::Identifier(_) | Expression::Traversal(_)
+                    ) =>
+                    {
+                        self.error(
+                            NOT_ITERABLE,
+                            for_loop.loc,
+                            "Only variables, parameters and traversals can be iterated over".to_string(),
+                        );
+                        Type::Unknown
+                    }
+                    Type::Scalar(FieldType::Array(inner)) => Type::Scalar(*inner),
+                    Type::Scalar(_) | Type::Groups(_) => {
+                        self.error(
+                            NOT_ITERABLE,
+                            for_loop.loc,
+                            format!("Cannot iterate over {}", iterable.describe()),
+                        );
+                        Type::Unknown
+                    }
+                    iterable => iterable,
+                };
+                self.check_block(&for_loop.body, Some((&for_loop.variable, item)), loc);
+            }
+            Statement::Return(ret) => {
+                for value in &ret.values {
+                    self.infer(value, &Type::Unknown, ret.loc);
+                }
+            }
+        }
+    }
+
+    /// Variables assigned in a block are only visible inside it, `binding` is a loop variable
+    fn check_block(&mut self, statements: &[Statement], binding: Option<(&str, Type)>, loc: Loc) {
+        let outer = self.scope.clone();
+        if let Some((name, value)) = binding {
+            self.scope.insert(name.to_string(), value);
+        }
+        for statement in statements {
+            self.check_statement(statement, loc);
+        }
+        self.scope = outer;
+    }
+
+    /// `item` is what `_` refers to in anonymous traversals
+    fn infer(&mut self, expression: &Expression, item: &Type, loc: Loc) -> Type {
+        match expression {
+            Expression::Traversal(traversal) => self.check_traversal(traversal, item),
+            Expression::Identifier(name) => self.lookup(name, loc),
+            Expression::StringLiteral(_) => Type::Scalar(FieldType::String),
+            Expression::IntegerLiteral(_) => Type::Scalar(FieldType::Integer),
+            Expression::FloatLiteral(_) => Type::Scalar(FieldType::Float),
+            Expression::BooleanLiteral(_) => Type::Scalar(FieldType::Boolean),
+            Expression::Exists(traversal) => {
+                self.check_traversal(traversal, item);
+                Type::Scalar(FieldType::Boolean)
+            }
+            Expression::And(conditions) | Expression::Or(conditions) => {
+                for condition in conditions {
+                    let condition = self.infer(condition, item, loc);
+                    self.expect_condition(&condition, loc);
+                }
+                Type::Scalar(FieldType::Boolean)
+            }
+            Expression::BatchAddVector(batch) => self.check_batch_add_vector(batch),
+            Expression::AddVector(add) => self.check_add_vector(add),
+            Expression::AddNode(add) => self.check_add_node(add),
+            Expression::AddEdge(add) | Expression::UpsertEdge(add) => {
+                self.check_add_edge(add, &Type::Unknown)
+            }
+            Expression::UpsertNode(upsert) => self.check_upsert_node(upsert),
+            Expression::SearchVector(search) => self.check_search_vector(search),
+            Expression::Match(pattern) => {
+                self.check_match(pattern);
+                Type::Unknown
+            }
+            Expression::None => Type::Unknown,
+        }
+    }
+
+    fn lookup(&mut self, name: &str, loc: Loc) -> Type {
+        match self.scope.get(name) {
+            Some(value) => value.clone(),
+            None => {
+                let suggestion = did_you_mean(name, self.scope.keys().map(String::as_str));
+                self.report(
+                    Diagnostic::new(UNKNOWN_VARIABLE, loc, format!("Unknown variable {}", name))
+                        .with_suggestion(suggestion),
+                );
+                Type::Unknown
+            }
+        }
+    }
+
+    fn expect_condition(&mut self, condition: &Type, loc: Loc) {
+        if matches!(condition, Type::Scalar(field_type) if *field_type != FieldType::Boolean) {
+            self.error(
+                NOT_A_CONDITION,
+                loc,
+                format!("Conditions must be booleans, got {}", condition.describe()),
+            );
+        }
+    }
+
+    /// `IF` conditions are compiled to a Rust boolean, which only literals, variables,
+    /// traversals and `AND`/`OR` of those can be turned into
+    fn expect_condition_form(&mut self, condition: &Expression, loc: Loc) {
+        match condition {
+            Expression::BooleanLiteral(_)
+            | Expression::Identifier(_)
+            | Expression::Traversal(_)
+            | Expression::Exists(_) => {}
+            Expression::And(conditions) | Expression::Or(conditions) => {
+                for condition in conditions {
+                    self.expect_condition_form(condition, loc);
+                }
+            }
+            _ => self.error(
+                NOT_A_CONDITION,
+                loc,
+                "Conditions must be a boolean, a variable or a traversal".to_string(),
+            ),
+        }
+    }
+
+    fn node_label(&mut self, label: &Option<String>, loc: Loc) -> Option<&'a NodeSchema> {
+        let label = label.as_ref()?;
+        let schema = self.nodes.get(label.as_str()).copied();
+        if let Some(schema) = schema {
+            self.reference(label, loc, schema.loc);
+        } else {
+            let suggestion = did_you_mean(label, self.nodes.keys().copied());
+            self.report(
+                Diagnostic::new(
+                    UNKNOWN_NODE_TYPE,
+                    loc,
+                    format!("Unknown node type {}", label),
+                )
+                .with_suggestion(suggestion),
+            );
+        }
+        schema
+    }
+
+    fn edge_label(&mut self, label: &Option<String>, loc: Loc) -> Option<&'a EdgeSchema> {
+        let label = label.as_ref()?;
+        let schema = self.edges.get(label.as_str()).copied();
+        if let Some(schema) = schema {
+            self.reference(label, loc, schema.loc);
+        } else {
+            let suggestion = did_you_mean(label, self.edges.keys().copied());
+            self.report(
+                Diagnostic::new(
+                    UNKNOWN_EDGE_TYPE,
+                    loc,
+                    format!("Unknown edge type {}", label),
+                )
+                .with_suggestion(suggestion),
+            );
+        }
+        schema
+    }
+
+    fn vector_label(&mut self, label: &Option<String>, loc: Loc) -> Option<String> {
+        let label = label.as_ref()?;
+        match self.vectors.get(label.as_str()).copied() {
+            Some(definition) => {
+                self.reference(label, loc, definition);
+                Some(label.clone())
+            }
+            None => {
+                let suggestion = did_you_mean(label, self.vectors.keys().copied());
+                self.report(
+                    Diagnostic::new(
+                        UNKNOWN_VECTOR_TYPE,
+                        loc,
+                        format!("Unknown vector type {}", label),
+                    )
+                    .with_suggestion(suggestion),
+                );
+                None
+            }
+        }
+    }
+
+    /// The schema of the nodes or edges `value` holds, if it is known
+    fn schema_fields(&self, value: &Type) -> Option<Fields<'a>> {
+        match value {
+            Type::Nodes(Some(label)) => self.nodes.get(label.as_str()).copied().map(node_fields),
+            Type::Edges(Some(label)) => self.edges.get(label.as_str()).copied().map(edge_fields),
+            _ => None,
+        }
+    }
+
+    fn property(&mut self, item: &Type, name: &str, loc: Loc) -> Type {
+        if name == "id" || name == "label" {
+            return Type::Scalar(FieldType::String);
+        }
+        match self.schema_fields(item) {
+            Some(schema) => match schema.1.iter().find(|field| field.name == name) {
+                Some(field) => {
+                    self.reference(name, loc, field.loc);
+                    Type::Scalar(field.field_type.clone())
+                }
+                None => {
+                    self.unknown_property(schema, name, loc);
+                    Type::Unknown
+                }
+            },
+            None => Type::Unknown,
+        }
+    }
+
+    fn unknown_property(&mut self, (owner, fields, schema_loc): Fields, name: &str, loc: Loc) {
+        let names = fields.iter().map(|field| field.name.as_str());
+        let suggestion = did_you_mean(name, names.chain(["id", "label"]));
+        self.report(
+            Diagnostic::new(
+                UNKNOWN_PROPERTY,
+                loc,
+                format!("{} has no property {}", owner, name),
+            )
+            .with_label(schema_loc, format!("{} is defined here", owner))
+            .with_suggestion(suggestion),
+        );
+    }
+
+    /// Checks a value written to property `name` of `owner`
+    fn check_property_value(&mut self, schema: Fields, name: &str, value: &Type, loc: Loc) {
+        let owner = schema.0;
+        let field = schema.1.iter().find(|field| field.name == name);
+        if let Some(field) = field {
+            self.reference(name, loc, field.loc);
+        }
+        match field {
+            Some(field) if !assignable(&field.field_type, value) => self.report(
+                Diagnostic::new(
+                    TYPE_MISMATCH,
+                    loc,
+                    format!(
+                        "Property {}.{} is {}, got {}",
+                        owner,
+                        name,
+                        type_name(&field.field_type),
+                        value.describe()
+                    ),
+                )
+                .with_label(field.loc, format!("{}.{} is declared here", owner, name)),
+            ),
+            Some(_) => {}
+            None => self.unknown_property(schema, name, loc),
+        }
+    }
+
+    fn check_assignments(
+        &mut self,
+        values: &[(String, ValueType)],
+        schema: Option<Fields>,
+        loc: Loc,
+    ) {
+        for (name, value) in values {
+            let value = match value {
+                ValueType::Literal(value) => literal_type(value),
+                ValueType::Identifier(identifier) => self.lookup(identifier, loc),
+                ValueType::Object(_) => Type::Unknown,
+            };
+            if let Some(schema) = schema {
+                self.check_property_value(schema, name, &value, loc);
+            }
+        }
+    }
+
+    fn check_add_node(&mut self, add: &AddNode) -> Type {
+        let schema = self.node_label(&add.vertex_type, add.loc);
+        if let Some(fields) = &add.fields {
+            self.check_assignments(fields, schema.map(node_fields), add.loc);
+        }
+        Type::Nodes(schema.map(|schema| schema.name.clone()))
+    }
+
+    fn check_upsert_node(&mut self, upsert: &UpsertNode) -> Type {
+        let schema = self.node_label(&upsert.vertex_type, upsert.loc);
+        let fields = upsert.fields.iter().flatten();
+        let assignments = upsert.match_fields.iter().chain(fields).cloned();
+        self.check_assignments(
+            &assignments.collect::<Vec<_>>(),
+            schema.map(node_fields),
+            upsert.loc,
+        );
+        Type::Nodes(schema.map(|schema| schema.name.clone()))
+    }
+
+    /// `item` is the node an `AddE` step is called on, it is the edge's source unless `From`
+    /// is given
+    fn check_add_edge(&mut self, add: &AddEdge, item: &Type) -> Type {
+        let schema = self.edge_label(&add.edge_type, add.loc);
+        if let Some(fields) = &add.fields {
+            self.check_assignments(fields, schema.map(edge_fields), add.loc);
+        }
+
+        let from = match &add.connection.from_id {
+            Some(id) => self.id_type(id, add.loc),
+            None => item.clone(),
+        };
+        let to = match &add.connection.to_id {
+            Some(id) => self.id_type(id, add.loc),
+            None => Type::Unknown,
+        };
+        if let Some(schema) = schema {
+            self.check_edge_end(schema, "From", &schema.from, &from, add.loc);
+            self.check_edge_end(schema, "To", &schema.to, &to, add.loc);
+        }
+        Type::Edges(schema.map(|schema| schema.name.clone()))
+    }
+
+    fn check_edge_end(
+        &mut self,
+        edge: &EdgeSchema,
+        end: &str,
+        expected: &str,
+        value: &Type,
+        loc: Loc,
+    ) {
+        let matches = match value {
+            Type::Nodes(Some(label)) => label == expected,
+            Type::Scalar(FieldType::Identifier(label)) => label == expected,
+            Type::Nodes(None) | Type::Scalar(FieldType::String) | Type::Unknown => true,
+            _ => false,
+        };
+        if !matches {
+            self.wrong_edge_end(edge, end, expected, &value.describe(), loc);
+        }
+    }
+
+    fn wrong_edge_end(
+        &mut self,
+        edge: &EdgeSchema,
+        end: &str,
+        expected: &str,
+        got: &str,
+        loc: Loc,
+    ) {
+        self.report(
+            Diagnostic::new(
+                WRONG_EDGE_END,
+                loc,
+                format!(
+                    "{} of edge {} must be {} nodes, got {}",
+                    end, edge.name, expected, got
+                ),
+            )
+            .with_label(edge.loc, format!("{} is defined here", edge.name)),
+        );
+    }
+
+    fn id_type(&mut self, id: &IdType, loc: Loc) -> Type {
+        match id {
+            IdType::Literal(_) => Type::Scalar(FieldType::String),
+            IdType::Identifier(name) => self.lookup(name, loc),
+        }
+    }
+
+    fn check_vector_data(&mut self, data: &Option<VectorData>, loc: Loc) {
+        if let Some(VectorData::Identifier(name)) = data {
+            let value = self.lookup(name, loc);
+            if !assignable(&FieldType::Array(Box::new(FieldType::Float)), &value) {
+                self.error(
+                    TYPE_MISMATCH,
+                    loc,
+                    format!(
+                        "Vector data {} must be [Float], got {}",
+                        name,
+                        value.describe()
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_number(&mut self, number: &EvaluatesToNumber, loc: Loc) {
+        if let EvaluatesToNumber::Identifier(name) = number {
+            let value = self.lookup(name, loc);
+            if !value.is_numeric() {
+                self.error(
+                    TYPE_MISMATCH,
+                    loc,
+                    format!("{} must be a number, got {}", name, value.describe()),
+                );
+            }
+        }
+    }
+
+    fn check_add_vector(&mut self, add: &AddVector) -> Type {
+        let label = self.vector_label(&add.vector_type, add.loc);
+        self.check_vector_data(&add.data, add.loc);
+        Type::Vectors(label)
+    }
+
+    fn check_search_vector(&mut self, search: &SearchVector) -> Type {
+        let label = self.vector_label(&search.vector_type, search.loc);
+        self.check_vector_data(&search.data, search.loc);
+        if let Some(k) = &search.k {
+            self.check_number(k, search.loc);
+        }
+        Type::Vectors(label)
+    }
+
+    fn check_batch_add_vector(&mut self, batch: &BatchAddVector) -> Type {
+        let label = self.vector_label(&batch.vector_type, batch.loc);
+        if let Some(name) = &batch.vec_identifier {
+            self.lookup(name, batch.loc);
+        }
+        Type::Vectors(label)
+    }
+
+    fn check_match(&mut self, pattern: &MatchPattern) {
+        let mut labels = HashMap::new();
+        for node in &pattern.nodes {
+            let schema = self.node_label(&node.label, node.loc);
+            if let Some(schema) = schema {
+                labels.insert(node.variable.as_str(), schema.name.as_str());
+            }
+            self.check_assignments(&node.properties, schema.map(node_fields), node.loc);
+        }
+
+        for edge in &pattern.edges {
+            let schema = self.edge_label(&edge.label, edge.loc);
+            if let Some(schema) = schema {
+                for (end, variable, expected) in [
+                    ("From", &edge.from, &schema.from),
+                    ("To", &edge.to, &schema.to),
+                ] {
+                    if let Some(label) = labels.get(variable.as_str()) {
+                        if *label != expected.as_str() {
+                            let got = format!("{} nodes", label);
+                            self.wrong_edge_end(schema, end, expected, &got, edge.loc);
+                        }
+                    }
+                }
+            }
+            self.check_assignments(&edge.properties, schema.map(edge_fields), edge.loc);
+        }
+    }
+
+    fn check_traversal(&mut self, traversal: &Traversal, item: &Type) -> Type {
+        let mut current = match &traversal.start {
+            StartNode::Node { types, ids } => {
+                self.check_ids(ids, traversal.start_loc);
+                let label = types.as_ref().and_then(|types| {
+                    let labels = types
+                        .iter()
+                        .filter_map(|label| {
+                            self.node_label(&Some(label.clone()), traversal.start_loc)
+                        })
+                        .collect::<Vec<_>>();
+                    match (types.len(), labels.as_slice()) {
+                        (1, [schema]) => Some(schema.name.clone()),
+                        _ => None,
+                    }
+                });
+                Type::Nodes(label)
+            }
+            StartNode::Edge { types, ids } => {
+                self.check_ids(ids, traversal.start_loc);
+                let label = types.as_ref().and_then(|types| {
+                    let labels = types
+                        .iter()
+                        .filter_map(|label| {
+                            self.edge_label(&Some(label.clone()), traversal.start_loc)
+                        })
+                        .collect::<Vec<_>>();
+                    match (types.len(), labels.as_slice()) {
+                        (1, [schema]) => Some(schema.name.clone()),
+                        _ => None,
+                    }
+                });
+                Type::Edges(label)
+            }
+            StartNode::Variable(name) => self.lookup(name, traversal.start_loc),
+            StartNode::Anonymous => item.clone(),
+            StartNode::Call(call) => self.check_call(call),
+        };
+
+        let mut labels = HashSet::new();
+        for (i, step) in traversal.steps.iter().enumerate() {
+            let loc = traversal.step_locs.get(i).copied().unwrap_or(traversal.loc);
+            current = self.check_step(step, current, &mut labels, loc);
+        }
+        current
+    }
+
+    fn check_ids(&mut self, ids: &Option<Vec<String>>, loc: Loc) {
+        for id in ids.iter().flatten() {
+            if !id.starts_with('"') {
+                self.lookup(id, loc);
+            }
+        }
+    }
+
+    fn check_step(
+        &mut self,
+        step: &Step,
+        current: Type,
+        labels: &mut HashSet<String>,
+        loc: Loc,
+    ) -> Type {
+        match step {
+            Step::Node(graph_step) | Step::Edge(graph_step) => {
+                self.check_graph_step(graph_step, &current, loc)
+            }
+            Step::Where(condition) => {
+                let condition = self.infer(condition, &current, loc);
+                self.expect_condition(&condition, loc);
+                current
+            }
+            Step::BooleanOperation(operation) => {
+                self.check_comparison(operation, &current, loc);
+                Type::Scalar(FieldType::Boolean)
+            }
+            Step::Count => Type::Scalar(FieldType::Integer),
+            Step::Path => Type::Unknown,
+            Step::Update(update) => {
+                self.expect_elements("UPDATE", &current, loc);
+                let schema = self.schema_fields(&current);
+                for field in &update.fields {
+                    let value = match &field.value {
+                        FieldValue::Traversal(traversal) => {
+                            self.check_traversal(traversal, &current)
+                        }
+                        FieldValue::Expression(expression) => self.infer(expression, &current, loc),
+                        FieldValue::Literal(value) => literal_type(value),
+                        FieldValue::Fields(_) | FieldValue::Empty => Type::Unknown,
+                    };
+                    if let Some(schema) = schema {
+                        self.check_property_value(schema, &field.name, &value, loc);
+                    }
+                }
+                current
+            }
+            Step::Object(object) => self.check_object(object, &current, loc),
+            Step::Exclude(exclude) => {
+                for field in &exclude.fields {
+                    self.property(&current, field, loc);
+                }
+                Type::Unknown
+            }
+            Step::Closure(closure) => {
+                let shadowed = self
+                    .scope
+                    .insert(closure.identifier.clone(), current.clone());
+                self.check_object(&closure.object, &current, loc);
+                match shadowed {
+                    Some(value) => self.scope.insert(closure.identifier.clone(), value),
+                    None => self.scope.remove(&closure.identifier),
+                };
+                Type::Unknown
+            }
+            Step::Range((start, end)) => {
+                for bound in [start, end] {
+                    let bound = self.infer(bound, &current, loc);
+                    if !bound.is_numeric() {
+                        self.error(
+                            TYPE_MISMATCH,
+                            loc,
+                            format!("RANGE bounds must be numbers, got {}", bound.describe()),
+                        );
+                    }
+                }
+                current
+            }
+            Step::AddEdge(add) => self.check_add_edge(add, &current),
+            Step::SearchVector(_) => current,
+            Step::OrderBy(keys) => {
+                for (key, _) in keys {
+                    self.property(&current, key, loc);
+                }
+                current
+            }
+            Step::Dedup(property) => {
+                if let Some(property) = property {
+                    self.property(&current, property, loc);
+                }
+                current
+            }
+            Step::GroupBy(property) => {
+                self.property(&current, property, loc);
+                Type::Groups(Box::new(current))
+            }
+            Step::Aggregate(aggregations) => {
+                let items = match current {
+                    Type::Groups(items) => *items,
+                    items => items,
+                };
+                for aggregation in aggregations {
+                    match aggregation {
+                        Aggregation::Count => {}
+                        Aggregation::Sum(property) | Aggregation::Avg(property) => {
+                            let value = self.property(&items, property, loc);
+                            if !value.is_numeric() {
+                                let function = match aggregation {
+                                    Aggregation::Sum(_) => "SUM",
+                                    _ => "AVG",
+                                };
+                                self.error(
+                                    TYPE_MISMATCH,
+                                    loc,
+                                    format!(
+                                        "{} needs a numeric property, {} is {}",
+                                        function,
+                                        property,
+                                        value.describe()
+                                    ),
+                                );
+                            }
+                        }
+                        Aggregation::Min(property)
+                        | Aggregation::Max(property)
+                        | Aggregation::Collect(property) => {
+                            self.property(&items, property, loc);
+                        }
+                    }
+                }
+                Type::Unknown
+            }
+            Step::As(label) => {
+                labels.insert(label.clone());
+                current
+            }
+            Step::Select(selected) => {
+                for label in selected {
+                    if !labels.contains(label) {
+                        let suggestion = did_you_mean(label, labels.iter().map(String::as_str));
+                        self.report(
+                            Diagnostic::new(
+                                UNKNOWN_LABEL,
+                                loc,
+                                format!("SELECT uses {}, which no AS step defines", label),
+                            )
+                            .with_suggestion(suggestion),
+                        );
+                    }
+                }
+                Type::Unknown
+            }
+            Step::Union(branches) | Step::Coalesce(branches) => {
+                let types = branches
+                    .iter()
+                    .map(|branch| self.check_traversal(branch, &current))
+                    .collect::<Vec<_>>();
+                match types.split_first() {
+                    Some((first, rest)) if rest.iter().all(|value| value == first) => first.clone(),
+                    _ => Type::Unknown,
+                }
+            }
+            Step::Optional(branch) => match self.check_traversal(branch, &current) {
+                value if value == current => current,
+                _ => Type::Unknown,
+            },
+            Step::PredictLinks(predict) => {
+                self.expect_nodes("PredictLinks", &current, loc);
+                self.edge_label(&predict.edge_type, loc);
+                self.check_number(&predict.limit, loc);
+                Type::Unknown
+            }
+            Step::Dag(dag) => {
+                self.expect_nodes("DAG steps", &current, loc);
+                let edge = self.edge_label(&Some(dag.edge_type.clone()), loc);
+                if let (Some(edge), Some(weight)) = (edge, &dag.weight) {
+                    let value = self.property(&Type::Edges(Some(edge.name.clone())), weight, loc);
+                    if !value.is_numeric() {
+                        self.error(
+                            TYPE_MISMATCH,
+                            loc,
+                            format!(
+                                "CriticalPath weight must be numeric, {} is {}",
+                                weight,
+                                value.describe()
+                            ),
+                        );
+                    }
+                }
+                current
+            }
+        }
+    }
+
+    fn expect_nodes(&mut self, step: &str, current: &Type, loc: Loc) {
+        if !matches!(current, Type::Nodes(_) | Type::Unknown) {
+            self.error(
+                WRONG_STEP_INPUT,
+                loc,
+                format!("{} expects nodes, got {}", step, current.describe()),
+            );
+        }
+    }
+
+    fn expect_elements(&mut self, step: &str, current: &Type, loc: Loc) {
+        if !matches!(current, Type::Nodes(_) | Type::Edges(_) | Type::Unknown) {
+            self.error(
+                WRONG_STEP_INPUT,
+                loc,
+                format!(
+                    "{} expects nodes or edges, got {}",
+                    step,
+                    current.describe()
+                ),
+            );
+        }
+    }
+
+    fn check_graph_step(&mut self, step: &GraphStep, current: &Type, loc: Loc) -> Type {
+        match step {
+            GraphStep::Out(types) => {
+                Type::Nodes(self.check_hop("Out", types, current, Direction::Out, loc).1)
+            }
+            GraphStep::In(types) => {
+                Type::Nodes(self.check_hop("In", types, current, Direction::In, loc).1)
+            }
+            GraphStep::Both(types) => Type::Nodes(
+                self.check_hop("Both", types, current, Direction::Both, loc)
+                    .1,
+            ),
+            GraphStep::OutE(types) => Type::Edges(
+                self.check_hop("OutE", types, current, Direction::Out, loc)
+                    .0,
+            ),
+            GraphStep::InE(types) => {
+                Type::Edges(self.check_hop("InE", types, current, Direction::In, loc).0)
+            }
+            GraphStep::BothE(types) => Type::Edges(
+                self.check_hop("BothE", types, current, Direction::Both, loc)
+                    .0,
+            ),
+            GraphStep::OutN => self.check_edge_nodes("OutN", current, Direction::Out, loc),
+            GraphStep::InN => self.check_edge_nodes("InN", current, Direction::In, loc),
+            GraphStep::BothN => self.check_edge_nodes("BothN", current, Direction::Both, loc),
+        }
+    }
+
+    /// Checks that every edge type of a hop can be followed from the current nodes, returns
+    /// the edge label and the label of the nodes at the other end when they are known
+    fn check_hop(
+        &mut self,
+        step: &str,
+        types: &Option<Vec<String>>,
+        current: &Type,
+        direction: Direction,
+        loc: Loc,
+    ) -> (Option<String>, Option<String>) {
+        self.expect_nodes(step, current, loc);
+        let Some(types) = types else {
+            return (None, None);
+        };
+        let from = match current {
+            Type::Nodes(label) => label.as_deref(),
+            _ => None,
+        };
+
+        let mut ends = Vec::new();
+        for label in types {
+            let Some(edge) = self.edge_label(&Some(label.clone()), loc) else {
+                ends.push(None);
+                continue;
+            };
+            let end = match (direction, from) {
+                (Direction::Out, Some(from)) if from != edge.from => None,
+                (Direction::Out, _) => Some(edge.to.clone()),
+                (Direction::In, Some(from)) if from != edge.to => None,
+                (Direction::In, _) => Some(edge.from.clone()),
+                (Direction::Both, Some(from)) if from == edge.from => Some(edge.to.clone()),
+                (Direction::Both, Some(from)) if from == edge.to => Some(edge.from.clone()),
+                (Direction::Both, Some(_)) => None,
+                (Direction::Both, None) if edge.from == edge.to => Some(edge.from.clone()),
+                (Direction::Both, None) => None,
+            };
+            if let (None, Some(from)) = (&end, from) {
+                self.report(
+                    Diagnostic::new(
+                        WRONG_EDGE_END,
+                        loc,
+                        format!(
+                            "{}<{}> can't be followed from {} nodes, {} goes from {} to {}",
+                            step, edge.name, from, edge.name, edge.from, edge.to
+                        ),
+                    )
+                    .with_label(edge.loc, format!("{} is defined here", edge.name)),
+                );
+            }
+            ends.push(end);
+        }
+
+        let edge = match types.as_slice() {
+            [label] if self.edges.contains_key(label.as_str()) => Some(label.clone()),
+            _ => None,
+        };
+        let end = match ends.split_first() {
+            Some((Some(first), rest)) if rest.iter().all(|end| end.as_ref() == Some(first)) => {
+                Some(first.clone())
+            }
+            _ => None,
+        };
+        (edge, end)
+    }
+
+    /// `OutN` is the node an edge comes from, `InN` the node it goes to
+    fn check_edge_nodes(
+        &mut self,
+        step: &str,
+        current: &Type,
+        direction: Direction,
+        loc: Loc,
+    ) -> Type {
+        let edge = match current {
+            Type::Edges(Some(label)) => self.edges.get(label.as_str()).copied(),
+            Type::Edges(None) | Type::Unknown => None,
+            _ => {
+                self.error(
+                    WRONG_STEP_INPUT,
+                    loc,
+                    format!("{} expects edges, got {}", step, current.describe()),
+                );
+                None
+            }
+        };
+        Type::Nodes(edge.and_then(|edge| match direction {
+            Direction::Out => Some(edge.from.clone()),
+            Direction::In => Some(edge.to.clone()),
+            Direction::Both if edge.from == edge.to => Some(edge.from.clone()),
+            Direction::Both => None,
+        }))
+    }
+
+    fn check_comparison(&mut self, operation: &BooleanOp, current: &Type, loc: Loc) {
+        let (name, operand, numeric) = match operation {
+            BooleanOp::GreaterThan(operand) => ("GT", operand, true),
+            BooleanOp::GreaterThanOrEqual(operand) => ("GTE", operand, true),
+            BooleanOp::LessThan(operand) => ("LT", operand, true),
+            BooleanOp::LessThanOrEqual(operand) => ("LTE", operand, true),
+            BooleanOp::Equal(operand) => ("EQ", operand, false),
+            BooleanOp::NotEqual(operand) => ("NEQ", operand, false),
+            BooleanOp::And(conditions) | BooleanOp::Or(conditions) => {
+                for condition in conditions {
+                    let condition = self.infer(condition, current, loc);
+                    self.expect_condition(&condition, loc);
+                }
+                return;
+            }
+        };
+
+        let operand = self.infer(operand, current, loc);
+        // dates are ISO 8601 strings, which order the same way as the dates they hold
+        let dates = [current, &operand]
+            .iter()
+            .all(|value| matches!(value, Type::Unknown | Type::Scalar(FieldType::String)));
+        if numeric && !dates {
+            for (side, value) in [("left", current), ("right", &operand)] {
+                if !value.is_numeric() {
+                    self.error(
+                        TYPE_MISMATCH,
+                        loc,
+                        format!(
+                            "{} compares numbers or dates, the {} side is {}",
+                            name,
+                            side,
+                            value.describe()
+                        ),
+                    );
+                }
+            }
+        } else if let Type::Scalar(expected) = current {
+            if !assignable(expected, &operand) {
+                self.error(
+                    TYPE_MISMATCH,
+                    loc,
+                    format!(
+                        "{} compares {} with {}",
+                        name,
+                        current.describe(),
+                        operand.describe()
+                    ),
+                );
+            }
+        }
+    }
+
+    fn check_object(&mut self, object: &Object, item: &Type, loc: Loc) -> Type {
+        let mut types = object
+            .fields
+            .iter()
+            .map(|(key, value)| self.check_field_value(key, value, item, loc))
+            .collect::<Vec<_>>();
+        // `::{name}` and `::ID` read a single property
+        match object.fields.as_slice() {
+            [(key, FieldValue::Literal(Value::String(name)))] if key == name => {
+                types.pop().unwrap_or(Type::Unknown)
+            }
+            [(_, FieldValue::Empty)] if !object.should_spread => {
+                types.pop().unwrap_or(Type::Unknown)
+            }
+            _ => Type::Unknown,
+        }
+    }
+
+    fn check_field_value(&mut self, key: &str, value: &FieldValue, item: &Type, loc: Loc) -> Type {
+        match value {
+            FieldValue::Literal(Value::String(name)) if name == key => {
+                self.property(item, name, loc)
+            }
+            FieldValue::Empty => self.property(item, key, loc),
+            // `{ alias: property }` unless `property` is a variable
+            FieldValue::Expression(Expression::Identifier(name))
+                if !self.scope.contains_key(name) =>
+            {
+                self.property(item, name, loc)
+            }
+            FieldValue::Traversal(traversal) => self.check_traversal(traversal, item),
+            FieldValue::Expression(expression) => self.infer(expression, item, loc),
+            FieldValue::Fields(fields) => {
+                for field in fields {
+                    self.check_field_value(&field.name, &field.value, item, loc);
+                }
+                Type::Unknown
+            }
+            FieldValue::Literal(value) => literal_type(value),
+        }
+    }
+}
+
+fn literal_type(value: &Value) -> Type {
+    match value {
+        Value::String(_) => Type::Scalar(FieldType::String),
+        Value::Integer(_) => Type::Scalar(FieldType::Integer),
+        Value::Float(_) => Type::Scalar(FieldType::Float),
+        Value::Boolean(_) => Type::Scalar(FieldType::Boolean),
+        Value::Array(values) => match values.first().map(literal_type) {
+            Some(Type::Scalar(element)) => Type::Scalar(FieldType::Array(Box::new(element))),
+            _ => Type::Unknown,
+        },
+        Value::Object(_) | Value::Empty => Type::Unknown,
+    }
+}
+
+/// Whether `value` can be stored in or compared with a property of type `expected`
+fn assignable(expected: &FieldType, value: &Type) -> bool {
+    match (expected, value) {
+        (_, Type::Unknown) => true,
+        (FieldType::Identifier(expected), Type::Nodes(label) | Type::Edges(label)) => {
+            label.as_ref().is_none_or(|label| label == expected)
+        }
+        (_, Type::Scalar(value)) => compatible(expected, value),
+        _ => false,
+    }
+}
+
+fn compatible(expected: &FieldType, value: &FieldType) -> bool {
+    match (expected, value) {
+        (FieldType::Optional(expected), value) => compatible(expected, value),
+        (expected, FieldType::Optional(value)) => compatible(expected, value),
+        (FieldType::Float, FieldType::Integer) => true,
+        (FieldType::Array(element), FieldType::Vector(_))
+        | (FieldType::Vector(_), FieldType::Array(element)) => {
+            matches!(**element, FieldType::Float | FieldType::Integer)
+        }
+        // ids of typed parameters are strings
+        (FieldType::String, FieldType::Identifier(_))
+        | (FieldType::Identifier(_), FieldType::String) => true,
+        (FieldType::Array(expected), FieldType::Array(value)) => compatible(expected, value),
+        _ => expected == value,
+    }
+}
+
+/// A type the way it is written in a schema, e.g. `[String]`
+pub fn type_name(field_type: &FieldType) -> String {
+    match field_type {
+        FieldType::String => "String".to_string(),
+        FieldType::Integer => "Integer".to_string(),
+        FieldType::Float => "Float".to_string(),
+        FieldType::Boolean => "Boolean".to_string(),
+        FieldType::Array(element) => format!("[{}]", type_name(element)),
+        FieldType::Identifier(name) => name.clone(),
+        FieldType::Optional(inner) => format!("Optional<{}>", type_name(inner)),
+        FieldType::Object(fields) => format!(
+            "{{{}}}",
+            fields
+                .iter()
+                .map(|(name, field_type)| format!("{}: {}", name, type_name(field_type)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        FieldType::Vector(dimensions) => format!("[F32; {}]", dimensions),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helixc::diagnostic::diagnostic::SYNTAX_ERROR;
+
+    const SCHEMA: &str = r#"
+N::User {
+    name: String,
+    age: Integer,
+    score: Float
+}
+
+N::Post {
+    title: String
+}
+
+E::Follows {
+    From: User,
+    To: User,
+    Properties: {
+        since: Integer
+    }
+}
+
+E::Wrote {
+    From: User,
+    To: Post,
+    Properties: {
+    }
+}
+
+V::Embedding
+"#;
+
+    fn check(queries: &str) -> Vec<String> {
+        let source = HelixParser::parse_source(&format!("{}{}", SCHEMA, queries)).unwrap();
+        analyze(&source)
+            .into_iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_queries_have_no_diagnostics() {
+        let diagnostics = check(
+            r#"
+QUERY authors(id: String, minAge: Integer) =>
+    user <- N<User>(id)
+    followers <- user::In<Follows>::WHERE(_::{age}::GT(minAge))
+    posts <- followers::Out<Wrote>::{title}
+    edges <- user::OutE<Follows>::OutN::ORDER(name)
+    RETURN followers, posts, edges
+
+QUERY create(name: String, age: Integer, other: String) =>
+    user <- AddN<User>({name: name, age: age, score: 1})
+    AddE<Follows>({since: 2020})::From(user)::To(other)
+    RETURN user
+"#,
+        );
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_unknown_labels_and_properties() {
+        let diagnostics = check(
+            r#"
+QUERY broken(id: String) =>
+    user <- N<Usr>(id)
+    posts <- N<User>(id)::Out<Likes>
+    names <- N<User>::{nmae}
+    RETURN user, posts, names, missing
+"#,
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                "30:13: Unknown node type Usr",
+                "31:25: Unknown edge type Likes",
+                "32:21: User has no property nmae",
+                "33:5: Unknown variable missing",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_edges_from_the_wrong_node_type() {
+        let diagnostics = check(
+            r#"
+QUERY wrong(id: String) =>
+    posts <- N<Post>(id)::Out<Wrote>
+    authors <- N<User>(id)::In<Wrote>
+    post <- N<Post>(id)
+    AddE<Follows>::From(post)::To(id)
+    RETURN posts, authors
+"#,
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                "30:25: Out<Wrote> can't be followed from Post nodes, Wrote goes from User to Post",
+                "31:27: In<Wrote> can't be followed from User nodes, Wrote goes from User to Post",
+                "33:5: From of edge Follows must be User nodes, got Post nodes",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comparisons_and_writes_are_type_checked() {
+        let diagnostics = check(
+            r#"
+QUERY types(name: String, age: Integer) =>
+    older <- N<User>::WHERE(_::{name}::GT(age))
+    named <- N<User>::WHERE(_::{age}::EQ(name))
+    user <- AddN<User>({name: age, age: "old"})
+    since <- N<User>::WHERE(_::{name}::GTE(2024-03-01))
+    RETURN older, named, user, since
+"#,
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                "30:38: GT compares numbers or dates, the left side is String",
+                "31:37: EQ compares Integer with String",
+                "32:13: Property User.name is String, got Integer",
+                "32:13: Property User.age is Integer, got String",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_complex_parameter_types() {
+        let diagnostics = check(
+            r#"
+QUERY params(minAge: Optional<Integer>, vec: [F32; 3], nick: Optional<String>) =>
+    older <- N<User>::WHERE(_::{age}::GT(minAge))
+    embedding <- AddV<Embedding>(vec)
+    user <- AddN<User>({name: nick, age: nick})
+    RETURN older, embedding, user
+"#,
+        );
+        assert_eq!(
+            diagnostics,
+            vec!["32:13: Property User.age is Integer, got Optional<String>"]
+        );
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let diagnostics = check(
+            r#"
+QUERY control(ids: [String], name: String, admin: Boolean) =>
+    user <- N<User>(name)
+    IF name {
+        RETURN user
+    }
+    FOR id IN ids {
+        followed <- N<User>(id)
+    }
+    FOR letter IN name {
+        AddN<Post>({title: letter})
+    }
+    IF admin {
+        posts <- user::Out<Wrote>
+    } ELSE {
+        FOR post IN user::Out<Wrote> {
+            AddE<Wrote>()::From(user)::To(post)
+        }
+    }
+    RETURN user, followed, posts
+"#,
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                "31:5: Conditions must be booleans, got String",
+                "37:5: Cannot iterate over String",
+                "47:5: Unknown variable followed",
+                "47:5: Unknown variable posts",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_conditions_and_iterables() {
+        let diagnostics = check(
+            r#"
+QUERY unsupported(ids: [String]) =>
+    IF MATCH (a:User)-[:Follows]->(b:User) {
+        users <- N<User>
+    }
+    IF NONE {
+        users <- N<User>
+    }
+    FOR pair IN MATCH (a:User)-[:Follows]->(b:User) {
+        users <- N<User>
+    }
+    RETURN ids
+"#,
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                "30:5: Conditions must be a boolean, a variable or a traversal",
+                "33:5: Conditions must be a boolean, a variable or a traversal",
+                "36:5: Only variables, parameters and traversals can be iterated over",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_upserts() {
+        let diagnostics = check(
+            r#"
+QUERY ingest(name: String, age: Integer, friend: String) =>
+    user <- UpsertN<User>({name: name}, {age: age})
+    UpsertE<Follows>({since: 2020})::From(user)::To(friend)
+    post <- UpsertN<Post>({title: age})
+    UpsertE<Follows>::From(user)::To(post)
+    UpsertN<Usr>({name: name})
+    RETURN user
+"#,
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                "32:13: Property Post.title is String, got Integer",
+                "33:5: To of edge Follows must be User nodes, got Post nodes",
+                "34:5: Unknown node type Usr",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        let diagnostics = check(
+            r#"
+FN followers(user: User, minAge: Integer) =>
+    user::In<Follows>::WHERE(_::{age}::GTE(minAge))
+
+FN loop(user: User) => loop(user)
+
+FN follow(user: User) => AddE<Follows>::From(user)::To(user)
+
+QUERY fans(id: String) =>
+    user <- N<User>(id)
+    fans <- followers(user, 2020)::{name}
+    posts <- followers(user)
+    others <- followers("alice", 2020)
+    missing <- follower(user, 2020)
+    RETURN fans
+"#,
+        );
+        assert_eq!(
+            diagnostics,
+            vec![
+                "32:24: Function loop calls itself",
+                "34:1: Function follow writes to the graph, functions can only read",
+                "39:14: followers takes 2 arguments, got 1",
+                "40:15: Argument user of followers is User, got String",
+                "41:16: Unknown function follower",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_schema_errors() {
+        let source = HelixParser::parse_source(
+            r#"
+N::User {
+    name: String,
+    name: Integer
+}
+
+E::Owns {
+    From: User,
+    To: Car,
+    Properties: {
+    }
+}
+"#,
+        )
+        .unwrap();
+        let diagnostics = analyze(&source)
+            .into_iter()
+            .map(|diagnostic| diagnostic.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            diagnostics,
+            vec![
+                "4:5: Duplicate field name",
+                "7:1: To of edge Owns is an unknown node type Car",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diagnostics_have_codes_suggestions_and_labels() {
+        let diagnostics = check_source(&format!(
+            "{}{}",
+            SCHEMA,
+            r#"
+QUERY typos(id: String) =>
+    user <- N<Usr>(id)
+    names <- N<User>::{nmae}
+    AddN<User>({age: "old"})
+    RETURN usr
+"#
+        ))
+        .unwrap_err();
+        let summary = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let labels = diagnostic
+                    .labels
+                    .iter()
+                    .map(|label| format!("{}: {}", label.loc, label.message))
+                    .collect::<Vec<_>>();
+                (diagnostic.code, diagnostic.suggestion.as_deref(), labels)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            vec![
+                (UNKNOWN_NODE_TYPE, Some("User"), vec![]),
+                (
+                    UNKNOWN_PROPERTY,
+                    Some("name"),
+                    vec!["2:1: User is defined here".to_string()]
+                ),
+                (
+                    TYPE_MISMATCH,
+                    None,
+                    vec!["4:5: User.age is declared here".to_string()]
+                ),
+                (UNKNOWN_VARIABLE, Some("user"), vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_source_reports_syntax_errors() {
+        let diagnostics = check_source("N::User {\n    name String\n}\n").unwrap_err();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code, SYNTAX_ERROR);
+        assert_eq!((diagnostics[0].loc.line, diagnostics[0].loc.column), (2, 5));
+    }
+
+    #[test]
+    fn test_references_point_at_declarations() {
+        let source = HelixParser::parse_source(&format!(
+            "{}{}",
+            SCHEMA,
+            r#"
+QUERY names() =>
+    users <- N<User>::Out<Follows>::{name}
+    RETURN users
+"#
+        ))
+        .unwrap();
+        let references = references(&source)
+            .into_iter()
+            .filter(|reference| reference.loc.line > 27)
+            .map(|reference| {
+                format!(
+                    "{} {} -> {}",
+                    reference.name, reference.loc, reference.definition
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            references,
+            vec![
+                "User 30:14 -> 2:1",
+                "Follows 30:21 -> 12:1",
+                "name 30:35 -> 3:5",
+            ]
+        );
+    }
+}
@@ -0,0 +1,141 @@
+use std::fmt;
+
+use crate::helixc::parser::helix_parser::Loc;
+
+// Error codes, stable so editors and CI can match on them
+pub const SYNTAX_ERROR: &str = "E0001";
+pub const INVALID_DEFINITION: &str = "E0002";
+pub const UNKNOWN_NODE_TYPE: &str = "E0101";
+pub const UNKNOWN_EDGE_TYPE: &str = "E0102";
+pub const UNKNOWN_VECTOR_TYPE: &str = "E0103";
+pub const UNKNOWN_TYPE: &str = "E0104";
+pub const UNKNOWN_PROPERTY: &str = "E0105";
+pub const UNKNOWN_VARIABLE: &str = "E0106";
+pub const UNKNOWN_LABEL: &str = "E0107";
+pub const DUPLICATE_DEFINITION: &str = "E0108";
+pub const UNKNOWN_FUNCTION: &str = "E0109";
+pub const WRONG_EDGE_END: &str = "E0201";
+pub const TYPE_MISMATCH: &str = "E0202";
+pub const WRONG_STEP_INPUT: &str = "E0203";
+pub const NOT_A_CONDITION: &str = "E0204";
+pub const NOT_ITERABLE: &str = "E0205";
+pub const WRONG_ARGUMENT_COUNT: &str = "E0206";
+pub const RECURSIVE_FUNCTION: &str = "E0207";
+pub const FUNCTION_WRITES: &str = "E0208";
+
+/// An error in a `.hx` source
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    /// The span the error is about
+    pub loc: Loc,
+    pub message: String,
+    /// Related spans, e.g. the schema a label was declared in
+    pub labels: Vec<Label>,
+    /// Name that was probably meant, for misspelled labels, properties and variables
+    pub suggestion: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub loc: Loc,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(code: &'static str, loc: Loc, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            loc,
+            message: message.into(),
+            labels: Vec::new(),
+            suggestion: None,
+        }
+    }
+
+    pub fn with_label(mut self, loc: Loc, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            loc,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_suggestion(mut self, suggestion: Option<String>) -> Self {
+        self.suggestion = suggestion;
+        self
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.loc, self.message)
+    }
+}
+
+/// The candidate closest to `name`, if it is close enough to be a typo
+pub fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<String> {
+    let max_cost = (name.chars().count() / 3).max(1) * 2;
+    candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| (edit_cost(name, candidate), candidate))
+        .filter(|(cost, _)| *cost <= max_cost)
+        .min()
+        .map(|(_, candidate)| candidate.to_string())
+}
+
+/// Levenshtein distance in half edits, a change of case costs one and any other edit two,
+/// so `user` suggests `User` over `uses`. Swapping two neighbouring characters is one edit.
+fn edit_cost(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    let mut before = Vec::new();
+    let mut previous = (0..=b.len()).map(|i| i * 2).collect::<Vec<_>>();
+    for i in 0..a.len() {
+        let mut current = vec![(i + 1) * 2; b.len() + 1];
+        for j in 0..b.len() {
+            let substitution = match (a[i], b[j]) {
+                (a, b) if a == b => 0,
+                (a, b) if a.eq_ignore_ascii_case(&b) => 1,
+                _ => 2,
+            };
+            current[j + 1] = (previous[j] + substitution)
+                .min(previous[j + 1] + 2)
+                .min(current[j] + 2);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] && a[i] != b[j] {
+                current[j + 1] = current[j + 1].min(before[j - 1] + 2);
+            }
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_did_you_mean() {
+        let labels = ["User", "Post", "Follows"];
+        assert_eq!(did_you_mean("Usr", labels), Some("User".to_string()));
+        assert_eq!(did_you_mean("user", labels), Some("User".to_string()));
+        assert_eq!(did_you_mean("Folows", labels), Some("Follows".to_string()));
+        assert_eq!(did_you_mean("Psot", labels), Some("Post".to_string()));
+        assert_eq!(did_you_mean("Comment", labels), None);
+        assert_eq!(did_you_mean("User", labels), None);
+    }
+
+    #[test]
+    fn test_edit_cost() {
+        assert_eq!(edit_cost("kitten", "sitting"), 6);
+        assert_eq!(edit_cost("", "abc"), 6);
+        assert_eq!(edit_cost("name", "Name"), 1);
+        assert_eq!(edit_cost("nmae", "name"), 2);
+        assert!(edit_cost("user", "User") < edit_cost("user", "uses"));
+    }
+}
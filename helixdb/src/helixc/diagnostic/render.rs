@@ -0,0 +1,262 @@
+use sonic_rs::Serialize;
+
+use super::diagnostic::Diagnostic;
+use crate::helixc::parser::helix_parser::Loc;
+
+/// The `.hx` files of a project joined into one source, so diagnostics from the joined
+/// source can be mapped back to the file they came from
+pub struct SourceFiles {
+    files: Vec<(String, usize)>,
+    contents: String,
+}
+
+impl SourceFiles {
+    pub fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            contents: String::new(),
+        }
+    }
+
+    pub fn add(&mut self, name: impl Into<String>, contents: &str) {
+        if !self.files.is_empty() {
+            self.contents.push('\n');
+        }
+        let start_line = self.contents.matches('\n').count() + 1;
+        self.files.push((name.into(), start_line));
+        self.contents.push_str(contents);
+    }
+
+    pub fn contents(&self) -> &str {
+        &self.contents
+    }
+
+    /// File name and line within that file for a line of the joined source
+    pub fn locate(&self, line: usize) -> (&str, usize) {
+        match self
+            .files
+            .iter()
+            .rev()
+            .find(|(_, start_line)| *start_line <= line)
+        {
+            Some((name, start_line)) => (name, line - start_line + 1),
+            None => (
+                self.files.first().map_or("", |(name, _)| name.as_str()),
+                line,
+            ),
+        }
+    }
+
+    /// Line of the joined source that file `name` starts at
+    pub fn start_line(&self, name: &str) -> Option<usize> {
+        self.files
+            .iter()
+            .find(|(file, _)| file == name)
+            .map(|(_, start_line)| *start_line)
+    }
+
+    /// Renders a diagnostic with the offending source lines underlined
+    pub fn render(&self, diagnostic: &Diagnostic) -> String {
+        let width = std::iter::once(&diagnostic.loc)
+            .chain(diagnostic.labels.iter().map(|label| &label.loc))
+            .map(|loc| self.locate(loc.line).1.to_string().len())
+            .max()
+            .unwrap_or(1);
+        let gutter = " ".repeat(width);
+
+        let mut out = format!("error[{}]: {}\n", diagnostic.code, diagnostic.message);
+        self.snippet(&mut out, &gutter, "-->", &diagnostic.loc, '^', "");
+        for label in &diagnostic.labels {
+            self.snippet(&mut out, &gutter, ":::", &label.loc, '-', &label.message);
+        }
+        if let Some(suggestion) = &diagnostic.suggestion {
+            out.push_str(&format!("{gutter} = help: did you mean `{suggestion}`?\n"));
+        }
+        out
+    }
+
+    fn snippet(
+        &self,
+        out: &mut String,
+        gutter: &str,
+        arrow: &str,
+        loc: &Loc,
+        marker: char,
+        message: &str,
+    ) {
+        let (name, line) = self.locate(loc.line);
+        if loc.line == 0 {
+            out.push_str(&format!("{gutter}{arrow} {name}\n"));
+            return;
+        }
+        out.push_str(&format!("{gutter}{arrow} {name}:{line}:{}\n", loc.column));
+        let text = match self.contents.lines().nth(loc.line - 1) {
+            Some(text) => text,
+            None => return,
+        };
+
+        // spans over several lines are underlined to the end of their first line
+        let length = text.chars().count();
+        let start = loc.column.saturating_sub(1).min(length);
+        let end = match loc.end_line == loc.line {
+            true => loc.end_column.saturating_sub(1).min(length),
+            false => length,
+        };
+        // keep tabs so the markers line up with the source line
+        let padding = text
+            .chars()
+            .take(start)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        let markers = marker.to_string().repeat(end.saturating_sub(start).max(1));
+
+        out.push_str(&format!("{gutter} |\n"));
+        out.push_str(&format!("{line:>width$} | {text}\n", width = gutter.len()));
+        let underline = format!("{gutter} | {padding}{markers} {message}");
+        out.push_str(underline.trim_end());
+        out.push('\n');
+    }
+
+    /// Diagnostics as a JSON array, for editors and CI
+    pub fn to_json(&self, diagnostics: &[Diagnostic]) -> String {
+        let diagnostics = diagnostics
+            .iter()
+            .map(|diagnostic| JsonDiagnostic {
+                span: self.span(&diagnostic.loc),
+                severity: "error",
+                code: diagnostic.code,
+                message: &diagnostic.message,
+                labels: diagnostic
+                    .labels
+                    .iter()
+                    .map(|label| JsonLabel {
+                        span: self.span(&label.loc),
+                        message: &label.message,
+                    })
+                    .collect(),
+                suggestion: diagnostic.suggestion.as_deref(),
+            })
+            .collect::<Vec<_>>();
+        sonic_rs::to_string(&diagnostics).unwrap_or_else(|_| "[]".to_string())
+    }
+
+    fn span(&self, loc: &Loc) -> JsonSpan<'_> {
+        let (file, line) = self.locate(loc.line);
+        let end_line = loc.end_line.max(loc.line) - loc.line + line;
+        JsonSpan {
+            file,
+            line,
+            column: loc.column,
+            end_line,
+            end_column: loc.end_column,
+        }
+    }
+}
+
+impl Default for SourceFiles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Serialize)]
+struct JsonSpan<'a> {
+    file: &'a str,
+    line: usize,
+    column: usize,
+    end_line: usize,
+    end_column: usize,
+}
+
+#[derive(Serialize)]
+struct JsonLabel<'a> {
+    #[serde(flatten)]
+    span: JsonSpan<'a>,
+    message: &'a str,
+}
+
+#[derive(Serialize)]
+struct JsonDiagnostic<'a> {
+    #[serde(flatten)]
+    span: JsonSpan<'a>,
+    severity: &'static str,
+    code: &'static str,
+    message: &'a str,
+    labels: Vec<JsonLabel<'a>>,
+    suggestion: Option<&'a str>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helixc::diagnostic::diagnostic::UNKNOWN_EDGE_TYPE;
+
+    fn at(line: usize, column: usize, end_line: usize, end_column: usize) -> Loc {
+        Loc {
+            line,
+            column,
+            end_line,
+            end_column,
+        }
+    }
+
+    fn files() -> SourceFiles {
+        let mut files = SourceFiles::new();
+        files.add("schema.hx", "N::User {\n    name: String\n}\n\nE::Follows {\n    From: User,\n    To: User,\n    Properties: {}\n}");
+        files.add(
+            "queries.hx",
+            "QUERY follows() =>\n    users <- N<User>::Out<Folows>\n    RETURN users",
+        );
+        files
+    }
+
+    #[test]
+    fn test_locate() {
+        let files = files();
+        assert_eq!(files.locate(1), ("schema.hx", 1));
+        assert_eq!(files.locate(9), ("schema.hx", 9));
+        assert_eq!(files.locate(10), ("queries.hx", 1));
+        assert_eq!(files.locate(11), ("queries.hx", 2));
+        assert_eq!(files.start_line("queries.hx"), Some(10));
+        assert_eq!(files.start_line("missing.hx"), None);
+    }
+
+    #[test]
+    fn test_render() {
+        let files = files();
+        let diagnostic = Diagnostic::new(
+            UNKNOWN_EDGE_TYPE,
+            at(11, 21, 11, 34),
+            "Unknown edge type Folows",
+        )
+        .with_label(at(5, 1, 9, 2), "Follows is defined here")
+        .with_suggestion(Some("Follows".to_string()));
+        let expected = "\
+error[E0102]: Unknown edge type Folows
+ --> queries.hx:2:21
+  |
+2 |     users <- N<User>::Out<Folows>
+  |                     ^^^^^^^^^^^^^
+ ::: schema.hx:5:1
+  |
+5 | E::Follows {
+  | ------------ Follows is defined here
+  = help: did you mean `Follows`?
+";
+        assert_eq!(files.render(&diagnostic), expected);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let files = files();
+        let diagnostic = Diagnostic::new(
+            UNKNOWN_EDGE_TYPE,
+            at(11, 21, 11, 34),
+            "Unknown edge type Folows",
+        );
+        assert_eq!(
+            files.to_json(&[diagnostic]),
+            r#"[{"file":"queries.hx","line":2,"column":21,"end_line":2,"end_column":34,"severity":"error","code":"E0102","message":"Unknown edge type Folows","labels":[],"suggestion":null}]"#
+        );
+    }
+}
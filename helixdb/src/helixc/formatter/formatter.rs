@@ -0,0 +1,604 @@
+use super::syntax::{Element, SyntaxNode};
+use crate::helixc::{diagnostic::diagnostic::Diagnostic, parser::helix_parser::Rule};
+
+/// Lines longer than this are broken up
+const MAX_WIDTH: usize = 100;
+const INDENT: usize = 4;
+
+/// Formats the contents of a `.hx` file. Comments are kept, and so are blank lines
+/// between statements and schema fields
+pub fn format_source(input: &str) -> Result<String, Diagnostic> {
+    let tree = SyntaxNode::parse(input)?;
+    let mut builder = Builder::new();
+    builder.source(&tree);
+    let formatted = print(&builder.finish());
+    let formatted = formatted.trim_end();
+    if formatted.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(format!("{}\n", formatted))
+}
+
+/// Layout of the formatted text, a group goes on one line if it fits and has its line
+/// breaks taken otherwise
+#[derive(Debug)]
+enum Doc {
+    Text(String),
+    /// A space, or a line break if the group is broken
+    Line,
+    /// Nothing, or a line break if the group is broken
+    SoftLine,
+    /// Always a line break, the groups around it are broken too
+    HardLine,
+    Indent(Vec<Doc>),
+    Group(Vec<Doc>),
+}
+
+/// Separator asked for before the next token, the strongest one asked for is used
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Break {
+    Soft,
+    Line,
+    Hard,
+}
+
+#[derive(Debug)]
+enum Frame {
+    Root,
+    /// A statement
+    Group,
+    /// A traversal or pattern, its steps or paths go on indented lines when it does not fit
+    Chain,
+    /// The statements of a query
+    Block,
+    /// Between brackets, schema bodies and `IF`/`FOR` blocks are `hard` and always have a
+    /// field or statement per line
+    Bracket {
+        hard: bool,
+    },
+}
+
+struct Builder {
+    frames: Vec<(Frame, Vec<Doc>)>,
+    pending: Option<Break>,
+    /// Whether the separator before the next token has already been written
+    separated: bool,
+    previous: Option<(String, Rule)>,
+    /// Whether a line break was skipped since the last token
+    newline: bool,
+    /// Whether a blank line was skipped since the last token
+    blank: bool,
+}
+
+impl Builder {
+    fn new() -> Self {
+        Self {
+            frames: vec![(Frame::Root, Vec::new())],
+            pending: None,
+            separated: false,
+            previous: None,
+            newline: false,
+            blank: false,
+        }
+    }
+
+    fn finish(mut self) -> Vec<Doc> {
+        self.frames.pop().map(|(_, docs)| docs).unwrap_or_default()
+    }
+
+    /// Definitions get a blank line between them, apart from consecutive imports, and
+    /// comments stay where they were
+    fn source(&mut self, source: &SyntaxNode) {
+        let mut after_definition = false;
+        let mut previous_rule = None;
+        for child in &source.children {
+            match child {
+                Element::Whitespace(whitespace) => self.whitespace(whitespace),
+                Element::Comment(comment) => {
+                    if after_definition && self.newline {
+                        self.blank = true;
+                        after_definition = false;
+                    }
+                    self.comment(comment, source.rule);
+                }
+                Element::Node(node) if node.rule != Rule::EOI => {
+                    if self.previous.is_some() {
+                        let imports =
+                            previous_rule == Some(Rule::import) && node.rule == Rule::import;
+                        self.blank |= after_definition && !imports;
+                        self.request(Break::Hard);
+                    }
+                    self.node(node);
+                    after_definition = true;
+                    previous_rule = Some(node.rule);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn node(&mut self, node: &SyntaxNode) {
+        let depth = self.frames.len();
+        if matches!(
+            node.rule,
+            Rule::traversal
+                | Rule::id_traversal
+                | Rule::anonymous_traversal
+                | Rule::AddE
+                | Rule::UpsertE
+                | Rule::match_pattern
+        ) {
+            self.separate_before(node);
+            self.open(Frame::Chain);
+        }
+        let mut brackets = Vec::new();
+        for (i, child) in node.children.iter().enumerate() {
+            match child {
+                Element::Whitespace(whitespace) => self.whitespace(whitespace),
+                Element::Comment(comment) => self.comment(comment, node.rule),
+                Element::Token(token) => {
+                    self.token(token, node.rule, &node.children[i + 1..], &mut brackets)
+                }
+                Element::Node(child) => self.child(node.rule, child),
+            }
+        }
+        while self.frames.len() > depth {
+            self.close();
+        }
+    }
+
+    fn child(&mut self, parent: Rule, child: &SyntaxNode) {
+        match child.rule {
+            Rule::step | Rule::last_step | Rule::to | Rule::from => {
+                self.request(Break::Soft);
+                self.node(child);
+            }
+            Rule::return_stmt => self.statement(child),
+            // the body of a function goes on its own line, like the statements of a query
+            Rule::evaluates_to_anything if parent == Rule::function_def => self.statement(child),
+            _ if matches!(parent, Rule::query_body | Rule::block) => self.statement(child),
+            _ => self.node(child),
+        }
+    }
+
+    fn statement(&mut self, statement: &SyntaxNode) {
+        self.request(Break::Hard);
+        self.separate_before(statement);
+        self.open(Frame::Group);
+        self.node(statement);
+        self.close();
+    }
+
+    fn token(&mut self, token: &str, rule: Rule, rest: &[Element], brackets: &mut Vec<bool>) {
+        match token {
+            "(" | "{" | "[" => {
+                self.text(token, rule);
+                // `({` hugs, the object inside breaks instead of the parentheses
+                let next = rest.iter().find_map(|element| match element {
+                    Element::Node(node) => node.first_token().map(|(text, _)| text),
+                    Element::Token(text) => Some(text.as_str()),
+                    _ => None,
+                });
+                let hug = token == "(" && next == Some("{");
+                brackets.push(!hug);
+                if !hug {
+                    let hard = token == "{"
+                        && matches!(
+                            rule,
+                            Rule::node_body | Rule::edge_body | Rule::properties | Rule::block
+                        );
+                    self.open(Frame::Bracket { hard });
+                    self.request(if hard { Break::Hard } else { Break::Soft });
+                }
+            }
+            ")" | "}" | "]" if brackets.pop() == Some(true) => self.close_bracket(token, rule),
+            "," => {
+                self.text(token, rule);
+                let hard = matches!(self.frames.last(), Some((Frame::Bracket { hard: true }, _)));
+                self.request(if hard { Break::Hard } else { Break::Line });
+            }
+            "=>" => {
+                self.text(token, rule);
+                self.open(Frame::Block);
+            }
+            _ => self.text(token, rule),
+        }
+    }
+
+    fn text(&mut self, text: &str, rule: Rule) {
+        if !std::mem::take(&mut self.separated) {
+            self.separate(text, rule);
+        }
+        self.push(Doc::Text(text.to_string()));
+        self.previous = Some((text.to_string(), rule));
+        self.newline = false;
+    }
+
+    /// Line comments end the line, one that had a line to itself keeps it
+    fn comment(&mut self, comment: &str, rule: Rule) {
+        if self.previous.is_some() {
+            if self.newline {
+                self.request(Break::Hard);
+                self.separate(comment, rule);
+            } else {
+                self.push(Doc::Text(" ".to_string()));
+            }
+        }
+        self.push(Doc::Text(comment.trim_end().to_string()));
+        self.previous = Some((comment.to_string(), rule));
+        self.newline = false;
+        self.blank = false;
+        self.request(Break::Hard);
+    }
+
+    fn whitespace(&mut self, whitespace: &str) {
+        let lines = whitespace.matches('\n').count();
+        self.newline |= lines > 0;
+        self.blank |= lines > 1;
+    }
+
+    fn request(&mut self, separator: Break) {
+        self.pending = self.pending.max(Some(separator));
+    }
+
+    /// Writes the separator between the previous token and `text`
+    fn separate(&mut self, text: &str, rule: Rule) {
+        match self.pending.take() {
+            Some(Break::Hard) => {
+                self.push(Doc::HardLine);
+                let opened = self.previous.as_ref().is_some_and(|(previous, _)| {
+                    matches!(previous.as_str(), "=>" | "(" | "{" | "[")
+                });
+                if self.blank && !opened {
+                    self.push(Doc::HardLine);
+                }
+            }
+            Some(Break::Line) => self.push(Doc::Line),
+            Some(Break::Soft) => self.push(Doc::SoftLine),
+            None => {
+                if let Some((previous, previous_rule)) = &self.previous {
+                    if spaced(previous, *previous_rule, text, rule) {
+                        self.push(Doc::Text(" ".to_string()));
+                    }
+                }
+            }
+        }
+        self.blank = false;
+    }
+
+    /// Writes the separator before `node` ahead of time, so it goes outside the group
+    /// `node` is about to open
+    fn separate_before(&mut self, node: &SyntaxNode) {
+        if let Some((text, rule)) = node.first_token() {
+            if !self.separated {
+                self.separate(text, rule);
+                self.separated = true;
+            }
+        }
+    }
+
+    fn push(&mut self, doc: Doc) {
+        if let Some((_, docs)) = self.frames.last_mut() {
+            docs.push(doc);
+        }
+    }
+
+    fn open(&mut self, frame: Frame) {
+        self.frames.push((frame, Vec::new()));
+    }
+
+    fn close(&mut self) {
+        let Some((frame, docs)) = self.frames.pop() else {
+            return;
+        };
+        self.push(match frame {
+            Frame::Group => Doc::Group(docs),
+            Frame::Chain => Doc::Group(vec![Doc::Indent(docs)]),
+            Frame::Block => Doc::Indent(docs),
+            Frame::Root | Frame::Bracket { .. } => unreachable!("{:?} closed as a group", frame),
+        });
+    }
+
+    fn close_bracket(&mut self, text: &str, rule: Rule) {
+        let Some((Frame::Bracket { hard }, docs)) = self.frames.pop() else {
+            unreachable!("closing bracket without an open one");
+        };
+        let mut group = Vec::new();
+        if !docs.is_empty() {
+            let hard = hard || self.pending == Some(Break::Hard);
+            group.push(Doc::Indent(docs));
+            group.push(if hard { Doc::HardLine } else { Doc::SoftLine });
+        }
+        group.push(Doc::Text(text.to_string()));
+        self.push(Doc::Group(group));
+        self.pending = None;
+        self.blank = false;
+        self.newline = false;
+        self.previous = Some((text.to_string(), rule));
+    }
+}
+
+/// Whether two tokens on the same line have a space between them
+fn spaced(previous: &str, previous_rule: Rule, next: &str, next_rule: Rule) -> bool {
+    match (previous, next) {
+        ("MATCH", _) => true,
+        ("(" | "[" | "{" | "<" | "::" | "!" | "|" | "-[" | "<-[" | "]->" | "]-", _) => false,
+        // `(u:User)` in patterns, `name: String` everywhere else
+        (":", _) => !matches!(previous_rule, Rule::pattern_node | Rule::pattern_edge_body),
+        (_, "{") => matches!(
+            next_rule,
+            Rule::node_body | Rule::edge_body | Rule::pattern_props | Rule::block
+        ),
+        (
+            _,
+            ")" | "]" | "}" | ">" | "," | ":" | "::" | "(" | "<" | "|" | "-[" | "<-[" | "]->"
+            | "]-",
+        ) => false,
+        _ => true,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+fn print(docs: &[Doc]) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    // indentation of the last line break, written with the first text after it so blank
+    // lines stay empty
+    let mut line_start = None;
+    let mut stack = docs
+        .iter()
+        .rev()
+        .map(|doc| (0, Mode::Break, doc))
+        .collect::<Vec<_>>();
+    while let Some((indent, mode, doc)) = stack.pop() {
+        match doc {
+            Doc::Text(text) => {
+                if let Some(indent) = line_start.take() {
+                    out.push_str(&" ".repeat(indent));
+                }
+                out.push_str(text);
+                column += text.chars().count();
+            }
+            Doc::Line if mode == Mode::Flat => {
+                out.push(' ');
+                column += 1;
+            }
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                out.truncate(out.trim_end_matches(' ').len());
+                out.push('\n');
+                column = indent;
+                line_start = Some(indent);
+            }
+            Doc::Indent(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (indent + INDENT, mode, doc)))
+            }
+            Doc::Group(docs) => {
+                let width = MAX_WIDTH as isize - column as isize;
+                let mode = if mode == Mode::Flat || fits(width, docs, &stack) {
+                    Mode::Flat
+                } else {
+                    Mode::Break
+                };
+                stack.extend(docs.iter().rev().map(|doc| (indent, mode, doc)));
+            }
+        }
+    }
+    out
+}
+
+/// Whether `docs` fit in `width` on one line, along with what follows them up to the next
+/// line break
+fn fits(mut width: isize, docs: &[Doc], rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut stack = docs
+        .iter()
+        .rev()
+        .map(|doc| (Mode::Flat, doc))
+        .collect::<Vec<_>>();
+    let mut rest = rest.iter().rev();
+    while width >= 0 {
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some((_, mode, doc)) => (*mode, *doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(text) => width -= text.chars().count() as isize,
+            Doc::Line if mode == Mode::Flat => width -= 1,
+            Doc::SoftLine if mode == Mode::Flat => {}
+            Doc::HardLine if mode == Mode::Flat => return false,
+            Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+            Doc::Indent(docs) | Doc::Group(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (mode, doc)))
+            }
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helixc::diagnostic::diagnostic::SYNTAX_ERROR;
+
+    #[test]
+    fn test_format_schema() {
+        let input = r#"N::User{name:String,
+  age:Integer}
+E::Follows {From:User,To:User,Properties:{since:Integer}}
+V::Embedding
+"#;
+        let expected = r#"N::User {
+    name: String,
+    age: Integer
+}
+
+E::Follows {
+    From: User,
+    To: User,
+    Properties: {
+        since: Integer
+    }
+}
+
+V::Embedding
+"#;
+        assert_eq!(format_source(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_query_keeps_comments_and_blank_lines() {
+        let input = r#"// lookups
+QUERY find(id:String,limit:Integer)PARALLEL(4)=>  // by id
+  user<-N<User>(id)
+  // who they follow
+
+  followed <- user::Out<Follows>::RANGE(0,limit)::{name,age}
+  RETURN user,followed
+"#;
+        let expected = r#"// lookups
+QUERY find(id: String, limit: Integer) PARALLEL(4) => // by id
+    user <- N<User>(id)
+    // who they follow
+
+    followed <- user::Out<Follows>::RANGE(0, limit)::{name, age}
+    RETURN user, followed
+"#;
+        assert_eq!(format_source(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_breaks_long_lines() {
+        let input = r#"QUERY long(id: String) =>
+    posts <- N<User>(id)::Out<Follows>::Out<Authored>::WHERE(_::{published}::EQ(true))::ORDER(score DESC)::RANGE(0, 10)
+    AddN<Account>({name: "a fairly long account name", email: "someone@example.com", age: 42, verified: true})
+    RETURN posts
+"#;
+        let expected = r#"QUERY long(id: String) =>
+    posts <- N<User>(id)
+        ::Out<Follows>
+        ::Out<Authored>
+        ::WHERE(_::{published}::EQ(true))
+        ::ORDER(score DESC)
+        ::RANGE(0, 10)
+    AddN<Account>({
+        name: "a fairly long account name",
+        email: "someone@example.com",
+        age: 42,
+        verified: true
+    })
+    RETURN posts
+"#;
+        assert_eq!(format_source(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_patterns_and_edges() {
+        let input = r#"QUERY mutual() =>
+    pairs <- MATCH (a:User)-[:Follows]->(b:User {name: "x"}), (b)<-[f:Follows]-(a)
+    rows <- MATCH (u:User {name: name})-[b:Bought]->(p1:Product)-[:InCategory]->(c:Category), (c)<-[:InCategory]-(p2:Product)
+    AddE<Follows>({since:1})::To(a)::From(b)
+    RETURN pairs::!{id,..}
+"#;
+        let expected = r#"QUERY mutual() =>
+    pairs <- MATCH (a:User)-[:Follows]->(b:User {name: "x"}), (b)<-[f:Follows]-(a)
+    rows <- MATCH (u:User {name: name})-[b:Bought]->(p1:Product)-[:InCategory]->(c:Category),
+        (c)<-[:InCategory]-(p2:Product)
+    AddE<Follows>({since: 1})::To(a)::From(b)
+    RETURN pairs::!{id, ..}
+"#;
+        assert_eq!(format_source(input).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_is_idempotent() {
+        let input = r#"N::User { name: String } // users
+// queries
+QUERY q(id: String) =>
+    u <- N<User>(id) // start
+        // then follows
+        ::Out<Follows>
+    RETURN u
+"#;
+        let once = format_source(input).unwrap();
+        assert_eq!(format_source(&once).unwrap(), once);
+        assert!(once.contains("N::User {\n    name: String\n} // users\n\n// queries\n"));
+        assert!(once.contains("    u <- N<User>(id) // start\n        // then follows\n"));
+    }
+
+    #[test]
+    fn test_format_keeps_literals_intact() {
+        let input = r#"QUERY q() =>
+    cold <- N<Reading>::WHERE(_::{celsius}::GT(-1.5e3))
+    e <- AddN<Event>({at: 2024-02-29T13:45:30Z, note: "a \"b\"", body: """
+        kept   as is
+    """})
+    RETURN cold
+"#;
+        let formatted = format_source(input).unwrap();
+        assert!(formatted.contains("::GT(-1.5e3)"));
+        assert!(formatted.contains("at: 2024-02-29T13:45:30Z"));
+        assert!(formatted.contains(r#"note: "a \"b\"""#));
+        assert!(formatted.contains("\"\"\"\n        kept   as is\n    \"\"\""));
+    }
+
+    #[test]
+    fn test_format_control_flow() {
+        let input = r#"QUERY follow(id: String, ids: [String], force: Boolean) =>
+  user <- N<User>(id)
+  IF force{FOR other IN ids{AddE<Follows>::From(user)::To(other)}}ELSE IF EXISTS(user::Out<Follows>){
+  RETURN "done"}
+  RETURN user
+"#;
+        let expected = r#"QUERY follow(id: String, ids: [String], force: Boolean) =>
+    user <- N<User>(id)
+    IF force {
+        FOR other IN ids {
+            AddE<Follows>::From(user)::To(other)
+        }
+    } ELSE IF EXISTS(user::Out<Follows>) {
+        RETURN "done"
+    }
+    RETURN user
+"#;
+        assert_eq!(format_source(input).unwrap(), expected);
+        assert_eq!(format_source(expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_functions_and_imports() {
+        let input = r#"IMPORT "users.hx"
+IMPORT   "posts.hx"
+FN followers(user: User) => user::In<Follows>
+QUERY fans(id: String) =>
+  user <- N<User>(id)
+  RETURN followers( user )
+"#;
+        let expected = r#"IMPORT "users.hx"
+IMPORT "posts.hx"
+
+FN followers(user: User) =>
+    user::In<Follows>
+
+QUERY fans(id: String) =>
+    user <- N<User>(id)
+    RETURN followers(user)
+"#;
+        assert_eq!(format_source(input).unwrap(), expected);
+        assert_eq!(format_source(expected).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_format_reports_syntax_errors() {
+        let error = format_source("N::User {\n    name String\n}\n").unwrap_err();
+        assert_eq!(error.code, SYNTAX_ERROR);
+        assert_eq!(error.loc.line, 2);
+    }
+}
@@ -0,0 +1,2 @@
+pub mod formatter;
+pub mod syntax;
@@ -0,0 +1,172 @@
+use std::fmt;
+
+use pest::{iterators::Pair, Parser as PestParser};
+
+use crate::helixc::{
+    diagnostic::diagnostic::Diagnostic,
+    parser::helix_parser::{syntax_error, HelixParser, Rule},
+};
+
+/// Rules kept as a single token, so strings and types are never split up
+const LEAVES: &[Rule] = &[
+    Rule::string_literal,
+    Rule::date,
+    Rule::type_name,
+    Rule::identifier,
+    Rule::identifier_upper,
+    Rule::integer,
+    Rule::float,
+];
+
+/// Punctuation longer than one character, longest first so `<-[` is not read as `<-`
+const PUNCTUATION: &[&str] = &["<-[", "]->", "=>", "<-", "::", "-[", "]-", ".."];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element {
+    Node(SyntaxNode),
+    Token(String),
+    Whitespace(String),
+    Comment(String),
+}
+
+/// A rule of the grammar with all of its text, including the whitespace and comments the
+/// grammar skips, so printing the tree gives back the input it was parsed from
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxNode {
+    pub rule: Rule,
+    pub children: Vec<Element>,
+}
+
+impl SyntaxNode {
+    pub fn parse(input: &str) -> Result<SyntaxNode, Diagnostic> {
+        let pair = HelixParser::parse(Rule::source, input)
+            .map_err(|error| syntax_error(&error))?
+            .next()
+            .expect("source always produces a pair");
+        let (mut node, trailing) = Self::build(pair, input);
+        node.children.extend(trailing);
+        Ok(node)
+    }
+
+    /// Builds the node for `pair`, handing back the whitespace and comments at its end.
+    /// Rules ending in an optional part take the whitespace after them, it belongs to the
+    /// parent so a comment on the next line is not attached to the end of a traversal.
+    fn build(pair: Pair<Rule>, input: &str) -> (SyntaxNode, Vec<Element>) {
+        let rule = pair.as_rule();
+        let span = pair.as_span();
+        if LEAVES.contains(&rule) {
+            let children = vec![Element::Token(span.as_str().to_string())];
+            return (SyntaxNode { rule, children }, Vec::new());
+        }
+
+        let mut children = Vec::new();
+        let mut position = span.start();
+        for child in pair.into_inner() {
+            let child_span = child.as_span();
+            lex(&input[position..child_span.start()], &mut children);
+            position = child_span.end();
+            let (node, trailing) = Self::build(child, input);
+            children.push(Element::Node(node));
+            children.extend(trailing);
+        }
+        lex(&input[position..span.end()], &mut children);
+
+        let end = children
+            .iter()
+            .rposition(|child| !matches!(child, Element::Whitespace(_) | Element::Comment(_)))
+            .map_or(0, |i| i + 1);
+        let trailing = children.split_off(end);
+        (SyntaxNode { rule, children }, trailing)
+    }
+
+    /// The first token of the node and the rule it belongs to
+    pub fn first_token(&self) -> Option<(&str, Rule)> {
+        self.children.iter().find_map(|child| match child {
+            Element::Node(node) => node.first_token(),
+            Element::Token(text) => Some((text.as_str(), self.rule)),
+            _ => None,
+        })
+    }
+}
+
+impl fmt::Display for SyntaxNode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                Element::Node(node) => write!(f, "{}", node)?,
+                Element::Token(text) | Element::Whitespace(text) | Element::Comment(text) => {
+                    f.write_str(text)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Splits the text between two child rules into keywords, punctuation, whitespace and
+/// comments
+fn lex(mut text: &str, elements: &mut Vec<Element>) {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    while let Some(c) = text.chars().next() {
+        let end = if c.is_whitespace() {
+            text.find(|c: char| !c.is_whitespace())
+        } else if text.starts_with("//") {
+            text.find('\n')
+        } else if is_word(c) {
+            text.find(|c: char| !is_word(c))
+        } else {
+            Some(
+                PUNCTUATION
+                    .iter()
+                    .find(|punctuation| text.starts_with(**punctuation))
+                    .map_or(c.len_utf8(), |punctuation| punctuation.len()),
+            )
+        };
+        let (piece, rest) = text.split_at(end.unwrap_or(text.len()));
+        elements.push(if c.is_whitespace() {
+            Element::Whitespace(piece.to_string())
+        } else if piece.starts_with("//") {
+            Element::Comment(piece.to_string())
+        } else {
+            Element::Token(piece.to_string())
+        });
+        text = rest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_syntax_tree_is_lossless() {
+        let input = r#"// users
+N::User {
+    name: String, // shown on profiles
+    age: Integer
+}
+
+QUERY  find(name:String)=>
+    user <- N<User>::WHERE(_::{name}::EQ(name))  // by name
+    // nothing else
+    RETURN user
+"#;
+        let tree = SyntaxNode::parse(input).unwrap();
+        assert_eq!(tree.to_string(), input);
+    }
+
+    #[test]
+    fn test_trailing_comments_belong_to_the_parent() {
+        let input = "QUERY q() =>\n    u <- N<User>\n    // last\n    RETURN u\n";
+        let tree = SyntaxNode::parse(input).unwrap();
+        let Some(Element::Node(query)) = tree.children.first() else {
+            panic!("expected a query, got {:?}", tree.children);
+        };
+        // not inside the traversal or the query body, both end before it
+        assert!(query
+            .children
+            .iter()
+            .any(|child| matches!(child, Element::Comment(comment) if comment == "// last")));
+        assert_eq!(query.first_token(), Some(("QUERY", Rule::query_def)));
+    }
+}
@@ -0,0 +1,366 @@
+use super::generator::{lazy_prefix_len, writes_to_graph};
+use crate::helixc::parser::helix_parser::{
+    BooleanOp, DagOperation, Expression, GraphStep, OrderDirection, Query, Source, StartNode,
+    Statement, Step, Traversal, UpsertNode,
+};
+
+/// Renders the plan `helix compile --explain` prints: the steps each query runs in order,
+/// which index every step reads through and whether it is streamed by `LazyTraversal` or
+/// run on a materialized `TraversalBuilder`.
+pub fn explain_source(source: &Source) -> String {
+    source
+        .queries
+        .iter()
+        .map(explain_query)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub fn explain_query(query: &Query) -> String {
+    let mut output = format!("QUERY {}", query.name);
+    let writes = writes_to_graph(query);
+    output.push_str(match writes {
+        true => " (write transaction",
+        false => " (read transaction",
+    });
+    match (writes, query.parallelism) {
+        (true, _) => output.push_str(", sequential)\n"),
+        (false, Some(threads)) => output.push_str(&format!(", parallelism {})\n", threads)),
+        (false, None) => output.push_str(")\n"),
+    }
+    output.push_str(&explain_statements(&query.statements));
+    output
+}
+
+fn explain_upsert_node(upsert: &UpsertNode) -> String {
+    let fields = upsert
+        .match_fields
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    format!(
+        "UpsertN<{}> matching {{{}}} via secondary index or label scan\n",
+        upsert.vertex_type.as_deref().unwrap_or(""),
+        fields.join(", ")
+    )
+}
+
+fn explain_statements(statements: &[Statement]) -> String {
+    let mut output = String::new();
+    for statement in statements {
+        match statement {
+            Statement::Assignment(assignment) => {
+                output.push_str(&format!("  {} <-", assignment.variable));
+                match &assignment.value {
+                    Expression::Traversal(traversal) => {
+                        output.push('\n');
+                        output.push_str(&explain_traversal(traversal));
+                    }
+                    Expression::Match(pattern) => output.push_str(&format!(
+                        " MATCH over {} nodes and {} edges, joined smallest label first\n",
+                        pattern.nodes.len(),
+                        pattern.edges.len()
+                    )),
+                    Expression::SearchVector(search) => output.push_str(&format!(
+                        " SearchV<{}> via HNSW vector index\n",
+                        search.vector_type.as_deref().unwrap_or("")
+                    )),
+                    Expression::AddNode(add) => output.push_str(&format!(
+                        " AddN<{}>\n",
+                        add.vertex_type.as_deref().unwrap_or("")
+                    )),
+                    Expression::AddEdge(add) => output.push_str(&format!(
+                        " AddE<{}>\n",
+                        add.edge_type.as_deref().unwrap_or("")
+                    )),
+                    Expression::UpsertNode(upsert) => {
+                        output.push_str(&format!(" {}", explain_upsert_node(upsert)))
+                    }
+                    Expression::UpsertEdge(upsert) => output.push_str(&format!(
+                        " UpsertE<{}>\n",
+                        upsert.edge_type.as_deref().unwrap_or("")
+                    )),
+                    _ => output.push_str(" value\n"),
+                }
+            }
+            Statement::AddNode(add) => output.push_str(&format!(
+                "  AddN<{}>\n",
+                add.vertex_type.as_deref().unwrap_or("")
+            )),
+            Statement::AddEdge(add) => output.push_str(&format!(
+                "  AddE<{}>\n",
+                add.edge_type.as_deref().unwrap_or("")
+            )),
+            Statement::UpsertNode(upsert) => {
+                output.push_str(&format!("  {}", explain_upsert_node(upsert)))
+            }
+            Statement::UpsertEdge(upsert) => output.push_str(&format!(
+                "  UpsertE<{}>\n",
+                upsert.edge_type.as_deref().unwrap_or("")
+            )),
+            Statement::Drop(Expression::Traversal(traversal)) => {
+                output.push_str("  DROP\n");
+                output.push_str(&explain_traversal(traversal));
+            }
+            Statement::Drop(_) => output.push_str("  DROP\n"),
+            Statement::AddVector(add) => output.push_str(&format!(
+                "  AddV<{}> into HNSW vector index\n",
+                add.vector_type.as_deref().unwrap_or("")
+            )),
+            Statement::BatchAddVector(add) => output.push_str(&format!(
+                "  BatchAddV<{}> into HNSW vector index\n",
+                add.vector_type.as_deref().unwrap_or("")
+            )),
+            Statement::SearchVector(search) => output.push_str(&format!(
+                "  SearchV<{}> via HNSW vector index\n",
+                search.vector_type.as_deref().unwrap_or("")
+            )),
+            Statement::If(if_statement) => {
+                output.push_str("  IF");
+                output.push_str(&explain_operand(&if_statement.condition));
+                output.push_str(&nested(&explain_statements(&if_statement.then)));
+                if !if_statement.otherwise.is_empty() {
+                    output.push_str("  ELSE\n");
+                    output.push_str(&nested(&explain_statements(&if_statement.otherwise)));
+                }
+            }
+            Statement::For(for_loop) => {
+                output.push_str(&format!("  FOR {} IN", for_loop.variable));
+                output.push_str(&explain_operand(&for_loop.iterable));
+                output.push_str(&nested(&explain_statements(&for_loop.body)));
+            }
+            Statement::Return(_) => output.push_str("  RETURN\n"),
+        }
+    }
+    output
+}
+
+/// The traversal an `IF` or `FOR` runs, or the variable it reads
+fn explain_operand(expression: &Expression) -> String {
+    match expression {
+        Expression::Traversal(traversal) | Expression::Exists(traversal) => {
+            format!("\n{}", explain_traversal(traversal))
+        }
+        Expression::Identifier(name) => format!(" {}\n", name),
+        _ => " value\n".to_string(),
+    }
+}
+
+/// Indents the plan of a block under the statement it belongs to
+fn nested(plan: &str) -> String {
+    plan.lines().map(|line| format!("  {}\n", line)).collect()
+}
+
+fn explain_traversal(traversal: &Traversal) -> String {
+    let tracks_paths = traversal.steps.iter().any(|step| matches!(step, Step::Path));
+    let from_storage = matches!(
+        traversal.start,
+        StartNode::Node { .. } | StartNode::Edge { .. }
+    );
+    let lazy_len = match from_storage && !tracks_paths {
+        true => lazy_prefix_len(
+            &traversal.steps,
+            matches!(traversal.start, StartNode::Node { .. }),
+        ),
+        false => 0,
+    };
+    let mode = |streamed: bool| match streamed {
+        true => "streamed",
+        false => "materialized",
+    };
+
+    let mut rows = vec![(
+        start_name(&traversal.start),
+        start_index(&traversal.start),
+        mode(from_storage),
+    )];
+    for (i, step) in traversal.steps.iter().enumerate() {
+        rows.push((step_name(step), step_index(step), mode(i < lazy_len)));
+    }
+
+    let width = rows.iter().map(|(name, _, _)| name.len()).max().unwrap_or(0);
+    rows.iter()
+        .enumerate()
+        .map(|(i, (name, index, mode))| {
+            format!("    {}. {:<width$}  {:<12}  {}\n", i + 1, name, mode, index, width = width)
+        })
+        .collect()
+}
+
+/// The start of a traversal as written in HelixQL, e.g. `N<User>(userID)`
+pub fn start_name(start: &StartNode) -> String {
+    let with_args = |prefix: &str, types: &Option<Vec<String>>, ids: &Option<Vec<String>>| {
+        let mut name = prefix.to_string();
+        if let Some(types) = types {
+            name.push_str(&format!("<{}>", types.join(", ")));
+        }
+        if let Some(ids) = ids {
+            name.push_str(&format!("({})", ids.join(", ")));
+        }
+        name
+    };
+    match start {
+        StartNode::Node { types, ids } => with_args("N", types, ids),
+        StartNode::Edge { types, ids } => with_args("E", types, ids),
+        StartNode::Variable(variable) => variable.clone(),
+        StartNode::Anonymous => "_".to_string(),
+        StartNode::Call(call) if call.args.is_empty() => format!("{}()", call.name),
+        StartNode::Call(call) => format!("{}(..)", call.name),
+    }
+}
+
+/// A step as written in HelixQL, nested traversals and arguments are left out
+pub fn step_name(step: &Step) -> String {
+    let labelled = |name: &str, types: &Option<Vec<String>>| match types {
+        Some(types) => format!("{}<{}>", name, types.join(", ")),
+        None => name.to_string(),
+    };
+    match step {
+        Step::Node(graph_step) | Step::Edge(graph_step) => match graph_step {
+            GraphStep::Out(types) => labelled("Out", types),
+            GraphStep::In(types) => labelled("In", types),
+            GraphStep::Both(types) => labelled("Both", types),
+            GraphStep::OutE(types) => labelled("OutE", types),
+            GraphStep::InE(types) => labelled("InE", types),
+            GraphStep::BothE(types) => labelled("BothE", types),
+            GraphStep::OutN => "OutN".to_string(),
+            GraphStep::InN => "InN".to_string(),
+            GraphStep::BothN => "BothN".to_string(),
+        },
+        Step::Where(_) => "WHERE".to_string(),
+        Step::BooleanOperation(op) => match op {
+            BooleanOp::And(_) => "AND",
+            BooleanOp::Or(_) => "OR",
+            BooleanOp::GreaterThan(_) => "GT",
+            BooleanOp::GreaterThanOrEqual(_) => "GTE",
+            BooleanOp::LessThan(_) => "LT",
+            BooleanOp::LessThanOrEqual(_) => "LTE",
+            BooleanOp::Equal(_) => "EQ",
+            BooleanOp::NotEqual(_) => "NEQ",
+        }
+        .to_string(),
+        Step::Count => "COUNT".to_string(),
+        Step::Path => "PATH".to_string(),
+        Step::Update(_) => "UPDATE".to_string(),
+        Step::Object(_) => "{..}".to_string(),
+        Step::Exclude(exclude) => format!("!{{{}}}", exclude.fields.join(", ")),
+        Step::Closure(closure) => format!("|{}|{{..}}", closure.identifier),
+        Step::Range(_) => "RANGE".to_string(),
+        Step::AddEdge(add) => format!("AddE<{}>", add.edge_type.as_deref().unwrap_or("")),
+        Step::SearchVector(vector_type) => format!("SearchV<{}>", vector_type),
+        Step::OrderBy(keys) => format!(
+            "ORDER({})",
+            keys.iter()
+                .map(|(key, direction)| match direction {
+                    OrderDirection::Asc => key.clone(),
+                    OrderDirection::Desc => format!("{} DESC", key),
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Step::Dedup(Some(property)) => format!("DEDUP({})", property),
+        Step::Dedup(None) => "DEDUP".to_string(),
+        Step::GroupBy(property) => format!("GROUP_BY({})", property),
+        Step::Aggregate(_) => "AGG".to_string(),
+        Step::As(label) => format!("AS({})", label),
+        Step::Select(labels) => format!("SELECT({})", labels.join(", ")),
+        Step::Union(branches) => format!("UNION({} branches)", branches.len()),
+        Step::Optional(_) => "OPTIONAL".to_string(),
+        Step::Coalesce(branches) => format!("COALESCE({} branches)", branches.len()),
+        Step::PredictLinks(predict) => format!(
+            "PredictLinks<{}>({})",
+            predict.edge_type.as_deref().unwrap_or(""),
+            predict.metric
+        ),
+        Step::Dag(dag) => {
+            let operation = match dag.operation {
+                DagOperation::Ancestors => "Ancestors",
+                DagOperation::Descendants => "Descendants",
+                DagOperation::TopoSort => "TopoSort",
+                DagOperation::FindCycle => "FindCycle",
+                DagOperation::CriticalPath => "CriticalPath",
+            };
+            format!("{}<{}>", operation, dag.edge_type)
+        }
+    }
+}
+
+fn start_index(start: &StartNode) -> String {
+    match start {
+        StartNode::Node { ids: Some(_), .. } => "nodes by id (n:)".to_string(),
+        StartNode::Node { types: Some(types), .. } => format!(
+            "node label index ({})",
+            types
+                .iter()
+                .map(|label| format!("nl:{}", label))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        StartNode::Node { .. } => "full node scan".to_string(),
+        StartNode::Edge { ids: Some(_), .. } => "edges by id (e:)".to_string(),
+        StartNode::Edge { .. } => "full edge scan".to_string(),
+        StartNode::Variable(_) => "variable".to_string(),
+        StartNode::Anonymous => "current item".to_string(),
+        StartNode::Call(_) => "function body".to_string(),
+    }
+}
+
+fn step_index(step: &Step) -> String {
+    match step {
+        Step::Node(graph_step) | Step::Edge(graph_step) => match graph_step {
+            GraphStep::Out(_) | GraphStep::OutE(_) => "out-edge index (o:)",
+            GraphStep::In(_) | GraphStep::InE(_) => "in-edge index (i:)",
+            GraphStep::Both(_) | GraphStep::BothE(_) => "out-edge and in-edge indices (o:, i:)",
+            GraphStep::OutN | GraphStep::InN | GraphStep::BothN => "nodes by id (n:)",
+        },
+        Step::PredictLinks(_) | Step::Dag(_) => "out-edge and in-edge indices (o:, i:)",
+        Step::SearchVector(_) => "HNSW vector index",
+        Step::AddEdge(_) | Step::Update(_) => "write",
+        _ => "none",
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helixc::parser::helix_parser::HelixParser;
+
+    #[test]
+    fn test_explain_query() {
+        let input = r#"
+        QUERY FriendsOfFriends(userID: String) PARALLEL(4) =>
+            friends <- N<User>(userID)::Out<Follows>::Out<Follows>::WHERE(_::{age}::GT(21))::ORDER(age DESC)
+            RETURN friends
+        "#;
+        let source = HelixParser::parse_source(input).unwrap();
+        let plan = explain_source(&source);
+
+        assert!(plan.starts_with("QUERY FriendsOfFriends (read transaction, parallelism 4)\n"));
+        assert!(plan.contains("  friends <-\n"));
+        let rows: Vec<&str> = plan.lines().skip(2).collect();
+        assert_eq!(rows.len(), 5);
+        assert!(rows[0].contains("1. N<User>(userID)"));
+        assert!(rows[0].contains("nodes by id (n:)"));
+        assert!(rows[1].contains("Out<Follows>") && rows[1].contains("streamed"));
+        assert!(rows[1].contains("out-edge index (o:)"));
+        assert!(rows[3].contains("WHERE") && rows[3].contains("materialized"));
+        assert!(rows[4].contains("ORDER(age DESC)"));
+    }
+
+    #[test]
+    fn test_explain_range_after_where() {
+        let input = r#"
+        QUERY AdultFollows(userID: String) =>
+            follows <- N<User>(userID)::Out<Follows>::WHERE(_::{age}::GT(21))::RANGE(0, 5)
+            RETURN follows
+        "#;
+        let source = HelixParser::parse_source(input).unwrap();
+        let plan = explain_source(&source);
+
+        let rows: Vec<&str> = plan.lines().skip(2).collect();
+        assert_eq!(rows.len(), 4);
+        assert!(rows[2].contains("WHERE") && rows[2].contains("streamed"));
+        assert!(rows[3].contains("RANGE") && rows[3].contains("streamed"));
+    }
+}
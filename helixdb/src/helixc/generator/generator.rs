@@ -1,13 +1,21 @@
 use crate::helixc::parser::helix_parser::{
-    AddEdge, AddNode, AddVector, Assignment, BatchAddVector, BooleanOp, EdgeConnection, EdgeSchema, EvaluatesToNumber, Expression, Field, FieldAddition, FieldType, FieldValue, GraphStep, IdType, NodeSchema, Parameter, Query, SearchVector, Source, StartNode::{Anonymous, Edge, Node, Variable}, Statement, Step, Traversal, ValueType, VectorData
+    AddEdge, AddNode, AddVector, Aggregation, Assignment, BatchAddVector, BooleanOp, Call, DagOperation, EdgeConnection, EdgeSchema, EvaluatesToNumber, Expression, Field, FieldAddition, FieldType, FieldValue, Function, GraphStep, IdType, IfStatement, ForLoop, MatchPattern, NodeSchema, OrderDirection, Parameter, Query, SearchVector, Source, StartNode::{Anonymous, Edge, Node, Variable}, Statement, Step, Traversal, UpsertNode, ValueType, VectorData
 };
+use super::explain;
 use crate::helixc::parser::helix_parser::{Exclude, Object, StartNode};
 use crate::protocol::value::Value;
-use std::{collections::HashMap, vec};
+use std::{collections::{HashMap, HashSet}, vec};
 
 pub struct CodeGenerator {
     indent_level: usize,
     current_variables: HashMap<String, String>,
+    /// `FOR` variables over array parameters and scalar function arguments, they stand in for
+    /// a parameter of the same name
+    loop_params: HashSet<String>,
+    /// Set for the traversal of an assignment, so that only top level steps are profiled
+    profile_next: bool,
+    /// `FN` definitions, inlined at each call
+    functions: HashMap<String, Function>,
 }
 
 impl CodeGenerator {
@@ -15,6 +23,9 @@ impl CodeGenerator {
         Self {
             indent_level: 0,
             current_variables: HashMap::new(),
+            loop_params: HashSet::new(),
+            profile_next: false,
+            functions: HashMap::new(),
         }
     }
 
@@ -33,6 +44,12 @@ impl CodeGenerator {
         output.push_str("        SourceTraversalSteps, TraversalBuilderMethods, TraversalSteps, TraversalMethods,\n");
         output.push_str("        TraversalSearchMethods, VectorTraversalSteps\n");
         output.push_str("    },\n");
+        output.push_str("    helix_engine::graph_core::aggregate::{Aggregation, Order},\n");
+        output.push_str("    helix_engine::graph_core::algorithms::link_prediction::LinkPredictionMetric,\n");
+        output.push_str("    helix_engine::graph_core::limits::QueryBudget,\n");
+        output.push_str("    helix_engine::graph_core::pattern::Pattern,\n");
+        output.push_str("    helix_engine::graph_core::profile::{QueryProfiler, PROFILE_KEY},\n");
+        output.push_str("    helix_engine::graph_core::traversal_iter::LazyTraversal,\n");
         output.push_str("    helix_engine::types::GraphError,\n");
         output.push_str("    helix_gateway::router::router::HandlerInput,\n");
         output.push_str("    protocol::count::Count,\n");
@@ -51,6 +68,15 @@ impl CodeGenerator {
         "    ".repeat(self.indent_level)
     }
 
+    /// The Rust expression for a query parameter, or for the element of a parameter array
+    /// a `FOR` loop is at
+    fn param(&self, name: &str) -> String {
+        match self.loop_params.contains(name) {
+            true => to_snake_case(name),
+            false => format!("data.{}", to_snake_case(name)),
+        }
+    }
+
     fn generate_props_macro(&mut self, props: &[(String, ValueType)]) -> String {
         let props_str = props
             .iter()
@@ -75,6 +101,10 @@ impl CodeGenerator {
             output.push_str("\n");
         }
 
+        for function in &source.functions {
+            self.functions.insert(function.name.clone(), function.clone());
+        }
+
         // Generate query implementations
         for query in &source.queries {
             output.push_str(&mut self.generate_query(query));
@@ -87,6 +117,10 @@ impl CodeGenerator {
     fn generate_node_schema(&mut self, schema: &NodeSchema) -> String {
         let mut output = String::new();
         output.push_str(&format!("// Node Schema: {}\n", schema.name));
+        for field in &schema.fields {
+            let name = object_name(&schema.name, &field.name);
+            output.push_str(&self.generate_object_structs(&name, &field.field_type));
+        }
         output.push_str("#[derive(Serialize, Deserialize)]\n");
         output.push_str("struct ");
         output.push_str(&schema.name);
@@ -96,7 +130,7 @@ impl CodeGenerator {
             output.push_str(&format!(
                 "    {}: {},\n",
                 to_snake_case(&field.name),
-                self.field_type_to_rust(&field.field_type)
+                self.field_type_to_rust(&object_name(&schema.name, &field.name), &field.field_type)
             ));
         }
 
@@ -107,6 +141,10 @@ impl CodeGenerator {
     fn generate_edge_schema(&mut self, schema: &EdgeSchema) -> String {
         let mut output = String::new();
         output.push_str(&format!("// Edge Schema: {}\n", schema.name));
+        for field in schema.properties.as_ref().unwrap_or(&vec![]) {
+            let name = object_name(&schema.name, &field.name);
+            output.push_str(&self.generate_object_structs(&name, &field.field_type));
+        }
         output.push_str("#[derive(Serialize, Deserialize)]\n");
         output.push_str("struct ");
         output.push_str(&schema.name);
@@ -116,7 +154,7 @@ impl CodeGenerator {
             output.push_str(&format!(
                 "    {}: {},\n",
                 to_snake_case(&field.name),
-                self.field_type_to_rust(&field.field_type)
+                self.field_type_to_rust(&object_name(&schema.name, &field.name), &field.field_type)
             ));
         }
 
@@ -124,16 +162,128 @@ impl CodeGenerator {
         output
     }
 
-    fn field_type_to_rust(&self, field_type: &FieldType) -> String {
+    /// The Rust type of a field, `name` is the struct generated for an inline object type
+    fn field_type_to_rust(&self, name: &str, field_type: &FieldType) -> String {
         match field_type {
             FieldType::String => "String".to_string(),
             FieldType::Integer => "i32".to_string(),
             FieldType::Float => "f64".to_string(),
             FieldType::Boolean => "bool".to_string(),
-            FieldType::Array(field) => format!("Vec<{}>", &Self::field_type_to_rust(&self, field)),
+            FieldType::Array(field) => format!("Vec<{}>", self.field_type_to_rust(name, field)),
             FieldType::Identifier(id) => format!("{}", id),
-            _ => "".to_string(),
+            FieldType::Optional(field) => {
+                format!("Option<{}>", self.field_type_to_rust(name, field))
+            }
+            FieldType::Object(_) => name.to_string(),
+            FieldType::Vector(_) => "Vec<f64>".to_string(),
+        }
+    }
+
+    /// Structs for the inline object types in `field_type`, nested objects are named after
+    /// the field path to them
+    fn generate_object_structs(&self, name: &str, field_type: &FieldType) -> String {
+        let fields = match field_type {
+            FieldType::Array(field) | FieldType::Optional(field) => {
+                return self.generate_object_structs(name, field)
+            }
+            FieldType::Object(fields) => fields,
+            _ => return String::new(),
+        };
+
+        let mut output = String::new();
+        for (field, field_type) in fields {
+            output.push_str(&self.generate_object_structs(&object_name(name, field), field_type));
+        }
+        output.push_str(&self.indent());
+        output.push_str("#[derive(Serialize, Deserialize)]\n");
+        output.push_str(&self.indent());
+        output.push_str(&format!("struct {} {{\n", name));
+        for (field, field_type) in fields {
+            output.push_str(&self.indent());
+            output.push_str(&format!(
+                "    {}: {},\n",
+                to_snake_case(field),
+                self.field_type_to_rust(&object_name(name, field), field_type)
+            ));
+        }
+        output.push_str(&self.indent());
+        output.push_str("}\n\n");
+        output
+    }
+
+    /// Rejects `[F32; N]` values in `value` that don't have `N` dimensions, naming the
+    /// field they were found at. `path` is a format string taking `indices` for the
+    /// positions in the arrays around the vector.
+    fn generate_vector_checks(
+        &mut self,
+        value: &str,
+        path: &str,
+        indices: &[String],
+        field_type: &FieldType,
+    ) -> String {
+        let mut output = String::new();
+        match field_type {
+            FieldType::Vector(dimensions) => {
+                let args = indices
+                    .iter()
+                    .map(|i| format!("{}, ", i))
+                    .collect::<String>();
+                output.push_str(&self.indent());
+                output.push_str(&format!("if {}.len() != {} {{\n", value, dimensions));
+                output.push_str(&self.indent());
+                output.push_str(&format!(
+                    "    return Err(GraphError::New(format!(\"{}: expected {} dimensions, found {{}}\", {}{}.len())));\n",
+                    path, dimensions, args, value
+                ));
+                output.push_str(&self.indent());
+                output.push_str("}\n");
+            }
+            FieldType::Optional(field) => {
+                let item = format!("value{}", indices.len());
+                self.indent_level += 1;
+                let checks = self.generate_vector_checks(&item, path, indices, field);
+                self.indent_level -= 1;
+                if !checks.is_empty() {
+                    output.push_str(&self.indent());
+                    output.push_str(&format!("if let Some({}) = &{} {{\n", item, value));
+                    output.push_str(&checks);
+                    output.push_str(&self.indent());
+                    output.push_str("}\n");
+                }
+            }
+            FieldType::Array(field) => {
+                let index = format!("i{}", indices.len());
+                let item = format!("item{}", indices.len());
+                let mut indices = indices.to_vec();
+                indices.push(index.clone());
+                self.indent_level += 1;
+                let checks =
+                    self.generate_vector_checks(&item, &format!("{}[{{}}]", path), &indices, field);
+                self.indent_level -= 1;
+                if !checks.is_empty() {
+                    output.push_str(&self.indent());
+                    output.push_str(&format!(
+                        "for ({}, {}) in {}.iter().enumerate() {{\n",
+                        index, item, value
+                    ));
+                    output.push_str(&checks);
+                    output.push_str(&self.indent());
+                    output.push_str("}\n");
+                }
+            }
+            FieldType::Object(fields) => {
+                for (field, field_type) in fields {
+                    output.push_str(&self.generate_vector_checks(
+                        &format!("{}.{}", value, to_snake_case(field)),
+                        &format!("{}.{}", path, to_snake_case(field)),
+                        indices,
+                        field_type,
+                    ));
+                }
+            }
+            _ => {}
         }
+        output
     }
 
     pub fn generate_query(&mut self, query: &Query) -> String {
@@ -147,10 +297,15 @@ impl CodeGenerator {
 
         // Generate input struct if there are parameters
         if !query.parameters.is_empty() {
+            let data = format!("{}Data", query.name);
+            for param in &query.parameters {
+                let name = object_name(&data, &param.name);
+                output.push_str(&self.generate_object_structs(&name, &param.param_type));
+            }
             output.push_str(&mut self.indent());
             output.push_str("#[derive(Serialize, Deserialize)]\n");
             output.push_str(&mut self.indent());
-            output.push_str(&format!("struct {}Data {{\n", query.name));
+            output.push_str(&format!("struct {} {{\n", data));
             self.indent_level += 1;
 
             for param in &query.parameters {
@@ -158,7 +313,7 @@ impl CodeGenerator {
                 output.push_str(&format!(
                     "{}: {},\n",
                     to_snake_case(&param.name),
-                    self.field_type_to_rust(&param.param_type)
+                    self.field_type_to_rust(&object_name(&data, &param.name), &param.param_type)
                 ));
             }
 
@@ -177,46 +332,54 @@ impl CodeGenerator {
             output.push_str(&mut self.indent());
             output.push_str("    Err(err) => return Err(GraphError::from(err)),\n");
             output.push_str(&mut self.indent());
-            output.push_str("};\n\n");
+            output.push_str("};\n");
+            for param in &query.parameters {
+                output.push_str(&self.generate_vector_checks(
+                    &format!("data.{}", to_snake_case(&param.name)),
+                    &to_snake_case(&param.name),
+                    &[],
+                    &param.param_type,
+                ));
+            }
+            output.push_str("\n");
         }
 
         //
         output.push_str(&mut self.indent());
         output.push_str("let mut remapping_vals: RefCell<HashMap<String, ResponseRemapping>> = RefCell::new(HashMap::new());\n");
+        output.push_str(&mut self.indent());
+        output.push_str("let mut profiler = QueryProfiler::from_request(&input.request);\n");
 
         // Setup database transaction
         output.push_str(&mut self.indent());
         output.push_str("let db = Arc::clone(&input.graph.storage);\n");
         output.push_str(&mut self.indent());
-        if query.statements.iter().any(|s| {
-            matches!(s, Statement::AddNode(_))
-                || matches!(s, Statement::AddEdge(_))
-                || matches!(s, Statement::Drop(_))
-                || matches!(s, Statement::AddVector(_))
-                || matches!(s, Statement::BatchAddVector(_))
-                || {
-                    if let Statement::Assignment(assignment) = s {
-                        matches!(assignment.value, Expression::AddNode(_))
-                            || matches!(assignment.value, Expression::AddEdge(_))
-                            || matches!(assignment.value, Expression::AddVector(_))
-                            || matches!(assignment.value, Expression::BatchAddVector(_))
-                            || {
-                                let steps = match &assignment.value {
-                                    Expression::Traversal(traversal) => &traversal.steps,
-                                    _ => return false,
-                                };
-                                steps.iter().any(|step| matches!(step, Step::Update(_)))
-                            }
-                    } else {
-                        false
-                    }
-                }
-        }) {
+        if writes_to_graph(query) {
             output.push_str("let mut txn = db.graph_env.write_txn().unwrap();\n\n");
         } else {
             output.push_str("let txn = db.graph_env.read_txn().unwrap();\n\n");
         }
 
+        // Queries can override the server's limits
+        let mut limits = "db.query_limits".to_string();
+        if query.timeout_ms.is_some() || query.max_items.is_some() || query.max_memory.is_some() {
+            limits.push_str(".clone()");
+        }
+        if let Some(timeout_ms) = query.timeout_ms {
+            limits.push_str(&format!(".timeout_ms({})", timeout_ms));
+        }
+        if let Some(max_items) = query.max_items {
+            limits.push_str(&format!(".max_items({})", max_items));
+        }
+        if let Some(max_memory) = query.max_memory {
+            limits.push_str(&format!(".max_memory({})", max_memory));
+        }
+        output.push_str(&mut self.indent());
+        output.push_str(&format!(
+            "let budget = QueryBudget::new(&{}, &input.cancel);\n\n",
+            limits
+        ));
+
         // Generate return values map if needed
         if !query.return_values.is_empty() {
             output.push_str(&mut self.indent());
@@ -231,26 +394,17 @@ impl CodeGenerator {
         // Generate return statement
         if !query.return_values.is_empty() {
             output.push_str(&mut self.generate_return_values(&query.return_values, &query));
+        } else {
+            output.push_str(&mut self.indent());
+            output.push_str("if let Some(profile) = profiler.finish() {\n");
+            output.push_str(&mut self.indent());
+            output.push_str("    response.body = sonic_rs::to_vec(&HashMap::from([(PROFILE_KEY, ReturnValue::from(profile))])).unwrap();\n");
+            output.push_str(&mut self.indent());
+            output.push_str("}\n");
         }
 
-        if query.statements.iter().any(|s| {
-            matches!(s, Statement::AddNode(_))
-                || matches!(s, Statement::AddEdge(_))
-                || matches!(s, Statement::Drop(_))
-                || matches!(s, Statement::AddVector(_))
-                || matches!(s, Statement::BatchAddVector(_))
-                || {
-                    if let Statement::Assignment(assignment) = s {
-                        matches!(assignment.value, Expression::AddNode(_))
-                            || matches!(assignment.value, Expression::AddEdge(_))
-                            || matches!(assignment.value, Expression::AddVector(_))
-                            || matches!(assignment.value, Expression::BatchAddVector(_))
-                    } else {
-                        false
-                    }
-                }
-        }) {
-            output.push_str(&mut self.indent());
+        if writes_to_graph(query) {
+            output.push_str(&self.indent());
             output.push_str("txn.commit()?;\n");
         }
 
@@ -268,11 +422,139 @@ impl CodeGenerator {
             Statement::Assignment(assignment) => self.generate_assignment(assignment, query),
             Statement::AddNode(add_vertex) => self.generate_add_vertex(add_vertex, None),
             Statement::AddEdge(add_edge) => self.generate_add_edge(add_edge),
+            Statement::UpsertNode(upsert) => self.generate_upsert_node(upsert),
+            Statement::UpsertEdge(upsert) => self.generate_upsert_edge(upsert),
             Statement::Drop(expr) => self.generate_drop(expr, query),
             Statement::AddVector(add_vector) => self.generate_add_vector(add_vector),
             Statement::SearchVector(search_vector) => self.generate_search_vector(search_vector),
             Statement::BatchAddVector(batch_add_vector) => self.generate_batch_add_vector(batch_add_vector),
+            Statement::If(if_statement) => self.generate_if(if_statement, query),
+            Statement::For(for_loop) => self.generate_for(for_loop, query),
+            Statement::Return(ret) => {
+                let mut output = self.generate_return_values(&ret.values, query);
+                if writes_to_graph(query) {
+                    output.push_str(&self.indent());
+                    output.push_str("txn.commit()?;\n");
+                }
+                output.push_str(&self.indent());
+                output.push_str("return Ok(());\n");
+                output
+            }
+        }
+    }
+
+    /// Statements of an `IF` or `FOR` body, one level deeper. Variables assigned in the block
+    /// are only visible inside it.
+    fn generate_block(&mut self, statements: &[Statement], query: &Query) -> String {
+        let mut output = String::new();
+        let variables = self.current_variables.clone();
+        let loop_params = self.loop_params.clone();
+        self.indent_level += 1;
+        for statement in statements {
+            output.push_str(&self.generate_statement(statement, query));
+        }
+        self.indent_level -= 1;
+        self.current_variables = variables;
+        self.loop_params = loop_params;
+        output
+    }
+
+    fn generate_if(&mut self, if_statement: &IfStatement, query: &Query) -> String {
+        let mut output = String::new();
+        let mut setup = Vec::new();
+        let condition = self.generate_condition(&if_statement.condition, query, &mut setup);
+        output.push_str(&setup.concat());
+        output.push_str(&self.indent());
+        output.push_str(&format!("if {} {{\n", condition));
+        output.push_str(&self.generate_block(&if_statement.then, query));
+        if !if_statement.otherwise.is_empty() {
+            output.push_str(&self.indent());
+            output.push_str("} else {\n");
+            output.push_str(&self.generate_block(&if_statement.otherwise, query));
+        }
+        output.push_str(&self.indent());
+        output.push_str("}\n");
+        output
+    }
+
+    /// Rust boolean for an `IF` condition. Traversals in it are run beforehand, their code
+    /// is pushed to `setup` and their result bound to `condition_{n}`.
+    fn generate_condition(
+        &mut self,
+        condition: &Expression,
+        query: &Query,
+        setup: &mut Vec<String>,
+    ) -> String {
+        match condition {
+            Expression::BooleanLiteral(b) => b.to_string(),
+            Expression::Identifier(id) => match self.current_variables.get(id) {
+                Some(var_name) => format!("{}.is_truthy()", to_snake_case(var_name)),
+                None => self.param(id),
+            },
+            Expression::Traversal(traversal) | Expression::Exists(traversal) => {
+                let name = format!("condition_{}", setup.len());
+                let mut output = self.generate_traversal(traversal, query);
+                output.push_str(&self.indent());
+                output.push_str(&format!("let {} = tr.finish()?.is_truthy();\n", name));
+                setup.push(output);
+                name
+            }
+            Expression::And(conditions) | Expression::Or(conditions) => {
+                let operator = match condition {
+                    Expression::And(_) => " && ",
+                    _ => " || ",
+                };
+                let conditions = conditions
+                    .iter()
+                    .map(|condition| self.generate_condition(condition, query, setup))
+                    .collect::<Vec<_>>();
+                format!("({})", conditions.join(operator))
+            }
+            // The analyzer rejects every other form, nothing to run
+            _ => "false".to_string(),
+        }
+    }
+
+    /// Loops over the elements of an array parameter, or over the items of a variable or
+    /// traversal, one `TraversalValue` per item
+    fn generate_for(&mut self, for_loop: &ForLoop, query: &Query) -> String {
+        let mut output = String::new();
+        let variable = &for_loop.variable;
+        let (items, over_param) = match &for_loop.iterable {
+            Expression::Identifier(id) => match self.current_variables.get(id) {
+                Some(var_name) => {
+                    (format!("{}.clone().into_items()", to_snake_case(var_name)), false)
+                }
+                None => (format!("{}.clone()", self.param(id)), true),
+            },
+            Expression::Traversal(traversal) => {
+                output.push_str(&self.generate_traversal(traversal, query));
+                ("tr.finish()?.into_items()".to_string(), false)
+            }
+            // The analyzer rejects every other form, nothing to loop over
+            _ => ("std::iter::empty::<TraversalValue>()".to_string(), false),
+        };
+
+        output.push_str(&self.indent());
+        output.push_str(&format!("for {} in {} {{\n", to_snake_case(variable), items));
+        let variables = self.current_variables.clone();
+        let loop_params = self.loop_params.clone();
+        match over_param {
+            true => {
+                self.current_variables.remove(variable);
+                self.loop_params.insert(variable.clone());
+            }
+            false => {
+                self.loop_params.remove(variable);
+                self.current_variables.insert(variable.clone(), variable.clone());
+            }
         }
+        output.push_str(&self.generate_block(&for_loop.body, query));
+        self.current_variables = variables;
+        self.loop_params = loop_params;
+        output.push_str(&self.indent());
+        output.push_str("}\n");
+        output
     }
 
     fn generate_add_vector(&mut self, add_vector: &AddVector) -> String {
@@ -286,10 +568,12 @@ impl CodeGenerator {
                 output.push_str(&format!("tr.insert_vector(&mut txn, &{:?});\n", vec));
             }
             Some(VectorData::Identifier(id)) => {
-                output.push_str(&format!("tr.insert_vector(&mut txn, &data.{});\n", id));
+                output.push_str(&format!("tr.insert_vector(&mut txn, &{});\n", self.param(id)));
             }
             None => (),
         };
+        output.push_str(&mut self.indent());
+        output.push_str("tr.execute()?;\n");
 
         output
     }
@@ -311,6 +595,8 @@ impl CodeGenerator {
         output.push_str("    tr.insert_vector(&mut txn, &vec);\n");
         output.push_str(&mut self.indent());
         output.push_str("}\n");
+        output.push_str(&mut self.indent());
+        output.push_str("tr.execute()?;\n");
         output
     }
 
@@ -323,7 +609,7 @@ impl CodeGenerator {
         let k = match &vec.k {
             Some(EvaluatesToNumber::Integer(k)) => k.to_string(),
             Some(EvaluatesToNumber::Float(k)) => k.to_string(),
-            Some(EvaluatesToNumber::Identifier(id)) => format!("data.{} as usize", id),
+            Some(EvaluatesToNumber::Identifier(id)) => format!("{} as usize", self.param(id)),
             None => "10".to_string(),
         };
         match &vec.data {
@@ -331,7 +617,7 @@ impl CodeGenerator {
                 output.push_str(&format!("tr.vector_search(&txn, &{:?}, {});\n", v, k));
             }
             Some(VectorData::Identifier(id)) => {
-                output.push_str(&format!("tr.vector_search(&txn, &data.{}, {});\n", id, k));
+                output.push_str(&format!("tr.vector_search(&txn, &{}, {});\n", self.param(id), k));
             }
             None => panic!("No vector data provided for search vector, {:?}", vec),
         };
@@ -347,7 +633,9 @@ impl CodeGenerator {
             "let mut tr = TraversalBuilder::new(Arc::clone(&db), TraversalValue::Empty);\n",
         );
 
+        self.profile_next = true;
         output.push_str(&mut self.generate_expression(&assignment.value, query));
+        self.profile_next = false;
 
         // Store variable for later use
         self.current_variables
@@ -383,7 +671,7 @@ impl CodeGenerator {
             }
             Expression::StringLiteral(s) => {
                 output.push_str(&mut self.indent());
-                output.push_str(&format!("\"{}\"", s));
+                output.push_str(&format!("{:?}", s));
             }
             Expression::IntegerLiteral(i) => {
                 output.push_str(&mut self.indent());
@@ -403,6 +691,12 @@ impl CodeGenerator {
             Expression::AddEdge(add_edge) => {
                 output.push_str(&mut self.generate_add_edge(add_edge));
             }
+            Expression::UpsertNode(upsert) => {
+                output.push_str(&self.generate_upsert_node(upsert));
+            }
+            Expression::UpsertEdge(upsert) => {
+                output.push_str(&self.generate_upsert_edge(upsert));
+            }
             Expression::BatchAddVector(batch_add_vector) => {
                 output.push_str(&mut self.generate_batch_add_vector(batch_add_vector));
             }
@@ -415,61 +709,174 @@ impl CodeGenerator {
             Expression::Exists(traversal) => {
                 output.push_str(&mut self.generate_exists_check(traversal, query));
             }
+            Expression::Match(pattern) => {
+                output.push_str(&mut self.generate_match_pattern(pattern));
+            }
             _ => {}
         }
 
         output
     }
 
+    /// Inlines a function call as a block evaluating to the traversal of the function's body.
+    /// Node and edge arguments become variables, scalar ones stand in for parameters.
+    fn generate_call(&mut self, call: &Call, query: &Query) -> String {
+        let mut output = String::new();
+        let Some(function) = self.functions.get(&call.name).cloned() else {
+            return output;
+        };
+        output.push_str(&self.indent());
+        output.push_str("let mut tr = {\n");
+        self.indent_level += 1;
+
+        let mut variables = HashMap::new();
+        let mut params = HashSet::new();
+        let mut names = Vec::new();
+        let mut values = Vec::new();
+        for (i, (parameter, arg)) in function.parameters.iter().zip(&call.args).enumerate() {
+            let value = match arg {
+                Expression::Identifier(id) if self.current_variables.contains_key(id) => {
+                    variables.insert(parameter.name.clone(), parameter.name.clone());
+                    format!("{}.clone()", to_snake_case(&self.current_variables[id]))
+                }
+                Expression::Identifier(id) => {
+                    params.insert(parameter.name.clone());
+                    format!("{}.clone()", self.param(id))
+                }
+                Expression::StringLiteral(s) => {
+                    params.insert(parameter.name.clone());
+                    format!("{:?}.to_string()", s)
+                }
+                Expression::IntegerLiteral(_)
+                | Expression::FloatLiteral(_)
+                | Expression::BooleanLiteral(_) => {
+                    params.insert(parameter.name.clone());
+                    self.expression_to_value(arg)
+                }
+                arg => {
+                    output.push_str(&self.indent());
+                    output.push_str(
+                        "let mut tr = TraversalBuilder::new(Arc::clone(&db), TraversalValue::Empty);\n",
+                    );
+                    output.push_str(&self.generate_expression(arg, query));
+                    output.push_str(&self.indent());
+                    output.push_str(&format!("let arg_{} = tr.finish()?;\n", i));
+                    variables.insert(parameter.name.clone(), parameter.name.clone());
+                    format!("arg_{}", i)
+                }
+            };
+            names.push(to_snake_case(&parameter.name));
+            values.push(value);
+        }
+        // Bound together so that an argument can't see a parameter bound before it
+        if names.len() == 1 {
+            output.push_str(&self.indent());
+            output.push_str(&format!("let {} = {};\n", names[0], values[0]));
+        } else if !names.is_empty() {
+            output.push_str(&self.indent());
+            output.push_str(&format!("let ({}) = ({});\n", names.join(", "), values.join(", ")));
+        }
+
+        let outer_variables = std::mem::replace(&mut self.current_variables, variables);
+        let outer_params = std::mem::replace(&mut self.loop_params, params);
+        output.push_str(&self.generate_expression(&function.body, query));
+        self.current_variables = outer_variables;
+        self.loop_params = outer_params;
+
+        output.push_str(&self.indent());
+        output.push_str("tr\n");
+        self.indent_level -= 1;
+        output.push_str(&self.indent());
+        output.push_str("};\n");
+        output
+    }
+
     fn generate_traversal(&mut self, traversal: &Traversal, query: &Query) -> String {
         let mut output = String::new();
 
+        // Sources and the leading run of streamable steps go through LazyTraversal so that
+        // ranges only read what they return; the remaining steps run on the TraversalBuilder
+        // Paths have to be recorded from the source, so nothing is streamed when they are returned
+        let tracks_paths = traversal.steps.iter().any(|step| matches!(step, Step::Path));
+
+        // Only the steps of an assigned traversal are profiled and checked against the query's
+        // budget, nested traversals run inside closures and count towards the step running them
+        let top_level =
+            std::mem::take(&mut self.profile_next) && !matches!(traversal.start, Anonymous);
+        let within = match top_level {
+            true => ".within(&budget)",
+            false => "",
+        };
+
+        let (lazy_len, lazy_steps) = match &traversal.start {
+            Node { .. } | Edge { .. } if tracks_paths => (0, format!("{}.collect()?", within)),
+            Node { .. } | Edge { .. } => {
+                let nodes = matches!(traversal.start, Node { .. });
+                let (len, chain) = self.generate_lazy_steps(&traversal.steps, nodes, query);
+                if len + 1 == traversal.steps.len() && matches!(traversal.steps[len], Step::Count) {
+                    (len + 1, format!("{}{}.collect_count()?", chain, within))
+                } else {
+                    (len, format!("{}{}.collect()?", chain, within))
+                }
+            }
+            _ => (0, String::new()),
+        };
+
+        let profiles_source = top_level && matches!(traversal.start, Node { .. } | Edge { .. });
+        if profiles_source {
+            let source_name = std::iter::once(explain::start_name(&traversal.start))
+                .chain(traversal.steps[..lazy_len].iter().map(explain::step_name))
+                .collect::<Vec<_>>()
+                .join("::");
+            output.push_str(&mut self.indent());
+            output.push_str(&format!(
+                "profiler.begin(\"{}\", &TraversalValue::Empty);\n",
+                source_name
+            ));
+        }
+
         // Generate start node
         match &traversal.start {
             Node { types, ids } => {
-                if let Some(ids) = ids {
-                    output.push_str(&mut self.indent());
+                let source = if let Some(ids) = ids {
                     if let Some(var_name) = self.current_variables.get(&ids[0]) {
-                        output.push_str(&format!("tr.v_from_id(&txn, {});\n", var_name));
+                        format!("LazyTraversal::v_from_id(&db, &txn, {})", var_name)
                     } else {
-                        output.push_str(&format!(
-                            "tr.v_from_id(&txn, &data.{});\n",
-                            to_snake_case(&ids[0])
-                        ));
+                        format!("LazyTraversal::v_from_id(&db, &txn, &{})", self.param(&ids[0]))
                     }
                 } else if let Some(types) = types {
-                    output.push_str(&mut self.indent());
-                    output.push_str(&format!(
-                        "tr.v_from_types(&txn, &[{}]);\n",
+                    format!(
+                        "LazyTraversal::v_from_types(&db, &txn, &[{}])",
                         types
                             .iter()
                             .map(|t| format!("\"{}\"", t))
                             .collect::<Vec<_>>()
                             .join(", ")
-                    ));
+                    )
                 } else {
-                    output.push_str(&mut self.indent());
-                    output.push_str("tr.v(&txn);\n");
-                }
+                    "LazyTraversal::v(&db, &txn)".to_string()
+                };
+                output.push_str(&mut self.indent());
+                output.push_str(&format!(
+                    "let mut tr = TraversalBuilder::new(Arc::clone(&db), {}{});\n",
+                    source, lazy_steps
+                ));
             }
-            Edge { types, ids } => {
-                if let Some(ids) = ids {
-                    output.push_str(&mut self.indent());
+            Edge { ids, .. } => {
+                let source = if let Some(ids) = ids {
                     if let Some(var_name) = self.current_variables.get(&ids[0]) {
-                        output.push_str(&format!("tr.e_from_id(&txn, {});\n", var_name));
+                        format!("LazyTraversal::e_from_id(&db, &txn, {})", var_name)
                     } else {
-                        output.push_str(&format!(
-                            "tr.e_from_id(&txn, &data.{});\n",
-                            to_snake_case(&ids[0])
-                        ));
+                        format!("LazyTraversal::e_from_id(&db, &txn, &{})", self.param(&ids[0]))
                     }
-                } else if let Some(types) = types {
-                    output.push_str(&mut self.indent());
-                    output.push_str("tr.e(&txn);\n");
                 } else {
-                    output.push_str(&mut self.indent());
-                    output.push_str("tr.e(&txn);\n");
-                }
+                    "LazyTraversal::e(&db, &txn)".to_string()
+                };
+                output.push_str(&mut self.indent());
+                output.push_str(&format!(
+                    "let mut tr = TraversalBuilder::new(Arc::clone(&db), {}{});\n",
+                    source, lazy_steps
+                ));
             }
             Variable(var) => {
                 if let Some(var_name) = self.current_variables.get(var) {
@@ -480,16 +887,43 @@ impl CodeGenerator {
                     ));
                 }
             }
+            StartNode::Call(call) => {
+                output.push_str(&self.generate_call(call, query));
+            }
             Anonymous => {}
         }
+        if profiles_source {
+            output.push_str(&mut self.indent());
+            output.push_str("profiler.end(&tr.current_step);\n");
+        }
+        if tracks_paths {
+            output.push_str(&mut self.indent());
+            output.push_str("tr.track_paths();\n");
+        }
+        // Parallel steps read through their own transactions, which can't see this query's
+        // uncommitted writes
+        let parallelism = match writes_to_graph(query) {
+            true => Some(1),
+            false => query.parallelism,
+        };
+        if let (Some(threads), false) = (parallelism, matches!(traversal.start, Anonymous)) {
+            output.push_str(&mut self.indent());
+            output.push_str(&format!("tr.parallelism({});\n", threads));
+        }
+        // Steps running on the builder check the budget while they read, not just after
+        if top_level && lazy_len < traversal.steps.len() {
+            output.push_str(&mut self.indent());
+            output.push_str("tr.within(&budget);\n");
+        }
 
         // Generate steps
         let mut skip_next = false;
-        for (i, step) in traversal.steps.iter().enumerate() {
+        for (i, step) in traversal.steps.iter().enumerate().skip(lazy_len) {
             if skip_next {
                 skip_next = false;
                 continue;
             }
+            let step_start = output.len();
 
             match step {
                 // Step::Object(_) => {
@@ -557,6 +991,19 @@ impl CodeGenerator {
                     }
                     _ => output.push_str(&mut self.generate_step(step, query)),
                 },
+                Step::GroupBy(property) => {
+                    let aggregations = match traversal.steps.get(i + 1) {
+                        Some(Step::Aggregate(aggregations)) => {
+                            skip_next = true;
+                            self.generate_aggregations(aggregations)
+                        }
+                        _ => self.generate_aggregations(&[Aggregation::Count]),
+                    };
+                    output.push_str(&format!(
+                        "tr.group_by(\"{}\", &[{}]);\n",
+                        property, aggregations
+                    ));
+                }
                 Step::Edge(graph_step) => match graph_step {
                     GraphStep::InN => output.push_str("tr.in_v(&txn);\n"),
                     GraphStep::OutN => output.push_str("tr.out_v(&txn);\n"),
@@ -565,97 +1012,196 @@ impl CodeGenerator {
                 },
                 _ => output.push_str(&mut self.generate_step(step, query)),
             }
+
+            if top_level && output.len() > step_start {
+                output.insert_str(
+                    step_start,
+                    &format!(
+                        "profiler.begin(\"{}\", &tr.current_step);\n",
+                        explain::step_name(step)
+                    ),
+                );
+                output.push_str("profiler.end(&tr.current_step);\n");
+                output.push_str("tr.check_budget(&budget)?;\n");
+            }
         }
 
         output
     }
+    /// Chains the leading steps that `LazyTraversal` can stream,
+    /// returning how many steps were consumed
+    fn generate_lazy_steps(
+        &mut self,
+        steps: &[Step],
+        nodes: bool,
+        query: &Query,
+    ) -> (usize, String) {
+        let consumed = lazy_prefix_len(steps, nodes);
+        let mut output = String::new();
+        for step in &steps[..consumed] {
+            let label = |types: &Option<Vec<String>>| match types {
+                Some(types) => types[0].clone(),
+                None => String::new(),
+            };
+            match step {
+                Step::Node(GraphStep::Out(types)) => {
+                    output.push_str(&format!(".out(\"{}\")", label(types)))
+                }
+                Step::Node(GraphStep::In(types)) => {
+                    output.push_str(&format!(".in_(\"{}\")", label(types)))
+                }
+                Step::Node(GraphStep::OutE(types)) => {
+                    output.push_str(&format!(".out_e(\"{}\")", label(types)))
+                }
+                Step::Node(GraphStep::InE(types)) => {
+                    output.push_str(&format!(".in_e(\"{}\")", label(types)))
+                }
+                Step::Node(GraphStep::InN) | Step::Edge(GraphStep::InN) => {
+                    output.push_str(".in_v()")
+                }
+                Step::Node(GraphStep::OutN) | Step::Edge(GraphStep::OutN) => {
+                    output.push_str(".out_v()")
+                }
+                Step::Range((start, end)) => output.push_str(&format!(
+                    ".range({}, {})",
+                    range_bound(start),
+                    range_bound(end)
+                )),
+                Step::Where(expr) => {
+                    let (exprs, op) = match &**expr {
+                        Expression::And(exprs) => (exprs.as_slice(), " && "),
+                        Expression::Or(exprs) => (exprs.as_slice(), " || "),
+                        expr => (std::slice::from_ref(expr), ""),
+                    };
+                    let condition = exprs
+                        .iter()
+                        .map(|expr| self.generate_filter_condition(expr, query))
+                        .collect::<Vec<_>>()
+                        .join(op);
+                    output.push_str(&format!(".filter_nodes(|node| Ok({}))", condition))
+                }
+                _ => break,
+            }
+        }
+        (consumed, output)
+    }
+
+    /// A sub-traversal as a closure over a `TraversalBuilder` started from the current item
+    fn generate_branch(&mut self, traversal: &Traversal, query: &Query) -> String {
+        let steps = self.generate_traversal(traversal, query);
+        format!("|tr: &mut TraversalBuilder| {{\n{}}}", steps)
+    }
+
+    fn generate_branches(&mut self, traversals: &[Traversal], query: &Query) -> String {
+        traversals
+            .iter()
+            .map(|traversal| format!("&{}", self.generate_branch(traversal, query)))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    fn generate_aggregations(&self, aggregations: &[Aggregation]) -> String {
+        aggregations
+            .iter()
+            .map(|aggregation| match aggregation {
+                Aggregation::Count => "Aggregation::Count".to_string(),
+                Aggregation::Sum(p) => format!("Aggregation::Sum(\"{}\".to_string())", p),
+                Aggregation::Avg(p) => format!("Aggregation::Avg(\"{}\".to_string())", p),
+                Aggregation::Min(p) => format!("Aggregation::Min(\"{}\".to_string())", p),
+                Aggregation::Max(p) => format!("Aggregation::Max(\"{}\".to_string())", p),
+                Aggregation::Collect(p) => format!("Aggregation::Collect(\"{}\".to_string())", p),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
     fn generate_boolean_operation(&mut self, bool_op: &BooleanOp) -> String {
         let mut output = String::new();
         match bool_op {
             BooleanOp::Equal(value) => match &**value {
                 Expression::BooleanLiteral(b) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Boolean(val) if *val == {})));\n", b));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Boolean(val) if *val == {})));\n", b));
                 }
                 Expression::IntegerLiteral(i) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if *val == {})));\n", i));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if *val == {})));\n", i));
                 }
                 Expression::FloatLiteral(f) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if *val == {})));\n", f));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if *val == {})));\n", f));
                 }
                 Expression::StringLiteral(s) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val == \"{}\")));\n", s));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val == {:?})));\n", s));
                 }
                 Expression::Identifier(id) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val == \"{}\")));\n", id));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val == \"{}\")));\n", id));
                 }
                 _ => output.push_str(&format!("// Unhandled value type in EQ\n {:?}", value)),
             },
             BooleanOp::GreaterThan(value) => match &**value {
                 Expression::IntegerLiteral(i) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val > {})));\n", i));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val > {})));\n", i));
                 }
                 Expression::FloatLiteral(f) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if val > {})));\n", f));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if val > {})));\n", f));
                 }
                 Expression::Identifier(id) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val > {})));\n", id));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val > {})));\n", id));
                 }
                 _ => output.push_str("// Unhandled value type in GT\n"),
             },
             BooleanOp::GreaterThanOrEqual(value) => match &**value {
                 Expression::IntegerLiteral(i) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val >= {})));\n", i));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val >= {})));\n", i));
                 }
                 Expression::FloatLiteral(f) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if val >= {})));\n", f));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if val >= {})));\n", f));
                 }
                 Expression::StringLiteral(s) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::String(val) if val >= \"{}\")));\n", s));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::String(val) if val >= {:?})));\n", s));
                 }
                 Expression::Identifier(id) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val >= {})));\n", id));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val >= {})));\n", id));
                 }
                 _ => output.push_str("// Unhandled value type in GTE\n"),
             },
             BooleanOp::LessThan(value) => match &**value {
                 Expression::IntegerLiteral(i) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val < {})));\n", i));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val < {})));\n", i));
                 }
                 Expression::FloatLiteral(f) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if val < {})));\n", f));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if val < {})));\n", f));
                 }
                 Expression::Identifier(id) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val < {})));\n", id));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val < {})));\n", id));
                 }
                 _ => output.push_str("// Unhandled value type in LT\n"),
             },
             BooleanOp::LessThanOrEqual(value) => match &**value {
                 Expression::IntegerLiteral(i) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val <= {})));\n", i));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val <= {})));\n", i));
                 }
                 Expression::FloatLiteral(f) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if val <= {})));\n", f));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if val <= {})));\n", f));
                 }
                 Expression::Identifier(id) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val <= {})));\n", id));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val <= {})));\n", id));
                 }
                 _ => output.push_str("// Unhandled value type in LTE\n"),
             },
             BooleanOp::NotEqual(value) => match &**value {
                 Expression::Identifier(id) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val != \"{}\"))", id));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val != \"{}\"))", id));
                 }
                 Expression::StringLiteral(s) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val != \"{}\"))", s));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val != {:?}))", s));
                 }
                 Expression::IntegerLiteral(i) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if *val != {}))", i));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if *val != {}))", i));
                 }
                 Expression::FloatLiteral(f) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if *val != {}))", f));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if *val != {}))", f));
                 }
                 Expression::BooleanLiteral(b) => {
-                    output.push_str(&format!("tr.filter_nodes(&txn, |node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Boolean(val) if *val != {}))", b));
+                    output.push_str(&format!("tr.filter_nodes(&txn, |txn, node| Ok(matches!(node.check_property(current_prop).unwrap(), Value::Boolean(val) if *val != {}))", b));
                 }
                 _ => output.push_str(&format!("// Unhandled value type in NEQ\n {:?}", value)),
             },
@@ -741,29 +1287,68 @@ impl CodeGenerator {
                 }
             },
             Step::Range((start, end)) => {
-                let start = match start {
-                    Expression::IntegerLiteral(val) => format!("{}", val),
-                    Expression::Identifier(id) => format!("data.{}", to_snake_case(id)),
-                    _ => unreachable!(),
-                };
-                let end = match end {
-                    Expression::IntegerLiteral(val) => format!("{}", val),
-                    Expression::Identifier(id) => format!("data.{}", to_snake_case(id)),
-                    _ => unreachable!(),
-                };
-
-                output.push_str(&format!("tr.range({}, {});\n", start, end));
+                output.push_str(&format!(
+                    "tr.range({}, {});\n",
+                    range_bound(start),
+                    range_bound(end)
+                ));
+            }
+            Step::Path => output.push_str("tr.path();\n"),
+            Step::As(name) => output.push_str(&format!("tr.as_(\"{}\");\n", name)),
+            Step::Select(names) => {
+                let names = names
+                    .iter()
+                    .map(|name| format!("\"{}\"", name))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                output.push_str(&format!("tr.select(&[{}]);\n", names));
+            }
+            Step::Union(branches) => {
+                let branches = self.generate_branches(branches, query);
+                output.push_str(&format!("tr.union(&[{}]);\n", branches));
+            }
+            Step::Optional(branch) => {
+                let branch = self.generate_branch(branch, query);
+                output.push_str(&format!("tr.optional({});\n", branch));
+            }
+            Step::Coalesce(branches) => {
+                let branches = self.generate_branches(branches, query);
+                output.push_str(&format!("tr.coalesce(&[{}]);\n", branches));
+            }
+            Step::OrderBy(keys) => {
+                let keys = keys
+                    .iter()
+                    .map(|(property, direction)| {
+                        let order = match direction {
+                            OrderDirection::Asc => "Order::Asc",
+                            OrderDirection::Desc => "Order::Desc",
+                        };
+                        format!("(\"{}\", {})", property, order)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                output.push_str(&format!("tr.order_by(&[{}]);\n", keys));
+            }
+            Step::Dedup(property) => match property {
+                Some(property) => output.push_str(&format!("tr.dedup_by(\"{}\");\n", property)),
+                None => output.push_str("tr.dedup();\n"),
+            },
+            Step::Aggregate(aggregations) => {
+                output.push_str(&format!(
+                    "tr.aggregate(&[{}]);\n",
+                    self.generate_aggregations(aggregations)
+                ));
             }
             Step::Where(expr) => {
                 match &**expr {
                     Expression::BooleanLiteral(b) => {
-                        output.push_str(&format!("tr.filter_nodes(&txn, |_| Ok({}));\n", b));
+                        output.push_str(&format!("tr.filter_nodes(&txn, |_, _| Ok({}));\n", b));
                     }
                     Expression::Exists(traversal) => {
                         output.push_str(&mut self.generate_exists_check(traversal, query));
                     }
                     Expression::And(exprs) => {
-                        output.push_str("tr.filter_nodes(&txn, |node| {\n");
+                        output.push_str("tr.filter_nodes(&txn, |txn, node| {\n");
                         output.push_str(&mut self.indent());
                         output.push_str("    Ok(");
                         for (i, expr) in exprs.iter().enumerate() {
@@ -777,7 +1362,7 @@ impl CodeGenerator {
                         output.push_str("});\n");
                     }
                     Expression::Or(exprs) => {
-                        output.push_str("tr.filter_nodes(&txn, |node| {\n");
+                        output.push_str("tr.filter_nodes(&txn, |txn, node| {\n");
                         output.push_str(&mut self.indent());
                         output.push_str("    Ok(");
                         for (i, expr) in exprs.iter().enumerate() {
@@ -792,7 +1377,7 @@ impl CodeGenerator {
                     }
                     Expression::Traversal(_) => {
                         // For traversal-based conditions
-                        output.push_str("tr.filter_nodes(&txn, |node| {\n");
+                        output.push_str("tr.filter_nodes(&txn, |txn, node| {\n");
                         output.push_str(&mut self.indent());
                         output.push_str("    Ok(");
                         output.push_str(&mut self.generate_filter_condition(expr, query));
@@ -854,6 +1439,46 @@ impl CodeGenerator {
             Step::Exclude(exclude) => {
                 output.push_str(&self.generate_exclude_remapping(true, None, exclude));
             }
+            Step::PredictLinks(predict) => {
+                let limit = match &predict.limit {
+                    EvaluatesToNumber::Integer(limit) => limit.to_string(),
+                    EvaluatesToNumber::Float(limit) => format!("{} as usize", limit),
+                    EvaluatesToNumber::Identifier(id) => {
+                        format!("{} as usize", self.param(id))
+                    }
+                };
+                output.push_str(&format!(
+                    "tr.predict_links(&txn, \"{}\", LinkPredictionMetric::{}, {});\n",
+                    predict.edge_type.as_deref().unwrap_or(""),
+                    predict.metric,
+                    limit
+                ));
+            }
+            Step::Dag(dag) => {
+                let edge_type = &dag.edge_type;
+                output.push_str(&match dag.operation {
+                    DagOperation::Ancestors => {
+                        format!("tr.ancestors(&txn, \"{}\");\n", edge_type)
+                    }
+                    DagOperation::Descendants => {
+                        format!("tr.descendants(&txn, \"{}\");\n", edge_type)
+                    }
+                    DagOperation::TopoSort => {
+                        format!("tr.topological_sort(&txn, \"{}\");\n", edge_type)
+                    }
+                    DagOperation::FindCycle => {
+                        format!("tr.find_cycle(&txn, \"{}\");\n", edge_type)
+                    }
+                    DagOperation::CriticalPath => format!(
+                        "tr.critical_path(&txn, \"{}\", {});\n",
+                        edge_type,
+                        match &dag.weight {
+                            Some(weight) => format!("Some(\"{}\")", weight),
+                            None => "None".to_string(),
+                        }
+                    ),
+                });
+            }
             _ => {}
         }
 
@@ -916,6 +1541,9 @@ impl CodeGenerator {
                                         Expression::FloatLiteral(f) => {
                                             output.push_str(&format!("node.check_property(\"{}\").map_or(false, |v| matches!(v, Value::Float(val) if *val > {}))", prop_name, f));
                                         }
+                                        Expression::StringLiteral(s) => {
+                                            output.push_str(&format!("node.check_property(\"{}\").map_or(false, |v| matches!(v, Value::String(val) if val.as_str() > {:?}))", prop_name, s));
+                                        }
                                         Expression::Identifier(id) => {
                                             output.push_str(&format!("node.check_property(\"{}\").map_or(false, |v| matches!(v, Value::Integer(val) if *val > {}))", prop_name, id));
                                         }
@@ -928,6 +1556,9 @@ impl CodeGenerator {
                                         Expression::FloatLiteral(f) => {
                                             output.push_str(&format!("node.check_property(\"{}\").map_or(false, |v| matches!(v, Value::Float(val) if *val >= {}))", prop_name, f));
                                         }
+                                        Expression::StringLiteral(s) => {
+                                            output.push_str(&format!("node.check_property(\"{}\").map_or(false, |v| matches!(v, Value::String(val) if val.as_str() >= {:?}))", prop_name, s));
+                                        }
                                         Expression::Identifier(id) => {
                                             output.push_str(&format!("node.check_property(\"{}\").map_or(false, |v| matches!(v, Value::Integer(val) if *val >= {}))", prop_name, id));
                                         }
@@ -940,6 +1571,9 @@ impl CodeGenerator {
                                         Expression::FloatLiteral(f) => {
                                             output.push_str(&format!("node.check_property(\"{}\").map_or(false, |v| matches!(v, Value::Float(val) if *val < {}))", prop_name, f));
                                         }
+                                        Expression::StringLiteral(s) => {
+                                            output.push_str(&format!("node.check_property(\"{}\").map_or(false, |v| matches!(v, Value::String(val) if val.as_str() < {:?}))", prop_name, s));
+                                        }
                                         Expression::Identifier(id) => {
                                             output.push_str(&format!("node.check_property(\"{}\").map_or(false, |v| matches!(v, Value::Integer(val) if *val < {}))", prop_name, id));
                                         }
@@ -952,6 +1586,9 @@ impl CodeGenerator {
                                         Expression::FloatLiteral(f) => {
                                             output.push_str(&format!("node.check_property(\"{}\").map_or(false, |v| matches!(v, Value::Float(val) if *val <= {}))", prop_name, f));
                                         }
+                                        Expression::StringLiteral(s) => {
+                                            output.push_str(&format!("node.check_property(\"{}\").map_or(false, |v| matches!(v, Value::String(val) if val.as_str() <= {:?}))", prop_name, s));
+                                        }
                                         Expression::Identifier(id) => {
                                             output.push_str(&format!("node.check_property(\"{}\").map_or(false, |v| matches!(v, Value::Integer(val) if *val <= {}))", prop_name, id));
                                         }
@@ -1078,7 +1715,7 @@ impl CodeGenerator {
                                     output.push_str(&format!("matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if *val == {}))\n", f));
                                 }
                                 Expression::StringLiteral(s) => {
-                                    output.push_str(&format!("matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val == \"{}\"))\n", s));
+                                    output.push_str(&format!("matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val == {:?}))\n", s));
                                 }
                                 Expression::Identifier(id) => {
                                     output.push_str(&format!("matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val == {}))\n", id));
@@ -1108,7 +1745,7 @@ impl CodeGenerator {
                                     output.push_str(&format!("matches!(node.check_property(current_prop).unwrap(), Value::Float(val) if val >= {}))\n", f));
                                 }
                                 Expression::StringLiteral(s) => {
-                                    output.push_str(&format!("matches!(node.check_property(current_prop).unwrap(), Value::String(val) if val >= \"{}\"))\n", s));
+                                    output.push_str(&format!("matches!(node.check_property(current_prop).unwrap(), Value::String(val) if val >= {:?}))\n", s));
                                 }
                                 Expression::Identifier(id) => {
                                     output.push_str(&format!("matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if val >= {}))\n", id));
@@ -1144,7 +1781,7 @@ impl CodeGenerator {
                                     output.push_str(&format!("matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val != \"{}\")", id));
                                 }
                                 Expression::StringLiteral(s) => {
-                                    output.push_str(&format!("matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val != \"{}\")", s));
+                                    output.push_str(&format!("matches!(node.check_property(current_prop).unwrap(), Value::String(val) if *val != {:?})", s));
                                 }
                                 Expression::IntegerLiteral(i) => {
                                     output.push_str(&format!("matches!(node.check_property(current_prop).unwrap(), Value::Integer(val) if *val != {})", i));
@@ -1233,7 +1870,66 @@ impl CodeGenerator {
                 unreachable!()
             }
         }
-        output
+        output
+    }
+
+    fn generate_match_pattern(&mut self, pattern: &MatchPattern) -> String {
+        let mut output = String::new();
+        output.push_str(&mut self.indent());
+        output.push_str("let pattern = Pattern::new()\n");
+        self.indent_level += 1;
+        for node in &pattern.nodes {
+            let props = self.generate_pattern_props(&node.properties);
+            output.push_str(&mut self.indent());
+            output.push_str(&format!(
+                ".node(\"{}\", {}, {})\n",
+                node.variable,
+                Self::option_str(&node.label),
+                props
+            ));
+        }
+        for edge in &pattern.edges {
+            let props = self.generate_pattern_props(&edge.properties);
+            output.push_str(&mut self.indent());
+            output.push_str(&format!(
+                ".edge({}, {}, \"{}\", \"{}\", {})\n",
+                Self::option_str(&edge.variable),
+                Self::option_str(&edge.label),
+                edge.from,
+                edge.to,
+                props
+            ));
+        }
+        self.indent_level -= 1;
+        output.push_str(&mut self.indent());
+        output.push_str(";\n");
+        output.push_str(&mut self.indent());
+        output.push_str("tr.match_pattern(&txn, &pattern);\n");
+        output
+    }
+
+    /// Pattern and upsert property values can refer to query parameters, unlike `props_macro`
+    /// values
+    fn generate_pattern_props(&mut self, props: &[(String, ValueType)]) -> String {
+        let props_str = props
+            .iter()
+            .map(|(k, v)| {
+                let value = match v {
+                    ValueType::Identifier(id) => format!("{}.clone()", self.param(id)),
+                    _ => self.value_type_to_rust(v),
+                };
+                format!("\"{}\" => {}", k, value)
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("props!{{ {} }}", props_str)
+    }
+
+    fn option_str(value: &Option<String>) -> String {
+        match value {
+            Some(value) => format!("Some(\"{}\")", value),
+            None => "None".to_string(),
+        }
     }
 
     fn generate_add_vertex(&mut self, add_vertex: &AddNode, var_name: Option<&str>) -> String {
@@ -1289,35 +1985,76 @@ impl CodeGenerator {
         };
 
         // TODO: change
-        let from_id = match &add_edge.connection.from_id.as_ref().unwrap() {
-            IdType::Literal(id) => format!("\"{}\"", id),
-            IdType::Identifier(var) => {
-                if let Some(var_name) = self.current_variables.get(var) {
-                    format!("&{}.get_id()?", to_snake_case(var_name))
-                } else {
-                    format!("\"{}\"", var)
-                }
-            }
-        };
+        let from_id = self.edge_end(add_edge.connection.from_id.as_ref().unwrap());
+        let to_id = self.edge_end(add_edge.connection.to_id.as_ref().unwrap());
+
+        output.push_str(&mut self.indent());
+        output.push_str(&format!(
+            "tr.add_e(&mut txn, \"{}\", {}, {}, {});\n",
+            edge_type, from_id, to_id, props
+        ));
+        // output.push_str(&format!("tr.result()?;\n"));
+
+        output
+    }
 
-        let to_id = match &add_edge.connection.to_id.as_ref().unwrap() {
+    /// The id argument for the `From` or `To` of an edge
+    fn edge_end(&self, id: &IdType) -> String {
+        match id {
             IdType::Literal(id) => format!("\"{}\"", id),
             IdType::Identifier(var) => {
                 if let Some(var_name) = self.current_variables.get(var) {
                     format!("&{}.get_id()?", to_snake_case(var_name))
+                } else if self.loop_params.contains(var) {
+                    format!("&{}", self.param(var))
                 } else {
                     format!("\"{}\"", var)
                 }
             }
-        };
+        }
+    }
 
-        output.push_str(&mut self.indent());
+    /// The fields after the match object are set whether the node is created or matched
+    fn generate_upsert_node(&mut self, upsert: &UpsertNode) -> String {
+        let mut output = String::new();
+        output.push_str(&self.indent());
+        output.push_str(
+            "let mut tr = TraversalBuilder::new(Arc::clone(&db), TraversalValue::Empty);\n",
+        );
+
+        let vertex_type = upsert.vertex_type.as_deref().unwrap_or("");
+        let match_props = self.generate_pattern_props(&upsert.match_fields);
+        let props = match &upsert.fields {
+            Some(fields) => self.generate_pattern_props(fields),
+            None => "props!{}".to_string(),
+        };
+        output.push_str(&self.indent());
         output.push_str(&format!(
-            "tr.add_e(&mut txn, \"{}\", {}, {}, {});\n",
-            edge_type, from_id, to_id, props
+            "tr.upsert_v(&mut txn, \"{}\", {}, {}, {});\n",
+            vertex_type, match_props, props, props
         ));
-        // output.push_str(&format!("tr.result()?;\n"));
+        output
+    }
+
+    fn generate_upsert_edge(&mut self, upsert: &AddEdge) -> String {
+        let mut output = String::new();
+        output.push_str(&self.indent());
+        output.push_str(
+            "let mut tr = TraversalBuilder::new(Arc::clone(&db), TraversalValue::Empty);\n",
+        );
 
+        let edge_type = upsert.edge_type.as_deref().unwrap_or("");
+        let props = match &upsert.fields {
+            Some(fields) => self.generate_pattern_props(fields),
+            None => "props!{}".to_string(),
+        };
+        let from_id = self.edge_end(upsert.connection.from_id.as_ref().unwrap());
+        let to_id = self.edge_end(upsert.connection.to_id.as_ref().unwrap());
+        output.push_str(&self.indent());
+        output.push_str(&format!(
+            "tr.upsert_e(&mut txn, \"{}\", {}, {}, {}, {});\n",
+            edge_type, from_id, to_id, props, props
+        ));
         output
     }
 
@@ -1342,7 +2079,16 @@ impl CodeGenerator {
 
     fn generate_exists_check(&mut self, traversal: &Traversal, query: &Query) -> String {
         let mut output = String::new();
-        output.push_str("tr.filter_nodes(&txn, |node| {\n");
+        // Streaming stops at the first match instead of counting every one
+        let (lazy_len, lazy_steps) = self.generate_lazy_steps(&traversal.steps, true, query);
+        if lazy_len == traversal.steps.len() {
+            output.push_str(&format!(
+                "tr.filter_nodes(&txn, |txn, node| LazyTraversal::from_value(&db, &txn, TraversalValue::from(node.clone())){}.exists());\n",
+                lazy_steps
+            ));
+            return output;
+        }
+        output.push_str("tr.filter_nodes(&txn, |txn, node| {\n");
         output.push_str(&mut self.indent());
         output.push_str("let mut tr = TraversalBuilder::new(Arc::clone(&db), TraversalValue::from(node.clone()));\n");
         output.push_str(&mut self.indent());
@@ -1388,7 +2134,9 @@ impl CodeGenerator {
                     output.push_str(&mut self.indent());
                     output.push_str("let return_val = tr.finish()?;\n");
                     output.push_str(&mut self.indent());
-                    if let Variable(var_name) = &traversal.start {
+                    if let Variable(var_name) | StartNode::Call(Call { name: var_name, .. }) =
+                        &traversal.start
+                    {
                         output.push_str(&format!(
                             "return_vals.insert(\"{}\".to_string(), ReturnValue::from_traversal_value_array_with_mixin(return_val, remapping_vals.borrow_mut()));\n", 
                             var_name,
@@ -1406,6 +2154,12 @@ impl CodeGenerator {
             }
         }
 
+        output.push_str(&mut self.indent());
+        output.push_str("if let Some(profile) = profiler.finish() {\n");
+        output.push_str(&mut self.indent());
+        output.push_str("    return_vals.insert(PROFILE_KEY.to_string(), ReturnValue::from(profile));\n");
+        output.push_str(&mut self.indent());
+        output.push_str("}\n");
         output.push_str(&mut self.indent());
         output.push_str("response.body = sonic_rs::to_vec(&return_vals).unwrap();\n\n");
 
@@ -1438,9 +2192,9 @@ impl CodeGenerator {
 
     fn value_to_rust(&mut self, value: &Value) -> String {
         match value {
-            Value::String(s) => format!("\"{}\"", s),
+            Value::String(s) => format!("{:?}", s),
             Value::Integer(i) => i.to_string(),
-            Value::Float(f) => f.to_string(),
+            Value::Float(f) => format!("{:?}", f),
             Value::Boolean(b) => b.to_string(),
             Value::Array(arr) => format!(
                 "vec![{}]",
@@ -1455,9 +2209,9 @@ impl CodeGenerator {
 
     fn expression_to_value(&mut self, expr: &Expression) -> String {
         match expr {
-            Expression::StringLiteral(s) => format!("\"{}\"", s),
+            Expression::StringLiteral(s) => format!("{:?}", s),
             Expression::IntegerLiteral(i) => i.to_string(),
-            Expression::FloatLiteral(f) => f.to_string(),
+            Expression::FloatLiteral(f) => format!("{:?}", f),
             Expression::BooleanLiteral(b) => b.to_string(),
             Expression::Identifier(id) => {
                 if let Some(var_name) = self.current_variables.get(id) {
@@ -1823,6 +2577,92 @@ impl CodeGenerator {
 /// - insert at the end of the function before the return
 ///
 
+/// How many leading steps the generated handler streams through `LazyTraversal`,
+/// `nodes` being whether the traversal starts from nodes.
+///
+/// A WHERE on nodes only streams when a later step does too, so that e.g. a RANGE after it
+/// stops reading early. Otherwise it runs on the `TraversalBuilder`, which can filter in parallel.
+pub(crate) fn lazy_prefix_len(steps: &[Step], mut nodes: bool) -> usize {
+    let mut consumed = 0;
+    for (i, step) in steps.iter().enumerate() {
+        match step {
+            Step::Node(GraphStep::Out(_))
+            | Step::Node(GraphStep::In(_))
+            | Step::Node(GraphStep::InN)
+            | Step::Edge(GraphStep::InN)
+            | Step::Node(GraphStep::OutN)
+            | Step::Edge(GraphStep::OutN) => nodes = true,
+            Step::Node(GraphStep::OutE(_)) | Step::Node(GraphStep::InE(_)) => nodes = false,
+            Step::Range(_) => {}
+            Step::Where(expr)
+                if nodes
+                    && matches!(
+                        **expr,
+                        Expression::BooleanLiteral(_)
+                            | Expression::And(_)
+                            | Expression::Or(_)
+                            | Expression::Traversal(_)
+                    ) =>
+            {
+                continue
+            }
+            _ => break,
+        }
+        consumed = i + 1;
+    }
+    consumed
+}
+
+fn range_bound(expr: &Expression) -> String {
+    match expr {
+        Expression::IntegerLiteral(val) => format!("{}", val),
+        Expression::Identifier(id) => format!("data.{}", to_snake_case(id)),
+        _ => unreachable!(),
+    }
+}
+
+/// Whether the query needs a write transaction
+pub(crate) fn writes_to_graph(query: &Query) -> bool {
+    statements_write(&query.statements)
+}
+
+/// Whether evaluating `expression` changes the graph
+pub(crate) fn expression_writes(expression: &Expression) -> bool {
+    match expression {
+        Expression::AddNode(_)
+        | Expression::AddEdge(_)
+        | Expression::UpsertNode(_)
+        | Expression::UpsertEdge(_)
+        | Expression::AddVector(_)
+        | Expression::BatchAddVector(_) => true,
+        Expression::Traversal(traversal) => traversal
+            .steps
+            .iter()
+            .any(|step| matches!(step, Step::Update(_) | Step::AddEdge(_))),
+        _ => false,
+    }
+}
+
+fn statements_write(statements: &[Statement]) -> bool {
+    statements.iter().any(|s| {
+        matches!(s, Statement::AddNode(_))
+            || matches!(s, Statement::AddEdge(_))
+            || matches!(s, Statement::UpsertNode(_) | Statement::UpsertEdge(_))
+            || matches!(s, Statement::Drop(_))
+            || matches!(s, Statement::AddVector(_))
+            || matches!(s, Statement::BatchAddVector(_))
+            || match s {
+                Statement::Assignment(assignment) => expression_writes(&assignment.value),
+                Statement::If(if_statement) => {
+                    statements_write(&if_statement.then)
+                        || statements_write(&if_statement.otherwise)
+                }
+                Statement::For(for_loop) => statements_write(&for_loop.body),
+                _ => false,
+            }
+    })
+}
+
 fn to_snake_case(s: &str) -> String {
     let mut result = String::with_capacity(s.len());
     let mut chars = s.chars().peekable();
@@ -1853,6 +2693,19 @@ fn to_snake_case(s: &str) -> String {
     result
 }
 
+/// The struct name for an inline object type at `field` of `parent`
+fn object_name(parent: &str, field: &str) -> String {
+    let mut name = parent.to_string();
+    for part in field.split('_') {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            name.extend(first.to_uppercase());
+            name.push_str(chars.as_str());
+        }
+    }
+    name
+}
+
 fn tr_is_object_remapping(tr: &Traversal) -> bool {
     match tr.steps.last() {
         Some(Step::Object(_)) => true,
@@ -1918,6 +2771,22 @@ mod tests {
         assert!(generated.contains("=="));
     }
 
+    #[test]
+    fn test_where_date_comparison() {
+        let input = r#"
+        QUERY RecentEvents() =>
+            events <- N<Event>::WHERE(_::{at}::GT(2024-03-01))
+            RETURN events
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let generated = generator.generate_source(&source);
+        println!("Generated code:\n{}", generated);
+        assert!(generated.contains("tr.filter_nodes"));
+        assert!(generated.contains("val.as_str() > \"2024-03-01\""));
+    }
+
     #[test]
     fn test_where_exists_condition() {
         let input = r#"
@@ -1933,9 +2802,7 @@ mod tests {
         println!("Generated code:\n{}", generated);
 
         assert!(generated.contains("tr.filter_nodes"));
-        assert!(generated.contains("out_e"));
-        assert!(generated.contains("count"));
-        assert!(generated.contains("count > 0"));
+        assert!(generated.contains(".out_e(\"Authored\").exists()"));
     }
 
     #[test]
@@ -2091,4 +2958,352 @@ mod tests {
         assert_eq!(to_snake_case("XMLHttpRequest"), "xml_http_request");
         assert_eq!(to_snake_case("iOS"), "i_os");
     }
+
+    #[test]
+    fn test_predict_links_generation() {
+        let input = r#"
+        QUERY PeopleYouMayKnow(userID: String) =>
+            user <- N<User>(userID)
+            suggestions <- user::PredictLinks<Follows>(Jaccard, 10)
+            RETURN suggestions
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let output = generator.generate_source(&source);
+
+        assert!(output.contains(
+            "tr.predict_links(&txn, \"Follows\", LinkPredictionMetric::Jaccard, 10);"
+        ));
+    }
+
+    #[test]
+    fn test_dag_step_generation() {
+        let input = r#"
+        QUERY BuildOrder() =>
+            order <- N<Target>::TopoSort<DependsOn>
+            cycle <- N<Target>::FindCycle<DependsOn>
+            critical <- N<Target>::CriticalPath<DependsOn>(duration)
+            RETURN order, cycle, critical
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let output = generator.generate_source(&source);
+
+        assert!(output.contains("tr.topological_sort(&txn, \"DependsOn\");"));
+        assert!(output.contains("tr.find_cycle(&txn, \"DependsOn\");"));
+        assert!(output.contains("tr.critical_path(&txn, \"DependsOn\", Some(\"duration\"));"));
+    }
+
+    #[test]
+    fn test_match_pattern_generation() {
+        let input = r#"
+        QUERY MutualFollows(userName: String) =>
+            rows <- MATCH (a:User {name: userName})-[:Follows]->(b:User)-[f:Follows]->(a)
+            RETURN rows
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let output = generator.generate_source(&source);
+
+        assert!(output.contains(".node(\"a\", Some(\"User\"), props!{ \"name\" => data.user_name.clone() })"));
+        assert!(output.contains(".edge(None, Some(\"Follows\"), \"a\", \"b\", props!{  })"));
+        assert!(output.contains(".edge(Some(\"f\"), Some(\"Follows\"), \"b\", \"a\", props!{  })"));
+        assert!(output.contains("tr.match_pattern(&txn, &pattern);"));
+    }
+
+    #[test]
+    fn test_lazy_traversal_generation() {
+        let input = r#"
+        QUERY RecentFollows(userID: String) =>
+            follows <- N<User>(userID)::Out<Follows>::RANGE(0, 10)
+            total <- N<User>::OutE<Follows>::InN::COUNT
+            RETURN follows, total
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let output = generator.generate_source(&source);
+
+        assert!(output.contains(
+            "LazyTraversal::v_from_id(&db, &txn, &data.user_id).out(\"Follows\").range(0, 10).within(&budget).collect()?"
+        ));
+        assert!(output.contains(
+            "LazyTraversal::v_from_types(&db, &txn, &[\"User\"]).out_e(\"Follows\").in_v().within(&budget).collect_count()?"
+        ));
+        assert!(!output.contains("tr.range("));
+        assert!(!output.contains("tr.count();"));
+    }
+
+    #[test]
+    fn test_range_streams_through_where() {
+        let input = r#"
+        QUERY AdultFollows(userID: String) =>
+            follows <- N<User>(userID)::Out<Follows>::WHERE(_::{age}::GT(21))::RANGE(0, 5)
+            edges <- N<User>(userID)::OutE<Follows>::WHERE(_::{age}::GT(21))::RANGE(0, 5)
+            adults <- N<User>::WHERE(_::{age}::GT(21))
+            RETURN follows, edges, adults
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let output = generator.generate_source(&source);
+
+        assert!(output.contains(
+            "LazyTraversal::v_from_id(&db, &txn, &data.user_id).out(\"Follows\").filter_nodes(|node| Ok(node.check_property(\"age\").map_or(false, |v| matches!(v, Value::Integer(val) if *val > 21)))).range(0, 5).within(&budget).collect()?"
+        ));
+        // edges are filtered on the builder, and so is a WHERE nothing streams after
+        assert!(output.contains(
+            "LazyTraversal::v_from_id(&db, &txn, &data.user_id).out_e(\"Follows\").within(&budget).collect()?"
+        ));
+        assert!(output.contains(
+            "LazyTraversal::v_from_types(&db, &txn, &[\"User\"]).within(&budget).collect()?"
+        ));
+        assert_eq!(output.matches("tr.filter_nodes(&txn, |txn, node|").count(), 2);
+        assert_eq!(output.matches("tr.range(0, 5);").count(), 1);
+    }
+
+    #[test]
+    fn test_order_and_group_generation() {
+        let input = r#"
+        QUERY Leaderboard() =>
+            top <- N<Player>::ORDER(score DESC, name)::DEDUP(team)::RANGE(0, 10)
+            teams <- N<Player>::GROUP_BY(team)::AGG(COUNT, SUM(score))
+            RETURN top, teams
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let output = generator.generate_source(&source);
+
+        assert!(output.contains("tr.order_by(&[(\"score\", Order::Desc), (\"name\", Order::Asc)]);"));
+        assert!(output.contains("tr.dedup_by(\"team\");"));
+        assert!(output.contains("tr.range(0, 10);"));
+        assert!(output.contains(
+            "tr.group_by(\"team\", &[Aggregation::Count, Aggregation::Sum(\"score\".to_string())]);"
+        ));
+        assert!(!output.contains("tr.aggregate("));
+    }
+
+    #[test]
+    fn test_label_and_branch_generation() {
+        let input = r#"
+        QUERY OrderItems(userID: String) =>
+            rows <- N<User>(userID)::AS(u)::Out<Ordered>::Out<Contains>::AS(item)::SELECT(u, item)
+            boss <- N<User>(userID)::COALESCE(_::Out<Manager>, _::Out<TeamLead>)
+            RETURN rows, boss
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let output = generator.generate_source(&source);
+
+        assert!(output.contains("tr.as_(\"u\");"));
+        assert!(output.contains("tr.out(&txn, \"Contains\");"));
+        assert!(output.contains("tr.select(&[\"u\", \"item\"]);"));
+        assert!(output.contains(
+            "tr.coalesce(&[&|tr: &mut TraversalBuilder| {\ntr.out(&txn, \"Manager\");\n}, &|tr: &mut TraversalBuilder| {\ntr.out(&txn, \"TeamLead\");\n}]);"
+        ));
+    }
+
+    #[test]
+    fn test_path_generation() {
+        let input = r#"
+        QUERY ExplainRecommendation(userID: String) =>
+            routes <- N<User>(userID)::Out<Bought>::In<Bought>::Out<Bought>::PATH
+            RETURN routes
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let output = generator.generate_source(&source);
+
+        assert!(output.contains("LazyTraversal::v_from_id(&db, &txn, &data.user_id).within(&budget).collect()?);"));
+        assert!(output.contains("tr.track_paths();"));
+        assert!(output.contains("tr.in_(&txn, \"Bought\");"));
+        assert!(output.contains("tr.path();"));
+    }
+
+    #[test]
+    fn test_parallel_hint_generation() {
+        let input = r#"
+        QUERY FanOut(userID: String) PARALLEL(4) =>
+            friends <- N<User>(userID)::Out<Follows>::WHERE(_::{age}::GT(21))::Out<Follows>
+            RETURN friends
+
+        QUERY Follow(userID: String) PARALLEL(4) =>
+            friends <- N<User>(userID)::Out<Follows>::WHERE(_::{age}::GT(21))::Out<Follows>
+            AddE<Follows>::To("123")::From("456")
+            RETURN friends
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let query = |name: &str| source.queries.iter().find(|q| q.name == name).unwrap();
+        let read = generator.generate_query(query("FanOut"));
+        let write = generator.generate_query(query("Follow"));
+
+        assert!(read.contains("tr.parallelism(4);"));
+        // writes aren't visible to the per-thread read transactions
+        assert!(write.contains("tr.parallelism(1);"));
+        assert!(!write.contains("tr.parallelism(4);"));
+    }
+
+    #[test]
+    fn test_profiled_steps_generation() {
+        let input = r#"
+        QUERY TopFollowers(userID: String) =>
+            followers <- N<User>(userID)::In<Follows>::WHERE(_::Out<Follows>::COUNT::GT(10))::ORDER(age DESC)
+            RETURN followers
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let output = generator.generate_source(&source);
+
+        assert!(output.contains("let mut profiler = QueryProfiler::from_request(&input.request);"));
+        assert!(output.contains("profiler.begin(\"N<User>(userID)::In<Follows>\", &TraversalValue::Empty);"));
+        assert!(output.contains("profiler.begin(\"WHERE\", &tr.current_step);"));
+        assert!(output.contains("profiler.begin(\"ORDER(age DESC)\", &tr.current_step);"));
+        // the traversal inside WHERE is part of the WHERE step
+        assert!(!output.contains("profiler.begin(\"Out<Follows>\""));
+        assert_eq!(output.matches("profiler.end(&tr.current_step);").count(), 3);
+        assert_eq!(output.matches("tr.check_budget(&budget)?;").count(), 2);
+        assert!(output.contains("return_vals.insert(PROFILE_KEY.to_string(), ReturnValue::from(profile));"));
+    }
+
+    #[test]
+    fn test_parameter_types_generation() {
+        let input = r#"
+        QUERY AddDoc(nick: Optional<String>, doc: {title: String, pages: [{vec: Optional<[F32; 2]>}]}, query: [F32; 4]) =>
+            docs <- N<Doc>
+            RETURN docs
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let output = generator.generate_query(&source.queries[0]);
+
+        assert!(output.contains("    struct AddDocDataDocPages {\n        vec: Option<Vec<f64>>,\n    }"));
+        assert!(output.contains("    struct AddDocDataDoc {\n        title: String,\n        pages: Vec<AddDocDataDocPages>,\n    }"));
+        assert!(output.contains("        nick: Option<String>,\n        doc: AddDocDataDoc,\n        query: Vec<f64>,\n"));
+        // clients may send more fields than the query declares
+        assert!(!output.contains("deny_unknown_fields"));
+        assert!(output.contains("    for (i0, item0) in data.doc.pages.iter().enumerate() {\n        if let Some(value1) = &item0.vec {\n            if value1.len() != 2 {"));
+        assert!(output.contains("format!(\"doc.pages[{}].vec: expected 2 dimensions, found {}\", i0, value1.len())"));
+        assert!(output.contains("format!(\"query: expected 4 dimensions, found {}\", data.query.len())"));
+    }
+
+    #[test]
+    fn test_query_limits_generation() {
+        let input = r#"
+        QUERY Bounded(userID: String) TIMEOUT(500) MAX_ITEMS(10000) =>
+            friends <- N<User>(userID)::Out<Follows>::Out<Follows>
+            RETURN friends
+
+        QUERY Unbounded(userID: String) =>
+            friends <- N<User>(userID)::Out<Follows>::DEDUP
+            RETURN friends
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let query = |name: &str| source.queries.iter().find(|q| q.name == name).unwrap();
+        let bounded = generator.generate_query(query("Bounded"));
+        let unbounded = generator.generate_query(query("Unbounded"));
+
+        assert!(bounded.contains(
+            "let budget = QueryBudget::new(&db.query_limits.clone().timeout_ms(500).max_items(10000), &input.cancel);"
+        ));
+        assert!(bounded.contains(".out(\"Follows\").out(\"Follows\").within(&budget).collect()?"));
+        assert!(unbounded.contains("let budget = QueryBudget::new(&db.query_limits, &input.cancel);"));
+        assert!(unbounded.contains("tr.dedup();"));
+        assert!(unbounded.contains("tr.within(&budget);\n"));
+        assert!(unbounded.contains("tr.check_budget(&budget)?;"));
+        // everything was streamed with the budget already
+        assert!(!bounded.contains("tr.within(&budget);"));
+    }
+
+    #[test]
+    fn test_control_flow_generation() {
+        let input = r#"
+        QUERY Follow(userID: String, ids: [String], notify: Boolean) =>
+            user <- N<User>(userID)
+            IF EXISTS(N<User>(userID)::Out<Follows>) {
+                RETURN "already following"
+            } ELSE IF notify {
+                FOR id IN ids {
+                    AddE<Follows>()::From(user)::To(id)
+                }
+            }
+            RETURN user
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let output = generator.generate_query(&source.queries[0]);
+
+        assert!(output.contains("let mut txn = db.graph_env.write_txn().unwrap();"));
+        assert!(output.contains("    let condition_0 = tr.finish()?.is_truthy();\n    if condition_0 {\n"));
+        assert!(output.contains("        txn.commit()?;\n        return Ok(());\n    } else {\n        if data.notify {\n"));
+        assert!(output.contains("            for id in data.ids.clone() {\n"));
+        assert!(output.contains("tr.add_e(&mut txn, \"Follows\", &user.get_id()?, &id, props!{});"));
+        assert_eq!(output.matches("txn.commit()?;").count(), 2);
+    }
+
+    #[test]
+    fn test_upsert_generation() {
+        let input = r#"
+        QUERY Ingest(email: String, name: String) =>
+            user <- UpsertN<User>({email: email}, {name: name})
+            UpsertE<Follows>::From(user)::To("admin")
+            RETURN user
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let output = generator.generate_query(&source.queries[0]);
+
+        assert!(output.contains("let mut txn = db.graph_env.write_txn().unwrap();"));
+        assert!(output.contains("tr.upsert_v(&mut txn, \"User\", props!{ \"email\" => data.email.clone() }, props!{ \"name\" => data.name.clone() }, props!{ \"name\" => data.name.clone() });"));
+        assert!(output.contains("tr.upsert_e(&mut txn, \"Follows\", &user.get_id()?, \"admin\", props!{}, props!{});"));
+        assert!(output.contains("txn.commit()?;"));
+    }
+
+    #[test]
+    fn test_function_call_generation() {
+        let input = r#"
+        N::User {
+            name: String,
+            age: Integer
+        }
+
+        E::Follows {
+            From: User,
+            To: User,
+            Properties: {
+            }
+        }
+
+        FN olderFollowers(user: User, minAge: Integer) =>
+            user::In<Follows>::WHERE(_::{age}::GTE(minAge))
+
+        QUERY GetFollowers(id: String, age: Integer) =>
+            user <- N<User>(id)
+            followers <- olderFollowers(user, age)
+            RETURN followers, olderFollowers(N<User>(id), 30)
+        "#;
+
+        let source = HelixParser::parse_source(input).unwrap();
+        let mut generator = CodeGenerator::new();
+        let output = generator.generate_source(&source);
+
+        assert!(output.contains("let (user, min_age) = (user.clone(), data.age.clone());"));
+        assert!(output.contains(
+            "TraversalBuilder::new(Arc::clone(&db), TraversalValue::from(user.clone()));"
+        ));
+        assert!(output.contains("let arg_0 = tr.finish()?;"));
+    }
 }
@@ -1,2 +1,3 @@
 pub mod generator;
-pub mod example;
\ No newline at end of file
+pub mod example;
+pub mod explain;
\ No newline at end of file
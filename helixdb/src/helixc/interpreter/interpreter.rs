@@ -1,9 +1,1608 @@
-use super::parser::helix_parser::Source;
+//! Runs a parsed HelixQL query directly against the graph, so queries can be executed
+//! without generating and compiling a handler for them first.
+//!
+//! The interpreter follows the generated handlers step for step: parameters are bound
+//! in declaration order, writing queries run in a single write transaction, objects
+//! register remappings that are mixed into the returned items, and the response is the
+//! same JSON map of return values.
 
-pub fn interpret_ast(ast: &Source) -> Result<(), Error> {
-    let queries = ast.queries;
-    let nodes = ast.node_schemas;
-    let edges = ast.edge_schemas;
+use crate::{
+    helix_engine::{
+        graph_core::{
+            aggregate::{self, compare_values, Order},
+            algorithms::link_prediction::LinkPredictionMetric,
+            graph_core::QueryInput,
+            limits::{CancelToken, QueryBudget},
+            pattern::Pattern,
+            traversal::TraversalBuilder,
+            traversal_steps::{
+                SourceTraversalSteps, TraversalBuilderMethods, TraversalMethods,
+                TraversalSearchMethods, TraversalSteps, VectorTraversalSteps,
+            },
+        },
+        storage_core::storage_core::HelixGraphStorage,
+        types::GraphError,
+    },
+    helixc::{
+        analyzer::analyzer::type_name,
+        generator::generator::writes_to_graph,
+        parser::helix_parser::{
+            AddEdge, AddNode, AddVector, Aggregation, BooleanOp, Call, DagOperation,
+            EvaluatesToNumber, Expression, FieldType, FieldValue, Function, GraphStep, IdType,
+            MatchPattern, Object, OrderDirection, Query, SearchVector, StartNode, Statement, Step,
+            Traversal, UpsertNode, ValueType, VectorData,
+        },
+    },
+    protocol::{
+        remapping::{Remapping, ResponseRemapping},
+        return_values::ReturnValue,
+        traversal_value::TraversalValue,
+        value::Value,
+    },
+};
+use heed3::{RoTxn, RwTxn, WithTls};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
 
-    for node in nodes {}
+/// Runs `query` against `storage` with `params` bound to its parameters in order,
+/// returning the JSON body the generated handler for the query would respond with.
+/// `functions` are the `FN` definitions the query can call.
+pub fn interpret_query(
+    storage: Arc<HelixGraphStorage>,
+    query: &Query,
+    functions: &[Function],
+    params: Vec<QueryInput>,
+) -> Result<String, GraphError> {
+    Interpreter::new(storage, query, functions, params)?.run()
+}
+
+/// The transaction a query runs in, a write transaction when the query changes the graph
+enum Txn<'a> {
+    Read(RoTxn<'a, WithTls>),
+    Write(RwTxn<'a>),
+}
+
+impl<'a> Txn<'a> {
+    fn read(&self) -> &RoTxn<'a> {
+        match self {
+            Txn::Read(txn) => txn,
+            Txn::Write(txn) => txn,
+        }
+    }
+}
+
+/// Runs `step` with the write transaction. Fails inside WHERE conditions, which hold
+/// the transaction for reading while they run.
+fn write<'a, T>(
+    txn: &RefCell<Txn<'a>>,
+    step: impl FnOnce(&mut RwTxn<'a>) -> T,
+) -> Result<T, GraphError> {
+    let mut txn = txn.try_borrow_mut().map_err(|_| {
+        GraphError::New("Cannot write to the graph inside a WHERE condition".to_string())
+    })?;
+    match &mut *txn {
+        Txn::Write(txn) => Ok(step(txn)),
+        Txn::Read(_) => Err(GraphError::New(
+            "Cannot write to the graph in a read-only query".to_string(),
+        )),
+    }
+}
+
+struct Interpreter<'q> {
+    storage: Arc<HelixGraphStorage>,
+    query: &'q Query,
+    functions: HashMap<&'q str, &'q Function>,
+    /// Functions being called, innermost last
+    calling: RefCell<Vec<&'q str>>,
+    params: HashMap<String, Value>,
+    variables: RefCell<HashMap<String, TraversalValue>>,
+    /// Object remappings by item id, mixed into the items when they are returned
+    remappings: RefCell<HashMap<String, ResponseRemapping>>,
+    budget: QueryBudget,
+    parallelism: Option<usize>,
+}
+
+impl<'q> Interpreter<'q> {
+    fn new(
+        storage: Arc<HelixGraphStorage>,
+        query: &'q Query,
+        functions: &'q [Function],
+        params: Vec<QueryInput>,
+    ) -> Result<Self, GraphError> {
+        let mut limits = storage.query_limits.clone();
+        if let Some(timeout_ms) = query.timeout_ms {
+            limits = limits.timeout_ms(timeout_ms);
+        }
+        if let Some(max_items) = query.max_items {
+            limits = limits.max_items(max_items);
+        }
+        if let Some(max_memory) = query.max_memory {
+            limits = limits.max_memory(max_memory);
+        }
+        // writes share one transaction, so they can't be spread over threads
+        let parallelism = if writes_to_graph(query) {
+            Some(1)
+        } else {
+            query.parallelism
+        };
+        Ok(Self {
+            params: bind_params(query, params)?,
+            budget: QueryBudget::new(&limits, &CancelToken::default()),
+            storage,
+            query,
+            functions: functions.iter().map(|f| (f.name.as_str(), f)).collect(),
+            calling: RefCell::new(Vec::new()),
+            variables: RefCell::new(HashMap::new()),
+            remappings: RefCell::new(HashMap::new()),
+            parallelism,
+        })
+    }
+
+    fn run(&self) -> Result<String, GraphError> {
+        let txn = RefCell::new(if writes_to_graph(self.query) {
+            Txn::Write(self.storage.graph_env.write_txn()?)
+        } else {
+            Txn::Read(self.storage.graph_env.read_txn()?)
+        });
+        let returned = self.statements(&self.query.statements, &txn)?;
+        let return_values =
+            self.return_values(returned.unwrap_or(&self.query.return_values), &txn)?;
+        if let Txn::Write(txn) = txn.into_inner() {
+            txn.commit()?;
+        }
+        Ok(sonic_rs::to_string(&return_values)?)
+    }
+
+    /// Runs statements in order until one of them returns, handing back the returned values
+    fn statements(
+        &self,
+        statements: &'q [Statement],
+        txn: &RefCell<Txn>,
+    ) -> Result<Option<&'q [Expression]>, GraphError> {
+        for statement in statements {
+            if let Some(values) = self.statement(statement, txn)? {
+                return Ok(Some(values));
+            }
+        }
+        Ok(None)
+    }
+
+    fn statement(
+        &self,
+        statement: &'q Statement,
+        txn: &RefCell<Txn>,
+    ) -> Result<Option<&'q [Expression]>, GraphError> {
+        match statement {
+            Statement::Assignment(assignment) => {
+                let value = self.expression(&assignment.value, txn)?;
+                self.variables
+                    .borrow_mut()
+                    .insert(assignment.variable.clone(), value);
+            }
+            Statement::AddNode(add) => {
+                self.add_node(add, txn)?;
+            }
+            Statement::AddEdge(add) => {
+                self.add_edge(add, txn)?;
+            }
+            Statement::UpsertNode(upsert) => {
+                self.upsert_node(upsert, txn)?;
+            }
+            Statement::UpsertEdge(upsert) => {
+                self.upsert_edge(upsert, txn)?;
+            }
+            Statement::AddVector(add) => {
+                self.add_vector(add, txn)?;
+            }
+            Statement::SearchVector(search) => {
+                self.search_vector(search, txn)?;
+            }
+            Statement::Drop(expression) => {
+                let mut tr = self.builder(self.expression(expression, txn)?);
+                write(txn, |txn| {
+                    tr.drop(txn);
+                })?;
+                tr.execute()?;
+            }
+            Statement::BatchAddVector(_) => return Err(unsupported("BatchAddV")),
+            Statement::If(if_statement) => {
+                let branch =
+                    match self.condition(&TraversalValue::Empty, &if_statement.condition, txn)? {
+                        true => &if_statement.then,
+                        false => &if_statement.otherwise,
+                    };
+                return self.statements(branch, txn);
+            }
+            Statement::For(for_loop) => {
+                let items = self.expression(&for_loop.iterable, txn)?.into_items();
+                let shadowed = self.variables.borrow().get(&for_loop.variable).cloned();
+                let mut returned = None;
+                for item in items {
+                    self.variables
+                        .borrow_mut()
+                        .insert(for_loop.variable.clone(), item);
+                    returned = self.statements(&for_loop.body, txn)?;
+                    if returned.is_some() {
+                        break;
+                    }
+                }
+                let mut variables = self.variables.borrow_mut();
+                match shadowed {
+                    Some(value) => variables.insert(for_loop.variable.clone(), value),
+                    None => variables.remove(&for_loop.variable),
+                };
+                return Ok(returned);
+            }
+            Statement::Return(ret) => return Ok(Some(&ret.values)),
+        }
+        Ok(None)
+    }
+
+    fn expression(
+        &self,
+        expression: &Expression,
+        txn: &RefCell<Txn>,
+    ) -> Result<TraversalValue, GraphError> {
+        match expression {
+            Expression::Traversal(traversal) => self.traversal(traversal, None, txn),
+            Expression::Identifier(name) => self.variable(name),
+            Expression::AddNode(add) => self.add_node(add, txn),
+            Expression::AddEdge(add) => self.add_edge(add, txn),
+            Expression::UpsertNode(upsert) => self.upsert_node(upsert, txn),
+            Expression::UpsertEdge(upsert) => self.upsert_edge(upsert, txn),
+            Expression::AddVector(add) => self.add_vector(add, txn),
+            Expression::SearchVector(search) => self.search_vector(search, txn),
+            Expression::Match(pattern) => {
+                let pattern = self.pattern(pattern)?;
+                let mut tr = self.builder(TraversalValue::Empty);
+                tr.match_pattern(txn.borrow().read(), &pattern);
+                tr.finish()
+            }
+            Expression::Exists(traversal) => {
+                let exists = self.traversal(traversal, None, txn)?.is_truthy();
+                Ok(TraversalValue::from((
+                    "exists".to_string(),
+                    Value::Boolean(exists),
+                )))
+            }
+            Expression::StringLiteral(_)
+            | Expression::IntegerLiteral(_)
+            | Expression::FloatLiteral(_)
+            | Expression::BooleanLiteral(_) => Ok(TraversalValue::from((
+                "value".to_string(),
+                self.value(expression, None, txn)?,
+            ))),
+            Expression::None => Ok(TraversalValue::Empty),
+            Expression::And(_) | Expression::Or(_) => Err(GraphError::New(
+                "AND and OR can only be used inside WHERE".to_string(),
+            )),
+            Expression::BatchAddVector(_) => Err(unsupported("BatchAddV")),
+        }
+    }
+
+    /// Runs a traversal, starting from `item` when it is anonymous.
+    /// A traversal ending in a comparison evaluates to whether any of its values matched.
+    fn traversal(
+        &self,
+        traversal: &Traversal,
+        item: Option<&TraversalValue>,
+        txn: &RefCell<Txn>,
+    ) -> Result<TraversalValue, GraphError> {
+        let start = self.start(traversal, item, txn)?;
+        match traversal.steps.split_last() {
+            Some((Step::BooleanOperation(op), steps)) => {
+                let matched = self.compare(start, steps, op, item, txn)?;
+                Ok(TraversalValue::from((
+                    "value".to_string(),
+                    Value::Boolean(matched),
+                )))
+            }
+            _ => self.steps(start, &traversal.steps, txn),
+        }
+    }
+
+    fn start(
+        &self,
+        traversal: &Traversal,
+        item: Option<&TraversalValue>,
+        txn: &RefCell<Txn>,
+    ) -> Result<TraversalValue, GraphError> {
+        match &traversal.start {
+            StartNode::Node { types, ids } => {
+                let txn = txn.borrow();
+                let mut tr = self.builder(TraversalValue::Empty);
+                match (ids, types) {
+                    (Some(ids), _) => {
+                        let ids = ids
+                            .iter()
+                            .map(|id| self.id(id))
+                            .collect::<Result<Vec<_>, _>>()?;
+                        let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+                        tr.v_from_ids(txn.read(), &ids);
+                    }
+                    (None, Some(types)) => {
+                        let types: Vec<&str> = types.iter().map(String::as_str).collect();
+                        tr.v_from_types(txn.read(), &types);
+                    }
+                    (None, None) => {
+                        tr.v(txn.read());
+                    }
+                }
+                tr.finish()
+            }
+            StartNode::Edge { types, ids } => {
+                let txn = txn.borrow();
+                let mut tr = match ids {
+                    Some(ids) => self.builder(
+                        ids.iter()
+                            .map(|id| {
+                                let mut tr = self.builder(TraversalValue::Empty);
+                                tr.e_from_id(txn.read(), &self.id(id)?);
+                                tr.finish()
+                            })
+                            .collect::<Result<_, GraphError>>()?,
+                    ),
+                    None => {
+                        let mut tr = self.builder(TraversalValue::Empty);
+                        tr.e(txn.read());
+                        tr
+                    }
+                };
+                if let Some(types) = types {
+                    tr.filter_edges(txn.read(), |_, edge| Ok(types.contains(&edge.label)));
+                }
+                tr.finish()
+            }
+            StartNode::Variable(name) => self.variable(name),
+            StartNode::Call(call) => self.call(call, txn),
+            StartNode::Anonymous => item.cloned().ok_or_else(|| {
+                GraphError::New("`_` can only be used inside a traversal step".to_string())
+            }),
+        }
+    }
+
+    /// Runs the body of the called function with its parameters bound to the arguments,
+    /// shadowing variables of the same name while it runs
+    fn call(&self, call: &Call, txn: &RefCell<Txn>) -> Result<TraversalValue, GraphError> {
+        let function = *self
+            .functions
+            .get(call.name.as_str())
+            .ok_or_else(|| GraphError::New(format!("{} is not a function", call.name)))?;
+        if call.args.len() != function.parameters.len() {
+            return Err(GraphError::New(format!(
+                "{} takes {} arguments, got {}",
+                function.name,
+                function.parameters.len(),
+                call.args.len()
+            )));
+        }
+        if self.calling.borrow().contains(&function.name.as_str()) {
+            return Err(GraphError::New(format!(
+                "Function {} calls itself",
+                function.name
+            )));
+        }
+        let args = call
+            .args
+            .iter()
+            .map(|arg| self.expression(arg, txn))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let shadowed: Vec<_> = {
+            let mut variables = self.variables.borrow_mut();
+            function
+                .parameters
+                .iter()
+                .zip(args)
+                .map(|(parameter, arg)| {
+                    let name = parameter.name.clone();
+                    (name.clone(), variables.insert(name, arg))
+                })
+                .collect()
+        };
+        self.calling.borrow_mut().push(&function.name);
+        let result = self.expression(&function.body, txn);
+        self.calling.borrow_mut().pop();
+
+        let mut variables = self.variables.borrow_mut();
+        for (name, value) in shadowed.into_iter().rev() {
+            match value {
+                Some(value) => variables.insert(name, value),
+                None => variables.remove(&name),
+            };
+        }
+        result
+    }
+
+    fn steps(
+        &self,
+        start: TraversalValue,
+        steps: &[Step],
+        txn: &RefCell<Txn>,
+    ) -> Result<TraversalValue, GraphError> {
+        let mut tr = self.builder(start);
+        self.apply(&mut tr, steps, txn)?;
+        tr.finish()
+    }
+
+    /// Applies `steps` to `tr`, checking the query's budget after each of them
+    fn apply(
+        &self,
+        tr: &mut TraversalBuilder,
+        steps: &[Step],
+        txn: &RefCell<Txn>,
+    ) -> Result<(), GraphError> {
+        if steps.iter().any(|step| matches!(step, Step::Path)) {
+            tr.track_paths();
+        }
+        let mut steps = steps.iter().peekable();
+        while let Some(step) = steps.next() {
+            match step {
+                // GROUP_BY followed by AGGREGATE is one grouping, like in the generated code
+                Step::GroupBy(property) => {
+                    let aggregations =
+                        match steps.next_if(|step| matches!(step, Step::Aggregate(_))) {
+                            Some(Step::Aggregate(aggregations)) => {
+                                aggregations.iter().map(aggregation).collect()
+                            }
+                            _ => vec![aggregate::Aggregation::Count],
+                        };
+                    tr.group_by(property, &aggregations);
+                }
+                step => self.step(tr, step, txn)?,
+            }
+            if let Some(err) = tr.error.take() {
+                return Err(err);
+            }
+            tr.check_budget(&self.budget)?;
+        }
+        Ok(())
+    }
+
+    fn step(
+        &self,
+        tr: &mut TraversalBuilder,
+        step: &Step,
+        txn: &RefCell<Txn>,
+    ) -> Result<(), GraphError> {
+        match step {
+            Step::Node(graph_step) | Step::Edge(graph_step) => {
+                graph_step_on(tr, graph_step, txn.borrow().read());
+            }
+            Step::Where(condition) => {
+                // conditions read through the interpreter's transaction, which the builder's
+                // worker threads can't share, so they are evaluated before filtering
+                let mut kept = HashSet::new();
+                match &tr.current_step {
+                    TraversalValue::NodeArray(nodes) => {
+                        for node in nodes {
+                            if self.condition(&TraversalValue::from(node), condition, txn)? {
+                                kept.insert(node.id.clone());
+                            }
+                        }
+                    }
+                    TraversalValue::EdgeArray(edges) => {
+                        for edge in edges {
+                            if self.condition(&TraversalValue::from(edge), condition, txn)? {
+                                kept.insert(edge.id.clone());
+                            }
+                        }
+                    }
+                    TraversalValue::Empty => {}
+                    _ => {
+                        return Err(GraphError::TraversalError(
+                            "WHERE can only filter nodes or edges".to_string(),
+                        ))
+                    }
+                }
+                let read = txn.borrow();
+                if matches!(tr.current_step, TraversalValue::NodeArray(_)) {
+                    tr.filter_nodes(read.read(), |_, node| Ok(kept.contains(&node.id)));
+                } else {
+                    tr.filter_edges(read.read(), |_, edge| Ok(kept.contains(&edge.id)));
+                }
+            }
+            Step::BooleanOperation(_) => {
+                return Err(GraphError::TraversalError(
+                    "A comparison must be the last step of a traversal".to_string(),
+                ))
+            }
+            Step::Count => {
+                tr.count();
+            }
+            Step::Path => {
+                tr.path();
+            }
+            Step::Update(update) => {
+                let props = update
+                    .fields
+                    .iter()
+                    .map(|field| Ok((field.name.clone(), self.field_value(&field.value, txn)?)))
+                    .collect::<Result<Vec<_>, GraphError>>()?;
+                write(txn, |txn| {
+                    tr.update_props(txn, props);
+                })?;
+            }
+            Step::Object(object) => self.remap(tr, object, None, txn)?,
+            Step::Closure(closure) => {
+                self.remap(tr, &closure.object, Some(&closure.identifier), txn)?
+            }
+            Step::Exclude(exclude) => {
+                for (id, _) in items(&tr.current_step)? {
+                    let remappings = exclude
+                        .fields
+                        .iter()
+                        .map(|field| {
+                            (
+                                field.clone(),
+                                Remapping::new(true, Some(field.clone()), None),
+                            )
+                        })
+                        .collect();
+                    self.remappings
+                        .borrow_mut()
+                        .insert(id, ResponseRemapping::new(remappings, true));
+                }
+            }
+            Step::Range((start, end)) => {
+                let (start, end) = (self.integer(start, txn)?, self.integer(end, txn)?);
+                tr.range(start, end);
+            }
+            Step::AddEdge(add) => {
+                let label = add.edge_type.as_deref().unwrap_or_default();
+                let props = self.props(add.fields.as_deref())?;
+                match (&add.connection.to_id, &add.connection.from_id) {
+                    (Some(to), _) => {
+                        let to = self.id_type(to)?;
+                        write(txn, |txn| {
+                            tr.add_e_to(txn, label, &to, props);
+                        })?;
+                    }
+                    (None, Some(from)) => {
+                        let from = self.id_type(from)?;
+                        write(txn, |txn| {
+                            tr.add_e_from(txn, label, &from, props);
+                        })?;
+                    }
+                    (None, None) => {
+                        return Err(GraphError::New("AddE needs a From or To node".to_string()))
+                    }
+                }
+            }
+            Step::SearchVector(_) => return Err(unsupported("SearchV as a traversal step")),
+            Step::OrderBy(keys) => {
+                let keys: Vec<(&str, Order)> = keys
+                    .iter()
+                    .map(|(property, direction)| {
+                        let order = match direction {
+                            OrderDirection::Asc => Order::Asc,
+                            OrderDirection::Desc => Order::Desc,
+                        };
+                        (property.as_str(), order)
+                    })
+                    .collect();
+                tr.order_by(&keys);
+            }
+            Step::Dedup(Some(property)) => {
+                tr.dedup_by(property);
+            }
+            Step::Dedup(None) => {
+                tr.dedup();
+            }
+            Step::GroupBy(property) => {
+                tr.group_by(property, &[aggregate::Aggregation::Count]);
+            }
+            Step::Aggregate(aggregations) => {
+                let aggregations: Vec<_> = aggregations.iter().map(aggregation).collect();
+                tr.aggregate(&aggregations);
+            }
+            Step::As(name) => {
+                tr.as_(name);
+            }
+            Step::Select(names) => {
+                let names: Vec<&str> = names.iter().map(String::as_str).collect();
+                tr.select(&names);
+            }
+            Step::Union(branches) | Step::Coalesce(branches) => {
+                let branches: Vec<_> = branches
+                    .iter()
+                    .map(|branch| self.branch(branch, txn))
+                    .collect();
+                let branches: Vec<&dyn Fn(&mut TraversalBuilder)> = branches
+                    .iter()
+                    .map(|branch| &**branch as &dyn Fn(&mut TraversalBuilder))
+                    .collect();
+                match step {
+                    Step::Union(_) => tr.union(&branches),
+                    _ => tr.coalesce(&branches),
+                };
+            }
+            Step::Optional(branch) => {
+                tr.optional(self.branch(branch, txn));
+            }
+            Step::PredictLinks(predict) => {
+                let metric = LinkPredictionMetric::try_from(predict.metric.as_str())?;
+                let limit = self.number(&predict.limit)?;
+                let edge_type = predict.edge_type.as_deref().unwrap_or_default();
+                tr.predict_links(txn.borrow().read(), edge_type, metric, limit);
+            }
+            Step::Dag(dag) => {
+                let txn = txn.borrow();
+                match dag.operation {
+                    DagOperation::Ancestors => tr.ancestors(txn.read(), &dag.edge_type),
+                    DagOperation::Descendants => tr.descendants(txn.read(), &dag.edge_type),
+                    DagOperation::TopoSort => tr.topological_sort(txn.read(), &dag.edge_type),
+                    DagOperation::FindCycle => tr.find_cycle(txn.read(), &dag.edge_type),
+                    DagOperation::CriticalPath => {
+                        tr.critical_path(txn.read(), &dag.edge_type, dag.weight.as_deref())
+                    }
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// A UNION, COALESCE or OPTIONAL branch, run by the builder from each current item
+    fn branch<'b>(
+        &'b self,
+        branch: &'b Traversal,
+        txn: &'b RefCell<Txn>,
+    ) -> Box<dyn Fn(&mut TraversalBuilder) + 'b> {
+        Box::new(move |tr: &mut TraversalBuilder| {
+            if let Err(err) = self.apply(tr, &branch.steps, txn) {
+                tr.error.get_or_insert(err);
+            }
+        })
+    }
+
+    /// Registers the remapping `object` makes of each current item, binding the item to
+    /// `binding` while its fields are evaluated for closures
+    fn remap(
+        &self,
+        tr: &TraversalBuilder,
+        object: &Object,
+        binding: Option<&str>,
+        txn: &RefCell<Txn>,
+    ) -> Result<(), GraphError> {
+        for (id, item) in items(&tr.current_step)? {
+            if let Some(binding) = binding {
+                self.variables
+                    .borrow_mut()
+                    .insert(binding.to_string(), item.clone());
+            }
+            let mut remappings = HashMap::with_capacity(object.fields.len());
+            for (key, field) in &object.fields {
+                let value = self.field(&item, key, field, txn)?;
+                remappings.insert(key.clone(), Remapping::new(false, None, Some(value)));
+            }
+            self.remappings
+                .borrow_mut()
+                .insert(id, ResponseRemapping::new(remappings, object.should_spread));
+        }
+        if let Some(binding) = binding {
+            self.variables.borrow_mut().remove(binding);
+        }
+        Ok(())
+    }
+
+    fn field(
+        &self,
+        item: &TraversalValue,
+        key: &str,
+        field: &FieldValue,
+        txn: &RefCell<Txn>,
+    ) -> Result<ReturnValue, GraphError> {
+        Ok(match field {
+            FieldValue::Literal(Value::String(name)) if name == key => property(item, name)?,
+            FieldValue::Literal(value) => ReturnValue::from(value),
+            FieldValue::Empty => property(item, key)?,
+            FieldValue::Expression(Expression::Identifier(name)) if !self.is_bound(name) => {
+                property(item, name)?
+            }
+            FieldValue::Expression(Expression::Identifier(name)) => {
+                let value = self.variable(name)?;
+                self.return_value(value)
+            }
+            FieldValue::Traversal(traversal)
+            | FieldValue::Expression(Expression::Traversal(traversal)) => {
+                match property_read(&traversal.steps) {
+                    Some(_) => {
+                        let start = self.start(traversal, Some(item), txn)?;
+                        let values = self.values(start, &traversal.steps, txn)?;
+                        values
+                            .into_iter()
+                            .next()
+                            .map_or(ReturnValue::Empty, ReturnValue::from)
+                    }
+                    None => {
+                        let value = self.traversal(traversal, Some(item), txn)?;
+                        self.return_value(value)
+                    }
+                }
+            }
+            FieldValue::Expression(expression) => {
+                ReturnValue::from(self.value(expression, Some(item), txn)?)
+            }
+            FieldValue::Fields(fields) => ReturnValue::Object(
+                fields
+                    .iter()
+                    .map(|field| {
+                        Ok((
+                            field.name.clone(),
+                            self.field(item, &field.name, &field.value, txn)?,
+                        ))
+                    })
+                    .collect::<Result<_, GraphError>>()?,
+            ),
+        })
+    }
+
+    /// Evaluates a WHERE condition for one item, or an IF condition for `TraversalValue::Empty`
+    fn condition(
+        &self,
+        item: &TraversalValue,
+        condition: &Expression,
+        txn: &RefCell<Txn>,
+    ) -> Result<bool, GraphError> {
+        match condition {
+            Expression::BooleanLiteral(value) => Ok(*value),
+            Expression::And(conditions) => {
+                for condition in conditions {
+                    if !self.condition(item, condition, txn)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Expression::Or(conditions) => {
+                for condition in conditions {
+                    if self.condition(item, condition, txn)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+            Expression::Exists(traversal) => {
+                Ok(self.traversal(traversal, Some(item), txn)?.is_truthy())
+            }
+            Expression::Identifier(name) if self.variables.borrow().contains_key(name) => {
+                Ok(self.variable(name)?.is_truthy())
+            }
+            Expression::Identifier(name) => Ok(self.scalar(name)? == Value::Boolean(true)),
+            Expression::Traversal(traversal) => {
+                let start = self.start(traversal, Some(item), txn)?;
+                match traversal.steps.split_last() {
+                    Some((Step::BooleanOperation(op), steps)) => {
+                        self.compare(start, steps, op, Some(item), txn)
+                    }
+                    _ if property_read(&traversal.steps).is_some() => Ok(self
+                        .values(start, &traversal.steps, txn)?
+                        .contains(&Value::Boolean(true))),
+                    _ => Ok(self.steps(start, &traversal.steps, txn)?.is_truthy()),
+                }
+            }
+            _ => Err(GraphError::New(
+                "WHERE expects a traversal, EXISTS, AND or OR".to_string(),
+            )),
+        }
+    }
+
+    /// Whether any value the steps produce from `start` passes the comparison
+    fn compare(
+        &self,
+        start: TraversalValue,
+        steps: &[Step],
+        op: &BooleanOp,
+        item: Option<&TraversalValue>,
+        txn: &RefCell<Txn>,
+    ) -> Result<bool, GraphError> {
+        let operand = match op {
+            BooleanOp::GreaterThan(operand)
+            | BooleanOp::GreaterThanOrEqual(operand)
+            | BooleanOp::LessThan(operand)
+            | BooleanOp::LessThanOrEqual(operand)
+            | BooleanOp::Equal(operand)
+            | BooleanOp::NotEqual(operand) => self.value(operand, item, txn)?,
+            BooleanOp::And(_) | BooleanOp::Or(_) => {
+                return Err(GraphError::New(
+                    "AND and OR can't be used as a traversal step".to_string(),
+                ))
+            }
+        };
+        let values = self.values(start, steps, txn)?;
+        Ok(values.iter().any(|value| {
+            if let Value::Empty = value {
+                return false;
+            }
+            let ordering = compare_values(value, &operand);
+            match op {
+                BooleanOp::GreaterThan(_) => ordering.is_gt(),
+                BooleanOp::GreaterThanOrEqual(_) => ordering.is_ge(),
+                BooleanOp::LessThan(_) => ordering.is_lt(),
+                BooleanOp::LessThanOrEqual(_) => ordering.is_le(),
+                BooleanOp::Equal(_) => ordering.is_eq(),
+                BooleanOp::NotEqual(_) => ordering.is_ne(),
+                BooleanOp::And(_) | BooleanOp::Or(_) => false,
+            }
+        }))
+    }
+
+    /// The values the steps produce from `start`: the property read by a trailing
+    /// `::{property}` or `::ID`, a count, or the ids of the resulting items
+    fn values(
+        &self,
+        start: TraversalValue,
+        steps: &[Step],
+        txn: &RefCell<Txn>,
+    ) -> Result<Vec<Value>, GraphError> {
+        if let Some(name) = property_read(steps) {
+            let items = self.steps(start, &steps[..steps.len() - 1], txn)?;
+            return Ok(properties(&items, name));
+        }
+        Ok(match self.steps(start, steps, txn)? {
+            TraversalValue::Count(count) => vec![Value::Integer(count.value() as i32)],
+            TraversalValue::ValueArray(values) => {
+                values.into_iter().map(|(_, value)| value).collect()
+            }
+            TraversalValue::NodeArray(nodes) => nodes
+                .into_iter()
+                .map(|node| Value::String(node.id))
+                .collect(),
+            TraversalValue::EdgeArray(edges) => edges
+                .into_iter()
+                .map(|edge| Value::String(edge.id))
+                .collect(),
+            _ => Vec::new(),
+        })
+    }
+
+    /// A literal, a parameter or variable, or the first value of a traversal
+    fn value(
+        &self,
+        expression: &Expression,
+        item: Option<&TraversalValue>,
+        txn: &RefCell<Txn>,
+    ) -> Result<Value, GraphError> {
+        match expression {
+            Expression::StringLiteral(value) => Ok(Value::String(value.clone())),
+            Expression::IntegerLiteral(value) => Ok(Value::Integer(*value)),
+            Expression::FloatLiteral(value) => Ok(Value::Float(*value)),
+            Expression::BooleanLiteral(value) => Ok(Value::Boolean(*value)),
+            Expression::Identifier(name) => self.scalar(name),
+            Expression::Traversal(traversal) => {
+                let start = self.start(traversal, item, txn)?;
+                let values = self.values(start, &traversal.steps, txn)?;
+                Ok(values.into_iter().next().unwrap_or(Value::Empty))
+            }
+            _ => Err(GraphError::New(
+                "Expected a literal, parameter or traversal".to_string(),
+            )),
+        }
+    }
+
+    /// The value of a parameter, or of a variable holding a value, a count or an item.
+    /// Variables come first, so that function arguments shadow query parameters.
+    fn scalar(&self, name: &str) -> Result<Value, GraphError> {
+        if !self.variables.borrow().contains_key(name) {
+            if let Some(value) = self.params.get(name) {
+                return Ok(value.clone());
+            }
+        }
+        match self.variable(name)? {
+            TraversalValue::ValueArray(values) => Ok(values
+                .into_iter()
+                .next()
+                .map_or(Value::Empty, |(_, value)| value)),
+            TraversalValue::Count(count) => Ok(Value::Integer(count.value() as i32)),
+            value => Ok(Value::String(value.get_id()?)),
+        }
+    }
+
+    fn integer(&self, expression: &Expression, txn: &RefCell<Txn>) -> Result<i32, GraphError> {
+        match self.value(expression, None, txn)? {
+            Value::Integer(value) => Ok(value),
+            value => Err(GraphError::ConversionError(format!(
+                "Expected an integer, got {:?}",
+                value
+            ))),
+        }
+    }
+
+    fn number(&self, number: &EvaluatesToNumber) -> Result<usize, GraphError> {
+        match number {
+            EvaluatesToNumber::Integer(value) => Ok(*value),
+            EvaluatesToNumber::Float(value) => Ok(*value as usize),
+            EvaluatesToNumber::Identifier(name) => match self.scalar(name)? {
+                Value::Integer(value) => Ok(value as usize),
+                value => Err(GraphError::ConversionError(format!(
+                    "Expected an integer for {}, got {:?}",
+                    name, value
+                ))),
+            },
+        }
+    }
+
+    fn field_value(&self, field: &FieldValue, txn: &RefCell<Txn>) -> Result<Value, GraphError> {
+        match field {
+            FieldValue::Literal(value) => Ok(value.clone()),
+            FieldValue::Expression(expression) => self.value(expression, None, txn),
+            _ => Err(GraphError::New(
+                "UPDATE fields must be literals or parameters".to_string(),
+            )),
+        }
+    }
+
+    fn value_type(&self, value: &ValueType) -> Result<Value, GraphError> {
+        match value {
+            ValueType::Literal(value) => Ok(value.clone()),
+            ValueType::Identifier(name) => self.scalar(name),
+            ValueType::Object(_) => Err(unsupported("Object properties")),
+        }
+    }
+
+    fn props(
+        &self,
+        fields: Option<&[(String, ValueType)]>,
+    ) -> Result<Vec<(String, Value)>, GraphError> {
+        fields
+            .unwrap_or_default()
+            .iter()
+            .map(|(name, value)| Ok((name.clone(), self.value_type(value)?)))
+            .collect()
+    }
+
+    /// The id a start node refers to, either quoted or held by a parameter or variable
+    fn id(&self, id: &str) -> Result<String, GraphError> {
+        if id.starts_with('"') {
+            return Ok(id.trim_matches('"').to_string());
+        }
+        match self.scalar(id)? {
+            Value::String(id) => Ok(id),
+            value => Err(GraphError::ConversionError(format!(
+                "Expected an id, got {:?}",
+                value
+            ))),
+        }
+    }
+
+    fn id_type(&self, id: &IdType) -> Result<String, GraphError> {
+        match id {
+            IdType::Literal(id) => Ok(id.trim_matches('"').to_string()),
+            IdType::Identifier(name) => self.id(name),
+        }
+    }
+
+    fn variable(&self, name: &str) -> Result<TraversalValue, GraphError> {
+        if let Some(value) = self.variables.borrow().get(name) {
+            return Ok(value.clone());
+        }
+        match self.params.get(name) {
+            Some(value) => Ok(TraversalValue::from((name.to_string(), value.clone()))),
+            None => Err(GraphError::New(format!(
+                "{} is not a variable or parameter",
+                name
+            ))),
+        }
+    }
+
+    fn is_bound(&self, name: &str) -> bool {
+        self.params.contains_key(name) || self.variables.borrow().contains_key(name)
+    }
+
+    fn add_node(&self, add: &AddNode, txn: &RefCell<Txn>) -> Result<TraversalValue, GraphError> {
+        let props = self.props(add.fields.as_deref())?;
+        let mut tr = self.builder(TraversalValue::Empty);
+        write(txn, |txn| {
+            tr.add_v(
+                txn,
+                add.vertex_type.as_deref().unwrap_or_default(),
+                props,
+                None,
+            );
+        })?;
+        tr.finish()
+    }
+
+    fn add_edge(&self, add: &AddEdge, txn: &RefCell<Txn>) -> Result<TraversalValue, GraphError> {
+        let (Some(from), Some(to)) = (&add.connection.from_id, &add.connection.to_id) else {
+            return Err(GraphError::New(
+                "AddE needs both a From and a To node".to_string(),
+            ));
+        };
+        let (from, to) = (self.id_type(from)?, self.id_type(to)?);
+        let props = self.props(add.fields.as_deref())?;
+        let mut tr = self.builder(TraversalValue::Empty);
+        write(txn, |txn| {
+            tr.add_e(
+                txn,
+                add.edge_type.as_deref().unwrap_or_default(),
+                &from,
+                &to,
+                props,
+            );
+        })?;
+        tr.finish()
+    }
+
+    fn upsert_node(
+        &self,
+        upsert: &UpsertNode,
+        txn: &RefCell<Txn>,
+    ) -> Result<TraversalValue, GraphError> {
+        let match_props = self.props(Some(upsert.match_fields.as_slice()))?;
+        let props = self.props(upsert.fields.as_deref())?;
+        let mut tr = self.builder(TraversalValue::Empty);
+        write(txn, |txn| {
+            tr.upsert_v(
+                txn,
+                upsert.vertex_type.as_deref().unwrap_or_default(),
+                match_props,
+                props.clone(),
+                props,
+            );
+        })?;
+        tr.finish()
+    }
+
+    fn upsert_edge(
+        &self,
+        upsert: &AddEdge,
+        txn: &RefCell<Txn>,
+    ) -> Result<TraversalValue, GraphError> {
+        let (Some(from), Some(to)) = (&upsert.connection.from_id, &upsert.connection.to_id) else {
+            return Err(GraphError::New(
+                "UpsertE needs both a From and a To node".to_string(),
+            ));
+        };
+        let (from, to) = (self.id_type(from)?, self.id_type(to)?);
+        let props = self.props(upsert.fields.as_deref())?;
+        let mut tr = self.builder(TraversalValue::Empty);
+        write(txn, |txn| {
+            tr.upsert_e(
+                txn,
+                upsert.edge_type.as_deref().unwrap_or_default(),
+                &from,
+                &to,
+                props.clone(),
+                props,
+            );
+        })?;
+        tr.finish()
+    }
+
+    fn add_vector(
+        &self,
+        add: &AddVector,
+        txn: &RefCell<Txn>,
+    ) -> Result<TraversalValue, GraphError> {
+        let vector = vector(add.data.as_ref())?;
+        let mut tr = self.builder(TraversalValue::Empty);
+        write(txn, |txn| {
+            tr.insert_vector(txn, vector);
+        })?;
+        tr.finish()
+    }
+
+    fn search_vector(
+        &self,
+        search: &SearchVector,
+        txn: &RefCell<Txn>,
+    ) -> Result<TraversalValue, GraphError> {
+        let vector = vector(search.data.as_ref())?;
+        let k = match &search.k {
+            Some(k) => self.number(k)?,
+            None => return Err(GraphError::New("SearchV needs a result count".to_string())),
+        };
+        let mut tr = self.builder(TraversalValue::Empty);
+        tr.vector_search(txn.borrow().read(), vector, k);
+        tr.finish()
+    }
+
+    fn pattern(&self, pattern: &MatchPattern) -> Result<Pattern, GraphError> {
+        let mut result = Pattern::new();
+        for node in &pattern.nodes {
+            result = result.node(
+                &node.variable,
+                node.label.as_deref(),
+                self.props(Some(node.properties.as_slice()))?,
+            );
+        }
+        for edge in &pattern.edges {
+            result = result.edge(
+                edge.variable.as_deref(),
+                edge.label.as_deref(),
+                &edge.from,
+                &edge.to,
+                self.props(Some(edge.properties.as_slice()))?,
+            );
+        }
+        Ok(result)
+    }
+
+    fn return_values(
+        &self,
+        expressions: &[Expression],
+        txn: &RefCell<Txn>,
+    ) -> Result<HashMap<String, ReturnValue>, GraphError> {
+        let mut return_values = HashMap::with_capacity(expressions.len());
+        for expression in expressions {
+            let (key, value) = match expression {
+                Expression::Identifier(name) => (name.clone(), self.variable(name)?),
+                Expression::Traversal(traversal) => match &traversal.start {
+                    StartNode::Variable(name) | StartNode::Call(Call { name, .. }) => {
+                        (name.clone(), self.traversal(traversal, None, txn)?)
+                    }
+                    _ => {
+                        return Err(GraphError::New(
+                            "Returned traversals must start from a variable or a call".to_string(),
+                        ))
+                    }
+                },
+                Expression::StringLiteral(message) => {
+                    return_values
+                        .insert("message".to_string(), ReturnValue::from(message.as_str()));
+                    continue;
+                }
+                Expression::None => {
+                    return_values.insert("message".to_string(), ReturnValue::Empty);
+                    continue;
+                }
+                _ => return Err(GraphError::New(
+                    "Only variables, traversals from variables, strings and NONE can be returned"
+                        .to_string(),
+                )),
+            };
+            return_values.insert(key, self.return_value(value));
+        }
+        Ok(return_values)
+    }
+
+    /// The JSON for a traversal value with the object remappings mixed in.
+    /// Single values such as parameters and comparisons are returned as they are.
+    fn return_value(&self, value: TraversalValue) -> ReturnValue {
+        match value {
+            TraversalValue::ValueArray(values) => values
+                .into_iter()
+                .next()
+                .map_or(ReturnValue::Empty, |(_, value)| ReturnValue::from(value)),
+            value => ReturnValue::from_traversal_value_array_with_mixin(
+                value,
+                self.remappings.borrow_mut(),
+            ),
+        }
+    }
+
+    fn builder(&self, start: TraversalValue) -> TraversalBuilder {
+        let mut tr = TraversalBuilder::new(Arc::clone(&self.storage), start);
+        tr.within(&self.budget);
+        if let Some(threads) = self.parallelism {
+            tr.parallelism(threads);
+        }
+        tr
+    }
+}
+
+/// Binds the inputs to the query's parameters in declaration order
+fn bind_params(
+    query: &Query,
+    params: Vec<QueryInput>,
+) -> Result<HashMap<String, Value>, GraphError> {
+    if params.len() != query.parameters.len() {
+        return Err(GraphError::New(format!(
+            "{} expects {} parameters, got {}",
+            query.name,
+            query.parameters.len(),
+            params.len()
+        )));
+    }
+    query
+        .parameters
+        .iter()
+        .zip(params)
+        .map(
+            |(param, input)| match bind_param(&param.param_type, input) {
+                Ok(value) => Ok((param.name.clone(), value)),
+                Err(input) => Err(GraphError::ConversionError(format!(
+                    "Parameter {} expects {}, got {:?}",
+                    param.name,
+                    type_name(&param.param_type),
+                    input
+                ))),
+            },
+        )
+        .collect()
+}
+
+/// The value of a scalar input, handing the input back if it doesn't fit the type
+fn bind_param(param_type: &FieldType, input: QueryInput) -> Result<Value, QueryInput> {
+    match (param_type, input) {
+        (FieldType::Optional(inner), input) => bind_param(inner, input),
+        (FieldType::String | FieldType::Identifier(_), QueryInput::StringValue { value }) => {
+            Ok(Value::String(value))
+        }
+        (FieldType::Integer, QueryInput::IntegerValue { value }) => Ok(Value::Integer(value)),
+        (FieldType::Float, QueryInput::FloatValue { value }) => Ok(Value::Float(value)),
+        (FieldType::Float, QueryInput::IntegerValue { value }) => Ok(Value::Float(value as f64)),
+        (FieldType::Boolean, QueryInput::BooleanValue { value }) => Ok(Value::Boolean(value)),
+        (_, input) => Err(input),
+    }
+}
+
+/// Applies a graph step, following the first label like the generated code does
+fn graph_step_on(tr: &mut TraversalBuilder, step: &GraphStep, txn: &RoTxn) {
+    fn label(labels: &Option<Vec<String>>) -> &str {
+        labels
+            .as_ref()
+            .and_then(|labels| labels.first())
+            .map_or("", String::as_str)
+    }
+    match step {
+        GraphStep::Out(labels) => tr.out(txn, label(labels)),
+        GraphStep::In(labels) => tr.in_(txn, label(labels)),
+        GraphStep::Both(labels) => tr.both(txn, label(labels)),
+        GraphStep::OutE(labels) => tr.out_e(txn, label(labels)),
+        GraphStep::InE(labels) => tr.in_e(txn, label(labels)),
+        GraphStep::BothE(labels) => tr.both_e(txn, label(labels)),
+        GraphStep::OutN => tr.out_v(txn),
+        GraphStep::InN => tr.in_v(txn),
+        GraphStep::BothN => tr.both_v(txn),
+    };
+}
+
+fn aggregation(aggregation: &Aggregation) -> aggregate::Aggregation {
+    match aggregation {
+        Aggregation::Count => aggregate::Aggregation::Count,
+        Aggregation::Sum(property) => aggregate::Aggregation::Sum(property.clone()),
+        Aggregation::Avg(property) => aggregate::Aggregation::Avg(property.clone()),
+        Aggregation::Min(property) => aggregate::Aggregation::Min(property.clone()),
+        Aggregation::Max(property) => aggregate::Aggregation::Max(property.clone()),
+        Aggregation::Collect(property) => aggregate::Aggregation::Collect(property.clone()),
+    }
+}
+
+fn vector(data: Option<&VectorData>) -> Result<&[f64], GraphError> {
+    match data {
+        Some(VectorData::Vector(vector)) => Ok(vector.as_slice()),
+        Some(VectorData::Identifier(_)) => Err(unsupported("Vector parameters")),
+        None => Err(GraphError::New("Expected vector data".to_string())),
+    }
+}
+
+/// The property a trailing `::{property}` or `::ID` step reads
+fn property_read(steps: &[Step]) -> Option<&str> {
+    match steps.last() {
+        Some(Step::Object(Object {
+            fields,
+            should_spread: false,
+        })) => match fields.as_slice() {
+            [(key, FieldValue::Empty)] => Some(key.as_str()),
+            [(key, FieldValue::Literal(Value::String(name)))] if key == name => Some(name.as_str()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// One `(id, item)` pair for each current node or edge
+fn items(value: &TraversalValue) -> Result<Vec<(String, TraversalValue)>, GraphError> {
+    match value {
+        TraversalValue::NodeArray(nodes) => Ok(nodes
+            .iter()
+            .map(|node| (node.id.clone(), TraversalValue::from(node)))
+            .collect()),
+        TraversalValue::EdgeArray(edges) => Ok(edges
+            .iter()
+            .map(|edge| (edge.id.clone(), TraversalValue::from(edge)))
+            .collect()),
+        TraversalValue::Empty => Ok(Vec::new()),
+        _ => Err(GraphError::TraversalError(
+            "Objects can only be built from nodes or edges".to_string(),
+        )),
+    }
+}
+
+/// The values of a property across the current nodes or edges, `id` being their id
+fn properties(value: &TraversalValue, name: &str) -> Vec<Value> {
+    let property = |id: &String, properties: &HashMap<String, Value>| match name {
+        "id" => Some(Value::String(id.clone())),
+        _ => properties.get(name).cloned(),
+    };
+    match value {
+        TraversalValue::NodeArray(nodes) => nodes
+            .iter()
+            .filter_map(|node| property(&node.id, &node.properties))
+            .collect(),
+        TraversalValue::EdgeArray(edges) => edges
+            .iter()
+            .filter_map(|edge| property(&edge.id, &edge.properties))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// A property of a single item, which like in the generated code must be set
+fn property(item: &TraversalValue, name: &str) -> Result<ReturnValue, GraphError> {
+    properties(item, name)
+        .into_iter()
+        .next()
+        .map(ReturnValue::from)
+        .ok_or_else(|| GraphError::ConversionError(format!("Property not found on {}", name)))
+}
+
+fn unsupported(feature: &str) -> GraphError {
+    GraphError::New(format!(
+        "{} is not supported by the interpreter yet",
+        feature
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helix_engine::graph_core::config::Config;
+    use crate::helix_engine::storage_core::storage_methods::StorageMethods;
+    use crate::helixc::parser::helix_parser::HelixParser;
+    use crate::props;
+    use sonic_rs::{JsonContainerTrait, JsonValueTrait};
+    use tempfile::TempDir;
+
+    fn setup_temp_db() -> (Arc<HelixGraphStorage>, TempDir) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().to_str().unwrap();
+        let storage = HelixGraphStorage::new(db_path, Config::default()).unwrap();
+        (Arc::new(storage), temp_dir)
+    }
+
+    fn run(
+        storage: &Arc<HelixGraphStorage>,
+        query: &str,
+        params: Vec<QueryInput>,
+    ) -> Result<sonic_rs::Value, GraphError> {
+        let source = HelixParser::parse_source(query)?;
+        let json = interpret_query(
+            Arc::clone(storage),
+            &source.queries[0],
+            &source.functions,
+            params,
+        )?;
+        Ok(sonic_rs::from_str(&json)?)
+    }
+
+    fn string(value: &str) -> QueryInput {
+        QueryInput::StringValue {
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_add_node_with_params() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let result = run(
+            &storage,
+            r#"
+    QUERY addUser(name: String, age: Integer) =>
+        user <- AddN<User>({name: name, age: age})
+        RETURN user
+    "#,
+            vec![string("alice"), QueryInput::IntegerValue { value: 30 }],
+        )
+        .unwrap();
+
+        let user = &result["user"][0];
+        assert_eq!(user["label"].as_str(), Some("User"));
+        assert_eq!(user["name"].as_str(), Some("alice"));
+        assert_eq!(user["age"].as_i64(), Some(30));
+    }
+
+    #[test]
+    fn test_where_and_object() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        storage
+            .create_node(
+                &mut txn,
+                "User",
+                props! { "name" => "alice", "age" => 30 },
+                None,
+            )
+            .unwrap();
+        storage
+            .create_node(
+                &mut txn,
+                "User",
+                props! { "name" => "bob", "age" => 15 },
+                None,
+            )
+            .unwrap();
+        txn.commit().unwrap();
+
+        let result = run(
+            &storage,
+            r#"
+    QUERY adults(min: Integer) =>
+        users <- N<User>::WHERE(_::{age}::GTE(min))::{name}
+        RETURN users
+    "#,
+            vec![QueryInput::IntegerValue { value: 18 }],
+        )
+        .unwrap();
+
+        let users = result["users"].as_array().unwrap();
+        assert_eq!(users.len(), 1);
+        assert_eq!(users[0]["name"].as_str(), Some("alice"));
+        assert!(users[0].get("age").is_none());
+    }
+
+    #[test]
+    fn test_add_edge_and_count() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let alice = storage
+            .create_node(&mut txn, "User", props!(), None)
+            .unwrap();
+        let bob = storage
+            .create_node(&mut txn, "User", props!(), None)
+            .unwrap();
+        txn.commit().unwrap();
+
+        let query = r#"
+    QUERY follow(follower: String, followed: String) =>
+        AddE<Follows>::From(follower)::To(followed)
+        following <- N<User>(follower)::Out<Follows>::COUNT
+        RETURN following
+    "#;
+        let result = run(&storage, query, vec![string(&alice.id), string(&bob.id)]).unwrap();
+        assert_eq!(result["following"].as_u64(), Some(1));
+
+        // the edge was committed with the query
+        let txn = storage.graph_env.read_txn().unwrap();
+        let mut tr = TraversalBuilder::new(Arc::clone(&storage), TraversalValue::Empty);
+        tr.v_from_id(&txn, &alice.id).out(&txn, "Follows");
+        match tr.finish().unwrap() {
+            TraversalValue::NodeArray(nodes) => assert_eq!(nodes[0].id, bob.id),
+            value => panic!("Expected nodes, got {:?}", value),
+        }
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let alice = storage
+            .create_node(&mut txn, "User", props! { "name" => "alice" }, None)
+            .unwrap();
+        for name in ["bob", "carol"] {
+            storage
+                .create_node(&mut txn, "User", props! { "name" => name }, None)
+                .unwrap();
+        }
+        txn.commit().unwrap();
+
+        let query = r#"
+    QUERY followAll(userID: String, name: String, force: Boolean) =>
+        user <- N<User>(userID)
+        IF EXISTS(N<User>(userID)::Out<Follows>) {
+            RETURN "already following"
+        }
+        IF force {
+            FOR other IN N<User>::WHERE(_::{name}::NEQ(name)) {
+                AddE<Follows>::From(user)::To(other)
+            }
+        }
+        following <- N<User>(userID)::Out<Follows>::COUNT
+        RETURN following
+    "#;
+        let params = |force: bool| {
+            vec![
+                string(&alice.id),
+                string("alice"),
+                QueryInput::BooleanValue { value: force },
+            ]
+        };
+        let result = run(&storage, query, params(false)).unwrap();
+        assert_eq!(result["following"].as_u64(), Some(0));
+        let result = run(&storage, query, params(true)).unwrap();
+        assert_eq!(result["following"].as_u64(), Some(2));
+        let result = run(&storage, query, params(true)).unwrap();
+        assert_eq!(result["message"].as_str(), Some("already following"));
+        assert!(result["following"].is_null());
+    }
+
+    #[test]
+    fn test_upsert() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let query = r#"
+    QUERY ingest(email: String, name: String, friendID: String) =>
+        user <- UpsertN<User>({email: email}, {name: name})
+        UpsertE<Follows>({since: 2024})::From(user)::To(friendID)
+        users <- N<User>::COUNT
+        following <- user::Out<Follows>::COUNT
+        RETURN user, users, following
+    "#;
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let friend = storage
+            .create_node(&mut txn, "User", props! { "email" => "bob@helix.db" }, None)
+            .unwrap();
+        txn.commit().unwrap();
+        let params = |name: &str| vec![string("alice@helix.db"), string(name), string(&friend.id)];
+
+        let first = run(&storage, query, params("alice")).unwrap();
+        let second = run(&storage, query, params("Alice")).unwrap();
+        assert_eq!(first["user"][0]["id"], second["user"][0]["id"]);
+        assert_eq!(second["user"][0]["name"].as_str(), Some("Alice"));
+        assert_eq!(second["users"].as_u64(), Some(2));
+        assert_eq!(second["following"].as_u64(), Some(1));
+    }
+
+    #[test]
+    fn test_param_mismatch() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let query = r#"
+    QUERY getUser(name: String) =>
+        users <- N<User>::WHERE(_::{name}::EQ(name))
+        RETURN users
+    "#;
+        assert!(run(&storage, query, vec![]).is_err());
+        assert!(run(&storage, query, vec![QueryInput::IntegerValue { value: 1 }]).is_err());
+    }
+
+    #[test]
+    fn test_function_call() {
+        let (storage, _temp_dir) = setup_temp_db();
+        let mut txn = storage.graph_env.write_txn().unwrap();
+        let mut user = |name: &str, age: i32| {
+            storage
+                .create_node(
+                    &mut txn,
+                    "User",
+                    props! { "name" => name, "age" => age },
+                    None,
+                )
+                .unwrap()
+        };
+        let alice = user("alice", 30);
+        let bob = user("bob", 20);
+        let carol = user("carol", 40);
+        for follower in [&bob, &carol] {
+            storage
+                .create_edge(&mut txn, "Follows", &follower.id, &alice.id, props! {})
+                .unwrap();
+        }
+        txn.commit().unwrap();
+
+        let result = run(
+            &storage,
+            r#"
+    N::User {
+        name: String,
+        age: Integer
+    }
+
+    E::Follows {
+        From: User,
+        To: User,
+        Properties: {
+        }
+    }
+
+    FN olderFollowers(user: User, minAge: Integer) =>
+        user::In<Follows>::WHERE(_::{age}::GT(minAge))
+
+    QUERY followers(userID: String, minAge: Integer) =>
+        user <- N<User>(userID)
+        names <- olderFollowers(user, 25)::{name}
+        RETURN names, olderFollowers(user, minAge)
+    "#,
+            vec![string(&alice.id), QueryInput::IntegerValue { value: 10 }],
+        )
+        .unwrap();
+
+        let names = result["names"].as_array().unwrap();
+        assert_eq!(names.len(), 1);
+        assert_eq!(names[0]["name"].as_str(), Some("carol"));
+        assert_eq!(result["olderFollowers"].as_array().unwrap().len(), 2);
+    }
 }
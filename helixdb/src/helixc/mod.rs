@@ -1,2 +1,6 @@
 pub mod parser;
-pub mod generator;
\ No newline at end of file
+pub mod diagnostic;
+pub mod analyzer;
+pub mod formatter;
+pub mod generator;
+pub mod interpreter;
@@ -1,10 +1,12 @@
 use std::collections::HashSet;
 
 use super::parser_methods::ParserError;
+use crate::helixc::diagnostic::diagnostic::{Diagnostic, INVALID_DEFINITION, SYNTAX_ERROR};
 use crate::protocol::value::Value;
 use pest::{
+    error::LineColLocation,
     iterators::{Pair, Pairs},
-    Parser as PestParser,
+    Parser as PestParser, Position,
 };
 use pest_derive::Parser;
 
@@ -21,30 +23,87 @@ impl Default for HelixParser {
                 node_schemas: Vec::new(),
                 edge_schemas: Vec::new(),
                 vector_schemas: Vec::new(),
+                functions: Vec::new(),
                 queries: Vec::new(),
+                imports: Vec::new(),
             },
         }
     }
 }
 
 // AST Structures
+
+/// Span of a node in the parsed input, lines and columns start at 1 and the end is exclusive
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Loc {
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl Loc {
+    fn from_pair(pair: &Pair<Rule>) -> Self {
+        let span = pair.as_span();
+        let (line, column) = pair.line_col();
+        // rules ending in an optional part also take the whitespace after them
+        let end = span.start() + span.as_str().trim_end().len();
+        let (end_line, end_column) = Position::new(span.get_input(), end)
+            .unwrap_or(span.end_pos())
+            .line_col();
+        Loc {
+            line,
+            column,
+            end_line,
+            end_column,
+        }
+    }
+}
+
+impl std::fmt::Display for Loc {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Source {
     pub node_schemas: Vec<NodeSchema>,
     pub edge_schemas: Vec<EdgeSchema>,
     pub vector_schemas: Vec<VectorSchema>,
+    pub functions: Vec<Function>,
     pub queries: Vec<Query>,
+    pub imports: Vec<Import>,
+}
+
+/// `IMPORT "path"`, another file to compile with this one, relative to this one's directory
+#[derive(Debug, Clone)]
+pub struct Import {
+    pub path: String,
+    pub loc: Loc,
+}
+
+/// `FN name(parameters) => body`, a traversal or value that queries and other functions
+/// can start from. The body only sees the function's own parameters.
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name: String,
+    pub parameters: Vec<Parameter>,
+    pub body: Expression,
+    pub loc: Loc,
 }
 
 #[derive(Debug, Clone)]
 pub struct NodeSchema {
     pub name: String,
     pub fields: Vec<Field>,
+    pub loc: Loc,
 }
 
 #[derive(Debug, Clone)]
 pub struct VectorSchema {
     pub name: String,
+    pub loc: Loc,
 }
 
 #[derive(Debug, Clone)]
@@ -53,12 +112,14 @@ pub struct EdgeSchema {
     pub from: String,
     pub to: String,
     pub properties: Option<Vec<Field>>,
+    pub loc: Loc,
 }
 
 #[derive(Debug, Clone)]
 pub struct Field {
     pub name: String,
     pub field_type: FieldType,
+    pub loc: Loc,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -69,6 +130,12 @@ pub enum FieldType {
     Boolean,
     Array(Box<FieldType>),
     Identifier(String),
+    /// `Optional<T>`, the value may be missing or null
+    Optional(Box<FieldType>),
+    /// An inline object type, `{name: String, age: Integer}`
+    Object(Vec<(String, FieldType)>),
+    /// `[F32; N]`, a vector with `N` dimensions
+    Vector(usize),
 }
 
 #[derive(Debug, Clone)]
@@ -78,12 +145,23 @@ pub struct Query {
     pub parameters: Vec<Parameter>,
     pub statements: Vec<Statement>,
     pub return_values: Vec<Expression>,
+    /// Thread count from `PARALLEL(n)`, `None` runs every step on the calling thread
+    pub parallelism: Option<usize>,
+    /// `TIMEOUT(ms)`, `None` uses the server's limit
+    pub timeout_ms: Option<u64>,
+    /// `MAX_ITEMS(n)`, `None` uses the server's limit
+    pub max_items: Option<usize>,
+    /// `MAX_MEMORY(bytes)`, `None` uses the server's limit
+    pub max_memory: Option<usize>,
+    pub loc: Loc,
+    pub return_loc: Loc,
 }
 
 #[derive(Debug, Clone)]
 pub struct Parameter {
     pub name: String,
     pub param_type: FieldType,
+    pub loc: Loc,
 }
 
 #[derive(Debug, Clone)]
@@ -92,15 +170,48 @@ pub enum Statement {
     AddVector(AddVector),
     AddNode(AddNode),
     AddEdge(AddEdge),
+    UpsertNode(UpsertNode),
+    /// `UpsertE`, always has both `From` and `To`
+    UpsertEdge(AddEdge),
     Drop(Expression),
     SearchVector(SearchVector),
     BatchAddVector(BatchAddVector),
+    If(IfStatement),
+    For(ForLoop),
+    Return(Return),
 }
 
 #[derive(Debug, Clone)]
 pub struct Assignment {
     pub variable: String,
     pub value: Expression,
+    pub loc: Loc,
+}
+
+/// `IF condition { ... } ELSE { ... }`, an `ELSE IF` is a single nested `If` in `otherwise`
+#[derive(Debug, Clone)]
+pub struct IfStatement {
+    pub condition: Expression,
+    pub then: Vec<Statement>,
+    pub otherwise: Vec<Statement>,
+    pub loc: Loc,
+}
+
+/// `FOR variable IN iterable { ... }`, runs the body once per element of an array parameter
+/// or per item of a traversal
+#[derive(Debug, Clone)]
+pub struct ForLoop {
+    pub variable: String,
+    pub iterable: Expression,
+    pub body: Vec<Statement>,
+    pub loc: Loc,
+}
+
+/// A `RETURN` at the end of a block, ends the query early with these values
+#[derive(Debug, Clone)]
+pub struct Return {
+    pub values: Vec<Expression>,
+    pub loc: Loc,
 }
 
 #[derive(Debug, Clone)]
@@ -116,9 +227,13 @@ pub enum Expression {
     AddVector(AddVector),
     AddNode(AddNode),
     AddEdge(AddEdge),
+    UpsertNode(UpsertNode),
+    /// `UpsertE`, always has both `From` and `To`
+    UpsertEdge(AddEdge),
     And(Vec<Expression>),
     Or(Vec<Expression>),
     SearchVector(SearchVector),
+    Match(MatchPattern),
     None,
 }
 
@@ -126,12 +241,18 @@ pub enum Expression {
 pub struct Traversal {
     pub start: StartNode,
     pub steps: Vec<Step>,
+    pub loc: Loc,
+    /// Location of the start node, the whole traversal for anonymous traversals
+    pub start_loc: Loc,
+    /// Location of each step, `step_locs[i]` belongs to `steps[i]`
+    pub step_locs: Vec<Loc>,
 }
 
 #[derive(Debug, Clone)]
 pub struct BatchAddVector {
     pub vector_type: Option<String>,
     pub vec_identifier: Option<String>,
+    pub loc: Loc,
 }
 
 #[derive(Debug, Clone)]
@@ -146,6 +267,15 @@ pub enum StartNode {
     },
     Variable(String),
     Anonymous,
+    /// `name(args)`, the body of function `name` with its parameters bound to `args`
+    Call(Call),
+}
+
+#[derive(Debug, Clone)]
+pub struct Call {
+    pub name: String,
+    pub args: Vec<Expression>,
+    pub loc: Loc,
 }
 
 #[derive(Debug, Clone)]
@@ -155,6 +285,8 @@ pub enum Step {
     Where(Box<Expression>),
     BooleanOperation(BooleanOp),
     Count,
+    /// Returns the nodes and edges each item went through
+    Path,
     Update(Update),
     Object(Object),
     Exclude(Exclude),
@@ -162,6 +294,18 @@ pub enum Step {
     Range((Expression, Expression)),
     AddEdge(AddEdge),
     SearchVector(String),
+    OrderBy(Vec<(String, OrderDirection)>),
+    /// `DEDUP` by id, or `DEDUP(property)` by property value
+    Dedup(Option<String>),
+    GroupBy(String),
+    Aggregate(Vec<Aggregation>),
+    As(String),
+    Select(Vec<String>),
+    Union(Vec<Traversal>),
+    Optional(Box<Traversal>),
+    Coalesce(Vec<Traversal>),
+    PredictLinks(PredictLinks),
+    Dag(DagStep),
 }
 
 #[derive(Debug, Clone)]
@@ -208,6 +352,7 @@ pub enum BooleanOp {
 pub struct AddVector {
     pub vector_type: Option<String>,
     pub data: Option<VectorData>,
+    pub loc: Loc,
 }
 
 #[derive(Debug, Clone)]
@@ -221,6 +366,7 @@ pub struct SearchVector {
     pub vector_type: Option<String>,
     pub data: Option<VectorData>,
     pub k: Option<EvaluatesToNumber>,
+    pub loc: Loc,
 }
 
 #[derive(Debug, Clone)]
@@ -230,10 +376,86 @@ pub enum EvaluatesToNumber {
     Identifier(String),
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrderDirection {
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Aggregation {
+    Count,
+    Sum(String),
+    Avg(String),
+    Min(String),
+    Max(String),
+    Collect(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct PredictLinks {
+    pub edge_type: Option<String>,
+    pub metric: String,
+    pub limit: EvaluatesToNumber,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DagOperation {
+    Ancestors,
+    Descendants,
+    TopoSort,
+    FindCycle,
+    CriticalPath,
+}
+
+#[derive(Debug, Clone)]
+pub struct DagStep {
+    pub operation: DagOperation,
+    pub edge_type: String,
+    /// Edge property used as weight by `CriticalPath`
+    pub weight: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct MatchPattern {
+    pub nodes: Vec<PatternNode>,
+    pub edges: Vec<PatternEdge>,
+}
+
+/// A node in a MATCH pattern. The same variable may appear more than once.
+#[derive(Debug, Clone)]
+pub struct PatternNode {
+    pub variable: String,
+    pub label: Option<String>,
+    pub properties: Vec<(String, ValueType)>,
+    pub loc: Loc,
+}
+
+#[derive(Debug, Clone)]
+pub struct PatternEdge {
+    pub variable: Option<String>,
+    pub label: Option<String>,
+    pub from: String,
+    pub to: String,
+    pub properties: Vec<(String, ValueType)>,
+    pub loc: Loc,
+}
+
 #[derive(Debug, Clone)]
 pub struct AddNode {
     pub vertex_type: Option<String>,
     pub fields: Option<Vec<(String, ValueType)>>,
+    pub loc: Loc,
+}
+
+/// `UpsertN<Type>({match}, {fields})`, finds the node whose properties equal `match_fields`
+/// or creates it with them, then sets `fields` on it either way
+#[derive(Debug, Clone)]
+pub struct UpsertNode {
+    pub vertex_type: Option<String>,
+    pub match_fields: Vec<(String, ValueType)>,
+    pub fields: Option<Vec<(String, ValueType)>>,
+    pub loc: Loc,
 }
 
 #[derive(Debug, Clone)]
@@ -242,6 +464,7 @@ pub struct AddEdge {
     pub fields: Option<Vec<(String, ValueType)>>,
     pub connection: EdgeConnection,
     pub from_identifier: bool,
+    pub loc: Loc,
 }
 
 #[derive(Debug, Clone)]
@@ -264,15 +487,7 @@ pub enum ValueType {
 }
 impl From<Value> for ValueType {
     fn from(value: Value) -> ValueType {
-        match value {
-            Value::String(s) => ValueType::Literal(Value::String(s)),
-            Value::Integer(i) => ValueType::Literal(Value::Integer(i)),
-            Value::Float(f) => ValueType::Literal(Value::Float(f)),
-            Value::Boolean(b) => ValueType::Literal(Value::Boolean(b)),
-            Value::Array(arr) => ValueType::Literal(Value::Array(arr)),
-            Value::Empty => ValueType::Literal(Value::Empty),
-            _ => unreachable!(),
-        }
+        ValueType::Literal(value)
     }
 }
 
@@ -336,7 +551,9 @@ impl HelixParser {
                 node_schemas: Vec::new(),
                 edge_schemas: Vec::new(),
                 vector_schemas: Vec::new(),
+                functions: Vec::new(),
                 queries: Vec::new(),
+                imports: Vec::new(),
             },
         };
 
@@ -356,17 +573,24 @@ impl HelixParser {
                     .source
                     .vector_schemas
                     .push(parser.parse_vector_def(pair)?),
-                Rule::query_def => {
+                Rule::query_def | Rule::function_def => {
                     // parser.source.queries.push(parser.parse_query_def(pairs.next().unwrap())?),
                     remaining.insert(pair);
                 }
+                Rule::import => parser.source.imports.push(parser.parse_import(pair)?),
                 Rule::EOI => (),
                 _ => return Err(ParserError::from("Unexpected rule encountered")),
             }
         }
         for pair in remaining {
             // println!("{:?}", parser.source);
-            parser.source.queries.push(parser.parse_query_def(pair)?);
+            match pair.as_rule() {
+                Rule::function_def => {
+                    let function = parser.parse_function_def(pair)?;
+                    parser.source.functions.push(function);
+                }
+                _ => parser.source.queries.push(parser.parse_query_def(pair)?),
+            }
         }
 
         // parse all schemas first then parse queries using self
@@ -374,6 +598,124 @@ impl HelixParser {
         Ok(parser.source)
     }
 
+    /// The `IMPORT`s of a file, without checking its definitions against a schema that
+    /// may live in the imported files. A file that doesn't parse has none.
+    pub fn parse_imports(input: &str) -> Vec<Import> {
+        let Ok(mut pairs) = HelixParser::parse(Rule::source, input) else {
+            return Vec::new();
+        };
+        let parser = HelixParser::default();
+        pairs
+            .next()
+            .into_iter()
+            .flat_map(|file| file.into_inner())
+            .filter(|pair| pair.as_rule() == Rule::import)
+            .filter_map(|pair| parser.parse_import(pair).ok())
+            .collect()
+    }
+
+    /// Like `parse_source`, but keeps going after a definition fails to parse so every
+    /// broken definition gets a diagnostic instead of only the first
+    pub fn parse_source_diagnostics(input: &str) -> Result<Source, Vec<Diagnostic>> {
+        let error = match HelixParser::parse_source(input) {
+            Ok(source) => return Ok(source),
+            Err(e) => e,
+        };
+
+        // parse each top level definition on its own, padded with the lines before it so
+        // locations still point into `input`
+        let lines = input.lines().collect::<Vec<_>>();
+        let mut starts = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| {
+                let line = line.trim_start();
+                ["N::", "E::", "V::", "QUERY", "FN", "IMPORT"]
+                    .iter()
+                    .any(|keyword| line.starts_with(keyword))
+            })
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if starts.first() != Some(&0) {
+            // comments before the first definition go with it
+            starts.insert(0, 0);
+        }
+        let definitions = starts
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| {
+                let end = starts.get(i + 1).copied().unwrap_or(lines.len());
+                "\n".repeat(start) + &lines[start..end].join("\n")
+            })
+            .collect::<Vec<_>>();
+
+        let mut parser = HelixParser::default();
+        let mut diagnostics = Vec::new();
+        let mut queries = Vec::new();
+        for definition in &definitions {
+            let pairs = match HelixParser::parse(Rule::source, definition) {
+                Ok(mut pairs) => match pairs.next() {
+                    Some(pair) => pair.into_inner(),
+                    None => continue,
+                },
+                Err(e) => {
+                    diagnostics.push(syntax_error(&e));
+                    continue;
+                }
+            };
+            for pair in pairs {
+                let loc = Loc::from_pair(&pair);
+                let result = match pair.as_rule() {
+                    Rule::node_def => parser
+                        .parse_node_def(pair)
+                        .map(|schema| parser.source.node_schemas.push(schema)),
+                    Rule::edge_def => parser
+                        .parse_edge_def(pair)
+                        .map(|schema| parser.source.edge_schemas.push(schema)),
+                    Rule::vector_def => parser
+                        .parse_vector_def(pair)
+                        .map(|schema| parser.source.vector_schemas.push(schema)),
+                    Rule::query_def | Rule::function_def => {
+                        queries.push(pair);
+                        Ok(())
+                    }
+                    Rule::import => parser
+                        .parse_import(pair)
+                        .map(|import| parser.source.imports.push(import)),
+                    _ => Ok(()),
+                };
+                if let Err(e) = result {
+                    diagnostics.push(Diagnostic::new(INVALID_DEFINITION, loc, e.to_string()));
+                }
+            }
+        }
+        // queries are parsed once all schemas are known
+        for pair in queries {
+            let loc = Loc::from_pair(&pair);
+            let result = match pair.as_rule() {
+                Rule::function_def => parser
+                    .parse_function_def(pair)
+                    .map(|function| parser.source.functions.push(function)),
+                _ => parser
+                    .parse_query_def(pair)
+                    .map(|query| parser.source.queries.push(query)),
+            };
+            if let Err(e) = result {
+                diagnostics.push(Diagnostic::new(INVALID_DEFINITION, loc, e.to_string()));
+            }
+        }
+
+        if diagnostics.is_empty() {
+            // the definitions parse on their own, so the error comes from how they are put together
+            diagnostics.push(Diagnostic::new(
+                SYNTAX_ERROR,
+                Loc::default(),
+                error.to_string(),
+            ));
+        }
+        Err(diagnostics)
+    }
+
     fn parse_field_defs(&self, pair: Pair<Rule>) -> Result<Vec<Field>, ParserError> {
         pair.into_inner()
             .map(|p| self.parse_field_def(p))
@@ -381,15 +723,17 @@ impl HelixParser {
     }
 
     fn parse_node_def(&self, pair: Pair<Rule>) -> Result<NodeSchema, ParserError> {
+        let loc = Loc::from_pair(&pair);
         let mut pairs = pair.into_inner();
         let name = pairs.next().unwrap().as_str().to_string();
         let fields = self.parse_node_body(pairs.next().unwrap())?;
-        Ok(NodeSchema { name, fields })
+        Ok(NodeSchema { name, fields, loc })
     }
     fn parse_vector_def(&self, pair: Pair<Rule>) -> Result<VectorSchema, ParserError> {
+        let loc = Loc::from_pair(&pair);
         let mut pairs = pair.into_inner();
         let name = pairs.next().unwrap().as_str().to_string();
-        Ok(VectorSchema { name })
+        Ok(VectorSchema { name, loc })
     }
     fn parse_node_body(&self, pair: Pair<Rule>) -> Result<Vec<Field>, ParserError> {
         let field_defs = pair
@@ -404,65 +748,80 @@ impl HelixParser {
             .collect::<Result<Vec<_>, _>>()
     }
 
-    fn parse_field_type(
-        &self,
-        type_str: &str,
-        schema: Option<&Source>,
-    ) -> Result<FieldType, ParserError> {
-        match type_str {
-            "String" => Ok(FieldType::String),
-            "Integer" => Ok(FieldType::Integer),
-            "Float" => Ok(FieldType::Float),
-            "Boolean" => Ok(FieldType::Boolean),
-            _ if type_str.starts_with("[") && type_str.ends_with("]") => {
-                return Ok(FieldType::Array(Box::new(
-                    self.parse_field_type(&type_str[1..type_str.len() - 1], schema)?,
-                )));
+    fn parse_field_type(&self, pair: Pair<Rule>) -> Result<FieldType, ParserError> {
+        let inner = pair
+            .into_inner()
+            .next()
+            .ok_or_else(|| ParserError::from("Empty type"))?;
+        match inner.as_rule() {
+            Rule::scalar_type => match inner.as_str() {
+                "String" => Ok(FieldType::String),
+                "Integer" => Ok(FieldType::Integer),
+                "Float" => Ok(FieldType::Float),
+                _ => Ok(FieldType::Boolean),
+            },
+            Rule::optional_type => Ok(FieldType::Optional(Box::new(
+                self.parse_field_type(inner.into_inner().next().unwrap())?,
+            ))),
+            Rule::array => Ok(FieldType::Array(Box::new(
+                self.parse_field_type(inner.into_inner().next().unwrap())?,
+            ))),
+            Rule::vector_type => {
+                let dimensions = inner.into_inner().next().unwrap().as_str();
+                match dimensions.parse::<usize>() {
+                    Ok(dimensions) if dimensions > 0 => Ok(FieldType::Vector(dimensions)),
+                    _ => Err(ParserError::from(format!(
+                        "Invalid vector dimensions: {}",
+                        dimensions
+                    ))),
+                }
             }
-            _ if type_str.starts_with(
-                |c: char| {
-                    if c.is_ascii_uppercase() {
-                        true
-                    } else {
-                        false
-                    }
-                },
-            ) =>
-            {
-                // println!("{:?}", self.source);
-                if self.source.edge_schemas.iter().any(|e| {
-                    if e.name == type_str {
-                        true
-                    } else {
-                        false
-                    }
-                }) || self.source.node_schemas.iter().any(|n| {
-                    if n.name == type_str {
-                        true
-                    } else {
-                        false
+            Rule::object_type => {
+                let mut fields = Vec::new();
+                for field in inner.into_inner() {
+                    let mut pairs = field.into_inner();
+                    let name = pairs.next().unwrap().as_str().to_string();
+                    if fields.iter().any(|(existing, _)| *existing == name) {
+                        return Err(ParserError::from(format!(
+                            "Duplicate object field: {}",
+                            name
+                        )));
                     }
-                }) {
+                    let field_type = self.parse_field_type(pairs.next().unwrap())?;
+                    fields.push((name, field_type));
+                }
+                Ok(FieldType::Object(fields))
+            }
+            Rule::identifier_upper => {
+                let type_str = inner.as_str();
+                if self.source.edge_schemas.iter().any(|e| e.name == type_str)
+                    || self.source.node_schemas.iter().any(|n| n.name == type_str)
+                {
                     Ok(FieldType::Identifier(type_str.to_string()))
                 } else {
-                    return Err(ParserError::ParamDoesNotMatchSchema(type_str.to_string()));
+                    Err(ParserError::ParamDoesNotMatchSchema(type_str.to_string()))
                 }
             }
-            _ => unreachable!(),
+            rule => Err(ParserError::from(format!("Unexpected type: {:?}", rule))),
         }
     }
 
     fn parse_field_def(&self, pair: Pair<Rule>) -> Result<Field, ParserError> {
+        let loc = Loc::from_pair(&pair);
         let mut pairs = pair.into_inner();
         let name = pairs.next().unwrap().as_str().to_string();
 
-        let field_type =
-            self.parse_field_type(pairs.next().unwrap().as_str(), Some(&self.source))?;
+        let field_type = self.parse_field_type(pairs.next().unwrap())?;
 
-        Ok(Field { name, field_type })
+        Ok(Field {
+            name,
+            field_type,
+            loc,
+        })
     }
 
     fn parse_edge_def(&self, pair: Pair<Rule>) -> Result<EdgeSchema, ParserError> {
+        let loc = Loc::from_pair(&pair);
         let mut pairs = pair.into_inner();
         let name = pairs.next().unwrap().as_str().to_string();
         let body = pairs.next().unwrap();
@@ -477,6 +836,7 @@ impl HelixParser {
             from,
             to,
             properties,
+            loc,
         })
     }
     fn parse_properties(&self, pair: Pair<Rule>) -> Result<Vec<Field>, ParserError> {
@@ -492,12 +852,33 @@ impl HelixParser {
 
     fn parse_query_def(&self, pair: Pair<Rule>) -> Result<Query, ParserError> {
         let original_query = pair.clone().as_str().to_string();
+        let loc = Loc::from_pair(&pair);
         let mut pairs = pair.into_inner();
         let name = pairs.next().unwrap().as_str().to_string();
         let parameters = self.parse_parameters(pairs.next().unwrap())?;
-        let nect = pairs.next().unwrap();
+        let mut nect = pairs.next().unwrap();
+        let (mut parallelism, mut timeout_ms, mut max_items, mut max_memory) =
+            (None, None, None, None);
+        while matches!(
+            nect.as_rule(),
+            Rule::parallel_hint | Rule::timeout_hint | Rule::max_items_hint | Rule::max_memory_hint
+        ) {
+            let value = nect.clone().into_inner().next().unwrap().as_str();
+            let value = value.parse::<u64>().map_err(|_| {
+                ParserError::from(format!("Invalid query hint: {}", nect.as_str()))
+            })?;
+            match nect.as_rule() {
+                Rule::parallel_hint => parallelism = Some(value as usize),
+                Rule::timeout_hint => timeout_ms = Some(value),
+                Rule::max_items_hint => max_items = Some(value as usize),
+                _ => max_memory = Some(value as usize),
+            }
+            nect = pairs.next().unwrap();
+        }
         let statements = self.parse_query_body(nect)?;
-        let return_values = self.parse_return_statement(pairs.next().unwrap())?;
+        let return_pair = pairs.next().unwrap();
+        let return_loc = Loc::from_pair(&return_pair);
+        let return_values = self.parse_return_statement(return_pair)?;
 
         Ok(Query {
             name,
@@ -505,20 +886,60 @@ impl HelixParser {
             statements,
             return_values,
             original_query,
+            parallelism,
+            timeout_ms,
+            max_items,
+            max_memory,
+            loc,
+            return_loc,
+        })
+    }
+
+    fn parse_function_def(&self, pair: Pair<Rule>) -> Result<Function, ParserError> {
+        let loc = Loc::from_pair(&pair);
+        let mut pairs = pair.into_inner();
+        let name = pairs.next().unwrap().as_str().to_string();
+        let parameters = self.parse_parameters(pairs.next().unwrap())?;
+        let body = self.parse_expression(pairs.next().unwrap())?;
+        Ok(Function {
+            name,
+            parameters,
+            body,
+            loc,
         })
     }
 
+    fn parse_import(&self, pair: Pair<Rule>) -> Result<Import, ParserError> {
+        let loc = Loc::from_pair(&pair);
+        let path = self.parse_string_literal(pair.into_inner().next().unwrap())?;
+        Ok(Import { path, loc })
+    }
+
+    fn parse_call(&self, pair: Pair<Rule>) -> Result<Call, ParserError> {
+        let loc = Loc::from_pair(&pair);
+        let mut pairs = pair.into_inner();
+        let name = pairs.next().unwrap().as_str().to_string();
+        let args = pairs
+            .map(|p| self.parse_expression(p))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Call { name, args, loc })
+    }
+
     fn parse_parameters(&self, pair: Pair<Rule>) -> Result<Vec<Parameter>, ParserError> {
         let mut seen = HashSet::new();
         pair.clone()
             .into_inner()
             .map(|p: Pair<'_, Rule>| -> Result<Parameter, ParserError> {
+                let loc = Loc::from_pair(&p);
                 let mut inner = p.into_inner();
                 let name = inner.next().unwrap().as_str().to_string();
-                let param_type =
-                    self.parse_field_type(inner.next().unwrap().as_str(), Some(&self.source))?;
+                let param_type = self.parse_field_type(inner.next().unwrap())?;
                 if seen.insert(name.clone()) {
-                    Ok(Parameter { name, param_type })
+                    Ok(Parameter {
+                        name,
+                        param_type,
+                        loc,
+                    })
                 } else {
                     Err(ParserError::from(format!(
                         r#"Duplicate parameter name: {}
@@ -537,24 +958,73 @@ impl HelixParser {
     }
 
     fn parse_query_body(&self, pair: Pair<Rule>) -> Result<Vec<Statement>, ParserError> {
-        pair.into_inner()
-            .map(|p| match p.as_rule() {
-                Rule::get_stmt => Ok(Statement::Assignment(self.parse_get_statement(p)?)),
-                Rule::AddN => Ok(Statement::AddNode(self.parse_add_vertex(p)?)),
-                Rule::AddV => Ok(Statement::AddVector(self.parse_add_vector(p)?)),
-                Rule::AddE => Ok(Statement::AddEdge(self.parse_add_edge(p, false)?)),
-                Rule::drop => Ok(Statement::Drop(self.parse_expression(p)?)),
-                Rule::BatchAddV => Ok(Statement::BatchAddVector(self.parse_batch_add_vector(p)?)),
-                Rule::search_vector => Ok(Statement::SearchVector(self.parse_search_vector(p)?)),
-                _ => Err(ParserError::from(format!(
-                    "Unexpected statement type in query body: {:?}",
-                    p.as_rule()
-                ))),
-            })
-            .collect()
+        pair.into_inner().map(|p| self.parse_statement(p)).collect()
+    }
+
+    fn parse_statement(&self, p: Pair<Rule>) -> Result<Statement, ParserError> {
+        match p.as_rule() {
+            Rule::get_stmt => Ok(Statement::Assignment(self.parse_get_statement(p)?)),
+            Rule::AddN => Ok(Statement::AddNode(self.parse_add_vertex(p)?)),
+            Rule::AddV => Ok(Statement::AddVector(self.parse_add_vector(p)?)),
+            Rule::AddE => Ok(Statement::AddEdge(self.parse_add_edge(p, false)?)),
+            Rule::UpsertN => Ok(Statement::UpsertNode(self.parse_upsert_node(p)?)),
+            Rule::UpsertE => Ok(Statement::UpsertEdge(self.parse_upsert_edge(p)?)),
+            Rule::drop => Ok(Statement::Drop(self.parse_expression(p)?)),
+            Rule::BatchAddV => Ok(Statement::BatchAddVector(self.parse_batch_add_vector(p)?)),
+            Rule::search_vector => Ok(Statement::SearchVector(self.parse_search_vector(p)?)),
+            Rule::if_stmt => Ok(Statement::If(self.parse_if_statement(p)?)),
+            Rule::for_stmt => Ok(Statement::For(self.parse_for_loop(p)?)),
+            Rule::return_stmt => Ok(Statement::Return(Return {
+                loc: Loc::from_pair(&p),
+                values: self.parse_return_statement(p)?,
+            })),
+            _ => Err(ParserError::from(format!(
+                "Unexpected statement type in query body: {:?}",
+                p.as_rule()
+            ))),
+        }
+    }
+
+    fn parse_block(&self, pair: Pair<Rule>) -> Result<Vec<Statement>, ParserError> {
+        pair.into_inner().map(|p| self.parse_statement(p)).collect()
+    }
+
+    fn parse_if_statement(&self, pair: Pair<Rule>) -> Result<IfStatement, ParserError> {
+        let loc = Loc::from_pair(&pair);
+        let mut inner = pair.into_inner();
+        let condition = self.parse_expression(inner.next().unwrap())?;
+        let then = self.parse_block(inner.next().unwrap())?;
+        let otherwise = match inner.next() {
+            Some(p) if p.as_rule() == Rule::if_stmt => {
+                vec![Statement::If(self.parse_if_statement(p)?)]
+            }
+            Some(p) => self.parse_block(p)?,
+            None => Vec::new(),
+        };
+        Ok(IfStatement {
+            condition,
+            then,
+            otherwise,
+            loc,
+        })
+    }
+
+    fn parse_for_loop(&self, pair: Pair<Rule>) -> Result<ForLoop, ParserError> {
+        let loc = Loc::from_pair(&pair);
+        let mut inner = pair.into_inner();
+        let variable = inner.next().unwrap().as_str().to_string();
+        let iterable = self.parse_expression(inner.next().unwrap())?;
+        let body = self.parse_block(inner.next().unwrap())?;
+        Ok(ForLoop {
+            variable,
+            iterable,
+            body,
+            loc,
+        })
     }
 
     fn parse_batch_add_vector(&self, pair: Pair<Rule>) -> Result<BatchAddVector, ParserError> {
+        let loc = Loc::from_pair(&pair);
         let mut vector_type = None;
         let mut vec_identifier = None;
         
@@ -576,10 +1046,15 @@ impl HelixParser {
             }
         }
 
-        Ok(BatchAddVector { vector_type, vec_identifier })
+        Ok(BatchAddVector {
+            vector_type,
+            vec_identifier,
+            loc,
+        })
     }
 
     fn parse_add_vector(&self, pair: Pair<Rule>) -> Result<AddVector, ParserError> {
+        let loc = Loc::from_pair(&pair);
         let mut vector_type = None;
         let mut data = None;
 
@@ -607,10 +1082,15 @@ impl HelixParser {
             }
         }
 
-        Ok(AddVector { vector_type, data })
+        Ok(AddVector {
+            vector_type,
+            data,
+            loc,
+        })
     }
 
     fn parse_search_vector(&self, pair: Pair<Rule>) -> Result<SearchVector, ParserError> {
+        let loc = Loc::from_pair(&pair);
         let mut vector_type = None;
         let mut data = None;
         let mut k = None;
@@ -650,7 +1130,12 @@ impl HelixParser {
             }
         }
 
-        Ok(SearchVector { vector_type, data, k})
+        Ok(SearchVector {
+            vector_type,
+            data,
+            k,
+            loc,
+        })
     }
 
     fn parse_vec_literal(&self, pair: Pair<Rule>) -> Result<Vec<f64>, ParserError> {
@@ -667,6 +1152,7 @@ impl HelixParser {
     }
 
     fn parse_add_vertex(&self, pair: Pair<Rule>) -> Result<AddNode, ParserError> {
+        let loc = Loc::from_pair(&pair);
         let mut vertex_type = None;
         let mut fields = None;
 
@@ -691,6 +1177,7 @@ impl HelixParser {
         Ok(AddNode {
             vertex_type,
             fields,
+            loc,
         })
     }
 
@@ -715,9 +1202,12 @@ impl HelixParser {
                             .ok_or_else(|| ParserError::from("Empty property value"))?;
 
                         match value_pair.as_rule() {
-                            Rule::string_literal => Ok(ValueType::from(Value::from(
-                                value_pair.as_str().to_string(),
+                            Rule::string_literal => Ok(ValueType::from(Value::String(
+                                self.parse_string_literal(value_pair)?,
                             ))),
+                            Rule::date => {
+                                Ok(ValueType::from(Value::String(self.parse_date(value_pair)?)))
+                            }
                             Rule::integer => value_pair
                                 .as_str()
                                 .parse()
@@ -750,6 +1240,7 @@ impl HelixParser {
         pair: Pair<Rule>,
         from_identifier: bool,
     ) -> Result<AddEdge, ParserError> {
+        let loc = Loc::from_pair(&pair);
         let mut edge_type = None;
         let mut fields = None;
         let mut connection = None;
@@ -779,9 +1270,45 @@ impl HelixParser {
             fields,
             connection: connection.ok_or_else(|| ParserError::from("Missing edge connection"))?,
             from_identifier,
+            loc,
         })
     }
 
+    fn parse_upsert_node(&self, pair: Pair<Rule>) -> Result<UpsertNode, ParserError> {
+        let loc = Loc::from_pair(&pair);
+        let mut inner = pair.into_inner();
+        let vertex_type = inner
+            .next()
+            .map(|p| p.as_str().to_string())
+            .ok_or_else(|| ParserError::from("Missing node type"))?;
+        let match_fields = inner
+            .next()
+            .ok_or_else(|| ParserError::from("Missing match fields"))
+            .and_then(|p| self.parse_property_assignments(p))?;
+        let fields = inner
+            .next()
+            .map(|p| self.parse_property_assignments(p))
+            .transpose()?;
+
+        Ok(UpsertNode {
+            vertex_type: Some(vertex_type),
+            match_fields,
+            fields,
+            loc,
+        })
+    }
+
+    fn parse_upsert_edge(&self, pair: Pair<Rule>) -> Result<AddEdge, ParserError> {
+        let add = self.parse_add_edge(pair, false)?;
+        if add.connection.from_id.is_none() || add.connection.to_id.is_none() {
+            return Err(ParserError::from(format!(
+                "UpsertE needs both From and To at {}",
+                add.loc
+            )));
+        }
+        Ok(add)
+    }
+
     fn parse_id_args(&self, pair: Pair<Rule>) -> Result<Option<IdType>, ParserError> {
         let p = pair
             .into_inner()
@@ -789,9 +1316,7 @@ impl HelixParser {
             .ok_or_else(|| ParserError::from("Missing ID"))?;
         match p.as_rule() {
             Rule::identifier => Ok(Some(IdType::Identifier(p.as_str().to_string()))),
-            Rule::string_literal | Rule::inner_string => {
-                Ok(Some(IdType::from(p.as_str().to_string())))
-            }
+            Rule::string_literal => Ok(Some(IdType::Literal(self.parse_string_literal(p)?))),
             _ => Err(ParserError::from(format!(
                 "Unexpected rule in parse_id_args: {:?}",
                 p.as_rule()
@@ -822,11 +1347,16 @@ impl HelixParser {
     }
 
     fn parse_get_statement(&self, pair: Pair<Rule>) -> Result<Assignment, ParserError> {
+        let loc = Loc::from_pair(&pair);
         let mut pairs = pair.into_inner();
         let variable = pairs.next().unwrap().as_str().to_string();
         let value = self.parse_expression(pairs.next().unwrap())?;
 
-        Ok(Assignment { variable, value })
+        Ok(Assignment {
+            variable,
+            value,
+            loc,
+        })
     }
 
     fn parse_return_statement(&self, pair: Pair<Rule>) -> Result<Vec<Expression>, ParserError> {
@@ -873,6 +1403,10 @@ impl HelixParser {
             Rule::exists => Ok(Expression::Exists(Box::new(
                 self.parse_anon_traversal(expression.into_inner().next().unwrap())?,
             ))),
+            Rule::identifier => Ok(Expression::Identifier(expression.as_str().to_string())),
+            Rule::traversal | Rule::id_traversal => Ok(Expression::Traversal(Box::new(
+                self.parse_traversal(expression)?,
+            ))),
             _ => unreachable!(),
         }
     }
@@ -891,6 +1425,7 @@ impl HelixParser {
             ))),
             Rule::identifier => Ok(Expression::Identifier(pair.as_str().to_string())),
             Rule::string_literal => Ok(Expression::StringLiteral(self.parse_string_literal(pair)?)),
+            Rule::date => Ok(Expression::StringLiteral(self.parse_date(pair)?)),
             Rule::exists => {
                 let traversal = pair
                     .into_inner()
@@ -914,11 +1449,16 @@ impl HelixParser {
                 .map_err(|_| ParserError::from("Invalid float literal")),
             Rule::boolean => Ok(Expression::BooleanLiteral(pair.as_str() == "true")),
             Rule::evaluates_to_bool => Ok(self.parse_boolean_expression(pair)?),
+            Rule::and => Ok(Expression::And(self.parse_expression_vec(pair.into_inner())?)),
+            Rule::or => Ok(Expression::Or(self.parse_expression_vec(pair.into_inner())?)),
             Rule::AddN => Ok(Expression::AddNode(self.parse_add_vertex(pair)?)),
             Rule::AddV => Ok(Expression::AddVector(self.parse_add_vector(pair)?)),
             Rule::BatchAddV => Ok(Expression::BatchAddVector(self.parse_batch_add_vector(pair)?)),
             Rule::AddE => Ok(Expression::AddEdge(self.parse_add_edge(pair, false)?)),
+            Rule::UpsertN => Ok(Expression::UpsertNode(self.parse_upsert_node(pair)?)),
+            Rule::UpsertE => Ok(Expression::UpsertEdge(self.parse_upsert_edge(pair)?)),
             Rule::search_vector => Ok(Expression::SearchVector(self.parse_search_vector(pair)?)),
+            Rule::match_pattern => Ok(Expression::Match(self.parse_match_pattern(pair)?)),
             Rule::none => Ok(Expression::None),
             _ => Err(ParserError::from(format!(
                 "Unexpected expression type: {:?}",
@@ -927,35 +1467,231 @@ impl HelixParser {
         }
     }
 
+    fn parse_match_pattern(&self, pair: Pair<Rule>) -> Result<MatchPattern, ParserError> {
+        let mut pattern = MatchPattern {
+            nodes: Vec::new(),
+            edges: Vec::new(),
+        };
+
+        for path in pair.into_inner() {
+            let mut previous: Option<String> = None;
+            // an edge is only complete once the node on its right has been parsed
+            let mut pending_edge: Option<(bool, Pair<Rule>)> = None;
+            for p in path.into_inner() {
+                match p.as_rule() {
+                    Rule::pattern_node => {
+                        let node = self.parse_pattern_node(p)?;
+                        if let (Some((outgoing, edge)), Some(left)) =
+                            (pending_edge.take(), previous.take())
+                        {
+                            let (from, to) = match outgoing {
+                                true => (left, node.variable.clone()),
+                                false => (node.variable.clone(), left),
+                            };
+                            pattern.edges.push(self.parse_pattern_edge(edge, from, to)?);
+                        }
+                        previous = Some(node.variable.clone());
+                        pattern.nodes.push(node);
+                    }
+                    Rule::pattern_out_edge => pending_edge = Some((true, p)),
+                    Rule::pattern_in_edge => pending_edge = Some((false, p)),
+                    _ => {
+                        return Err(ParserError::from(format!(
+                            "Unexpected rule in MATCH: {:?}",
+                            p.as_rule()
+                        )))
+                    }
+                }
+            }
+        }
+
+        Ok(pattern)
+    }
+
+    fn parse_pattern_node(&self, pair: Pair<Rule>) -> Result<PatternNode, ParserError> {
+        let loc = Loc::from_pair(&pair);
+        let mut pairs = pair.into_inner();
+        let variable = pairs.next().unwrap().as_str().to_string();
+        let mut label = None;
+        let mut properties = Vec::new();
+        for p in pairs {
+            match p.as_rule() {
+                Rule::identifier_upper => label = Some(p.as_str().to_string()),
+                Rule::pattern_props => properties = self.parse_pattern_props(p)?,
+                _ => {
+                    return Err(ParserError::from(format!(
+                        "Unexpected rule in pattern node: {:?}",
+                        p.as_rule()
+                    )))
+                }
+            }
+        }
+
+        Ok(PatternNode {
+            variable,
+            label,
+            properties,
+            loc,
+        })
+    }
+
+    fn parse_pattern_edge(
+        &self,
+        pair: Pair<Rule>,
+        from: String,
+        to: String,
+    ) -> Result<PatternEdge, ParserError> {
+        let mut edge = PatternEdge {
+            variable: None,
+            label: None,
+            from,
+            to,
+            properties: Vec::new(),
+            loc: Loc::from_pair(&pair),
+        };
+        for p in pair.into_inner().next().unwrap().into_inner() {
+            match p.as_rule() {
+                Rule::identifier => edge.variable = Some(p.as_str().to_string()),
+                Rule::identifier_upper => edge.label = Some(p.as_str().to_string()),
+                Rule::pattern_props => edge.properties = self.parse_pattern_props(p)?,
+                _ => {
+                    return Err(ParserError::from(format!(
+                        "Unexpected rule in pattern edge: {:?}",
+                        p.as_rule()
+                    )))
+                }
+            }
+        }
+        Ok(edge)
+    }
+
+    fn parse_pattern_props(&self, pair: Pair<Rule>) -> Result<Vec<(String, ValueType)>, ParserError> {
+        pair.into_inner()
+            .map(|prop| {
+                let mut pairs = prop.into_inner();
+                let key = pairs.next().unwrap().as_str().to_string();
+                let value = pairs.next().unwrap();
+                let value = match value.as_rule() {
+                    Rule::string_literal => {
+                        ValueType::Literal(Value::String(self.parse_string_literal(value)?))
+                    }
+                    Rule::date => ValueType::Literal(Value::String(self.parse_date(value)?)),
+                    Rule::integer => ValueType::Literal(Value::Integer(
+                        value
+                            .as_str()
+                            .parse()
+                            .map_err(|_| ParserError::from("Invalid integer value"))?,
+                    )),
+                    Rule::float => ValueType::Literal(Value::Float(
+                        value
+                            .as_str()
+                            .parse()
+                            .map_err(|_| ParserError::from("Invalid float value"))?,
+                    )),
+                    Rule::boolean => ValueType::Literal(Value::Boolean(value.as_str() == "true")),
+                    Rule::identifier => ValueType::Identifier(value.as_str().to_string()),
+                    _ => return Err(ParserError::from("Invalid pattern property value")),
+                };
+                Ok((key, value))
+            })
+            .collect()
+    }
+
     fn parse_string_literal(&self, pair: Pair<Rule>) -> Result<String, ParserError> {
         let inner = pair
             .into_inner()
             .next()
             .ok_or_else(|| ParserError::from("Empty string literal"))?;
 
-        let mut literal = inner.as_str().to_string();
-        literal.retain(|c| c != '"');
-        Ok(literal)
+        match inner.as_rule() {
+            Rule::multiline_string => Ok(dedent(inner.as_str())),
+            _ => unescape(inner.as_str()),
+        }
+    }
+
+    /// Validates an ISO-8601 date or date-time literal, keeping its text as the value
+    fn parse_date(&self, pair: Pair<Rule>) -> Result<String, ParserError> {
+        let literal = pair.as_str();
+        let invalid = || ParserError::from(format!("Invalid date literal {}", literal));
+        let (date, time) = match literal.split_once('T') {
+            Some((date, time)) => (date, Some(time)),
+            None => (literal, None),
+        };
+
+        // The grammar has already checked the shape, so every field is made of digits
+        let numbers = |part: &str, separator: char| -> Vec<u32> {
+            part.split(separator)
+                .map(|n| n.parse().unwrap_or(u32::MAX))
+                .collect()
+        };
+        let [year, month, day] = numbers(date, '-')[..] else {
+            return Err(invalid());
+        };
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if leap => 29,
+            2 => 28,
+            _ => return Err(invalid()),
+        };
+        if day == 0 || day > days {
+            return Err(invalid());
+        }
+
+        if let Some(time) = time {
+            let (clock, offset) = time.split_at(time.find(['Z', '+', '-']).unwrap_or(time.len()));
+            let clock = clock.split('.').next().unwrap_or(clock);
+            let clock = numbers(clock, ':');
+            if clock[0] > 23 || clock[1..].iter().any(|&n| n > 59) {
+                return Err(invalid());
+            }
+            if let Some(offset) = offset.get(1..).filter(|o| !o.is_empty()) {
+                let offset = numbers(offset, ':');
+                if offset[0] > 23 || offset[1] > 59 {
+                    return Err(invalid());
+                }
+            }
+        }
+        Ok(literal.to_string())
     }
 
     fn parse_traversal(&self, pair: Pair<Rule>) -> Result<Traversal, ParserError> {
+        let loc = Loc::from_pair(&pair);
         let mut pairs = pair.into_inner();
-        let start = self.parse_start_node(pairs.next().unwrap())?;
-        let steps = pairs
-            .map(|p| self.parse_step(p))
-            .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(Traversal { start, steps })
+        let start_pair = pairs.next().unwrap();
+        let start_loc = Loc::from_pair(&start_pair);
+        let start = self.parse_start_node(start_pair)?;
+        let (steps, step_locs) = self.parse_steps(pairs)?;
+
+        Ok(Traversal {
+            start,
+            steps,
+            loc,
+            start_loc,
+            step_locs,
+        })
     }
 
     fn parse_anon_traversal(&self, pair: Pair<Rule>) -> Result<Traversal, ParserError> {
-        let pairs = pair.into_inner();
+        let loc = Loc::from_pair(&pair);
         let start = StartNode::Anonymous;
-        let steps = pairs
-            .map(|p| self.parse_step(p))
-            .collect::<Result<Vec<_>, _>>()?;
+        let (steps, step_locs) = self.parse_steps(pair.into_inner())?;
+
+        Ok(Traversal {
+            start,
+            steps,
+            loc,
+            start_loc: loc,
+            step_locs,
+        })
+    }
 
-        Ok(Traversal { start, steps })
+    fn parse_steps(&self, pairs: Pairs<Rule>) -> Result<(Vec<Step>, Vec<Loc>), ParserError> {
+        pairs
+            .map(|p| Ok((Loc::from_pair(&p), self.parse_step(p)?)))
+            .collect::<Result<Vec<_>, ParserError>>()
+            .map(|steps| steps.into_iter().map(|(loc, step)| (step, loc)).unzip())
     }
 
     fn parse_start_node(&self, pair: Pair<Rule>) -> Result<StartNode, ParserError> {
@@ -1011,6 +1747,7 @@ impl HelixParser {
                 Ok(StartNode::Edge { types, ids })
             }
             Rule::identifier => Ok(StartNode::Variable(pair.as_str().to_string())),
+            Rule::call => Ok(StartNode::Call(self.parse_call(pair)?)),
             _ => Ok(StartNode::Anonymous),
         }
     }
@@ -1026,6 +1763,7 @@ impl HelixParser {
 
             Rule::bool_operations => Ok(Step::BooleanOperation(self.parse_bool_operation(inner)?)),
             Rule::count => Ok(Step::Count),
+            Rule::path => Ok(Step::Path),
             Rule::ID => Ok(Step::Object(Object {
                 fields: vec![("id".to_string(), FieldValue::Empty)],
                 should_spread: false,
@@ -1033,28 +1771,165 @@ impl HelixParser {
             Rule::update => Ok(Step::Update(self.parse_update(inner)?)),
             Rule::exclude_field => Ok(Step::Exclude(self.parse_exclude(inner)?)),
             Rule::AddE => Ok(Step::AddEdge(self.parse_add_edge(inner, true)?)),
+            Rule::predict_links => Ok(Step::PredictLinks(self.parse_predict_links(inner)?)),
+            Rule::dag_step => Ok(Step::Dag(self.parse_dag_step(inner)?)),
+            Rule::order_step => Ok(Step::OrderBy(self.parse_order_step(inner))),
+            Rule::dedup_step => Ok(Step::Dedup(
+                inner.into_inner().next().map(|p| p.as_str().to_string()),
+            )),
+            Rule::group_by_step => Ok(Step::GroupBy(
+                inner.into_inner().next().unwrap().as_str().to_string(),
+            )),
+            Rule::agg_step => Ok(Step::Aggregate(self.parse_agg_step(inner)?)),
+            Rule::as_step => Ok(Step::As(inner.into_inner().next().unwrap().as_str().to_string())),
+            Rule::select_step => Ok(Step::Select(
+                inner.into_inner().map(|p| p.as_str().to_string()).collect(),
+            )),
+            Rule::union_step => Ok(Step::Union(self.parse_branches(inner)?)),
+            Rule::optional_step => Ok(Step::Optional(Box::new(
+                self.parse_anon_traversal(inner.into_inner().next().unwrap())?,
+            ))),
+            Rule::coalesce_step => Ok(Step::Coalesce(self.parse_branches(inner)?)),
+            _ => Err(ParserError::from(format!(
+                "Unexpected step type: {:?}",
+                inner.as_rule()
+            ))),
+        }
+    }
+
+    fn parse_range(&self, pair: Pair<Rule>) -> Result<(Expression, Expression), ParserError> {
+        let mut inner = pair.into_inner().next().unwrap().into_inner();
+        // println!("inner: {:?}", inner);
+        let start = match self.parse_expression(inner.next().unwrap()) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+        let end = match self.parse_expression(inner.next().unwrap()) {
+            Ok(val) => val,
+            Err(e) => return Err(e),
+        };
+
+        Ok((start, end))
+    }
+
+    fn parse_predict_links(&self, pair: Pair<Rule>) -> Result<PredictLinks, ParserError> {
+        let mut edge_type = None;
+        let mut metric = String::new();
+        let mut limit = None;
+        for p in pair.into_inner() {
+            match p.as_rule() {
+                Rule::identifier_upper => edge_type = Some(p.as_str().to_string()),
+                Rule::link_metric => metric = p.as_str().to_string(),
+                Rule::evaluates_to_number => limit = Some(self.parse_evaluates_to_number(p)?),
+                _ => {
+                    return Err(ParserError::from(format!(
+                        "Unexpected rule in PredictLinks: {:?}",
+                        p.as_rule()
+                    )))
+                }
+            }
+        }
+
+        Ok(PredictLinks {
+            edge_type,
+            metric,
+            limit: limit.ok_or_else(|| ParserError::from("Missing limit in PredictLinks"))?,
+        })
+    }
+
+    fn parse_branches(&self, pair: Pair<Rule>) -> Result<Vec<Traversal>, ParserError> {
+        pair.into_inner()
+            .map(|branch| self.parse_anon_traversal(branch))
+            .collect()
+    }
+
+    fn parse_order_step(&self, pair: Pair<Rule>) -> Vec<(String, OrderDirection)> {
+        pair.into_inner()
+            .map(|key| {
+                let mut pairs = key.into_inner();
+                let property = pairs.next().unwrap().as_str().to_string();
+                let direction = match pairs.next().map(|p| p.as_str()) {
+                    Some("DESC") => OrderDirection::Desc,
+                    _ => OrderDirection::Asc,
+                };
+                (property, direction)
+            })
+            .collect()
+    }
+
+    fn parse_agg_step(&self, pair: Pair<Rule>) -> Result<Vec<Aggregation>, ParserError> {
+        pair.into_inner()
+            .map(|aggregation| {
+                let mut pairs = aggregation.into_inner();
+                let function = pairs.next().unwrap();
+                if function.as_rule() == Rule::agg_count {
+                    return Ok(Aggregation::Count);
+                }
+                let property = pairs.next().unwrap().as_str().to_string();
+                match function.as_str() {
+                    "SUM" => Ok(Aggregation::Sum(property)),
+                    "AVG" => Ok(Aggregation::Avg(property)),
+                    "MIN" => Ok(Aggregation::Min(property)),
+                    "MAX" => Ok(Aggregation::Max(property)),
+                    "COLLECT" => Ok(Aggregation::Collect(property)),
+                    function => Err(ParserError::from(format!(
+                        "Unknown aggregation: {}",
+                        function
+                    ))),
+                }
+            })
+            .collect()
+    }
+
+    fn parse_dag_step(&self, pair: Pair<Rule>) -> Result<DagStep, ParserError> {
+        let mut pairs = pair.into_inner();
+        let operation = match pairs.next().unwrap().as_str() {
+            "Ancestors" => DagOperation::Ancestors,
+            "Descendants" => DagOperation::Descendants,
+            "TopoSort" => DagOperation::TopoSort,
+            "FindCycle" => DagOperation::FindCycle,
+            "CriticalPath" => DagOperation::CriticalPath,
+            op => return Err(ParserError::from(format!("Unknown DAG operation: {}", op))),
+        };
+        let edge_type = pairs.next().unwrap().as_str().to_string();
+        let weight = pairs.next().map(|p| p.as_str().to_string());
+        if weight.is_some() && operation != DagOperation::CriticalPath {
+            return Err(ParserError::from(format!(
+                "{:?} does not take a weight property",
+                operation
+            )));
+        }
+
+        Ok(DagStep {
+            operation,
+            edge_type,
+            weight,
+        })
+    }
+
+    fn parse_evaluates_to_number(&self, pair: Pair<Rule>) -> Result<EvaluatesToNumber, ParserError> {
+        let inner = pair.into_inner().next().unwrap();
+        match inner.as_rule() {
+            Rule::integer => Ok(EvaluatesToNumber::Integer(
+                inner
+                    .as_str()
+                    .parse::<usize>()
+                    .map_err(|_| ParserError::from("Invalid integer value"))?,
+            )),
+            Rule::float => Ok(EvaluatesToNumber::Float(
+                inner
+                    .as_str()
+                    .parse::<f64>()
+                    .map_err(|_| ParserError::from("Invalid float value"))?,
+            )),
+            Rule::identifier => Ok(EvaluatesToNumber::Identifier(inner.as_str().to_string())),
             _ => Err(ParserError::from(format!(
-                "Unexpected step type: {:?}",
+                "Expected a number or identifier, got {:?}",
                 inner.as_rule()
             ))),
         }
     }
 
-    fn parse_range(&self, pair: Pair<Rule>) -> Result<(Expression, Expression), ParserError> {
-        let mut inner = pair.into_inner().next().unwrap().into_inner();
-        // println!("inner: {:?}", inner);
-        let start = match self.parse_expression(inner.next().unwrap()) {
-            Ok(val) => val,
-            Err(e) => return Err(e),
-        };
-        let end = match self.parse_expression(inner.next().unwrap()) {
-            Ok(val) => val,
-            Err(e) => return Err(e),
-        };
-
-        Ok((start, end))
-    }
-
     fn parse_graph_step(&self, pair: Pair<Rule>) -> GraphStep {
         let rule_str = pair.as_str();
         let types = pair
@@ -1081,16 +1956,16 @@ impl HelixParser {
         let inner = pair.into_inner().next().unwrap();
         let expr = match inner.as_rule() {
             Rule::GT => BooleanOp::GreaterThan(Box::new(
-                self.parse_expression(inner.into_inner().next().unwrap())?,
+                self.parse_comparison_operand(inner.into_inner().next().unwrap())?,
             )),
             Rule::GTE => BooleanOp::GreaterThanOrEqual(Box::new(
-                self.parse_expression(inner.into_inner().next().unwrap())?,
+                self.parse_comparison_operand(inner.into_inner().next().unwrap())?,
             )),
             Rule::LT => BooleanOp::LessThan(Box::new(
-                self.parse_expression(inner.into_inner().next().unwrap())?,
+                self.parse_comparison_operand(inner.into_inner().next().unwrap())?,
             )),
             Rule::LTE => BooleanOp::LessThanOrEqual(Box::new(
-                self.parse_expression(inner.into_inner().next().unwrap())?,
+                self.parse_comparison_operand(inner.into_inner().next().unwrap())?,
             )),
             Rule::EQ => BooleanOp::Equal(Box::new(
                 self.parse_expression(inner.into_inner().next().unwrap())?,
@@ -1103,6 +1978,14 @@ impl HelixParser {
         Ok(expr)
     }
 
+    /// The operand of GT, GTE, LT or LTE, a number or a date
+    fn parse_comparison_operand(&self, pair: Pair<Rule>) -> Result<Expression, ParserError> {
+        match pair.as_rule() {
+            Rule::date => Ok(Expression::StringLiteral(self.parse_date(pair)?)),
+            _ => self.parse_expression(pair),
+        }
+    }
+
     fn parse_field_additions(&self, pair: Pair<Rule>) -> Result<Vec<FieldAddition>, ParserError> {
         pair.into_inner()
             .map(|p| self.parse_new_field_pair(p))
@@ -1261,6 +2144,89 @@ impl HelixParser {
 }
 
 // Tests module
+/// A diagnostic for a pest error, pointing at the position parsing stopped at
+pub(crate) fn syntax_error(error: &pest::error::Error<Rule>) -> Diagnostic {
+    let loc = match error.line_col {
+        LineColLocation::Pos((line, column)) => Loc {
+            line,
+            column,
+            end_line: line,
+            end_column: column + 1,
+        },
+        LineColLocation::Span((line, column), (end_line, end_column)) => Loc {
+            line,
+            column,
+            end_line,
+            end_column,
+        },
+    };
+    Diagnostic::new(SYNTAX_ERROR, loc, error.variant.message().to_string())
+}
+
+/// Resolves the escape sequences of a quoted string literal
+fn unescape(literal: &str) -> Result<String, ParserError> {
+    let mut unescaped = String::with_capacity(literal.len());
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some('t') => unescaped.push('\t'),
+            Some('0') => unescaped.push('\0'),
+            Some(c @ ('"' | '\\' | '/')) => unescaped.push(c),
+            Some('u') => {
+                let rest = chars.as_str();
+                let (digits, rest) = match rest.strip_prefix('{') {
+                    Some(braced) => braced.split_once('}').unwrap_or((braced, "")),
+                    None => rest.split_at(rest.len().min(4)),
+                };
+                let c = u32::from_str_radix(digits, 16)
+                    .ok()
+                    .and_then(char::from_u32)
+                    .ok_or_else(|| {
+                        ParserError::from(format!("Invalid unicode escape \\u{{{}}}", digits))
+                    })?;
+                unescaped.push(c);
+                chars = rest.chars();
+            }
+            c => {
+                return Err(ParserError::from(format!(
+                    "Invalid escape sequence \\{}",
+                    c.map(String::from).unwrap_or_default()
+                )))
+            }
+        }
+    }
+    Ok(unescaped)
+}
+
+/// Drops the blank first and last lines of a triple-quoted string along with the
+/// indentation its lines share, so the text can be indented with the query around it
+fn dedent(block: &str) -> String {
+    let mut lines = block.lines().collect::<Vec<_>>();
+    if lines.first().is_some_and(|line| line.trim().is_empty()) {
+        lines.remove(0);
+    }
+    if lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or_else(|| line.trim_start()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -2122,4 +3088,686 @@ mod tests {
         let query = &result.queries[0];
         assert_eq!(query.return_values.len(), 1);
     }
+
+    #[test]
+    fn test_predict_links_step() {
+        let input = r#"
+        QUERY peopleYouMayKnow(userID: String, limit: Integer) =>
+            user <- N<User>(userID)
+            suggestions <- user::PredictLinks<Follows>(AdamicAdar, limit)
+            RETURN suggestions
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        let query = &result.queries[0];
+        match &query.statements[1] {
+            Statement::Assignment(assignment) => match &assignment.value {
+                Expression::Traversal(traversal) => match &traversal.steps[0] {
+                    Step::PredictLinks(predict) => {
+                        assert_eq!(predict.edge_type.as_deref(), Some("Follows"));
+                        assert_eq!(predict.metric, "AdamicAdar");
+                        assert!(matches!(
+                            &predict.limit,
+                            EvaluatesToNumber::Identifier(id) if id == "limit"
+                        ));
+                    }
+                    step => panic!("Expected PredictLinks step, got {:?}", step),
+                },
+                expr => panic!("Expected traversal, got {:?}", expr),
+            },
+            statement => panic!("Expected assignment, got {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn test_order_dedup_group_steps() {
+        let input = r#"
+        QUERY teamScores() =>
+            top <- N<Player>::ORDER(score DESC, name)::DEDUP(team)::RANGE(0, 10)
+            teams <- N<Player>::DEDUP::GROUP_BY(team)::AGG(COUNT, AVG(score), COLLECT(name))
+            RETURN top, teams
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        let query = &result.queries[0];
+        let steps = |i: usize| match &query.statements[i] {
+            Statement::Assignment(assignment) => match &assignment.value {
+                Expression::Traversal(traversal) => traversal.steps.clone(),
+                expr => panic!("Expected traversal, got {:?}", expr),
+            },
+            statement => panic!("Expected assignment, got {:?}", statement),
+        };
+
+        let top = steps(0);
+        match &top[0] {
+            Step::OrderBy(keys) => assert_eq!(
+                keys,
+                &vec![
+                    ("score".to_string(), OrderDirection::Desc),
+                    ("name".to_string(), OrderDirection::Asc)
+                ]
+            ),
+            step => panic!("Expected ORDER step, got {:?}", step),
+        }
+        assert!(matches!(&top[1], Step::Dedup(Some(property)) if property == "team"));
+
+        let teams = steps(1);
+        assert!(matches!(&teams[0], Step::Dedup(None)));
+        assert!(matches!(&teams[1], Step::GroupBy(property) if property == "team"));
+        match &teams[2] {
+            Step::Aggregate(aggregations) => assert_eq!(
+                aggregations,
+                &vec![
+                    Aggregation::Count,
+                    Aggregation::Avg("score".to_string()),
+                    Aggregation::Collect("name".to_string())
+                ]
+            ),
+            step => panic!("Expected AGG step, got {:?}", step),
+        }
+    }
+
+    #[test]
+    fn test_label_and_branch_steps() {
+        let input = r#"
+        QUERY orderItems(userID: String) =>
+            rows <- N<User>(userID)::AS(u)::Out<Ordered>::Out<Contains>::AS(item)::SELECT(u, item)
+            boss <- N<User>(userID)::COALESCE(_::Out<Manager>, _::Out<TeamLead>)
+            people <- N<User>(userID)::UNION(_::Out<Follows>, _::In<Follows>)::OPTIONAL(_::Out<Manager>)
+            RETURN rows, boss, people
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        let query = &result.queries[0];
+        let steps = |i: usize| match &query.statements[i] {
+            Statement::Assignment(assignment) => match &assignment.value {
+                Expression::Traversal(traversal) => traversal.steps.clone(),
+                expr => panic!("Expected traversal, got {:?}", expr),
+            },
+            statement => panic!("Expected assignment, got {:?}", statement),
+        };
+
+        let rows = steps(0);
+        assert!(matches!(&rows[0], Step::As(name) if name == "u"));
+        assert!(matches!(&rows[3], Step::As(name) if name == "item"));
+        assert!(matches!(&rows[4], Step::Select(names) if names == &vec!["u".to_string(), "item".to_string()]));
+
+        match &steps(1)[0] {
+            Step::Coalesce(branches) => {
+                assert_eq!(branches.len(), 2);
+                assert!(matches!(branches[1].start, StartNode::Anonymous));
+                assert!(matches!(&branches[1].steps[0], Step::Node(GraphStep::Out(_))));
+            }
+            step => panic!("Expected COALESCE step, got {:?}", step),
+        }
+
+        let people = steps(2);
+        assert!(matches!(&people[0], Step::Union(branches) if branches.len() == 2));
+        assert!(matches!(&people[1], Step::Optional(_)));
+    }
+
+    #[test]
+    fn test_path_step() {
+        let input = r#"
+        QUERY explain(userID: String) =>
+            routes <- N<User>(userID)::Out<Bought>::In<Bought>::Out<Bought>::PATH
+            RETURN routes
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        match &result.queries[0].statements[0] {
+            Statement::Assignment(assignment) => match &assignment.value {
+                Expression::Traversal(traversal) => {
+                    assert_eq!(traversal.steps.len(), 4);
+                    assert!(matches!(traversal.steps[3], Step::Path));
+                }
+                expr => panic!("Expected traversal, got {:?}", expr),
+            },
+            statement => panic!("Expected assignment, got {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn test_parallel_hint() {
+        let input = r#"
+        QUERY fanOut(userID: String) PARALLEL(4) =>
+            friends <- N<User>(userID)::Out<Follows>::Out<Follows>
+            RETURN friends
+
+        QUERY plain() =>
+            users <- N<User>
+            RETURN users
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        let query = |name: &str| result.queries.iter().find(|q| q.name == name).unwrap();
+        assert_eq!(query("fanOut").parallelism, Some(4));
+        assert_eq!(query("fanOut").statements.len(), 1);
+        assert_eq!(query("plain").parallelism, None);
+    }
+
+    #[test]
+    fn test_limit_hints() {
+        let input = r#"
+        QUERY bounded(userID: String) TIMEOUT(500) MAX_ITEMS(10000) PARALLEL(2) MAX_MEMORY(1048576) =>
+            friends <- N<User>(userID)::Out<Follows>::Out<Follows>
+            RETURN friends
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        let query = &result.queries[0];
+        assert_eq!(query.timeout_ms, Some(500));
+        assert_eq!(query.max_items, Some(10000));
+        assert_eq!(query.parallelism, Some(2));
+        assert_eq!(query.max_memory, Some(1048576));
+        assert_eq!(query.statements.len(), 1);
+    }
+
+    #[test]
+    fn test_dag_steps() {
+        let input = r#"
+        QUERY buildOrder(targetID: String) =>
+            target <- N<Target>(targetID)
+            deps <- target::Ancestors<DependsOn>::TopoSort<DependsOn>
+            critical <- N<Target>::CriticalPath<DependsOn>(duration)
+            RETURN deps, critical
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        let query = &result.queries[0];
+        let steps = |i: usize| match &query.statements[i] {
+            Statement::Assignment(assignment) => match &assignment.value {
+                Expression::Traversal(traversal) => traversal.steps.clone(),
+                expr => panic!("Expected traversal, got {:?}", expr),
+            },
+            statement => panic!("Expected assignment, got {:?}", statement),
+        };
+
+        let deps = steps(1);
+        assert!(matches!(
+            &deps[0],
+            Step::Dag(dag) if dag.operation == DagOperation::Ancestors && dag.edge_type == "DependsOn"
+        ));
+        assert!(matches!(&deps[1], Step::Dag(dag) if dag.operation == DagOperation::TopoSort));
+
+        match &steps(2)[0] {
+            Step::Dag(dag) => {
+                assert_eq!(dag.operation, DagOperation::CriticalPath);
+                assert_eq!(dag.weight.as_deref(), Some("duration"));
+            }
+            step => panic!("Expected CriticalPath step, got {:?}", step),
+        }
+
+        let input = r#"
+        QUERY badWeight() =>
+            tasks <- N<Task>::TopoSort<DependsOn>(duration)
+            RETURN tasks
+        "#;
+        assert!(HelixParser::parse_source(input).is_err());
+    }
+
+    #[test]
+    fn test_match_pattern() {
+        let input = r#"
+        QUERY sameCategory(name: String) =>
+            rows <- MATCH (u:User {name: name})-[b:Bought]->(p1:Product)-[:InCategory]->(c:Category),
+                (c)<-[:InCategory]-(p2:Product)<-[:Bought {quantity: 1}]-(u)
+            RETURN rows
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        let query = &result.queries[0];
+        let pattern = match &query.statements[0] {
+            Statement::Assignment(assignment) => match &assignment.value {
+                Expression::Match(pattern) => pattern.clone(),
+                expr => panic!("Expected MATCH, got {:?}", expr),
+            },
+            statement => panic!("Expected assignment, got {:?}", statement),
+        };
+
+        assert_eq!(pattern.nodes.len(), 6);
+        assert_eq!(pattern.nodes[0].label.as_deref(), Some("User"));
+        assert!(matches!(
+            &pattern.nodes[0].properties[0],
+            (key, ValueType::Identifier(value)) if key == "name" && value == "name"
+        ));
+
+        let edges: Vec<(&str, &str, Option<&str>)> = pattern
+            .edges
+            .iter()
+            .map(|e| (e.from.as_str(), e.to.as_str(), e.label.as_deref()))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("u", "p1", Some("Bought")),
+                ("p1", "c", Some("InCategory")),
+                ("p2", "c", Some("InCategory")),
+                ("u", "p2", Some("Bought")),
+            ]
+        );
+        assert_eq!(pattern.edges[0].variable.as_deref(), Some("b"));
+        assert!(matches!(
+            &pattern.edges[3].properties[0],
+            (_, ValueType::Literal(Value::Integer(1)))
+        ));
+    }
+
+    #[test]
+    fn test_parse_locations() {
+        let input = r#"N::User {
+    name: String
+}
+
+QUERY getUser(id: String) =>
+    user <- N<User>(id)::OutE<Follows>
+    RETURN user
+"#;
+        let at = |line, column, end_line, end_column| Loc {
+            line,
+            column,
+            end_line,
+            end_column,
+        };
+        let result = HelixParser::parse_source(input).unwrap();
+        let schema = &result.node_schemas[0];
+        assert_eq!(schema.loc, at(1, 1, 3, 2));
+        assert_eq!(schema.fields[0].loc, at(2, 5, 2, 17));
+
+        let query = &result.queries[0];
+        assert_eq!(query.loc, at(5, 1, 7, 16));
+        assert_eq!(query.parameters[0].loc, at(5, 15, 5, 25));
+        assert_eq!(query.return_loc, at(7, 5, 7, 16));
+        match &query.statements[0] {
+            Statement::Assignment(assignment) => {
+                assert_eq!(assignment.loc, at(6, 5, 6, 39));
+                match &assignment.value {
+                    Expression::Traversal(traversal) => {
+                        assert_eq!(traversal.loc, at(6, 13, 6, 39));
+                        assert_eq!(traversal.start_loc, at(6, 13, 6, 24));
+                        assert_eq!(traversal.step_locs, vec![at(6, 24, 6, 39)]);
+                    }
+                    expr => panic!("Expected traversal, got {:?}", expr),
+                }
+            }
+            statement => panic!("Expected assignment, got {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn test_parse_source_diagnostics_reports_every_broken_definition() {
+        let input = r#"N::User {
+    name: String
+}
+
+N::Post {
+    title String
+}
+
+QUERY getUser(id: String)
+    user <- N<User>(id)
+    RETURN user
+
+QUERY getPosts() =>
+    posts <- N<Post>
+    RETURN posts
+"#;
+        let errors = HelixParser::parse_source_diagnostics(input).unwrap_err();
+        let positions = errors
+            .iter()
+            .map(|e| (e.code, e.loc.line, e.loc.column))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            positions,
+            vec![(SYNTAX_ERROR, 6, 5), (SYNTAX_ERROR, 10, 5)]
+        );
+
+        let fixed = input
+            .replace("title String", "title: String")
+            .replace("(id: String)\n", "(id: String) =>\n");
+        let source = HelixParser::parse_source_diagnostics(&fixed).unwrap();
+        assert_eq!(source.queries.len(), 2);
+    }
+
+    #[test]
+    fn test_parameter_types() {
+        let input = r#"
+        N::User { name: String }
+
+        QUERY addDoc(
+            user: User,
+            nick: Optional<String>,
+            tags: [[String]],
+            doc: {title: String, embedding: [F32; 3], pages: [{text: String}]},
+            scores: Optional<[Float]>
+        ) =>
+            users <- N<User>
+            RETURN users
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        let types = result.queries[0]
+            .parameters
+            .iter()
+            .map(|p| p.param_type.clone())
+            .collect::<Vec<_>>();
+        let array = |t| FieldType::Array(Box::new(t));
+        assert_eq!(
+            types,
+            vec![
+                FieldType::Identifier("User".to_string()),
+                FieldType::Optional(Box::new(FieldType::String)),
+                array(array(FieldType::String)),
+                FieldType::Object(vec![
+                    ("title".to_string(), FieldType::String),
+                    ("embedding".to_string(), FieldType::Vector(3)),
+                    (
+                        "pages".to_string(),
+                        array(FieldType::Object(vec![(
+                            "text".to_string(),
+                            FieldType::String
+                        )]))
+                    ),
+                ]),
+                FieldType::Optional(Box::new(array(FieldType::Float))),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_parameter_types() {
+        for param in [
+            "v: [F32; 0]",
+            "v: [F32; -3]",
+            "o: {a: String, a: Integer}",
+            "s: Strings",
+        ] {
+            let input = format!("QUERY q({}) =>\n    n <- N\n    RETURN n", param);
+            assert!(
+                HelixParser::parse_source(&input).is_err(),
+                "{} should not parse",
+                param
+            );
+        }
+    }
+
+    #[test]
+    fn test_literals() {
+        let input = r#"
+        QUERY addEvent() =>
+            event <- AddN<Event>({
+                delta: -5,
+                ratio: 1.5e-3,
+                big: 2E6,
+                note: "say \"hi\"\né\u{1F600}",
+                at: 2024-02-29T13:45:30.5+02:00,
+                day: 2024-03-01
+            })
+            cold <- N<Reading>::WHERE(_::{celsius}::GT(-5))
+            recent <- N<Event>::WHERE(_::{at}::LTE(2024-03-01T12:00Z))
+            RETURN event
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        let query = &result.queries[0];
+        let fields = match &query.statements[0] {
+            Statement::Assignment(Assignment {
+                value: Expression::AddNode(add),
+                ..
+            }) => add.fields.clone().unwrap(),
+            statement => panic!("Expected AddN, got {:?}", statement),
+        };
+        let values = fields
+            .into_iter()
+            .map(|(key, value)| match value {
+                ValueType::Literal(value) => (key, value),
+                value => panic!("Expected literal, got {:?}", value),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(values[0].1, Value::Integer(-5));
+        assert_eq!(values[1].1, Value::Float(0.0015));
+        assert_eq!(values[2].1, Value::Float(2e6));
+        assert_eq!(
+            values[3].1,
+            Value::String("say \"hi\"\n\u{e9}\u{1F600}".to_string())
+        );
+        assert_eq!(
+            values[4].1,
+            Value::String("2024-02-29T13:45:30.5+02:00".to_string())
+        );
+        assert_eq!(values[5].1, Value::String("2024-03-01".to_string()));
+
+        let steps = match &query.statements[1] {
+            Statement::Assignment(Assignment {
+                value: Expression::Traversal(traversal),
+                ..
+            }) => &traversal.steps,
+            statement => panic!("Expected traversal, got {:?}", statement),
+        };
+        let Step::Where(condition) = &steps[0] else {
+            panic!("Expected WHERE, got {:?}", steps[0]);
+        };
+        let Expression::Traversal(condition) = &**condition else {
+            panic!("Expected traversal, got {:?}", condition);
+        };
+        assert!(matches!(
+            condition.steps.last(),
+            Some(Step::BooleanOperation(BooleanOp::GreaterThan(value)))
+                if matches!(**value, Expression::IntegerLiteral(-5))
+        ));
+        let Statement::Assignment(Assignment {
+            value: Expression::Traversal(traversal),
+            ..
+        }) = &query.statements[2]
+        else {
+            panic!("Expected traversal, got {:?}", query.statements[2]);
+        };
+        let Step::Where(condition) = &traversal.steps[0] else {
+            panic!("Expected WHERE, got {:?}", traversal.steps[0]);
+        };
+        let Expression::Traversal(condition) = &**condition else {
+            panic!("Expected traversal, got {:?}", condition);
+        };
+        assert!(matches!(
+            condition.steps.last(),
+            Some(Step::BooleanOperation(BooleanOp::LessThanOrEqual(value)))
+                if matches!(&**value, Expression::StringLiteral(date) if date == "2024-03-01T12:00Z")
+        ));
+    }
+
+    #[test]
+    fn test_multiline_string() {
+        let input = r#"
+        QUERY addPost() =>
+            post <- AddN<Post>({body: """
+                First line
+                  keeps "quotes" and \n
+                """})
+            RETURN post
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        match &result.queries[0].statements[0] {
+            Statement::Assignment(Assignment {
+                value: Expression::AddNode(add),
+                ..
+            }) => assert!(matches!(
+                &add.fields.as_ref().unwrap()[0],
+                (_, ValueType::Literal(Value::String(body)))
+                    if body == "First line\n  keeps \"quotes\" and \\n"
+            )),
+            statement => panic!("Expected AddN, got {:?}", statement),
+        }
+    }
+
+    #[test]
+    fn test_invalid_literals() {
+        let query = |value: &str| {
+            format!(
+                "QUERY add() =>\n    n <- AddN<Event>({{at: {}}})\n    RETURN n",
+                value
+            )
+        };
+        for value in [
+            "2023-02-29",
+            "2024-13-01",
+            "2024-04-31",
+            "2024-01-01T24:00",
+            "2024-01-01T12:60",
+            "2024-01-01T12:00+02:75",
+            r#""\q""#,
+            r#""\uD800""#,
+        ] {
+            assert!(
+                HelixParser::parse_source(&query(value)).is_err(),
+                "{} should not parse",
+                value
+            );
+        }
+        assert!(HelixParser::parse_source(&query("2000-02-29T23:59:59Z")).is_ok());
+    }
+
+    #[test]
+    fn test_control_flow() {
+        let input = r#"
+        QUERY follow(userID: String, ids: [String], notify: Boolean) =>
+            user <- N<User>(userID)
+            IF EXISTS(N<User>(userID)::Out<Follows>) {
+                RETURN "already following"
+            } ELSE IF AND(notify, user::{active}::EQ(true)) {
+                FOR id IN ids {
+                    AddE<Follows>()::From(user)::To(id)
+                }
+            } ELSE {
+                DROP user
+            }
+            RETURN user
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        let query = &result.queries[0];
+        assert_eq!(query.statements.len(), 2);
+        let Statement::If(first) = &query.statements[1] else {
+            panic!("Expected IF, got {:?}", query.statements[1]);
+        };
+        assert!(matches!(first.condition, Expression::Exists(_)));
+        match &first.then[..] {
+            [Statement::Return(Return { values, .. })] => assert!(matches!(
+                &values[..],
+                [Expression::StringLiteral(message)] if message == "already following"
+            )),
+            statements => panic!("Expected RETURN, got {:?}", statements),
+        }
+        let [Statement::If(second)] = &first.otherwise[..] else {
+            panic!("Expected ELSE IF, got {:?}", first.otherwise);
+        };
+        assert!(matches!(
+            &second.condition,
+            Expression::And(conditions)
+                if matches!(&conditions[..], [Expression::Identifier(_), Expression::Traversal(_)])
+        ));
+        match &second.then[..] {
+            [Statement::For(ForLoop {
+                variable,
+                iterable: Expression::Identifier(iterable),
+                body,
+                ..
+            })] => {
+                assert_eq!((variable.as_str(), iterable.as_str()), ("id", "ids"));
+                assert!(matches!(&body[..], [Statement::AddEdge(_)]));
+            }
+            statements => panic!("Expected FOR, got {:?}", statements),
+        }
+        assert!(matches!(&second.otherwise[..], [Statement::Drop(_)]));
+        assert_eq!(query.return_values.len(), 1);
+    }
+
+    #[test]
+    fn test_upsert() {
+        let input = r#"
+        QUERY ingest(email: String, name: String, friendID: String) =>
+            user <- UpsertN<User>({email: email}, {name: name, active: true})
+            UpsertE<Follows>({since: "2025-01-01"})::From(user)::To(friendID)
+            RETURN user
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        let query = &result.queries[0];
+        match &query.statements[..] {
+            [Statement::Assignment(Assignment {
+                value: Expression::UpsertNode(upsert),
+                ..
+            }), Statement::UpsertEdge(edge)] => {
+                assert_eq!(upsert.vertex_type.as_deref(), Some("User"));
+                assert_eq!(upsert.match_fields.len(), 1);
+                assert_eq!(upsert.fields.as_ref().map(Vec::len), Some(2));
+                assert_eq!(edge.edge_type.as_deref(), Some("Follows"));
+                assert!(matches!(
+                    (&edge.connection.from_id, &edge.connection.to_id),
+                    (Some(IdType::Identifier(from)), Some(IdType::Identifier(to)))
+                        if from == "user" && to == "friendID"
+                ));
+            }
+            statements => panic!("Expected upserts, got {:?}", statements),
+        }
+
+        let input = r#"
+        QUERY follow(userID: String) =>
+            UpsertE<Follows>::To(userID)
+            RETURN "done"
+        "#;
+        assert!(HelixParser::parse_source(input).is_err());
+    }
+
+    #[test]
+    fn test_functions_and_imports() {
+        let input = r#"
+        IMPORT "shared/users.hx"
+
+        N::User {
+            name: String,
+            age: Integer
+        }
+
+        E::Follows {
+            From: User,
+            To: User,
+            Properties: {
+            }
+        }
+
+        FN activeFollowers(user: User, minAge: Integer) =>
+            user::In<Follows>::WHERE(_::{age}::GTE(minAge))
+
+        QUERY followers(userID: String) =>
+            user <- N<User>(userID)
+            names <- activeFollowers(user, 18)::{name}
+            RETURN names, activeFollowers(user, 21)
+        "#;
+        let result = HelixParser::parse_source(input).unwrap();
+        assert_eq!(result.imports[0].path, "shared/users.hx");
+        // queries can use types from the imported files
+        let imports =
+            HelixParser::parse_imports("IMPORT \"a.hx\"\nQUERY q(u: Unknown) =>\n    RETURN u");
+        assert_eq!(imports[0].path, "a.hx");
+
+        let function = &result.functions[0];
+        assert_eq!(function.name, "activeFollowers");
+        assert_eq!(function.parameters.len(), 2);
+        assert!(matches!(
+            &function.body,
+            Expression::Traversal(traversal) if traversal.steps.len() == 2
+        ));
+
+        let query = &result.queries[0];
+        match &query.statements[1] {
+            Statement::Assignment(Assignment {
+                value: Expression::Traversal(traversal),
+                ..
+            }) => {
+                let StartNode::Call(call) = &traversal.start else {
+                    panic!("Expected a call, got {:?}", traversal.start);
+                };
+                assert_eq!(call.name, "activeFollowers");
+                assert!(matches!(
+                    &call.args[..],
+                    [Expression::Identifier(user), Expression::IntegerLiteral(18)] if user == "user"
+                ));
+                assert!(matches!(&traversal.steps[..], [Step::Object(_)]));
+            }
+            statement => panic!("Expected an assignment, got {:?}", statement),
+        }
+        assert!(matches!(
+            &query.return_values[1],
+            Expression::Traversal(traversal)
+                if matches!(traversal.start, StartNode::Call(_)) && traversal.steps.is_empty()
+        ));
+    }
 }
@@ -52,6 +52,8 @@ impl Response {
                 self.body = b"404 - Route Not Found\n".to_vec();
                 "Not Found"
             }
+            408 => "Request Timeout",
+            413 => "Payload Too Large",
             500 => {
                 // self.body = b"500 - Internal Server Error\n".to_vec();
                 "Internal Server Error"
@@ -1,8 +1,9 @@
 use super::count::Count;
+use crate::helix_engine::graph_core::profile::QueryProfile;
 use super::filterable::{Filterable, FilterableType};
 use super::items::{Edge, Node};
 use super::remapping::{Remapping, ResponseRemapping};
-use super::traversal_value::TraversalValue;
+use super::traversal_value::{Binding, TraversalValue};
 use super::value::{properties_format, Value};
 use serde::{
     de::{DeserializeSeed, VariantAccess, Visitor},
@@ -84,6 +85,30 @@ impl From<f64> for ReturnValue {
     }
 }
 
+impl From<QueryProfile> for ReturnValue {
+    fn from(profile: QueryProfile) -> Self {
+        let integer = |n: u64| ReturnValue::from(i32::try_from(n).unwrap_or(i32::MAX));
+        let steps = profile
+            .steps
+            .into_iter()
+            .map(|step| {
+                ReturnValue::Object(HashMap::from([
+                    ("step".to_string(), ReturnValue::from(step.step)),
+                    ("elapsed_us".to_string(), integer(step.elapsed_us)),
+                    ("input".to_string(), integer(step.input as u64)),
+                    ("output".to_string(), integer(step.output as u64)),
+                    ("pages".to_string(), integer(step.pages as u64)),
+                    ("bytes".to_string(), integer(step.bytes as u64)),
+                ]))
+            })
+            .collect();
+        ReturnValue::Object(HashMap::from([
+            ("elapsed_us".to_string(), integer(profile.elapsed_us)),
+            ("steps".to_string(), ReturnValue::Array(steps)),
+        ]))
+    }
+}
+
 impl<I> From<I> for ReturnValue
 where
     for<'a> I: Filterable<'a> + Clone,
@@ -189,6 +214,52 @@ impl ReturnValue {
             TraversalValue::ValueArray(values) => ReturnValue::Empty,
             TraversalValue::Count(count) => ReturnValue::from(count),
             TraversalValue::Empty => ReturnValue::Empty,
+            TraversalValue::Bindings(rows) => ReturnValue::Array(
+                rows.into_iter()
+                    .map(|row| {
+                        ReturnValue::Object(
+                            row.into_iter()
+                                .map(|(variable, binding)| {
+                                    let value = match binding {
+                                        Binding::Node(node) => ReturnValue::from(node),
+                                        Binding::Edge(edge) => ReturnValue::from(edge),
+                                    };
+                                    (variable, value)
+                                })
+                                .collect(),
+                        )
+                    })
+                    .collect(),
+            ),
+            // same shape as serializing the traversal value: `[[nodes], [edges]]` per path
+            TraversalValue::Paths(paths) => ReturnValue::Array(
+                paths
+                    .into_iter()
+                    .map(|(nodes, edges)| {
+                        ReturnValue::Array(vec![
+                            ReturnValue::Array(
+                                nodes.into_iter().map(ReturnValue::path_element).collect(),
+                            ),
+                            ReturnValue::Array(
+                                edges.into_iter().map(ReturnValue::path_element).collect(),
+                            ),
+                        ])
+                    })
+                    .collect(),
+            ),
+            TraversalValue::Groups(groups) => ReturnValue::Array(
+                groups
+                    .into_iter()
+                    .map(|group| {
+                        ReturnValue::Object(
+                            group
+                                .into_iter()
+                                .map(|(key, value)| (key, ReturnValue::from(value)))
+                                .collect(),
+                        )
+                    })
+                    .collect(),
+            ),
             _ => {
                 println!("not working");
                 unreachable!()
@@ -196,6 +267,31 @@ impl ReturnValue {
         }
     }
 
+    /// A node or edge as it is serialized on its own, with the properties kept in a
+    /// nested `properties` object rather than spread like `ReturnValue::from` does
+    fn path_element<I>(item: I) -> Self
+    where
+        for<'a> I: Filterable<'a> + Clone,
+    {
+        let mut object = HashMap::with_capacity(Edge::NUM_PROPERTIES + 1);
+        object.insert("id".to_string(), ReturnValue::from(item.id().to_string()));
+        object.insert("label".to_string(), ReturnValue::from(item.label().to_string()));
+        if let FilterableType::Edge = item.type_name() {
+            object.insert("from_node".to_string(), ReturnValue::from(item.from_node()));
+            object.insert("to_node".to_string(), ReturnValue::from(item.to_node()));
+        }
+        object.insert(
+            "properties".to_string(),
+            ReturnValue::Object(
+                item.properties()
+                    .into_iter()
+                    .map(|(k, v)| (k, ReturnValue::from(v)))
+                    .collect(),
+            ),
+        );
+        ReturnValue::Object(object)
+    }
+
     #[inline(always)]
     #[allow(unused_attributes)]
     #[ignore = "No use for this function yet, however, I believe it may be useful in the future so I'm keeping it here"]
@@ -3,7 +3,18 @@ use crate::helix_engine::vector_core::vector::HVector;
 use super::{count::Count, items::Edge, filterable::Filterable, items::Node, value::Value};
 use serde::Serializer;
 use sonic_rs::{Deserialize, Serialize};
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashMap};
+
+/// A node or edge bound to a pattern variable
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(untagged)]
+pub enum Binding {
+    Node(Node),
+    Edge(Edge),
+}
+
+/// One match of a pattern, keyed by variable name
+pub type BindingRow = HashMap<String, Binding>;
 
 #[derive(Deserialize, Clone, PartialEq)]
 #[serde(untagged)]
@@ -15,6 +26,9 @@ pub enum TraversalValue {
     ValueArray(Vec<(String, Value)>),
     Paths(Vec<(Vec<Node>, Vec<Edge>)>),
     VectorArray(Vec<HVector>),
+    Bindings(Vec<BindingRow>),
+    /// Results of `group_by`, one map of group key and aggregates per group
+    Groups(Vec<HashMap<String, Value>>),
 }
 
 impl FromIterator<TraversalValue> for TraversalValue {
@@ -24,6 +38,8 @@ impl FromIterator<TraversalValue> for TraversalValue {
         let mut values = Vec::with_capacity(10);
         let mut paths = Vec::with_capacity(10);
         let mut vectors = Vec::with_capacity(10);
+        let mut bindings = Vec::new();
+        let mut groups = Vec::new();
         for value in iter {
             match value {
                 TraversalValue::Count(count) => return TraversalValue::Count(count),
@@ -32,6 +48,8 @@ impl FromIterator<TraversalValue> for TraversalValue {
                 TraversalValue::ValueArray(mut value_vec) => values.append(&mut value_vec),
                 TraversalValue::Paths(mut path_vecs) => paths.append(&mut path_vecs),
                 TraversalValue::VectorArray(mut vector_vec) => vectors.append(&mut vector_vec),
+                TraversalValue::Bindings(mut rows) => bindings.append(&mut rows),
+                TraversalValue::Groups(mut group_vec) => groups.append(&mut group_vec),
                 TraversalValue::Empty => (),
             }
         }
@@ -42,6 +60,12 @@ impl FromIterator<TraversalValue> for TraversalValue {
             TraversalValue::EdgeArray(edges)
         } else if !values.is_empty() {
             TraversalValue::ValueArray(values)
+        } else if !paths.is_empty() {
+            TraversalValue::Paths(paths)
+        } else if !bindings.is_empty() {
+            TraversalValue::Bindings(bindings)
+        } else if !groups.is_empty() {
+            TraversalValue::Groups(groups)
         } else {
             TraversalValue::Empty
         }
@@ -91,6 +115,8 @@ impl std::fmt::Debug for TraversalValue {
             TraversalValue::ValueArray(values) => values.fmt(f),
             TraversalValue::Paths(paths) => paths.fmt(f),
             TraversalValue::VectorArray(vectors) => vectors.fmt(f),
+            TraversalValue::Bindings(rows) => rows.fmt(f),
+            TraversalValue::Groups(groups) => groups.fmt(f),
         }
     }
 }
@@ -108,6 +134,8 @@ impl Serialize for TraversalValue {
             TraversalValue::ValueArray(values) => values.serialize(serializer),
             TraversalValue::Paths(paths) => paths.serialize(serializer),
             TraversalValue::VectorArray(vectors) => vectors.serialize(serializer),
+            TraversalValue::Bindings(rows) => rows.serialize(serializer),
+            TraversalValue::Groups(groups) => groups.serialize(serializer),
         }
     }
 }
@@ -151,6 +179,65 @@ impl TraversalValue {
             _ => Err(TraversalValueError::NoCount),
         }
     }
+
+    /// Whether a traversal found anything, or evaluated to true
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            TraversalValue::Empty => false,
+            TraversalValue::Count(count) => count.value() > 0,
+            TraversalValue::NodeArray(nodes) => !nodes.is_empty(),
+            TraversalValue::EdgeArray(edges) => !edges.is_empty(),
+            TraversalValue::ValueArray(values) => values
+                .iter()
+                .any(|(_, value)| *value == Value::Boolean(true)),
+            TraversalValue::Paths(paths) => !paths.is_empty(),
+            TraversalValue::VectorArray(vectors) => !vectors.is_empty(),
+            TraversalValue::Bindings(rows) => !rows.is_empty(),
+            TraversalValue::Groups(groups) => !groups.is_empty(),
+        }
+    }
+
+    /// Splits the value into one value per node, edge, value, path, vector, match or group,
+    /// what a `FOR` loop iterates over
+    pub fn into_items(self) -> Vec<TraversalValue> {
+        match self {
+            TraversalValue::Empty => Vec::new(),
+            TraversalValue::Count(_) => vec![self],
+            TraversalValue::NodeArray(nodes) => {
+                nodes.into_iter().map(TraversalValue::from).collect()
+            }
+            TraversalValue::EdgeArray(edges) => {
+                edges.into_iter().map(TraversalValue::from).collect()
+            }
+            // array parameters and properties are split into their elements
+            TraversalValue::ValueArray(values) => values
+                .into_iter()
+                .flat_map(|(key, value)| match value {
+                    Value::Array(values) => values
+                        .into_iter()
+                        .map(|value| TraversalValue::from((key.clone(), value)))
+                        .collect(),
+                    value => vec![TraversalValue::from((key, value))],
+                })
+                .collect(),
+            TraversalValue::Paths(paths) => paths
+                .into_iter()
+                .map(|path| TraversalValue::Paths(vec![path]))
+                .collect(),
+            TraversalValue::VectorArray(vectors) => vectors
+                .into_iter()
+                .map(|vector| TraversalValue::VectorArray(vec![vector]))
+                .collect(),
+            TraversalValue::Bindings(rows) => rows
+                .into_iter()
+                .map(|row| TraversalValue::Bindings(vec![row]))
+                .collect(),
+            TraversalValue::Groups(groups) => groups
+                .into_iter()
+                .map(|group| TraversalValue::Groups(vec![group]))
+                .collect(),
+        }
+    }
 }
 
 #[derive(Debug, Clone)]